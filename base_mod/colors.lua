@@ -79,5 +79,20 @@ data:extend {
         r = 0.2,
         g = 0.6,
         b = 0.25,
+    },
+    lot_commercial_col = {
+        r = 0.2,
+        g = 0.4,
+        b = 0.7,
+    },
+    lot_industrial_col = {
+        r = 0.7,
+        g = 0.6,
+        b = 0.2,
+    },
+    lot_mixed_col = {
+        r = 0.25,
+        g = 0.55,
+        b = 0.55,
     }
 }
\ No newline at end of file
@@ -20,6 +20,7 @@ data:extend {
         asset = "bakery.glb",
         price = 1000,
         power_consumption = "200W",
+        water_consumption = "200L/h",
     },
     {
         type = "goods-company",
@@ -43,6 +44,7 @@ data:extend {
         asset = "flour_factory.glb",
         price = 1000,
         power_consumption = "10kW",
+        water_consumption = "5m3/h",
     },
     {
         type = "goods-company",
@@ -114,6 +116,57 @@ data:extend {
         price = 1000,
         power_production = "2.46MW",
     },
+    {
+        type = "goods-company",
+        order = "b-3",
+        name = "water-pumping-station",
+        label = "Water pumping station",
+        bgen = {
+            kind = "centered_door",
+            vertical_factor = 1.0,
+        },
+        kind = "factory",
+        n_workers = 3,
+        size = 40.0,
+        asset = "assets/sprites/cement.jpg",
+        price = 1000,
+        power_consumption = "50kW",
+        water_production = "500m3/h",
+    },
+    {
+        type = "goods-company",
+        order = "b-4",
+        name = "sewage-treatment-plant",
+        label = "Sewage treatment plant",
+        bgen = {
+            kind = "centered_door",
+            vertical_factor = 1.0,
+        },
+        kind = "factory",
+        n_workers = 5,
+        size = 60.0,
+        asset = "assets/sprites/cement.jpg",
+        price = 1000,
+        power_consumption = "50kW",
+        sewage_treatment = "500m3/h",
+    },
+    {
+        type = "battery",
+        order = "b-5",
+        name = "battery",
+        label = "Battery",
+        bgen = {
+            kind = "centered_door",
+            vertical_factor = 1.0,
+        },
+        kind = "factory",
+        n_workers = 0,
+        size = 30.0,
+        asset = "assets/sprites/cement.jpg",
+        price = 2000,
+        capacity = "2MWh",
+        max_power = "500kW",
+    },
     {
         type = "goods-company",
         order = "c-1",
@@ -135,6 +188,7 @@ data:extend {
         asset = "assets/sprites/supermarket.png",
         price = 1000,
         power_consumption = "1kW",
+        water_consumption = "500L/h",
     },
     {
         type = "goods-company",
@@ -180,6 +234,7 @@ data:extend {
         asset = "assets/sprites/cloth_factory.png",
         price = 1000,
         power_consumption = "10kW",
+        water_consumption = "5m3/h",
     },
     {
         type = "goods-company",
@@ -203,6 +258,7 @@ data:extend {
         asset = "assets/sprites/textile_processing_facility.png",
         price = 1000,
         power_consumption = "1kW",
+        water_consumption = "5m3/h",
     },
     {
         type = "goods-company",
@@ -4,6 +4,7 @@ require("leisure")
 require("colors")
 require("roadvehicles")
 require("rollingstock")
+require("scenarios")
 
 data:extend {
     {
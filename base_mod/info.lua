@@ -0,0 +1,4 @@
+return {
+    name = "base",
+    version = "0.1.0",
+}
@@ -0,0 +1,45 @@
+data:extend {
+    {
+        type = "scenario",
+        order = "a-0",
+        name = "first-steps",
+        label = "First Steps",
+        description = "Grow a small town on an empty map.",
+        start_map = "empty",
+        starting_money = "150000$",
+        objectives = {
+            { kind = "population", amount = 200 },
+        },
+    },
+    {
+        type = "scenario",
+        order = "a-1",
+        name = "paris-bakeries",
+        label = "Bread for Paris",
+        description = "Feed the world with the bakeries of Paris.",
+        start_map = "paris",
+        starting_money = "100000$",
+        objectives = {
+            { kind = "population", amount = 1000 },
+            { kind = "export_per_hour", item = "bread", amount = 50 },
+        },
+        time_limit = "30d",
+    },
+    {
+        type = "scenario",
+        order = "a-2",
+        name = "grid-tycoon",
+        label = "Grid Tycoon",
+        description = "Get rich on a grid of streets.",
+        start_map = {
+            kind = "testfield",
+            size = 10,
+            spacing = 150.0,
+        },
+        starting_money = "50000$",
+        objectives = {
+            { kind = "money", amount = "500000$" },
+        },
+        time_limit = "20d",
+    },
+}
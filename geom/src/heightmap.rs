@@ -323,15 +323,29 @@ impl<const RESOLUTION: usize, const SIZE: u32> Heightmap<RESOLUTION, SIZE> {
             .and_then(|chunk| chunk.height(p - vec2(cell.0 as f32, cell.1 as f32) * SIZE as f32))
     }
 
-    /// get height by actual cell position
-    fn height_idx(&self, x: usize, y: usize) -> Option<f32> {
+    /// Returns height at any point using the cell to the bottom left of the point, ignoring overrides
+    pub fn natural_height(&self, p: Vec2) -> Option<f32> {
+        let v = p / Self::CELL_SIZE;
+        let (chunk, cellx, celly) = Self::cell_idx(v.x as usize, v.y as usize);
+        let h = self.get_chunk(chunk)?.heights()[celly][cellx];
+        Some(unpack_height(h))
+    }
+
+    /// Splits an actual cell position into its chunk and the cell position inside the chunk
+    fn cell_idx(x: usize, y: usize) -> (HeightmapChunkID, usize, usize) {
         let chunkx = x / RESOLUTION;
         let chunky = y / RESOLUTION;
 
         let cellx = x % RESOLUTION;
         let celly = y % RESOLUTION;
 
-        let chunk = self.get_chunk((chunkx as u16, chunky as u16))?;
+        ((chunkx as u16, chunky as u16), cellx, celly)
+    }
+
+    /// get height by actual cell position
+    fn height_idx(&self, x: usize, y: usize) -> Option<f32> {
+        let (chunk, cellx, celly) = Self::cell_idx(x, y);
+        let chunk = self.get_chunk(chunk)?;
 
         // Safety: modulo RESOLUTION
         unsafe { Some(chunk.height_idx(cellx, celly).unwrap_unchecked()) }
@@ -339,13 +353,8 @@ impl<const RESOLUTION: usize, const SIZE: u32> Heightmap<RESOLUTION, SIZE> {
 
     /// Always returns the normal heightmap height
     fn height_idx_mut(&mut self, x: usize, y: usize) -> Option<&mut u16> {
-        let chunkx = x / RESOLUTION;
-        let chunky = y / RESOLUTION;
-
-        let cellx = x % RESOLUTION;
-        let celly = y % RESOLUTION;
-
-        let chunk = self.get_chunk_mut((chunkx as u16, chunky as u16))?;
+        let (chunk, cellx, celly) = Self::cell_idx(x, y);
+        let chunk = self.get_chunk_mut(chunk)?;
 
         // Safety: modulo RESOLUTION
         unsafe { Some(chunk.height_idx_mut(cellx, celly).unwrap_unchecked()) }
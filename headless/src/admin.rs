@@ -0,0 +1,280 @@
+//! Admin console of the headless server
+//!
+//! Commands are read line by line from stdin and, if an admin port is given, from a TCP socket
+//! bound to localhost. Over TCP, each command line gets a response terminated by an empty line.
+
+use geom::Color;
+use networking::Server;
+use prototypes::GameTime;
+use simulation::economy::{Government, StatsRecorder};
+use simulation::multiplayer::chat::{Message, MessageKind};
+use simulation::world_command::{WorldCommand, WorldCommands};
+use simulation::Simulation;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+const HELP: &str = "commands:
+  help                 show this message
+  players              list connected players
+  kick <name>          disconnect a player
+  pause                stop the simulation
+  resume               resume the simulation
+  speed <n>            run the simulation n times faster
+  save                 save the world now
+  say <text>           broadcast a chat message
+  stats                show tick, time, population and money
+  export-stats <path>  write the recorded statistics as CSV (.csv) or JSON lines";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AdminCommand {
+    Help,
+    Players,
+    Kick(String),
+    Pause,
+    Resume,
+    Speed(u32),
+    Save,
+    Say(String),
+    Stats,
+    ExportStats(String),
+}
+
+impl FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (cmd, arg) = s.split_once(' ').unwrap_or((s, ""));
+        let arg = arg.trim();
+
+        match cmd {
+            "help" => Ok(Self::Help),
+            "players" => Ok(Self::Players),
+            "kick" if !arg.is_empty() => Ok(Self::Kick(arg.to_string())),
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            "speed" => match arg.parse() {
+                Ok(n) if n > 0 => Ok(Self::Speed(n)),
+                _ => Err("usage: speed <n> with n > 0".to_string()),
+            },
+            "save" => Ok(Self::Save),
+            "say" if !arg.is_empty() => Ok(Self::Say(arg.to_string())),
+            "stats" => Ok(Self::Stats),
+            "export-stats" if !arg.is_empty() => Ok(Self::ExportStats(arg.to_string())),
+            "kick" | "say" | "export-stats" => Err(format!("usage: {} <arg>", cmd)),
+            _ => Err(format!("unknown command: {}. type help for a list", cmd)),
+        }
+    }
+}
+
+/// A command line received from stdin or from the admin socket
+pub struct AdminRequest {
+    pub line: String,
+    /// None if the request comes from stdin
+    reply: Option<Sender<String>>,
+}
+
+impl AdminRequest {
+    pub fn reply(self, response: String) {
+        match self.reply {
+            Some(reply) => {
+                let _ = reply.send(response);
+            }
+            None => println!("{}", response),
+        }
+    }
+}
+
+pub struct AdminConsole {
+    requests: Receiver<AdminRequest>,
+}
+
+impl AdminConsole {
+    pub fn start(port: Option<u16>) -> Self {
+        let (tx, rx) = channel();
+
+        let stdin_tx = tx.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if stdin_tx.send(AdminRequest { line, reply: None }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        if let Some(port) = port {
+            match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+                Ok(listener) => {
+                    log::info!("admin console listening on 127.0.0.1:{}", port);
+                    std::thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            let tx = tx.clone();
+                            std::thread::spawn(move || handle_admin_conn(stream, tx));
+                        }
+                    });
+                }
+                Err(e) => log::error!("could not start admin console on port {}: {}", port, e),
+            }
+        }
+
+        Self { requests: rx }
+    }
+
+    pub fn poll(&self) -> impl Iterator<Item = AdminRequest> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+fn handle_admin_conn(stream: TcpStream, requests: Sender<AdminRequest>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply_tx, reply_rx) = channel();
+        if requests
+            .send(AdminRequest {
+                line,
+                reply: Some(reply_tx),
+            })
+            .is_err()
+        {
+            return;
+        }
+        let response = reply_rx
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap_or_else(|_| "error: no response from server".to_string());
+        if writeln!(writer, "{}\n", response).is_err() {
+            return;
+        }
+    }
+}
+
+/// State of the headless loop that admin commands act on
+pub struct AdminContext<'a> {
+    pub server: &'a mut Server<Simulation, WorldCommands>,
+    pub sim: &'a Simulation,
+    /// Commands to send to every player on the next frame
+    pub pending: &'a mut WorldCommands,
+    pub save_requested: &'a mut bool,
+}
+
+impl AdminCommand {
+    pub fn execute(self, ctx: AdminContext) -> String {
+        match self {
+            AdminCommand::Help => HELP.to_string(),
+            AdminCommand::Players => {
+                let players = ctx.server.players();
+                if players.is_empty() {
+                    return "no players connected".to_string();
+                }
+                players
+                    .iter()
+                    .map(|p| match p.lag {
+                        Some(lag) => format!(
+                            "{} [{:?}]: {} (lag: {} frames)",
+                            p.name, p.role, p.state, lag
+                        ),
+                        None => format!("{} [{:?}]: {} (too late)", p.name, p.role, p.state),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            AdminCommand::Kick(name) => {
+                if ctx.server.kick(&name) {
+                    format!("kicked {}", name)
+                } else {
+                    format!("no player named {}", name)
+                }
+            }
+            AdminCommand::Pause => {
+                ctx.server.set_paused(true);
+                "paused".to_string()
+            }
+            AdminCommand::Resume => {
+                ctx.server.set_paused(false);
+                "resumed".to_string()
+            }
+            AdminCommand::Speed(n) => {
+                ctx.server.set_speed(n);
+                format!("speed set to x{}", n)
+            }
+            AdminCommand::Save => {
+                *ctx.save_requested = true;
+                "saving".to_string()
+            }
+            AdminCommand::Say(text) => {
+                ctx.pending.push(WorldCommand::SendMessage {
+                    message: Message {
+                        name: "Server".to_string(),
+                        text,
+                        sent_at: ctx.sim.read::<GameTime>().instant(),
+                        color: Color::WHITE,
+                        kind: MessageKind::Info,
+                    },
+                });
+                "message sent".to_string()
+            }
+            AdminCommand::Stats => {
+                let time = ctx.sim.read::<GameTime>();
+                format!(
+                    "tick: {}\ntime: {}\npopulation: {}\nmoney: {}\nplayers: {}\npaused: {}\nspeed: x{}",
+                    time.tick,
+                    time.daytime,
+                    ctx.sim.world().humans.len(),
+                    ctx.sim.read::<Government>().money,
+                    ctx.server.players().len(),
+                    ctx.server.is_paused(),
+                    ctx.server.speed(),
+                )
+            }
+            AdminCommand::ExportStats(path) => {
+                match ctx.sim.read::<StatsRecorder>().export(Path::new(&path)) {
+                    Ok(()) => format!("statistics exported to {}", path),
+                    Err(e) => format!("could not export statistics to {}: {}", path, e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdminCommand;
+
+    #[test]
+    fn parse_admin_commands() {
+        assert_eq!("stats".parse(), Ok(AdminCommand::Stats));
+        assert_eq!("speed 3".parse(), Ok(AdminCommand::Speed(3)));
+        assert_eq!(
+            "export-stats stats.csv".parse(),
+            Ok(AdminCommand::ExportStats("stats.csv".to_string()))
+        );
+        assert_eq!(
+            "say hello everyone ".parse(),
+            Ok(AdminCommand::Say("hello everyone".to_string()))
+        );
+        assert_eq!(
+            " kick bob".parse(),
+            Ok(AdminCommand::Kick("bob".to_string()))
+        );
+        assert!("speed 0".parse::<AdminCommand>().is_err());
+        assert!("kick".parse::<AdminCommand>().is_err());
+        assert!("dance".parse::<AdminCommand>().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use common::saveload::{Encoder, JSONPretty};
+use serde::Serialize;
+use simulation::utils::scheduler::Schedule;
+use simulation::Simulation;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Instant;
+
+/// Timings of a system or of the whole tick, in milliseconds
+#[derive(Serialize)]
+pub struct TimeStats {
+    pub avg_ms: f32,
+    pub p50_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+}
+
+impl TimeStats {
+    fn new(mut times: Vec<f32>) -> Self {
+        times.sort_by(f32::total_cmp);
+        let percentile = |p: f32| {
+            if times.is_empty() {
+                return 0.0;
+            }
+            let rank = (p / 100.0 * times.len() as f32).ceil() as usize;
+            times[rank.clamp(1, times.len()) - 1]
+        };
+
+        Self {
+            avg_ms: times.iter().sum::<f32>() / times.len().max(1) as f32,
+            p50_ms: percentile(50.0),
+            p95_ms: percentile(95.0),
+            p99_ms: percentile(99.0),
+            max_ms: times.last().copied().unwrap_or(0.0),
+        }
+    }
+
+    fn write_csv(&self, out: &mut String, prefix: &str) {
+        for (name, v) in [
+            ("avg_ms", self.avg_ms),
+            ("p50_ms", self.p50_ms),
+            ("p95_ms", self.p95_ms),
+            ("p99_ms", self.p99_ms),
+            ("max_ms", self.max_ms),
+        ] {
+            let _ = writeln!(out, "{prefix}.{name},{v}");
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SystemStats {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub times: TimeStats,
+}
+
+#[derive(Serialize)]
+pub struct BenchReport {
+    pub version: String,
+    pub start_tick: u64,
+    pub ticks: u64,
+    pub parallel: bool,
+    pub total_seconds: f64,
+    pub ticks_per_second: f64,
+    pub tick: TimeStats,
+    pub systems: Vec<SystemStats>,
+    /// Number of entities of each kind at the end of the run
+    pub entities: BTreeMap<&'static str, usize>,
+}
+
+impl BenchReport {
+    /// Writes the report as CSV if the path ends with .csv, as JSON otherwise
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let data = if path.extension().is_some_and(|ext| ext == "csv") {
+            self.to_csv().into_bytes()
+        } else {
+            JSONPretty::encode(self)?
+        };
+        std::fs::write(path, data)
+    }
+
+    /// One `metric,value` line per measure, so that reports are easy to compare over time
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("metric,value\n");
+        let _ = writeln!(out, "version,{}", self.version.trim());
+        let _ = writeln!(out, "start_tick,{}", self.start_tick);
+        let _ = writeln!(out, "ticks,{}", self.ticks);
+        let _ = writeln!(out, "parallel,{}", self.parallel);
+        let _ = writeln!(out, "total_seconds,{}", self.total_seconds);
+        let _ = writeln!(out, "ticks_per_second,{}", self.ticks_per_second);
+        self.tick.write_csv(&mut out, "tick");
+        for system in &self.systems {
+            system
+                .times
+                .write_csv(&mut out, &format!("system.{}", system.name));
+        }
+        for (kind, count) in &self.entities {
+            let _ = writeln!(out, "entities.{kind},{count}");
+        }
+        out
+    }
+}
+
+/// Advances the simulation by `ticks` ticks as fast as possible and measures how long each system takes
+pub fn run(sim: &mut Simulation, sched: &mut Schedule, ticks: u64, parallel: bool) -> BenchReport {
+    sched.set_parallel(parallel);
+
+    let start_tick = sim.get_tick();
+    let mut tick_times = Vec::with_capacity(ticks as usize);
+    let mut system_times: Vec<(&'static str, Vec<f32>)> = sched
+        .last_times()
+        .into_iter()
+        .map(|(name, _)| (name, Vec::with_capacity(ticks as usize)))
+        .collect();
+
+    let start = Instant::now();
+    for _ in 0..ticks {
+        let elapsed = sim.tick(sched, &[]);
+        tick_times.push(1000.0 * elapsed.as_secs_f32());
+        for ((_, times), (_, t)) in system_times.iter_mut().zip(sched.last_times()) {
+            times.push(t);
+        }
+    }
+    let total_seconds = start.elapsed().as_secs_f64();
+
+    let world = sim.world();
+    let entities = BTreeMap::from([
+        ("vehicles", world.vehicles.len()),
+        ("humans", world.humans.len()),
+        ("trains", world.trains.len()),
+        ("wagons", world.wagons.len()),
+        ("freight_stations", world.freight_stations.len()),
+        ("companies", world.companies.len()),
+    ]);
+
+    BenchReport {
+        version: crate::VERSION.to_string(),
+        start_tick,
+        ticks,
+        parallel,
+        total_seconds,
+        ticks_per_second: ticks as f64 / total_seconds.max(f64::EPSILON),
+        tick: TimeStats::new(tick_times),
+        systems: system_times
+            .into_iter()
+            .map(|(name, times)| SystemStats {
+                name,
+                times: TimeStats::new(times),
+            })
+            .collect(),
+        entities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeStats;
+
+    #[test]
+    fn percentiles() {
+        let stats = TimeStats::new((1..=100).rev().map(|x| x as f32).collect());
+        assert_eq!(stats.avg_ms, 50.5);
+        assert_eq!(stats.p50_ms, 50.0);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.p99_ms, 99.0);
+        assert_eq!(stats.max_ms, 100.0);
+
+        let empty = TimeStats::new(vec![]);
+        assert_eq!(empty.avg_ms, 0.0);
+        assert_eq!(empty.p99_ms, 0.0);
+
+        let mut csv = String::new();
+        empty.write_csv(&mut csv, "tick");
+        assert!(csv.starts_with("tick.avg_ms,0\n"));
+    }
+}
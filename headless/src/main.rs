@@ -1,11 +1,22 @@
+use crate::admin::{AdminCommand, AdminConsole, AdminContext};
+use crate::query::QueryServer;
 use common::logger::MyLog;
+use common::saveload::{Encoder, JSONPretty};
 use common::unwrap_or;
-use networking::{Frame, Server, ServerConfiguration, ServerPollResult};
-use simulation::world_command::WorldCommands;
+use networking::{Frame, Role, Server, ServerConfiguration, ServerPollResult};
+use prototypes::ScenarioPrototypeID;
+use simulation::economy::{Metric, StatsRecorder};
+use simulation::utils::bisect::{bisect_replay, check_trace, HashTrace};
+use simulation::world_command::{WorldCommand, WorldCommands};
 use simulation::Simulation;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+mod admin;
+mod bench;
+mod query;
+
 const VERSION: &str = include_str!("../../VERSION");
 
 #[derive(StructOpt, Debug)]
@@ -15,6 +26,10 @@ struct Opt {
     #[structopt(long)]
     port: Option<u16>,
 
+    /// Name shown to the players discovering the server on the local network
+    #[structopt(long, default_value = "Egregoria server")]
+    name: String,
+
     /// Auto save frequency, in seconds
     #[structopt(long, default_value = "300")]
     autosave: u64,
@@ -27,6 +42,70 @@ struct Opt {
     /// i.e. 20ms = 50FPS
     #[structopt(long, default_value = "20")]
     timestep: u64,
+
+    /// Start a new world from the given scenario instead of loading the savegame
+    #[structopt(long)]
+    scenario: Option<String>,
+
+    /// Role of the players joining the server: admin, builder or spectator
+    #[structopt(long, default_value = "builder")]
+    default_role: Role,
+
+    /// Players joining with this password get the admin role
+    #[structopt(long)]
+    admin_password: Vec<String>,
+
+    /// Listen for admin commands on this localhost port, in addition to stdin
+    #[structopt(long)]
+    admin_port: Option<u16>,
+
+    /// Answer read-only JSON queries over HTTP on this localhost port (e.g. GET /tick)
+    #[structopt(long)]
+    query_port: Option<u16>,
+
+    /// Benchmark the simulation instead of starting a server:
+    /// advance this many ticks as fast as possible and write a report
+    #[structopt(long)]
+    bench: Option<u64>,
+
+    /// Replay to play before benchmarking (e.g. "world" for world/world_replay.json),
+    /// instead of loading the savegame
+    #[structopt(long)]
+    bench_replay: Option<String>,
+
+    /// Where to write the benchmark report, as CSV if the extension is .csv and JSON otherwise
+    #[structopt(long, default_value = "bench_report.json", parse(from_os_str))]
+    bench_report: PathBuf,
+
+    /// Run the systems one after the other during the benchmark
+    #[structopt(long)]
+    bench_sequential: bool,
+
+    /// Statistics to record every in-game hour, comma separated (e.g. population,money).
+    /// All of them are recorded by default
+    #[structopt(long, use_delimiter = true)]
+    stats_metrics: Vec<Metric>,
+
+    /// Export the recorded statistics to this file on every save,
+    /// as CSV if the extension is .csv and JSON lines otherwise
+    #[structopt(long, parse(from_os_str))]
+    stats_export: Option<PathBuf>,
+
+    /// Find the first tick where two runs of this replay diverge instead of starting a server
+    #[structopt(long)]
+    bisect: Option<String>,
+
+    /// Number of ticks between two hash comparisons while bisecting
+    #[structopt(long, default_value = "100")]
+    bisect_period: u64,
+
+    /// Compare the replay against this hash trace instead of a second run
+    #[structopt(long, parse(from_os_str))]
+    bisect_trace: Option<PathBuf>,
+
+    /// Record the hash trace of the replay to this file instead of bisecting
+    #[structopt(long, parse(from_os_str))]
+    record_trace: Option<PathBuf>,
 }
 
 fn main() {
@@ -34,12 +113,21 @@ fn main() {
     MyLog::init();
     simulation::init::init();
 
+    if let Some(ticks) = opt.bench {
+        run_bench(&opt, ticks);
+        return;
+    }
+
+    if let Some(ref name) = opt.bisect {
+        run_bisect(&opt, name);
+        return;
+    }
+
     log::info!("starting server with version: {}", VERSION);
 
-    let mut w = unwrap_or!(Simulation::load_from_disk("world"), {
-        log::info!("savegame not found defaulting to empty");
-        Simulation::new(true)
-    });
+    let Some(mut w) = initial_world(&opt) else {
+        return;
+    };
 
     let mut sched = Simulation::schedule();
 
@@ -47,8 +135,16 @@ fn main() {
         start_frame: Frame(w.get_tick()),
         period: Duration::from_millis(opt.timestep),
         port: opt.port,
+        name: opt.name.clone(),
         virtual_client: None,
         version: VERSION.to_string(),
+        mods: prototypes::mod_set(),
+        default_role: opt.default_role,
+        passwords: opt
+            .admin_password
+            .iter()
+            .map(|password| (password.clone(), Role::Admin))
+            .collect(),
         always_run: opt.always_run,
     }) {
         Ok(x) => x,
@@ -57,24 +153,172 @@ fn main() {
             return;
         }
     };
+    server.set_input_filter(|role, commands: &mut WorldCommands| {
+        commands.retain_permitted(role.can_build(), role.is_admin())
+    });
+    server.set_map_info(Simulation::summary);
+    server.set_world_parts(Simulation::to_parts);
     log::info!("server started!");
 
+    let admin = AdminConsole::start(opt.admin_port);
+    let queries = QueryServer::start(opt.query_port);
+    let mut admin_commands = WorldCommands::default();
+    if !opt.stats_metrics.is_empty() {
+        admin_commands.push(WorldCommand::SetRecordedMetrics(opt.stats_metrics.clone()));
+    }
+    let mut save_requested = false;
+
     let mut last_saved = Instant::now();
 
     loop {
-        if let ServerPollResult::Input(inputs) = server.poll(&w, Frame(w.get_tick()), None) {
-            for frame in inputs {
-                assert_eq!(frame.frame.0, w.get_tick() + 1);
-                let merged: WorldCommands = frame.inputs.into_iter().map(|x| x.inp).collect();
-                w.tick(&mut sched, merged.as_ref());
+        for req in admin.poll() {
+            let response = match req.line.parse::<AdminCommand>() {
+                Ok(cmd) => cmd.execute(AdminContext {
+                    server: &mut server,
+                    sim: &w,
+                    pending: &mut admin_commands,
+                    save_requested: &mut save_requested,
+                }),
+                Err(e) => e,
+            };
+            req.reply(response);
+        }
+
+        for req in queries.poll() {
+            let response = req.query.answer(&w, &server);
+            req.reply(response);
+        }
+
+        let local_inputs =
+            (!admin_commands.is_empty()).then(|| std::mem::take(&mut admin_commands));
+        match server.poll(&w, Frame(w.get_tick()), local_inputs) {
+            ServerPollResult::Input(inputs) => {
+                for frame in inputs {
+                    assert_eq!(frame.frame.0, w.get_tick() + 1);
+                    let merged: WorldCommands = frame.inputs.into_iter().map(|x| x.inp).collect();
+                    w.tick(&mut sched, merged.as_ref());
+                }
             }
+            ServerPollResult::Wait(Some(unsent)) => admin_commands = unsent,
+            ServerPollResult::Wait(None) => {}
         }
 
-        if last_saved.elapsed().as_secs() > opt.autosave {
+        if save_requested || last_saved.elapsed().as_secs() > opt.autosave {
+            save_requested = false;
             w.save_to_disk("world");
+            if let Some(ref path) = opt.stats_export {
+                if let Err(e) = w.read::<StatsRecorder>().export(path) {
+                    log::error!("could not export stats to {}: {}", path.display(), e);
+                }
+            }
             last_saved = Instant::now();
         }
 
         std::thread::sleep(Duration::from_millis(1));
     }
 }
+
+/// The world to start from: a new scenario, the savegame or an empty world
+fn initial_world(opt: &Opt) -> Option<Simulation> {
+    match opt.scenario {
+        Some(ref name) => {
+            let w = Simulation::new_scenario(ScenarioPrototypeID::new(name));
+            if w.is_none() {
+                log::error!("could not start scenario {}", name);
+            }
+            w
+        }
+        None => Some(unwrap_or!(Simulation::load_from_disk("world"), {
+            log::info!("savegame not found defaulting to empty");
+            Simulation::new(true)
+        })),
+    }
+}
+
+fn run_bench(opt: &Opt, ticks: u64) {
+    log::info!("benchmarking {} ticks with version: {}", ticks, VERSION);
+
+    let mut sched = Simulation::schedule();
+    let mut sim = match opt.bench_replay {
+        Some(ref name) => {
+            let Some(replay) = Simulation::load_replay_from_disk(name) else {
+                log::error!("could not load replay {}", name);
+                return;
+            };
+            let (mut sim, mut loader) = Simulation::from_replay(replay);
+            while !loader.advance_tick(&mut sim, &mut sched) {}
+            log::info!("replay {} played until tick {}", name, sim.get_tick());
+            sim
+        }
+        None => unwrap_or!(initial_world(opt), return),
+    };
+
+    let report = bench::run(&mut sim, &mut sched, ticks, !opt.bench_sequential);
+    log::info!(
+        "{} ticks in {:.2}s: {:.1} ticks/s, {:.3}ms per tick (p99 {:.3}ms)",
+        report.ticks,
+        report.total_seconds,
+        report.ticks_per_second,
+        report.tick.avg_ms,
+        report.tick.p99_ms
+    );
+
+    if let Err(e) = report.save(&opt.bench_report) {
+        log::error!(
+            "could not write report to {}: {}",
+            opt.bench_report.display(),
+            e
+        );
+        return;
+    }
+    log::info!("report written to {}", opt.bench_report.display());
+}
+
+fn run_bisect(opt: &Opt, name: &str) {
+    let Some(replay) = Simulation::load_replay_from_disk(name) else {
+        log::error!("could not load replay {}", name);
+        return;
+    };
+
+    if let Some(ref path) = opt.record_trace {
+        let trace = HashTrace::record(replay, opt.bisect_period);
+        let res = JSONPretty::encode(&trace).and_then(|v| std::fs::write(path, v));
+        match res {
+            Ok(()) => log::info!(
+                "hash trace of {} ticks written to {}",
+                trace.hashes.last().map_or(0, |(t, _)| t.0),
+                path.display()
+            ),
+            Err(e) => log::error!("could not write trace to {}: {}", path.display(), e),
+        }
+        return;
+    }
+
+    let divergence = match opt.bisect_trace {
+        Some(ref path) => {
+            let trace = match std::fs::read(path).and_then(|v| JSONPretty::decode(&v)) {
+                Ok(trace) => trace,
+                Err(e) => {
+                    log::error!("could not read trace {}: {}", path.display(), e);
+                    return;
+                }
+            };
+            check_trace(&replay, &trace)
+        }
+        None => bisect_replay(&replay, opt.bisect_period),
+    };
+
+    let Some(d) = divergence else {
+        log::info!("no divergence found, the replay is deterministic");
+        return;
+    };
+
+    log::error!(
+        "diverged at tick {} in: {}",
+        d.tick.0,
+        d.diverging.join(", ")
+    );
+    if let Some(diff) = d.diff {
+        println!("{}", diff);
+    }
+}
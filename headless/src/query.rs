@@ -0,0 +1,447 @@
+//! Read-only query endpoint of the headless server
+//!
+//! If a query port is given, a minimal HTTP server bound to localhost answers GET requests with
+//! JSON, so that dashboards and test scripts can observe the world without joining as a client:
+//! - `/tick`: current tick, time of the day, pause and speed
+//! - `/map`: number of roads, intersections, buildings and lots
+//! - `/buildings?kind=<kind>`: buildings, optionally only of a kind (house, train-station,
+//!   external-trading or a company/freight station prototype name)
+//! - `/market` or `/market?item=<item>`: orders count per item, or the orders of one item
+//! - `/government`: money of the city
+//! - `/players`: connected players
+
+use common::saveload::{Encoder, JSON};
+use geom::Vec2;
+use networking::{Role, Server};
+use prototypes::{prototypes_iter, DayTime, GameTime, ItemPrototype, Money};
+use serde::Serialize;
+use simulation::economy::{Government, Market};
+use simulation::map::BuildingID;
+use simulation::world_command::WorldCommands;
+use simulation::Simulation;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Query {
+    Tick,
+    Map,
+    Buildings { kind: Option<String> },
+    Market { item: Option<String> },
+    Government,
+    Players,
+}
+
+/// A query that could not be answered, with its HTTP status
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl QueryError {
+    fn not_found(message: String) -> Self {
+        Self {
+            status: 404,
+            message,
+        }
+    }
+
+    fn bad_request(message: String) -> Self {
+        Self {
+            status: 400,
+            message,
+        }
+    }
+}
+
+impl Query {
+    /// Parses the target of a request, e.g. `/market?item=wheat`
+    pub fn parse(target: &str) -> Result<Self, QueryError> {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut param = None;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if param.is_some() {
+                return Err(QueryError::bad_request(format!(
+                    "unexpected parameter: {}",
+                    key
+                )));
+            }
+            param = Some((key, value.to_string()));
+        }
+
+        // the only parameter a query accepts, if any
+        let expect = |name: Option<&str>| match param {
+            None => Ok(None),
+            Some((key, ref value)) if Some(key) == name && !value.is_empty() => {
+                Ok(Some(value.clone()))
+            }
+            Some((key, _)) => Err(QueryError::bad_request(format!(
+                "unexpected parameter: {}",
+                key
+            ))),
+        };
+
+        Ok(match path.trim_end_matches('/') {
+            "/tick" => expect(None).map(|_| Query::Tick)?,
+            "/map" => expect(None).map(|_| Query::Map)?,
+            "/buildings" => Query::Buildings {
+                kind: expect(Some("kind"))?,
+            },
+            "/market" => Query::Market {
+                item: expect(Some("item"))?,
+            },
+            "/government" => expect(None).map(|_| Query::Government)?,
+            "/players" => expect(None).map(|_| Query::Players)?,
+            _ => return Err(QueryError::not_found(format!("unknown path: {}", path))),
+        })
+    }
+
+    /// Answers the query as JSON
+    pub fn answer(
+        &self,
+        sim: &Simulation,
+        server: &Server<Simulation, WorldCommands>,
+    ) -> Result<Vec<u8>, QueryError> {
+        let encoded = match self {
+            Query::Tick => {
+                let time = sim.read::<GameTime>();
+                JSON::encode(&TickInfo {
+                    tick: time.tick.0,
+                    daytime: time.daytime,
+                    paused: server.is_paused(),
+                    speed: server.speed(),
+                })
+            }
+            Query::Map => {
+                let map = sim.map();
+                JSON::encode(&MapSummary {
+                    roads: map.roads().len(),
+                    intersections: map.intersections().len(),
+                    buildings: map.buildings().len(),
+                    lots: map.lots().len(),
+                })
+            }
+            Query::Buildings { kind } => {
+                let map = sim.map();
+                let buildings = map
+                    .buildings()
+                    .values()
+                    .map(|b| (b, b.kind.name()))
+                    .filter(|(_, name)| kind.as_ref().map_or(true, |k| k == name))
+                    .map(|(b, kind)| BuildingInfo {
+                        id: b.id,
+                        kind,
+                        pos: b.door_pos.xy(),
+                        height: b.height,
+                    })
+                    .collect::<Vec<_>>();
+                JSON::encode(&buildings)
+            }
+            Query::Market { item: None } => {
+                let market = sim.read::<Market>();
+                let items = market
+                    .iter()
+                    .map(|(id, m)| ItemSummary {
+                        item: id.prototype().name.clone(),
+                        ext_value: m.ext_value,
+                        buy_orders: m.buy_orders().len(),
+                        sell_orders: m.sell_orders().len(),
+                    })
+                    .collect::<Vec<_>>();
+                JSON::encode(&items)
+            }
+            Query::Market { item: Some(item) } => {
+                let Some(proto) = prototypes_iter::<ItemPrototype>().find(|p| &p.name == item)
+                else {
+                    return Err(QueryError::not_found(format!("unknown item: {}", item)));
+                };
+                let market = sim.read::<Market>();
+                let Some((_, m)) = market.iter().find(|(id, _)| **id == proto.id) else {
+                    return Err(QueryError::not_found(format!("unknown item: {}", item)));
+                };
+                JSON::encode(&ItemOrders {
+                    item: item.clone(),
+                    ext_value: m.ext_value,
+                    buy_orders: m
+                        .buy_orders()
+                        .iter()
+                        .map(|(soul, o)| Order {
+                            soul: soul.to_string(),
+                            pos: o.pos,
+                            qty: o.qty,
+                            stock: None,
+                        })
+                        .collect(),
+                    sell_orders: m
+                        .sell_orders()
+                        .iter()
+                        .map(|(soul, o)| Order {
+                            soul: soul.to_string(),
+                            pos: o.pos,
+                            qty: o.qty,
+                            stock: Some(o.stock),
+                        })
+                        .collect(),
+                })
+            }
+            Query::Government => {
+                let money = sim.read::<Government>().money;
+                JSON::encode(&GovernmentInfo {
+                    money: money.to_string(),
+                    money_cents: money.cents(),
+                })
+            }
+            Query::Players => {
+                let players = server
+                    .players()
+                    .into_iter()
+                    .map(|p| PlayerInfo {
+                        name: p.name,
+                        role: p.role,
+                        state: p.state,
+                        lag: p.lag,
+                    })
+                    .collect::<Vec<_>>();
+                JSON::encode(&players)
+            }
+        };
+
+        encoded.map_err(|e| QueryError {
+            status: 500,
+            message: e.to_string(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct TickInfo {
+    tick: u64,
+    daytime: DayTime,
+    paused: bool,
+    speed: u32,
+}
+
+#[derive(Serialize)]
+struct MapSummary {
+    roads: usize,
+    intersections: usize,
+    buildings: usize,
+    lots: usize,
+}
+
+#[derive(Serialize)]
+struct BuildingInfo {
+    id: BuildingID,
+    kind: String,
+    pos: Vec2,
+    height: f32,
+}
+
+#[derive(Serialize)]
+struct ItemSummary {
+    item: String,
+    ext_value: Money,
+    buy_orders: usize,
+    sell_orders: usize,
+}
+
+#[derive(Serialize)]
+struct ItemOrders {
+    item: String,
+    ext_value: Money,
+    buy_orders: Vec<Order>,
+    sell_orders: Vec<Order>,
+}
+
+#[derive(Serialize)]
+struct Order {
+    soul: String,
+    pos: Vec2,
+    qty: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stock: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct GovernmentInfo {
+    money: String,
+    money_cents: i64,
+}
+
+#[derive(Serialize)]
+struct PlayerInfo {
+    name: String,
+    role: Role,
+    state: String,
+    /// Frames behind the server, None if too late
+    lag: Option<u64>,
+}
+
+/// A parsed query received on the query port, answered by the main loop
+pub struct QueryRequest {
+    pub query: Query,
+    reply: Sender<Result<Vec<u8>, QueryError>>,
+}
+
+impl QueryRequest {
+    pub fn reply(self, response: Result<Vec<u8>, QueryError>) {
+        let _ = self.reply.send(response);
+    }
+}
+
+pub struct QueryServer {
+    requests: Receiver<QueryRequest>,
+}
+
+impl QueryServer {
+    pub fn start(port: Option<u16>) -> Self {
+        let (tx, rx) = channel();
+
+        if let Some(port) = port {
+            match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+                Ok(listener) => {
+                    log::info!("query endpoint listening on http://127.0.0.1:{}", port);
+                    std::thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            let tx = tx.clone();
+                            std::thread::spawn(move || handle_query_conn(stream, tx));
+                        }
+                    });
+                }
+                Err(e) => log::error!("could not start query endpoint on port {}: {}", port, e),
+            }
+        }
+
+        Self { requests: rx }
+    }
+
+    pub fn poll(&self) -> impl Iterator<Item = QueryRequest> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+/// Answers a single request then closes the connection
+fn handle_query_conn(stream: TcpStream, requests: Sender<QueryRequest>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // skip the headers, the requests have no body
+    let mut header = String::new();
+    loop {
+        header.clear();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Query::parse(target).and_then(|query| {
+            let (reply_tx, reply_rx) = channel();
+            requests
+                .send(QueryRequest {
+                    query,
+                    reply: reply_tx,
+                })
+                .map_err(|_| QueryError {
+                    status: 503,
+                    message: "server is shutting down".to_string(),
+                })?;
+            reply_rx
+                .recv_timeout(Duration::from_secs(10))
+                .unwrap_or_else(|_| {
+                    Err(QueryError {
+                        status: 503,
+                        message: "no response from server".to_string(),
+                    })
+                })
+        }),
+        (Some(_), Some(_)) => Err(QueryError {
+            status: 405,
+            message: "only GET is supported".to_string(),
+        }),
+        _ => Err(QueryError::bad_request("malformed request".to_string())),
+    };
+
+    let (status, body) = match response {
+        Ok(body) => (200, body),
+        Err(e) => (
+            e.status,
+            JSON::encode(&ErrorBody { error: e.message }).unwrap_or_default(),
+        ),
+    };
+    let _ = write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        body.len()
+    );
+    let _ = writer.write_all(&body);
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+
+    #[test]
+    fn parse_queries() {
+        assert_eq!(Query::parse("/tick"), Ok(Query::Tick));
+        assert_eq!(Query::parse("/players/"), Ok(Query::Players));
+        assert_eq!(
+            Query::parse("/buildings"),
+            Ok(Query::Buildings { kind: None })
+        );
+        assert_eq!(
+            Query::parse("/buildings?kind=house"),
+            Ok(Query::Buildings {
+                kind: Some("house".to_string())
+            })
+        );
+        assert_eq!(
+            Query::parse("/market?item=wheat"),
+            Ok(Query::Market {
+                item: Some("wheat".to_string())
+            })
+        );
+        assert_eq!(Query::parse("/dance").unwrap_err().status, 404);
+        assert_eq!(Query::parse("/market?kind=wheat").unwrap_err().status, 400);
+        assert_eq!(Query::parse("/market?item=").unwrap_err().status, 400);
+        assert_eq!(Query::parse("/tick?item=wheat").unwrap_err().status, 400);
+        assert_eq!(
+            Query::parse("/buildings?kind=house&kind=farm")
+                .unwrap_err()
+                .status,
+            400
+        );
+    }
+}
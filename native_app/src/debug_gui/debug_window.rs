@@ -335,6 +335,11 @@ pub fn debug_electricity(tess: &mut Tesselator, sim: &Simulation, _: &UiWorld) -
             let road = map.get(r)?;
             Some(road.points.middle().up(5.0))
         }
+        NetworkObjectID::Pylon(p) => Some(map.get(p)?.pos),
+        NetworkObjectID::PowerLine(l) => {
+            let line = map.get(l)?;
+            Some((line.src_pos + line.dst_pos) * 0.5)
+        }
     };
 
     for network in map.electricity.networks() {
@@ -24,7 +24,7 @@ use crate::inputmap::{Bindings, InputAction, InputMap};
 use crate::rendering::{InstancedRender, MapRenderOptions, MapRenderer, OrbitCamera};
 use crate::uiworld::{SaveLoadState, UiWorld};
 use prototypes::GameTime;
-use simulation::utils::scheduler::SeqSchedule;
+use simulation::utils::scheduler::Schedule;
 
 pub const VERSION: &str = include_str!("../../VERSION");
 
@@ -32,7 +32,7 @@ pub const VERSION: &str = include_str!("../../VERSION");
 pub struct State {
     pub sim: Arc<RwLock<Simulation>>,
     pub uiw: UiWorld,
-    pub game_schedule: SeqSchedule,
+    pub game_schedule: Schedule,
 
     instanced_renderer: InstancedRender,
     map_renderer: MapRenderer,
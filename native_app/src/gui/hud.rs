@@ -22,6 +22,7 @@ pub mod chat;
 pub mod keybinds;
 mod menu;
 mod time_controls;
+pub mod toasts;
 pub mod toolbox;
 pub mod windows;
 
@@ -45,6 +46,7 @@ pub fn render_newgui(uiworld: &UiWorld, sim: &Simulation) {
         new_toolbox(uiworld, sim);
         menu_bar(uiworld, sim);
         chat::chat(uiworld, sim);
+        toasts::toasts(uiworld, sim);
         new_inspector(uiworld, sim);
         uiworld.write::<GuiState>().windows.render(uiworld, sim);
         time_controls(uiworld, sim);
@@ -80,6 +82,9 @@ fn power_errors(uiworld: &UiWorld, sim: &Simulation) {
         let mut buildings_with_issues = Vec::with_capacity(network.buildings.len());
 
         for &building in &network.buildings {
+            if !flow.building_blackout(building) {
+                continue;
+            }
             let Some(b) = map.get(building) else {
                 continue;
             };
@@ -11,6 +11,7 @@ use goryak::{
 use simulation::economy::Government;
 use simulation::Simulation;
 
+use crate::gui::overlay::overlay_menu;
 use crate::gui::{ExitState, GuiState};
 use crate::i18n::I18n;
 use crate::inputmap::{InputAction, InputMap};
@@ -32,7 +33,8 @@ pub fn menu_bar(uiworld: &UiWorld, sim: &Simulation) {
 
                             l.show(|| {
                                 let mut gui = uiworld.write::<GuiState>();
-                                gui.windows.menu(uiworld);
+                                gui.windows.menu(uiworld, sim);
+                                overlay_menu(uiworld);
                                 save_window(&mut gui, uiworld);
                                 textc(
                                     on_primary_container(),
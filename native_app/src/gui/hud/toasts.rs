@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use yakui::{reflow, Alignment, Color, Dim2, Pivot};
+
+use goryak::{blur_bg, mincolumn, on_secondary_container, padxy, secondary_container, textc};
+use prototypes::{GameTime, Tick};
+use simulation::events::{SimEvent, SimEvents};
+use simulation::map::BuildingKind;
+use simulation::multiplayer::chat::MessageKind;
+use simulation::Simulation;
+
+use crate::i18n::I18n;
+use crate::uiworld::UiWorld;
+
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+const MAX_TOASTS: usize = 5;
+
+struct Toast {
+    text: String,
+    warning: bool,
+    shown_at: Instant,
+}
+
+#[derive(Default)]
+pub struct ToastsState {
+    /// Events up to this tick were already shown
+    last_seen: Option<Tick>,
+    toasts: VecDeque<Toast>,
+}
+
+/// Shows the notable simulation events for a few seconds in the top right corner
+pub fn toasts(uiw: &UiWorld, sim: &Simulation) {
+    profiling::scope!("hud::toasts");
+    let mut state = uiw.write::<ToastsState>();
+    let i18n = uiw.read::<I18n>();
+    let tick = sim.read::<GameTime>().tick;
+
+    // don't show the events of a save that was just loaded
+    let last_seen = match state.last_seen {
+        Some(last_seen) if last_seen <= tick => last_seen,
+        _ => tick,
+    };
+    for (event_tick, event) in sim.read::<SimEvents>().recent() {
+        if *event_tick <= last_seen {
+            continue;
+        }
+        let Some((kind, _)) = event.notice(sim) else {
+            continue;
+        };
+        let Some(text) = toast_text(&i18n, sim, event) else {
+            continue;
+        };
+        if state.toasts.len() == MAX_TOASTS {
+            state.toasts.pop_front();
+        }
+        state.toasts.push_back(Toast {
+            text,
+            warning: matches!(kind, MessageKind::Warning),
+            shown_at: Instant::now(),
+        });
+    }
+    state.last_seen = Some(tick);
+    state
+        .toasts
+        .retain(|t| t.shown_at.elapsed() < TOAST_DURATION);
+
+    if state.toasts.is_empty() {
+        return;
+    }
+
+    reflow(
+        Alignment::TOP_RIGHT,
+        Pivot::TOP_RIGHT,
+        Dim2::pixels(-10.0, 50.0),
+        || {
+            mincolumn(5.0, || {
+                for toast in &state.toasts {
+                    blur_bg(secondary_container().with_alpha(0.7), 5.0, || {
+                        padxy(8.0, 5.0, || {
+                            let color = if toast.warning {
+                                Color::rgb(255, 170, 60)
+                            } else {
+                                on_secondary_container()
+                            };
+                            textc(color, toast.text.clone());
+                        });
+                    });
+                }
+            });
+        },
+    );
+}
+
+fn toast_text(i18n: &I18n, sim: &Simulation, event: &SimEvent) -> Option<String> {
+    Some(match *event {
+        SimEvent::BuildingBuilt(id) => {
+            let name = match sim.map().buildings().get(id)?.kind {
+                BuildingKind::GoodsCompany(id) => {
+                    i18n.proto_label("goods_company", &id.prototype().name, &id.prototype().label)
+                }
+                BuildingKind::RailFreightStation(id) => i18n.proto_label(
+                    "freight_station",
+                    &id.prototype().name,
+                    &id.prototype().label,
+                ),
+                BuildingKind::Leisure(id) => {
+                    i18n.proto_label("leisure", &id.prototype().name, &id.prototype().label)
+                }
+                BuildingKind::TrainStation => i18n.tr("ui.inspect.train_station"),
+                BuildingKind::ExternalTrading => i18n.tr("ui.inspect.external_trading"),
+                BuildingKind::House => return None,
+            };
+            i18n.tr_args("ui.toasts.building_built", &[("name", name)])
+        }
+        SimEvent::BlackoutStarted(_) => i18n.tr("ui.toasts.blackout_started"),
+        SimEvent::BlackoutEnded(_) => i18n.tr("ui.toasts.blackout_ended"),
+        SimEvent::TrainStuck(_) => i18n.tr("ui.toasts.train_stuck"),
+        SimEvent::CitizensLeft { n, .. } => {
+            i18n.tr_args("ui.toasts.citizens_left", &[("n", n.to_string())])
+        }
+        SimEvent::TradeMade(_) => return None,
+    })
+}
@@ -0,0 +1,55 @@
+use yakui::widgets::List;
+use yakui::{column, label, CrossAxisAlignment, MainAxisAlignment};
+
+use goryak::{padxy, selectable_label_primary};
+use simulation::economy::ZoneDemand;
+use simulation::map::LotKind;
+use simulation::Simulation;
+
+use crate::gui::hud::toolbox::select_triangle;
+use crate::gui::lotbrush::LotBrushResource;
+use crate::i18n::I18n;
+use crate::uiworld::UiWorld;
+
+pub fn lotbrush_properties(uiw: &UiWorld, sim: &Simulation) {
+    let i18n = uiw.read::<I18n>();
+    let state = &mut *uiw.write::<LotBrushResource>();
+    let demand = *sim.read::<ZoneDemand>();
+
+    padxy(0.0, 10.0, || {
+        let mut l = List::row();
+        l.main_axis_alignment = MainAxisAlignment::Center;
+        l.cross_axis_alignment = CrossAxisAlignment::Start;
+        l.item_spacing = 10.0;
+        l.show(|| {
+            let kind_choices = &[
+                (LotKind::Residential, "ui.lotbrush.residential"),
+                (LotKind::Commercial, "ui.lotbrush.commercial"),
+                (LotKind::Industrial, "ui.lotbrush.industrial"),
+                (LotKind::Mixed, "ui.lotbrush.mixed"),
+                (LotKind::Unassigned, "ui.lotbrush.unassigned"),
+            ];
+
+            for (kind, name) in kind_choices {
+                column(|| {
+                    let enabled = state.kind == *kind;
+                    if selectable_label_primary(enabled, &i18n.tr(name)).clicked {
+                        state.kind = *kind;
+                    }
+
+                    if *kind != LotKind::Unassigned {
+                        label(format!(
+                            "{}: {:+.0}%",
+                            i18n.tr("ui.lotbrush.demand"),
+                            demand.get(*kind) * 100.0
+                        ));
+                    }
+
+                    if enabled {
+                        select_triangle(uiw);
+                    }
+                });
+            }
+        });
+    });
+}
@@ -17,6 +17,8 @@ use crate::inputmap::{InputAction, InputMap};
 use crate::uiworld::UiWorld;
 
 pub mod building;
+pub mod lotbrush;
+pub mod powerline;
 pub mod roadbuild;
 pub mod roadedit;
 pub mod terraforming;
@@ -67,13 +69,15 @@ pub fn new_toolbox(uiworld: &UiWorld, sim: &Simulation) {
     });
 }
 
-fn tool_properties(uiw: &UiWorld, _sim: &Simulation) -> bool {
+fn tool_properties(uiw: &UiWorld, sim: &Simulation) -> bool {
     let tool = *uiw.read::<Tool>();
 
     match tool {
         Tool::Hand => return false,
         Tool::Bulldozer => return false,
-        Tool::LotBrush => return false,
+        Tool::LotBrush => {
+            lotbrush::lotbrush_properties(uiw, sim);
+        }
         Tool::RoadbuildStraight | Tool::RoadbuildCurved => {
             roadbuild::roadbuild_properties(uiw);
         }
@@ -89,6 +93,9 @@ fn tool_properties(uiw: &UiWorld, _sim: &Simulation) -> bool {
         Tool::Terraforming => {
             terraforming::terraform_properties(uiw);
         }
+        Tool::PowerLine => {
+            powerline::powerline_properties(uiw);
+        }
     }
     true
 }
@@ -103,6 +110,7 @@ fn tools_list(uiworld: &UiWorld) {
         ("toolbar_bulldozer", Tool::Bulldozer),
         ("toolbar_train", Tool::Train),
         ("toolbar_terraform", Tool::Terraforming),
+        ("toolbar_power_line", Tool::PowerLine),
     ];
 
     for (name, tool) in &tools {
@@ -0,0 +1,42 @@
+use yakui::widgets::List;
+use yakui::{column, CrossAxisAlignment, MainAxisAlignment};
+
+use goryak::{padxy, selectable_label_primary};
+use simulation::map::PowerLineKind;
+
+use crate::gui::hud::toolbox::select_triangle;
+use crate::gui::powerline::PowerLineResource;
+use crate::i18n::I18n;
+use crate::uiworld::UiWorld;
+
+pub fn powerline_properties(uiw: &UiWorld) {
+    let i18n = uiw.read::<I18n>();
+    let state = &mut *uiw.write::<PowerLineResource>();
+
+    padxy(0.0, 10.0, || {
+        let mut l = List::row();
+        l.main_axis_alignment = MainAxisAlignment::Center;
+        l.cross_axis_alignment = CrossAxisAlignment::Start;
+        l.item_spacing = 10.0;
+        l.show(|| {
+            let kind_choices = &[
+                (PowerLineKind::LowVoltage, "ui.powerline.low_voltage"),
+                (PowerLineKind::HighVoltage, "ui.powerline.high_voltage"),
+            ];
+
+            for (kind, name) in kind_choices {
+                column(|| {
+                    let enabled = state.kind == *kind;
+                    let text = i18n.tr_args(name, &[("capacity", kind.capacity().to_string())]);
+                    if selectable_label_primary(enabled, &text).clicked {
+                        state.kind = *kind;
+                    }
+
+                    if enabled {
+                        select_triangle(uiw);
+                    }
+                });
+            }
+        });
+    });
+}
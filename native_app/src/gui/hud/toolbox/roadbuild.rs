@@ -4,7 +4,7 @@ use yakui::{
     Pivot, Vec2,
 };
 
-use goryak::{image_button, mincolumn, minrow, padxy, primary};
+use goryak::{image_button, mincolumn, minrow, padxy, primary, selectable_label_primary};
 use simulation::map::LanePatternBuilder;
 
 use crate::gui::hud::toolbox::updown_value;
@@ -133,6 +133,10 @@ pub fn roadbuild_properties(uiw: &UiWorld) {
             // Road elevation
             updown_value(&mut state.height_offset, 2.0, "m");
 
+            if selectable_label_primary(state.tunnel, &i18n.tr("ui.roadbuild.tunnel")).clicked {
+                state.tunnel = !state.tunnel;
+            }
+
             // image name, label, builder
             let builders: &[(&str, &str, LanePatternBuilder)] = &[
                 (
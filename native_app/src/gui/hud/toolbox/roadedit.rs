@@ -3,7 +3,7 @@ use yakui::{
     column, image, reflow, Alignment, CrossAxisAlignment, Dim2, MainAxisAlignment, Pivot, Vec2,
 };
 
-use goryak::{padxy, primary_image_button};
+use goryak::{padxy, primary_image_button, selectable_label_primary};
 use simulation::map::LightPolicy;
 
 use crate::gui::hud::toolbox;
@@ -16,9 +16,6 @@ use crate::uiworld::UiWorld;
 pub fn roadedit_properties(uiw: &UiWorld) {
     let i18n = uiw.read::<I18n>();
     let state = &mut *uiw.write::<RoadEditorResource>();
-    let Some(ref mut v) = state.inspect else {
-        return;
-    };
 
     padxy(0.0, 10.0, || {
         let mut l = List::row();
@@ -26,6 +23,15 @@ pub fn roadedit_properties(uiw: &UiWorld) {
         l.cross_axis_alignment = CrossAxisAlignment::Center;
         l.item_spacing = 10.0;
         l.show(|| {
+            if selectable_label_primary(state.upgrade_roads, &i18n.tr("ui.roadedit.upgrade_roads"))
+                .clicked
+            {
+                state.upgrade_roads = !state.upgrade_roads;
+            }
+
+            let Some(ref mut v) = state.inspect else {
+                return;
+            };
             let texs = uiw.read::<UiTextures>();
 
             let light_policy_choices = &[
@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::path::Path;
 
 use yakui::paint::PaintMesh;
 use yakui::widgets::{CountGrid, List, Pad};
@@ -10,13 +11,14 @@ use yakui::{
 use engine::Tesselator;
 use geom::AABB;
 use goryak::{
-    constrained_viewport, mincolumn, minrow, on_primary_container, padxy, pady,
-    selectable_label_primary, sized_canvas, textc, VertScrollSize, Window,
+    button_primary, checkbox_value, constrained_viewport, mincolumn, minrow, on_primary_container,
+    padxy, pady, selectable_label_primary, sized_canvas, textc, VertScrollSize, Window,
 };
 use prototypes::{ItemID, DELTA_F64};
 use simulation::economy::{
-    EcoStats, ItemHistories, Market, HISTORY_SIZE, LEVEL_FREQS, LEVEL_NAMES,
+    EcoStats, ItemHistories, Market, Metric, StatsRecorder, HISTORY_SIZE, LEVEL_FREQS, LEVEL_NAMES,
 };
+use simulation::world_command::WorldCommand;
 use simulation::Simulation;
 
 use crate::i18n::I18n;
@@ -28,6 +30,7 @@ pub enum EconomyTab {
     ImportExports,
     InternalTrade,
     MarketPrices,
+    Statistics,
 }
 
 #[derive(Copy, Clone, Default, PartialEq, Eq)]
@@ -63,6 +66,7 @@ pub fn economy(uiw: &UiWorld, sim: &Simulation, opened: &mut bool) {
                 ("ui.economy.tab.import_exports", EconomyTab::ImportExports),
                 ("ui.economy.tab.internal_trade", EconomyTab::InternalTrade),
                 ("ui.economy.tab.market_prices", EconomyTab::MarketPrices),
+                ("ui.economy.tab.statistics", EconomyTab::Statistics),
             ];
 
             for (label, tab) in tabs {
@@ -339,6 +343,9 @@ pub fn economy(uiw: &UiWorld, sim: &Simulation, opened: &mut bool) {
             EconomyTab::MarketPrices => {
                 render_market_prices(sim);
             }
+            EconomyTab::Statistics => {
+                render_statistics(uiw, sim, &i18n);
+            }
         }
     });
 }
@@ -362,6 +369,73 @@ fn render_market_prices(sim: &Simulation) {
     });
 }
 
+/// Chooses the metrics recorded every hour and exports them to the world folder
+fn render_statistics(uiw: &UiWorld, sim: &Simulation, i18n: &I18n) {
+    let recorder = sim.read::<StatsRecorder>();
+    let status = use_state(String::new);
+
+    mincolumn(5.0, || {
+        textc(
+            on_primary_container(),
+            i18n.tr_args(
+                "ui.economy.stats_samples",
+                &[("n", recorder.samples().len().to_string())],
+            ),
+        );
+
+        for metric in Metric::ALL {
+            let was_recorded = recorder.metrics().contains(&metric);
+            let mut recorded = was_recorded;
+            checkbox_value(
+                &mut recorded,
+                on_primary_container(),
+                i18n.tr(&format!("ui.economy.metric.{}", metric.name())),
+            );
+            if recorded != was_recorded {
+                let metrics = Metric::ALL
+                    .into_iter()
+                    .filter(|m| {
+                        if *m == metric {
+                            recorded
+                        } else {
+                            recorder.metrics().contains(m)
+                        }
+                    })
+                    .collect();
+                uiw.commands()
+                    .push(WorldCommand::SetRecordedMetrics(metrics));
+            }
+        }
+
+        minrow(5.0, || {
+            for path in ["world/stats.csv", "world/stats.jsonl"] {
+                if button_primary(
+                    i18n.tr_args("ui.economy.export_to", &[("path", path.to_string())]),
+                )
+                .show()
+                .clicked
+                {
+                    let msg = match recorder.export(Path::new(path)) {
+                        Ok(()) => {
+                            i18n.tr_args("ui.economy.exported", &[("path", path.to_string())])
+                        }
+                        Err(e) => {
+                            log::error!("could not export stats to {}: {}", path, e);
+                            i18n.tr_args("ui.economy.export_failed", &[("error", e.to_string())])
+                        }
+                    };
+                    status.set(msg);
+                }
+            }
+        });
+
+        let status = status.borrow();
+        if !status.is_empty() {
+            textc(on_primary_container(), status.clone());
+        }
+    });
+}
+
 /*
 let render_history = |ui: &mut Ui, history: &ItemHistories, hist_type: HistoryType| {
     egui_plot::Plot::new("ecoplot")
@@ -2,10 +2,11 @@
 use crate::uiworld::{SaveLoadState, UiWorld};
 use egui::{Color32, DroppedFile, Widget};
 use goryak::{
-    button_primary, error, minrow, on_primary, on_secondary_container, primary, textc, ProgressBar,
-    Window,
+    button_primary, dragvalue, error, minrow, on_primary, on_secondary_container, primary, textc,
+    ProgressBar, Window,
 };
-use simulation::utils::scheduler::SeqSchedule;
+use prototypes::{ScenarioPrototype, Tick};
+use simulation::utils::scheduler::Schedule;
 use simulation::Simulation;
 use std::path::PathBuf;
 use yakui::widgets::Pad;
@@ -16,6 +17,8 @@ pub struct LoadState {
     curpath: Option<PathBuf>,
     load_fail: String,
     has_save: bool,
+    /// Tick selected on the scrub bar
+    scrub_tick: u64,
 }
 
 impl Default for LoadState {
@@ -24,6 +27,7 @@ impl Default for LoadState {
             curpath: None,
             load_fail: String::new(),
             has_save: std::fs::metadata("world/world_replay.json").is_ok(),
+            scrub_tick: 0,
         }
     }
 }
@@ -37,6 +41,8 @@ pub fn load(uiw: &UiWorld, _: &Simulation, opened: &mut bool) {
     let load_world_label = uiw.read::<I18n>().tr("ui.load.load_world");
     let failed_label = uiw.read::<I18n>().tr("ui.load.failed");
     let no_replay_label = uiw.read::<I18n>().tr("ui.load.no_replay");
+    let scenarios_label = uiw.read::<I18n>().tr("ui.load.scenarios");
+    let seek_label = uiw.read::<I18n>().tr("ui.load.seek");
 
     Window {
         title: title.into(),
@@ -52,13 +58,27 @@ pub fn load(uiw: &UiWorld, _: &Simulation, opened: &mut bool) {
             uiw.write::<SaveLoadState>().please_load_sim = Some(Simulation::new(true));
         }
 
+        textc(on_secondary_container(), scenarios_label.clone());
+        for scenario in ScenarioPrototype::iter() {
+            let label = uiw
+                .read::<I18n>()
+                .proto_label("scenario", &scenario.name, &scenario.label);
+            if button_primary(label).show().clicked {
+                match Simulation::new_scenario(scenario.id) {
+                    Some(sim) => uiw.write::<SaveLoadState>().please_load_sim = Some(sim),
+                    None => state.load_fail = failed_label.clone(),
+                }
+            }
+        }
+
         if state.has_save {
             if button_primary(load_world_label.clone()).show().clicked {
                 let replay = Simulation::load_replay_from_disk("world");
 
                 if let Some(replay) = replay {
                     let (mut sim, mut loader) = Simulation::from_replay(replay);
-                    let mut s = SeqSchedule::default();
+                    loader.load_keyframes("world");
+                    let mut s = Schedule::default();
                     loader.advance_tick(&mut sim, &mut s); // advance by one tick to get the initial state (like map size info)
 
                     uiw.write::<SaveLoadState>().please_load = Some(loader);
@@ -123,6 +143,24 @@ pub fn load(uiw: &UiWorld, _: &Simulation, opened: &mut bool) {
                     loading.advance_n_ticks = 1000;
                 }
             });
+
+            minrow(5.0, || {
+                if button_primary("<").show().clicked && loading.pastt.0 > 0 {
+                    loading.speed = 0;
+                    loading.seek_to = Some(Tick(loading.pastt.0 - 1));
+                }
+
+                dragvalue()
+                    .min(0.0)
+                    .max(ticks_total as f64)
+                    .step(10.0)
+                    .show(&mut state.scrub_tick);
+
+                if button_primary(seek_label.clone()).show().clicked {
+                    loading.speed = 0;
+                    loading.seek_to = Some(Tick(state.scrub_tick));
+                }
+            });
         }
 
         if !state.load_fail.is_empty() {
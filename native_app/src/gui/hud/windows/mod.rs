@@ -1,11 +1,13 @@
 pub mod economy;
 pub mod load;
+pub mod scenario;
 pub mod settings;
 
 use crate::i18n::I18n;
 use crate::inputmap::{InputAction, InputMap};
 use crate::uiworld::UiWorld;
 use goryak::button_primary;
+use simulation::scenario::ScenarioState;
 use simulation::Simulation;
 
 #[cfg(feature = "multiplayer")]
@@ -16,12 +18,13 @@ pub struct GUIWindows {
     economy_open: bool,
     settings_open: bool,
     load_open: bool,
+    scenario_open: bool,
     #[cfg(feature = "multiplayer")]
     network_open: bool,
 }
 
 impl GUIWindows {
-    pub fn menu(&mut self, uiworld: &UiWorld) {
+    pub fn menu(&mut self, uiworld: &UiWorld, sim: &Simulation) {
         let i18n = uiworld.read::<I18n>();
         if button_primary(i18n.tr("ui.windows.economy")).show().clicked {
             self.economy_open ^= true;
@@ -38,6 +41,14 @@ impl GUIWindows {
             self.load_open ^= true;
         }
 
+        if sim.read::<ScenarioState>().scenario.is_some()
+            && button_primary(i18n.tr("ui.windows.scenario"))
+                .show()
+                .clicked
+        {
+            self.scenario_open ^= true;
+        }
+
         #[cfg(feature = "multiplayer")]
         if button_primary(i18n.tr("ui.windows.network")).show().clicked {
             self.network_open ^= true;
@@ -57,6 +68,7 @@ impl GUIWindows {
         economy::economy(uiworld, sim, &mut self.economy_open);
         settings::settings(uiworld, sim, &mut self.settings_open);
         load::load(uiworld, sim, &mut self.load_open);
+        scenario::scenario(uiworld, sim, &mut self.scenario_open);
 
         #[cfg(feature = "multiplayer")]
         network::network(uiworld, sim, &mut self.network_open);
@@ -6,11 +6,13 @@ use yakui::widgets::Pad;
 
 use common::saveload::Encoder;
 use goryak::{
-    button_primary, checkbox_value, error, on_secondary_container, outline, text_edit, textc,
-    Window,
+    button_primary, button_secondary, checkbox_value, error, on_secondary_container, outline,
+    text_edit, textc, Window,
 };
+use networking::Discovery;
 use simulation::Simulation;
 
+use crate::game_loop::VERSION;
 use crate::i18n::I18n;
 use crate::network::NetworkState;
 use crate::uiworld::UiWorld;
@@ -19,6 +21,11 @@ use crate::uiworld::UiWorld;
 pub struct NetworkConnectionInfo {
     pub name: String,
     pub ip: String,
+    #[serde(default)]
+    pub spectate: bool,
+    /// Given by the server owner to join with another role, not saved
+    #[serde(skip)]
+    pub password: String,
     #[serde(skip)]
     pub error: String,
     #[serde(skip)]
@@ -27,6 +34,9 @@ pub struct NetworkConnectionInfo {
     hashes: BTreeMap<String, u64>,
     #[serde(skip)]
     hashes_tick: u64,
+    /// Started when the window is first shown
+    #[serde(skip)]
+    discovery: Option<Discovery>,
 }
 
 fn label(x: impl Into<String>) {
@@ -75,12 +85,22 @@ pub fn network(uiworld: &UiWorld, sim: &Simulation, opened: &mut bool) {
                 divider(outline(), 5.0, 1.0);
 
                 text_edit(200.0, &mut info.ip, &i18n.tr("ui.network.ip"));
+                text_edit(200.0, &mut info.password, &i18n.tr("ui.network.password"));
+                checkbox_value(
+                    &mut info.spectate,
+                    on_secondary_container(),
+                    i18n.tr("ui.network.spectate").to_string(),
+                );
 
                 if button_primary(i18n.tr("ui.network.connect")).show().clicked {
                     if let Some(c) = crate::network::start_client(&mut info) {
                         *state = NetworkState::Client(c);
                     }
                 }
+
+                divider(outline(), 5.0, 1.0);
+
+                lan_servers(&mut info, &i18n);
             }
             NetworkState::Client(ref client) => {
                 label(client.lock().unwrap().describe());
@@ -95,6 +115,60 @@ pub fn network(uiworld: &UiWorld, sim: &Simulation, opened: &mut bool) {
     });
 }
 
+/// Lists the servers found on the local network, clicking one fills the IP field
+fn lan_servers(info: &mut NetworkConnectionInfo, i18n: &I18n) {
+    label(i18n.tr("ui.network.lan_servers"));
+
+    if info.discovery.is_none() {
+        match Discovery::new() {
+            Ok(d) => info.discovery = Some(d),
+            Err(e) => {
+                textc(error(), e.to_string());
+                return;
+            }
+        }
+    }
+    let Some(ref mut discovery) = info.discovery else {
+        return;
+    };
+    discovery.poll();
+
+    let mut picked = None;
+    for server in discovery.servers() {
+        let a = &server.announce;
+        let text = i18n.tr_args(
+            "ui.network.lan_server",
+            &[
+                ("name", a.name.clone()),
+                ("players", a.players.to_string()),
+                ("map", a.map.clone()),
+            ],
+        );
+        if button_secondary(format!("{} - {}", text, server.addr))
+            .show()
+            .clicked
+        {
+            picked = Some(server.addr);
+        }
+        if a.version != VERSION {
+            textc(
+                error(),
+                i18n.tr_args(
+                    "ui.network.other_version",
+                    &[("version", a.version.clone())],
+                ),
+            );
+        }
+    }
+    if discovery.servers().next().is_none() {
+        label(i18n.tr("ui.network.no_lan_servers"));
+    }
+
+    if let Some(addr) = picked {
+        info.ip = addr.to_string();
+    }
+}
+
 fn show_hashes(sim: &Simulation, info: &mut NetworkConnectionInfo, i18n: &I18n) {
     checkbox_value(
         &mut info.show_hashes,
@@ -0,0 +1,100 @@
+use goryak::{error, mincolumn, on_secondary_container, primary, textc, ProgressBar, Window};
+use prototypes::{ScenarioObjective, TICKS_PER_HOUR};
+use simulation::scenario::{ScenarioOutcome, ScenarioState};
+use simulation::Simulation;
+use yakui::widgets::Pad;
+use yakui::Vec2;
+
+use crate::i18n::I18n;
+use crate::uiworld::UiWorld;
+
+/// Scenario window
+/// Shows the objectives of the current scenario and their progress
+pub fn scenario(uiw: &UiWorld, sim: &Simulation, opened: &mut bool) {
+    let state = sim.read::<ScenarioState>();
+    let Some(proto) = state.prototype() else {
+        return;
+    };
+    let i18n = uiw.read::<I18n>();
+
+    Window {
+        title: i18n
+            .proto_label("scenario", &proto.name, &proto.label)
+            .into(),
+        pad: Pad::all(10.0),
+        radius: 10.0,
+        opened,
+        child_spacing: 10.0,
+    }
+    .show(|| {
+        if !proto.description.is_empty() {
+            textc(on_secondary_container(), proto.description.clone());
+        }
+
+        for (objective, progress) in proto.objectives.iter().zip(&state.progress) {
+            let text = match *objective {
+                ScenarioObjective::Population(_) => i18n.tr_args(
+                    "ui.scenario.objective.population",
+                    &[("target", format!("{}", progress.target))],
+                ),
+                ScenarioObjective::ExportPerHour { item, .. } => {
+                    let item = item.prototype();
+                    i18n.tr_args(
+                        "ui.scenario.objective.export_per_hour",
+                        &[
+                            ("target", format!("{}", progress.target)),
+                            ("item", i18n.proto_label("item", &item.name, &item.label)),
+                        ],
+                    )
+                }
+                ScenarioObjective::Money(_) => i18n.tr_args(
+                    "ui.scenario.objective.money",
+                    &[("target", format!("{}$", progress.target))],
+                ),
+            };
+
+            mincolumn(5.0, || {
+                textc(on_secondary_container(), text);
+                ProgressBar {
+                    value: if progress.completed {
+                        1.0
+                    } else {
+                        (progress.current as f32 / progress.target.max(1) as f32).clamp(0.0, 1.0)
+                    },
+                    size: Vec2::new(300.0, 20.0),
+                    color: primary().adjust(0.7),
+                }
+                .show_children(|| {
+                    textc(
+                        on_secondary_container(),
+                        format!("{} / {}", progress.current, progress.target),
+                    );
+                });
+            });
+        }
+
+        if let (Some(limit), Some(started_at)) = (proto.time_limit, state.started_at) {
+            if !state.is_over() {
+                let end = started_at + limit;
+                let hours_left = end.0 .0.saturating_sub(sim.get_tick()) / TICKS_PER_HOUR;
+                textc(
+                    on_secondary_container(),
+                    i18n.tr_args(
+                        "ui.scenario.time_left",
+                        &[("value", format!("{hours_left}"))],
+                    ),
+                );
+            }
+        }
+
+        match state.outcome {
+            Some(ScenarioOutcome::Won(_)) => {
+                textc(primary(), i18n.tr("ui.scenario.won"));
+            }
+            Some(ScenarioOutcome::Lost(_)) => {
+                textc(error(), i18n.tr("ui.scenario.lost"));
+            }
+            None => {}
+        }
+    });
+}
@@ -1,15 +1,21 @@
+use engine::Tesselator;
+use geom::vec3;
 use goryak::{
-    dragvalue, fixed_spacer, minrow, on_secondary_container, primary, textc, ProgressBar, Window,
+    dragvalue, fixed_spacer, minrow, on_secondary_container, primary, sized_canvas, textc,
+    ProgressBar, Window,
 };
-use prototypes::{ItemID, Recipe};
+use prototypes::{try_prototype, BatteryID, Energy, ItemID, Power, Recipe};
 use simulation::economy::Market;
-use simulation::map::{Building, BuildingID, BuildingKind, Zone, MAX_ZONE_AREA};
-use simulation::map_dynamic::{BuildingInfos, ElectricityFlow};
+use simulation::map::{Building, BuildingID, BuildingKind, NetworkID, Zone, MAX_ZONE_AREA};
+use simulation::map_dynamic::{
+    BuildingInfos, ElectricityFlow, LandValue, WaterFlow, FLOW_HISTORY_SIZE,
+};
 use simulation::souls::freight_station::FreightTrainState;
 use simulation::world_command::WorldCommand;
 use simulation::{Simulation, SoulID};
+use yakui::paint::PaintMesh;
 use yakui::widgets::Pad;
-use yakui::Vec2;
+use yakui::{Color, Vec2};
 
 use crate::gui::inspect::entity_link;
 use crate::gui::item_icon_yakui;
@@ -38,6 +44,9 @@ pub fn inspect_building(uiworld: &UiWorld, sim: &Simulation, id: BuildingID) ->
             &id.prototype().name,
             &id.prototype().label,
         ),
+        BuildingKind::Leisure(id) => {
+            i18n.proto_label("leisure", &id.prototype().name, &id.prototype().label)
+        }
         BuildingKind::TrainStation => i18n.tr("ui.inspect.train_station").to_string(),
         BuildingKind::ExternalTrading => i18n.tr("ui.inspect.external_trading").to_string(),
     };
@@ -63,6 +72,7 @@ pub fn inspect_building(uiworld: &UiWorld, sim: &Simulation, id: BuildingID) ->
             BuildingKind::RailFreightStation(_) => {
                 render_freightstation(uiworld, sim, building);
             }
+            BuildingKind::Leisure(_) => {}
             BuildingKind::TrainStation => {}
             BuildingKind::ExternalTrading => {}
         };
@@ -112,6 +122,32 @@ fn render_house(uiworld: &UiWorld, sim: &Simulation, b: &Building) {
     let Some(info) = binfos.get(b.id) else {
         return;
     };
+
+    if b.density.is_abandoned() {
+        label(i18n.tr("ui.inspect.abandoned"));
+    } else {
+        label(i18n.tr_args(
+            "ui.inspect.density",
+            &[
+                ("level", b.density.level.to_string()),
+                ("residents", info.residents.len().to_string()),
+                ("households", b.density.households().to_string()),
+            ],
+        ));
+    }
+
+    let value = sim.read::<LandValue>().value(b.obb.center());
+    label(i18n.tr_args(
+        "ui.inspect.land_value",
+        &[("value", format!("{:.0}", value * 100.0))],
+    ));
+
+    let service = sim.read::<WaterFlow>().service(&sim.map(), b.id);
+    label(i18n.tr_args(
+        "ui.inspect.water_service",
+        &[("value", format!("{:.0}", service * 100.0))],
+    ));
+
     let Some(SoulID::Human(owner)) = info.owner else {
         return;
     };
@@ -185,6 +221,7 @@ fn render_goodscompany(uiworld: &UiWorld, sim: &Simulation, b: &Building) {
     let market = &*sim.read::<Market>();
     let map = &*sim.map();
     let elec_flow = &*sim.read::<ElectricityFlow>();
+    let water_flow = &*sim.read::<WaterFlow>();
 
     let max_workers = goods.max_workers;
     ProgressBar {
@@ -208,7 +245,7 @@ fn render_goodscompany(uiworld: &UiWorld, sim: &Simulation, b: &Building) {
             entity_link(uiworld, sim, driver);
         });
     }
-    let productivity = c.productivity(proto, b.zone.as_ref(), map, elec_flow);
+    let productivity = c.productivity(proto, b.zone.as_ref(), map, elec_flow, water_flow);
     if productivity < 1.0 {
         ProgressBar {
             value: productivity,
@@ -228,7 +265,7 @@ fn render_goodscompany(uiworld: &UiWorld, sim: &Simulation, b: &Building) {
     }
 
     if let Some(net_id) = map.electricity.net_id(b.id) {
-        let blackout = elec_flow.blackout(net_id);
+        let blackout = elec_flow.building_blackout(b.id);
 
         if let Some(power_c) = proto.power_consumption {
             ProgressBar {
@@ -276,6 +313,96 @@ fn render_goodscompany(uiworld: &UiWorld, sim: &Simulation, b: &Building) {
                     ],
                 ));
             });
+
+            if stats.undelivered_power > Power::ZERO {
+                label(i18n.tr_args(
+                    "ui.inspect.undelivered_power",
+                    &[("value", format!("{}", stats.undelivered_power))],
+                ));
+            }
+        }
+
+        let battery = try_prototype(BatteryID::from(proto.id));
+        if let Some(battery) = battery {
+            let charge = elec_flow.battery_charge(b.id);
+            ProgressBar {
+                value: charge.0 as f32 / battery.capacity.0.max(1) as f32,
+                size: Vec2::new(200.0, 25.0),
+                color: primary().adjust(0.7),
+            }
+            .show_children(|| {
+                label(i18n.tr_args(
+                    "ui.inspect.battery_charge",
+                    &[
+                        ("value", format!("{}", charge)),
+                        ("max", format!("{}", battery.capacity)),
+                    ],
+                ));
+            });
+        }
+
+        if proto.power_production.is_some() || battery.is_some() {
+            let stats = elec_flow.network_stats(net_id);
+            if stats.storage_capacity > Energy::ZERO {
+                label(i18n.tr_args(
+                    "ui.inspect.network_storage",
+                    &[
+                        ("stored", format!("{}", stats.stored_energy)),
+                        ("capacity", format!("{}", stats.storage_capacity)),
+                        ("power", format!("{}", stats.battery_power)),
+                    ],
+                ));
+            }
+
+            label(i18n.tr("ui.inspect.load_curve"));
+            load_curve(elec_flow, net_id);
+        }
+    }
+
+    if let Some(net_id) = map.water_mains.net_id(b.id) {
+        if let Some(water_c) = proto.water_consumption {
+            label(i18n.tr_args(
+                "ui.inspect.water",
+                &[
+                    ("value", format!("{}", water_c * productivity as f64)),
+                    ("max", format!("{}", water_c)),
+                ],
+            ));
+        }
+
+        if let Some(water_prod) = proto.water_production {
+            label(i18n.tr_args(
+                "ui.inspect.water_producing",
+                &[("value", format!("{}", water_prod * productivity as f64))],
+            ));
+        }
+
+        if let Some(treatment) = proto.sewage_treatment {
+            label(i18n.tr_args(
+                "ui.inspect.sewage_treating",
+                &[("value", format!("{}", treatment * productivity as f64))],
+            ));
+        }
+
+        if proto.water_production.is_some() || proto.sewage_treatment.is_some() {
+            let stats = water_flow.network_stats(net_id);
+
+            ProgressBar {
+                value: stats.service(),
+                size: Vec2::new(200.0, 25.0),
+                color: primary().adjust(0.7),
+            }
+            .show_children(|| {
+                label(i18n.tr_args(
+                    "ui.inspect.water_network_health",
+                    &[
+                        ("prod", format!("{}", stats.produced_water)),
+                        ("cons", format!("{}", stats.consumed_water)),
+                        ("treated", format!("{}", stats.treatment_capacity)),
+                        ("sewage", format!("{}", stats.sewage)),
+                    ],
+                ));
+            });
         }
     }
 
@@ -339,3 +466,58 @@ fn render_recipe(uiworld: &UiWorld, recipe: &Recipe) {
         });
     }
 }
+
+/// Draws the produced (in primary color) and consumed power of the network over the last day
+fn load_curve(elec_flow: &ElectricityFlow, net_id: NetworkID) {
+    let size = Vec2::new(200.0, 80.0);
+    let history = elec_flow.history(net_id).collect::<Vec<_>>();
+    let max = history
+        .iter()
+        .map(|f| f.produced_power.max(f.consumed_power).0)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut tess = Tesselator::new(&mut vertices, &mut indices, None, 15.0);
+
+    let produced = history.iter().map(|f| f.produced_power).collect::<Vec<_>>();
+    let consumed = history.iter().map(|f| f.consumed_power).collect::<Vec<_>>();
+    for (col, values) in [
+        (simulation::colors().gui_primary, produced),
+        (simulation::colors().gui_danger, consumed),
+    ] {
+        tess.set_color(col);
+        let positions = values
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                vec3(
+                    i as f32 * size.x / (FLOW_HISTORY_SIZE - 1) as f32,
+                    p.0 as f32 / max as f32 * size.y * 0.9,
+                    0.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        tess.draw_polyline(&positions, 2.0, false);
+    }
+
+    sized_canvas(size, Color::BLACK, move |paint| {
+        let rect = paint.layout.get(paint.dom.current()).unwrap().rect;
+
+        let [x, y]: [f32; 2] = rect.pos().into();
+        let [_sx, sy]: [f32; 2] = rect.size().into();
+
+        paint.paint.add_mesh(PaintMesh::new(
+            vertices.into_iter().map(|v| {
+                yakui::paint::Vertex::new(
+                    [x + v.position[0], y + sy - v.position[1]],
+                    v.uv,
+                    v.color,
+                )
+            }),
+            indices.into_iter().map(|x| x as _),
+        ));
+    });
+}
@@ -10,6 +10,7 @@ use std::time::Instant;
 pub mod follow;
 mod hud;
 pub mod inspect;
+pub mod overlay;
 mod textures;
 mod tools;
 
@@ -46,8 +47,10 @@ pub fn run_ui_systems(sim: &Simulation, uiworld: &UiWorld) {
     roadeditor::roadeditor(sim, uiworld);
     specialbuilding::specialbuilding(sim, uiworld);
     addtrain::addtrain(sim, uiworld);
+    powerline::powerline(sim, uiworld);
     zoneedit::zoneedit(sim, uiworld);
     terraforming::terraforming(sim, uiworld);
+    overlay::overlay(sim, uiworld);
 
     // run last so other systems can have the chance to cancel select
     selectable::selectable(sim, uiworld);
@@ -128,6 +131,7 @@ pub enum Tool {
     SpecialBuilding,
     Train,
     Terraforming,
+    PowerLine,
 }
 
 impl Tool {
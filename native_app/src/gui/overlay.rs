@@ -0,0 +1,140 @@
+use geom::{Intersect, LinearColor, Vec2, AABB};
+use goryak::button_secondary;
+use simulation::map::WaterKind;
+use simulation::map_dynamic::{
+    LandValue, LandValueChunkID, PollutionField, PollutionKind, POLLUTION_CELL_SIZE,
+};
+use simulation::Simulation;
+
+use crate::i18n::I18n;
+use crate::rendering::immediate::ImmediateDraw;
+use crate::uiworld::UiWorld;
+
+/// A map-wide visualization drawn on top of the terrain
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    #[default]
+    None,
+    AirPollution,
+    Noise,
+    LandValue,
+    Water,
+}
+
+impl Overlay {
+    const ALL: &'static [Overlay] = &[
+        Overlay::None,
+        Overlay::AirPollution,
+        Overlay::Noise,
+        Overlay::LandValue,
+        Overlay::Water,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Overlay::None => "ui.overlay.none",
+            Overlay::AirPollution => "ui.overlay.air_pollution",
+            Overlay::Noise => "ui.overlay.noise",
+            Overlay::LandValue => "ui.overlay.land_value",
+            Overlay::Water => "ui.overlay.water",
+        }
+    }
+
+    fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|o| *o == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// Button in the menu bar cycling through the overlays
+pub fn overlay_menu(uiworld: &UiWorld) {
+    let i18n = uiworld.read::<I18n>();
+    let mut overlay = uiworld.write::<Overlay>();
+    let text = i18n.tr_args("ui.overlay", &[("value", i18n.tr(overlay.label()))]);
+    if button_secondary(text).show().clicked {
+        *overlay = overlay.next();
+    }
+}
+
+/// Draws the current overlay around the camera
+pub fn overlay(sim: &Simulation, uiworld: &UiWorld) {
+    profiling::scope!("gui::overlay");
+    let overlay = *uiworld.read::<Overlay>();
+    if overlay == Overlay::None {
+        return;
+    }
+
+    let cam = uiworld.camera();
+    let center = cam.targetpos.xy();
+    let radius = (cam.targetdist * 1.5).clamp(300.0, 3000.0);
+    drop(cam);
+
+    let map = sim.map();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+
+    // calls f with every cell of the given size around the camera
+    let for_cells = |cell_size: f32, f: &mut dyn FnMut(AABB)| {
+        let ll = ((center - Vec2::splat(radius)) / cell_size).floor();
+        let ur = ((center + Vec2::splat(radius)) / cell_size).ceil();
+        for y in ll.y as i32..ur.y as i32 {
+            for x in ll.x as i32..ur.x as i32 {
+                let cell_ll = Vec2::new(x as f32, y as f32) * cell_size;
+                f(AABB::new_ll_size(cell_ll, Vec2::splat(cell_size)));
+            }
+        }
+    };
+
+    match overlay {
+        Overlay::None => {}
+        Overlay::AirPollution | Overlay::Noise => {
+            let (kind, scale) = if overlay == Overlay::AirPollution {
+                (PollutionKind::Air, 20.0)
+            } else {
+                (PollutionKind::Noise, 1.0)
+            };
+            let field = sim.read::<PollutionField>();
+
+            for_cells(POLLUTION_CELL_SIZE, &mut |cell| {
+                let cell_center = cell.center();
+                let v = field.value(kind, cell_center);
+                if v <= 0.0 {
+                    return;
+                }
+                let Some(height) = map.environment.height(cell_center) else {
+                    return;
+                };
+
+                // saturates smoothly towards 1 for high values
+                let n = v / (v + scale);
+                let col: LinearColor = (1.0 - n) * LinearColor::YELLOW + n * LinearColor::RED;
+                draw.aabb(cell, height + 1.0).color(col.a(0.2 + 0.5 * n));
+            });
+        }
+        Overlay::LandValue => {
+            let lv = sim.read::<LandValue>();
+
+            for_cells(LandValueChunkID::SIZE_F32, &mut |cell| {
+                let cell_center = cell.center();
+                let Some(height) = map.environment.height(cell_center) else {
+                    return;
+                };
+                let v = lv.value(cell_center);
+                let col: LinearColor = (1.0 - v) * LinearColor::RED + v * LinearColor::GREEN;
+                draw.aabb(cell, height + 1.0).color(col.a(0.4));
+            });
+        }
+        Overlay::Water => {
+            let view = AABB::centered(center, Vec2::splat(radius * 2.0));
+            for (cell, water) in map.environment.water.cells() {
+                if !view.intersects(&cell) {
+                    continue;
+                }
+                let col = match water.kind {
+                    WaterKind::Lake => LinearColor::BLUE,
+                    WaterKind::River => LinearColor::CYAN,
+                };
+                draw.aabb(cell, water.level + 1.0).color(col.a(0.5));
+            }
+        }
+    }
+}
@@ -4,16 +4,20 @@ use crate::inputmap::{InputAction, InputMap};
 use crate::rendering::immediate::ImmediateDraw;
 use crate::uiworld::UiWorld;
 use egui_inspect::Inspect;
+use geom::Segment;
 use simulation::map::{BuildingKind, Map, ProjectFilter, ProjectKind};
 use simulation::Simulation;
 
+/// Power lines closer than this to the cursor can be removed
+const POWER_LINE_PICK_DISTANCE: f32 = 3.0;
+
 #[derive(Copy, Clone, Default, Inspect)]
 pub struct BulldozerState {
     hold: bool,
 }
 
 /// Bulldozer tool
-/// Allows to remove roads, intersections and buildings
+/// Allows to remove roads, intersections, buildings and power lines
 pub fn bulldozer(sim: &Simulation, uiworld: &UiWorld) {
     profiling::scope!("gui::bulldozer");
     let tool: &Tool = &uiworld.read::<Tool>();
@@ -28,7 +32,24 @@ pub fn bulldozer(sim: &Simulation, uiworld: &UiWorld) {
     let mut commands = uiworld.commands();
     let state: &BulldozerState = &uiworld.read::<BulldozerState>();
 
-    let cur_proj = map.project(unwrap_ret!(inp.unprojected), 0.0, ProjectFilter::ALL);
+    let mpos = unwrap_ret!(inp.unprojected);
+    let cur_proj = map.project(mpos, 0.0, ProjectFilter::ALL);
+
+    // power lines are not in the spatial map, they hang above the ground
+    if cur_proj.kind.is_ground() {
+        let line = map.power_lines().values().find(|line| {
+            let seg = Segment::new(line.src_pos.xy(), line.dst_pos.xy());
+            seg.project(mpos.xy()).distance(mpos.xy()) < POWER_LINE_PICK_DISTANCE
+        });
+        if let Some(line) = line {
+            draw.line(line.src_pos, line.dst_pos, 1.0)
+                .color(simulation::colors().gui_danger);
+            if inp.just_act.contains(&InputAction::Select) {
+                commands.map_remove_power_line(line.id);
+            }
+            return;
+        }
+    }
 
     let col = if matches!(
         cur_proj.kind,
@@ -2,6 +2,7 @@ use crate::gui::Tool;
 use crate::inputmap::{InputAction, InputMap};
 use crate::rendering::immediate::ImmediateDraw;
 use crate::uiworld::UiWorld;
+use geom::Color;
 use serde::{Deserialize, Serialize};
 use simulation::map::{LotKind, ProjectFilter, ProjectKind};
 use simulation::Simulation;
@@ -13,7 +14,7 @@ pub struct LotBrushResource {
 }
 
 /// Lot brush tool
-/// Allows to build houses on lots
+/// Allows to paint zones on lots, buildings then grow on them depending on demand
 pub fn lotbrush(sim: &Simulation, uiworld: &UiWorld) {
     profiling::scope!("gui::lotbrush");
     let mut res = uiworld.write::<LotBrushResource>();
@@ -37,10 +38,7 @@ pub fn lotbrush(sim: &Simulation, uiworld: &UiWorld) {
 
     let kind = res.kind;
 
-    let mut col = match kind {
-        LotKind::Unassigned => simulation::colors().lot_unassigned_col,
-        LotKind::Residential => simulation::colors().lot_residential_col,
-    };
+    let mut col = lot_kind_color(kind);
 
     col.a = 0.2;
 
@@ -53,12 +51,24 @@ pub fn lotbrush(sim: &Simulation, uiworld: &UiWorld) {
             .query_around(mpos.xy(), res.radius, ProjectFilter::LOT)
         {
             if let ProjectKind::Lot(id) = v {
-                commands.map_build_house(id);
+                if map.lots().get(id).is_some_and(|lot| lot.kind != kind) {
+                    commands.map_set_lot_kind(id, kind);
+                }
             }
         }
     }
 }
 
+pub fn lot_kind_color(kind: LotKind) -> Color {
+    match kind {
+        LotKind::Unassigned => simulation::colors().lot_unassigned_col,
+        LotKind::Residential => simulation::colors().lot_residential_col,
+        LotKind::Commercial => simulation::colors().lot_commercial_col,
+        LotKind::Industrial => simulation::colors().lot_industrial_col,
+        LotKind::Mixed => simulation::colors().lot_mixed_col,
+    }
+}
+
 impl Default for LotBrushResource {
     fn default() -> Self {
         Self {
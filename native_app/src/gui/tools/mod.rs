@@ -2,6 +2,7 @@ pub mod addtrain;
 pub mod bulldozer;
 pub mod inspected_aura;
 pub mod lotbrush;
+pub mod powerline;
 pub mod roadbuild;
 pub mod roadeditor;
 pub mod selectable;
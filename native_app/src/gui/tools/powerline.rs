@@ -0,0 +1,138 @@
+use crate::gui::{PotentialCommands, Tool};
+use crate::inputmap::{InputAction, InputMap};
+use crate::rendering::immediate::ImmediateDraw;
+use crate::uiworld::UiWorld;
+use geom::Vec3;
+use simulation::map::{
+    Map, NetworkObjectID, PowerLineEnd, PowerLineKind, ProjectFilter, ProjectKind, PYLON_HEIGHT,
+};
+use simulation::map_dynamic::ElectricityFlow;
+use simulation::world_command::WorldCommand;
+use simulation::Simulation;
+
+/// Pylons closer than this to the cursor are snapped to
+const PYLON_SNAP_DISTANCE: f32 = 10.0;
+
+pub struct PowerLineResource {
+    pub kind: PowerLineKind,
+    /// Where the line being built starts
+    pub start: Option<PowerLineEnd>,
+}
+
+impl Default for PowerLineResource {
+    fn default() -> Self {
+        Self {
+            kind: PowerLineKind::LowVoltage,
+            start: None,
+        }
+    }
+}
+
+/// Power line tool
+/// Allows to build power lines between buildings and pylons, one segment after the other
+pub fn powerline(sim: &Simulation, uiworld: &UiWorld) {
+    profiling::scope!("gui::powerline");
+    let state = &mut *uiworld.write::<PowerLineResource>();
+    let tool = *uiworld.read::<Tool>();
+    let map = sim.map();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+
+    draw_power_lines(sim, &map, &mut draw);
+
+    if !matches!(tool, Tool::PowerLine) {
+        state.start = None;
+        return;
+    }
+
+    let inp = uiworld.read::<InputMap>();
+    let mut potential = uiworld.write::<PotentialCommands>();
+    let commands = &mut *uiworld.commands();
+
+    if inp.just_act.contains(&InputAction::SecondarySelect) {
+        state.start = None;
+    }
+
+    let mpos = unwrap_ret!(inp.unprojected);
+    let hover = resolve_end(&map, hovered_end(&map, mpos));
+    let hover_pos = unwrap_ret!(map.power_line_end_pos(hover));
+
+    draw.circle(hover_pos, 3.0)
+        .color(simulation::colors().gui_primary);
+
+    let Some(start) = state.start.map(|s| resolve_end(&map, s)) else {
+        if inp.just_act.contains(&InputAction::Select) {
+            state.start = Some(hover);
+        }
+        return;
+    };
+    let Some(start_pos) = map.power_line_end_pos(start) else {
+        state.start = None;
+        return;
+    };
+
+    draw.line(start_pos, hover_pos, 1.0)
+        .color(simulation::colors().gui_primary);
+
+    let cmd = WorldCommand::MapBuildPowerLine {
+        src: start,
+        dst: hover,
+        kind: state.kind,
+    };
+
+    if inp.just_act.contains(&InputAction::Select) {
+        commands.push(cmd);
+        state.start = Some(hover);
+    } else {
+        potential.set(cmd);
+    }
+}
+
+/// The building under the cursor, or a new pylon on the ground
+fn hovered_end(map: &Map, mpos: Vec3) -> PowerLineEnd {
+    if let ProjectKind::Building(b) = map.project(mpos, 0.0, ProjectFilter::BUILDING).kind {
+        return PowerLineEnd::Building(b);
+    }
+    PowerLineEnd::Ground(mpos)
+}
+
+/// Snaps ground positions to the pylons placed there, so that lines can be chained
+/// even before the pylon of the previous segment is known
+fn resolve_end(map: &Map, end: PowerLineEnd) -> PowerLineEnd {
+    let PowerLineEnd::Ground(pos) = end else {
+        return end;
+    };
+    map.pylons()
+        .values()
+        .filter(|p| p.pos.xy().distance(pos.xy()) < PYLON_SNAP_DISTANCE)
+        .min_by_key(|p| ordered_float::OrderedFloat(p.pos.xy().distance(pos.xy())))
+        .map_or(end, |p| PowerLineEnd::Pylon(p.id))
+}
+
+/// Draws pylons and power lines, overloaded lines are shown in red
+fn draw_power_lines(sim: &Simulation, map: &Map, draw: &mut ImmediateDraw) {
+    let flow = sim.read::<ElectricityFlow>();
+
+    for pylon in map.pylons().values() {
+        draw.line(pylon.pos, pylon.pos.up(PYLON_HEIGHT), 1.5)
+            .color(simulation::colors().gui_disabled);
+    }
+
+    for line in map.power_lines().values() {
+        let col = if flow.is_overloaded(line.id) {
+            simulation::colors().gui_danger
+        } else {
+            simulation::colors().gui_disabled
+        };
+        let thickness = match line.kind {
+            PowerLineKind::LowVoltage => 0.3,
+            PowerLineKind::HighVoltage => 0.6,
+        };
+        draw.line(line.src_pos, line.dst_pos, thickness).color(col);
+
+        for (end, pos) in [(line.src, line.src_pos), (line.dst, line.dst_pos)] {
+            if matches!(end, NetworkObjectID::Building(_)) {
+                draw.circle(pos, 1.0).color(col);
+            }
+        }
+    }
+}
@@ -296,6 +296,7 @@ pub fn roadbuild(sim: &Simulation, uiworld: &UiWorld) {
             to: dst,
             inter,
             pat,
+            tunnel: state.tunnel,
         });
 
         let connection_segment = match inter {
@@ -308,6 +309,7 @@ pub fn roadbuild(sim: &Simulation, uiworld: &UiWorld) {
             dst.pos,
             connection_segment,
             is_rail,
+            state.tunnel,
             &map.environment,
         );
         points = Some(p);
@@ -386,6 +388,8 @@ pub struct RoadBuildResource {
     pub snapping: Snapping,
     pub height_offset: f32,
     pub height_reference: HeightReference,
+    /// Build the next roads under the terrain
+    pub tunnel: bool,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -1,3 +1,4 @@
+use crate::gui::roadbuild::RoadBuildResource;
 use crate::gui::Tool;
 use crate::inputmap::{InputAction, InputMap};
 use crate::rendering::immediate::ImmediateDraw;
@@ -18,10 +19,12 @@ pub struct IntersectionComponent {
 pub struct RoadEditorResource {
     pub inspect: Option<IntersectionComponent>,
     pub dirty: bool,
+    /// Clicking on a road applies the pattern selected in the road building tool
+    pub upgrade_roads: bool,
 }
 
 /// RoadEditor tool
-/// Allows to edit intersections properties like turns and signals
+/// Allows to edit intersections properties like turns and signals, and to change the lanes of roads
 pub fn roadeditor(sim: &Simulation, uiworld: &UiWorld) {
     profiling::scope!("gui::roadeditor");
     let tool = uiworld.read::<Tool>();
@@ -59,8 +62,56 @@ pub fn roadeditor(sim: &Simulation, uiworld: &UiWorld) {
         }
     }
 
+    if state.dirty {
+        if let Some(interc) = &state.inspect {
+            commands.map_update_intersection_policy(
+                interc.id,
+                interc.turn_policy,
+                interc.light_policy,
+            );
+        }
+        state.dirty = false;
+    }
+
     let mut proj_pos = unwrap_ret!(inp.unprojected);
-    let cur_proj = map.project(proj_pos, 10.0, ProjectFilter::INTER);
+    let filter = if state.upgrade_roads {
+        ProjectFilter::INTER | ProjectFilter::ROAD
+    } else {
+        ProjectFilter::INTER
+    };
+    let cur_proj = map.project(proj_pos, 10.0, filter);
+
+    // When upgrading roads, clicking on a road changes it in place to the pattern selected in
+    // the road building tool
+    if let ProjectKind::Road(id) = cur_proj.kind {
+        let r = &map.roads()[id];
+        let pattern = uiworld.read::<RoadBuildResource>().pattern_builder.build();
+        let current = r.pattern(map.lanes());
+
+        let col = if pattern == current {
+            simulation::colors().gui_disabled
+        } else if map.road_pattern_overlaps_building(id, &pattern) {
+            simulation::colors().gui_danger
+        } else if inp.just_act.contains(&InputAction::Select) {
+            commands.map_update_road_pattern(id, pattern.clone());
+            simulation::colors().gui_success
+        } else {
+            simulation::colors().gui_primary
+        };
+
+        imm_draw
+            .polyline(
+                r.points()
+                    .as_slice()
+                    .iter()
+                    .map(|x| x.up(0.1))
+                    .collect::<Vec<_>>(),
+                pattern.width(),
+                false,
+            )
+            .color(col.a(0.5));
+        return;
+    }
 
     let mut proj_col;
 
@@ -88,15 +139,4 @@ pub fn roadeditor(sim: &Simulation, uiworld: &UiWorld) {
     }
 
     imm_draw.circle(proj_pos.up(0.5), 10.0).color(proj_col);
-
-    if state.dirty {
-        if let Some(interc) = &state.inspect {
-            commands.map_update_intersection_policy(
-                interc.id,
-                interc.turn_policy,
-                interc.light_policy,
-            );
-        }
-        state.dirty = false;
-    }
 }
@@ -7,7 +7,7 @@ use engine::AudioKind;
 use geom::{Degrees, Intersect, OBB};
 use ordered_float::OrderedFloat;
 use prototypes::{RenderAsset, Size2D};
-use simulation::map::{ProjectFilter, ProjectKind, RoadID};
+use simulation::map::{ProjectFilter, ProjectKind, RoadID, WATER_BUILD_MARGIN};
 use simulation::world_command::WorldCommand;
 use simulation::Simulation;
 use std::borrow::Cow;
@@ -189,6 +189,13 @@ pub fn specialbuilding(sim: &Simulation, uiworld: &UiWorld) {
         return;
     }
 
+    if map.environment.water.overlaps(&obb, WATER_BUILD_MARGIN) {
+        *uiworld.write::<ErrorTooltip>() =
+            ErrorTooltip::new(Cow::Owned(i18n.tr("ui.error.near_water").to_string()));
+        draw(obb, true);
+        return;
+    }
+
     draw(obb, false);
 
     let cmds: Vec<WorldCommand> = make(&SpecialBuildArgs {
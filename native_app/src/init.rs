@@ -6,10 +6,13 @@ use crate::gui::chat::GUIChatState;
 use crate::gui::follow::FollowEntity;
 use crate::gui::keybinds::KeybindState;
 use crate::gui::lotbrush::LotBrushResource;
+use crate::gui::overlay::Overlay;
+use crate::gui::powerline::PowerLineResource;
 use crate::gui::roadbuild::RoadBuildResource;
 use crate::gui::roadeditor::RoadEditorResource;
 use crate::gui::specialbuilding::SpecialBuildingResource;
 use crate::gui::terraforming::TerraformingResource;
+use crate::gui::toasts::ToastsState;
 use crate::gui::toolbox::building::BuildingIcons;
 use crate::gui::windows::economy::EconomyState;
 use crate::gui::windows::load::LoadState;
@@ -49,12 +52,14 @@ pub fn init() {
     register_resource_noserialize::<ExitState>();
     register_resource_noserialize::<FollowEntity>();
     register_resource_noserialize::<GUIChatState>();
+    register_resource_noserialize::<ToastsState>();
     register_resource_noserialize::<TimeAlways>();
     register_resource_noserialize::<ImmediateDraw>();
     register_resource_noserialize::<ImmediateSound>();
     register_resource_noserialize::<InputMap>();
     register_resource_noserialize::<InspectedEntity>();
     register_resource_noserialize::<InspectedBuilding>();
+    register_resource_noserialize::<Overlay>();
     register_resource_noserialize::<NetworkState>();
     register_resource_noserialize::<PotentialCommands>();
     register_resource_noserialize::<ZoneEditState>();
@@ -63,6 +68,7 @@ pub fn init() {
     register_resource_noserialize::<RoadBuildResource>();
     register_resource_noserialize::<RoadEditorResource>();
     register_resource_noserialize::<SpecialBuildingResource>();
+    register_resource_noserialize::<PowerLineResource>();
     register_resource_noserialize::<TrainSpawnResource>();
     register_resource_noserialize::<Timings>();
     register_resource_noserialize::<Tool>();
@@ -3,7 +3,7 @@ use crate::game_loop::{State, Timings};
 use crate::gui::windows::settings::Settings;
 use crate::uiworld::{ReceivedCommands, SaveLoadState};
 use common::timestep::Timestep;
-use simulation::utils::scheduler::SeqSchedule;
+use simulation::utils::scheduler::Schedule;
 use simulation::world_command::{WorldCommand, WorldCommands};
 use simulation::Simulation;
 
@@ -80,7 +80,7 @@ fn handle_singleplayer(state: &mut State) {
 
 fn handle_replay(
     sim: &mut Simulation,
-    schedule: &mut SeqSchedule,
+    schedule: &mut Schedule,
     slstate: &mut SaveLoadState,
 ) -> bool {
     if let Some(new_sim) = slstate.please_load_sim.take() {
@@ -90,7 +90,9 @@ fn handle_replay(
     }
     if let Some(ref mut replay) = slstate.please_load {
         if replay.advance_tick(sim, schedule) {
-            slstate.please_load = None;
+            if let Some(replay) = slstate.please_load.take() {
+                replay.save_keyframes();
+            }
             log::info!("finished loading replay");
         }
         return true;
@@ -106,12 +108,14 @@ mod inner {
     use crate::uiworld::{ReceivedCommands, SaveLoadState};
     use common::timestep::Timestep;
     use networking::{
-        ConnectConf, Frame, PollResult, ServerConfiguration, ServerPollResult, VirtualClientConf,
+        ConnectConf, Frame, PollResult, Role, ServerConfiguration, ServerPollResult,
+        VirtualClientConf,
     };
     use prototypes::DELTA_F64;
     use simulation::world_command::WorldCommands;
     use simulation::Simulation;
     use std::net::ToSocketAddrs;
+    use std::path::PathBuf;
     use std::sync::Mutex;
     use std::time::Duration;
 
@@ -222,14 +226,18 @@ mod inner {
     }
 
     pub fn start_server(info: &mut NetworkConnectionInfo, sim: &Simulation) -> Option<Server> {
-        let server = match networking::Server::start(ServerConfiguration {
+        let mut server = match networking::Server::start(ServerConfiguration {
             start_frame: Frame(sim.get_tick()),
             period: Duration::from_secs_f64(DELTA_F64),
             port: None,
+            name: format!("{}'s city", info.name),
             virtual_client: Some(VirtualClientConf {
                 name: info.name.to_string(),
             }),
             version: VERSION.to_string(),
+            mods: prototypes::mod_set(),
+            default_role: Role::Builder,
+            passwords: vec![],
             always_run: true,
         }) {
             Ok(x) => x,
@@ -238,6 +246,11 @@ mod inner {
                 return None;
             }
         };
+        server.set_input_filter(|role, commands: &mut WorldCommands| {
+            commands.retain_permitted(role.can_build(), role.is_admin())
+        });
+        server.set_map_info(Simulation::summary);
+        server.set_world_parts(Simulation::to_parts);
 
         Some(Mutex::new(server))
     }
@@ -263,12 +276,21 @@ mod inner {
 
         let port = parsed_addr.port();
 
-        let client = match networking::Client::connect(ConnectConf {
+        // each server has its own world, so its own cache
+        let cache_dir = parsed_addr
+            .to_string()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+        let mut client = match networking::Client::connect(ConnectConf {
             name: info.name.clone(),
             addr: parsed_addr.ip(),
             port: if port != 23019 { Some(port) } else { None },
             frame_buffer_advance: 8,
             version: VERSION.to_string(),
+            mods: prototypes::mod_set(),
+            spectate: info.spectate,
+            password: (!info.password.is_empty()).then(|| info.password.clone()),
+            world_cache: Some(PathBuf::from("world/netcache").join(cache_dir)),
         }) {
             Ok(x) => x,
             Err(e) => {
@@ -277,6 +299,8 @@ mod inner {
             }
         };
 
+        client.set_world_from_parts(Simulation::from_parts);
+
         Some(Mutex::new(client))
     }
 }
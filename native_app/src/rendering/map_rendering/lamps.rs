@@ -71,6 +71,9 @@ impl LampsRender {
                     continue;
                 }
                 for (point, _) in road.points().equipoints_dir(45.0, true) {
+                    if map.environment.is_underground(point) {
+                        continue;
+                    }
                     add_light(point + 8.0 * V3::Z);
                 }
             }
@@ -7,13 +7,14 @@ use engine::{
     MeshVertex, MetallicRoughness, SpriteBatch, SpriteBatchBuilder, Tesselator,
 };
 use geom::{minmax, vec2, vec3, Color, LinearColor, PolyLine3, Polygon, Radians, Vec2, Vec3};
-use prototypes::{FreightStationPrototype, GoodsCompanyPrototype, RenderAsset};
+use prototypes::{FreightStationPrototype, GoodsCompanyPrototype, LeisurePrototype, RenderAsset};
 use simulation::map::{
     Building, BuildingKind, CanonicalPosition, Environment, Intersection, LaneKind, Lanes, LotKind,
     Map, MapSubscriber, ProjectFilter, ProjectKind, PylonPosition, Road, Roads, SubscriberChunkID,
     Turn, TurnKind, UpdateType, CROSSWALK_WIDTH, ROAD_Z_OFFSET,
 };
 use simulation::Simulation;
+use std::borrow::Cow;
 use std::ops::{Mul, Neg};
 use std::sync::Arc;
 
@@ -83,6 +84,10 @@ impl MapMeshHandler {
                 FreightStationPrototype::iter()
                     .map(|descr| (&descr.asset, BuildingKind::RailFreightStation(descr.id))),
             )
+            .chain(
+                LeisurePrototype::iter()
+                    .map(|descr| (&descr.asset, BuildingKind::Leisure(descr.id))),
+            )
             .chain([(
                 &RenderAsset::Mesh {
                     path: "external_trading.glb".into(),
@@ -278,7 +283,12 @@ impl MapMeshHandler {
 }
 
 impl MapBuilders {
-    fn arrows(arrow_builder: &mut SpriteBatchBuilder<false>, road: &Road, lanes: &Lanes) {
+    fn arrows(
+        arrow_builder: &mut SpriteBatchBuilder<false>,
+        road: &Road,
+        lanes: &Lanes,
+        env: &Environment,
+    ) {
         let has_forward = road
             .outgoing_lanes_from(road.src)
             .iter()
@@ -312,6 +322,10 @@ impl MapBuilders {
                     .points
                     .point_dir_along(l * (1.0 + i as f32) / (1.0 + n_arrows as f32));
 
+                if env.is_underground(mid) {
+                    continue;
+                }
+
                 arrow_builder.push(
                     mid.up(0.03),
                     dir,
@@ -633,69 +647,102 @@ impl MapBuilders {
         for road in chunk_roads {
             let road = &roads[road];
 
-            Self::arrows(&mut self.arrow_builder, road, lanes);
-
-            let cut = road.interfaced_points();
-            let first_dir = unwrap_cont!(cut.first_dir());
-            let last_dir = unwrap_cont!(cut.last_dir());
+            Self::arrows(&mut self.arrow_builder, road, lanes, env);
 
             road_pylons(&mut tess_map, env, road);
 
-            tess_map.normal.z = -1.0;
-            tess_map.draw_polyline_full(
-                cut.iter().map(|x| x.up(-0.3)),
-                first_dir.xy(),
-                last_dir.xy(),
-                road.width,
-                0.0,
-            );
-            tess_map.normal.z = 1.0;
+            // Tunnel interiors are not drawn, only the surface parts and the portals
+            let ip = road.interfaced_points();
+            let len = ip.length();
+            let tunnels = road.tunnels(env);
+            let mut surface = Vec::with_capacity(tunnels.len() + 1);
+            let mut last = 0.0;
+            for &(a, b) in &tunnels {
+                if a > last {
+                    surface.push((last, a));
+                }
+                last = b;
+            }
+            if last < len {
+                surface.push((last, len));
+            }
+
+            for &(a, b) in &tunnels {
+                if a > 0.0 {
+                    tunnel_portal(&mut tess_map, road, ip.point_dir_along(a));
+                }
+                if b < len {
+                    let (pos, dir) = ip.point_dir_along(b);
+                    tunnel_portal(&mut tess_map, road, (pos, -dir));
+                }
+            }
 
-            let draw_off = |tess: &mut Tesselator, col: LinearColor, w, off| {
-                tess.set_color(col);
-                tess.draw_polyline_full(
-                    cut.as_slice().iter().copied(),
+            for (a, b) in surface {
+                let cut = &*if tunnels.is_empty() {
+                    Cow::Borrowed(ip)
+                } else {
+                    Cow::Owned(ip.cut(a, len - b))
+                };
+                let first_dir = unwrap_cont!(cut.first_dir());
+                let last_dir = unwrap_cont!(cut.last_dir());
+
+                tess_map.normal.z = -1.0;
+                tess_map.draw_polyline_full(
+                    cut.iter().map(|x| x.up(-0.3)),
                     first_dir.xy(),
                     last_dir.xy(),
-                    w,
-                    off,
+                    road.width,
+                    0.0,
                 );
-            };
-
-            let mut start = true;
-            for l in road.lanes_iter().flat_map(|(l, _)| lanes.get(l)) {
-                if l.kind.is_rail() {
-                    let off = l.dist_from_bottom - road.width * 0.5 + LaneKind::Rail.width() * 0.5;
-                    draw_off(&mut tess_map, mid_col, LaneKind::Rail.width(), off);
-                    Self::draw_rail(&mut tess_map, cut, off, true);
-                    start = true;
-                    continue;
-                }
-                if start {
+                tess_map.normal.z = 1.0;
+
+                let draw_off = |tess: &mut Tesselator, col: LinearColor, w, off| {
+                    tess.set_color(col);
+                    tess.draw_polyline_full(
+                        cut.as_slice().iter().copied(),
+                        first_dir.xy(),
+                        last_dir.xy(),
+                        w,
+                        off,
+                    );
+                };
+
+                let mut start = true;
+                for l in road.lanes_iter().flat_map(|(l, _)| lanes.get(l)) {
+                    if l.kind.is_rail() {
+                        let off =
+                            l.dist_from_bottom - road.width * 0.5 + LaneKind::Rail.width() * 0.5;
+                        draw_off(&mut tess_map, mid_col, LaneKind::Rail.width(), off);
+                        Self::draw_rail(&mut tess_map, cut, off, true);
+                        start = true;
+                        continue;
+                    }
+                    if start {
+                        draw_off(
+                            &mut tess_map,
+                            line_col,
+                            0.25,
+                            l.dist_from_bottom - road.width * 0.5,
+                        );
+                        start = false;
+                    }
+                    draw_off(
+                        &mut tess_map,
+                        match l.kind {
+                            LaneKind::Walking => hig_col,
+                            LaneKind::Parking => low_col,
+                            _ => mid_col,
+                        },
+                        l.kind.width() - 0.25,
+                        l.dist_from_bottom - road.width * 0.5 + l.kind.width() * 0.5,
+                    );
                     draw_off(
                         &mut tess_map,
                         line_col,
                         0.25,
-                        l.dist_from_bottom - road.width * 0.5,
+                        l.dist_from_bottom - road.width * 0.5 + l.kind.width(),
                     );
-                    start = false;
                 }
-                draw_off(
-                    &mut tess_map,
-                    match l.kind {
-                        LaneKind::Walking => hig_col,
-                        LaneKind::Parking => low_col,
-                        _ => mid_col,
-                    },
-                    l.kind.width() - 0.25,
-                    l.dist_from_bottom - road.width * 0.5 + l.kind.width() * 0.5,
-                );
-                draw_off(
-                    &mut tess_map,
-                    line_col,
-                    0.25,
-                    l.dist_from_bottom - road.width * 0.5 + l.kind.width(),
-                );
             }
         }
 
@@ -764,6 +811,9 @@ impl MapBuilders {
             let col = match lot.kind {
                 LotKind::Unassigned => simulation::colors().lot_unassigned_col,
                 LotKind::Residential => simulation::colors().lot_residential_col,
+                LotKind::Commercial => simulation::colors().lot_commercial_col,
+                LotKind::Industrial => simulation::colors().lot_industrial_col,
+                LotKind::Mixed => simulation::colors().lot_mixed_col,
             };
             tess_lots.set_color(col);
             tess_lots.draw_filled_polygon(&lot.shape.corners, lot.height + 0.28);
@@ -849,6 +899,41 @@ fn add_polyon(
     quad(3, 0, 7, 4, d2p);
 }
 
+/// Draws the entrance of a tunnel: two pillars on the sides of the road and a slab above it.
+/// `dir` points towards the inside of the tunnel
+fn tunnel_portal(tess: &mut Tesselator, road: &Road, (pos, dir): (Vec3, Vec3)) {
+    const HEIGHT: f32 = 6.0;
+    const THICKNESS: f32 = 1.5;
+
+    let dir = dir.xy().normalize();
+    let side = dir.perpendicular() * (road.width * 0.5 + THICKNESS * 0.5);
+
+    for s in [side, -side] {
+        add_polyon(
+            tess,
+            THICKNESS,
+            PylonPosition {
+                terrain_height: pos.z + 20.0,
+                pos: (pos.xy() + s).z(pos.z + HEIGHT),
+                dir: dir.z(0.0),
+            },
+        );
+    }
+
+    tess.set_color(simulation::colors().road_pylon_col);
+    tess.draw_polyline_full(
+        [
+            (pos.xy() - dir * THICKNESS * 0.5).z(pos.z + HEIGHT),
+            (pos.xy() + dir * THICKNESS * 0.5).z(pos.z + HEIGHT),
+        ]
+        .into_iter(),
+        dir,
+        dir,
+        road.width + THICKNESS * 2.0,
+        0.0,
+    );
+}
+
 fn road_pylons(meshb: &mut Tesselator, env: &Environment, road: &Road) {
     for pylon in Road::pylons_positions(road.interfaced_points(), env) {
         add_polyon(meshb, road.width * 0.5, pylon);
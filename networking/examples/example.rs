@@ -1,6 +1,6 @@
 use log::LevelFilter;
 use networking::{
-    Client, ConnectConf, Frame, PollResult, Server, ServerConfiguration, ServerPollResult,
+    Client, ConnectConf, Frame, PollResult, Role, Server, ServerConfiguration, ServerPollResult,
 };
 use serde::{Deserialize, Serialize};
 use std::net::Ipv4Addr;
@@ -54,6 +54,10 @@ pub fn main() {
         port: None,
         frame_buffer_advance: 10,
         version: "v1".to_string(),
+        mods: vec![],
+        spectate: false,
+        password: None,
+        world_cache: None,
     })
     .unwrap();
 
@@ -98,8 +102,12 @@ pub fn serv_c() {
         start_frame: Frame(world.tick),
         period: UP_DT,
         port: None,
+        name: "example".to_string(),
         virtual_client: None,
         version: "v1".to_string(),
+        mods: vec![],
+        default_role: Role::Builder,
+        passwords: vec![],
         always_run: true,
     })
     .unwrap();
@@ -5,7 +5,11 @@ use common::{FastMap, FastSet};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How long the session of a disconnected player is kept so that they can resume it
+pub(crate) const RESUME_GRACE_PERIOD: Duration = Duration::from_secs(60);
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Hash, Debug)]
 #[repr(transparent)]
@@ -21,6 +25,38 @@ impl AuthentID {
     pub const VIRTUAL_ID: AuthentID = AuthentID(0);
 }
 
+/// What a player is allowed to do
+/// The server filters the inputs of each player according to its role before merging them
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Hash, Debug)]
+pub enum Role {
+    Admin,
+    Builder,
+    Spectator,
+}
+
+impl Role {
+    pub fn can_build(self) -> bool {
+        !matches!(self, Role::Spectator)
+    }
+
+    pub fn is_admin(self) -> bool {
+        matches!(self, Role::Admin)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(Role::Admin),
+            "builder" => Ok(Role::Builder),
+            "spectator" => Ok(Role::Spectator),
+            _ => Err(format!("unknown role: {}", s)),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub(crate) enum ClientGameState {
     Downloading,
@@ -33,10 +69,19 @@ pub(crate) struct Client {
     #[allow(dead_code)]
     pub uid: UserID,
     pub name: String,
+    pub role: Role,
     pub ack: Frame,
     pub udp_addr: SocketAddr,
     pub tcp_addr: SocketAddr,
     pub state: ClientGameState,
+    /// Secret given to the client to resume its session after losing the connection
+    pub resume_token: u64,
+}
+
+/// A player that lost its connection, with its name still reserved
+struct AwayClient {
+    client: Client,
+    left_at: Instant,
 }
 
 enum ClientConnectState {
@@ -52,23 +97,42 @@ pub(crate) struct Authent {
     names: FastSet<String>,
     clients: FastMap<AuthentID, ClientConnectState>,
     addr_to_client: FastMap<SocketAddr, AuthentID>,
+    /// Disconnected players by resume token
+    away: FastMap<u64, AwayClient>,
     n_connected_clients: u32,
     seq: u32,
     version: String,
+    mods: Vec<String>,
+    /// Role by password
+    passwords: FastMap<String, Role>,
+    default_role: Role,
 }
 
 impl Authent {
-    pub fn new(version: String) -> Self {
+    pub fn new(
+        version: String,
+        mods: Vec<String>,
+        passwords: Vec<(String, Role)>,
+        default_role: Role,
+    ) -> Self {
         Self {
             names: Default::default(),
             clients: Default::default(),
             addr_to_client: Default::default(),
+            away: Default::default(),
             n_connected_clients: 0,
             seq: 1,
             version,
+            mods,
+            passwords: passwords.into_iter().collect(),
+            default_role,
         }
     }
 
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
     /// returns true if the player was already registered
     pub fn register(&mut self, name: String) -> bool {
         !self.names.insert(name)
@@ -80,6 +144,9 @@ impl Authent {
         ack: Frame,
         name: String,
         version: String,
+        mods: Vec<String>,
+        spectate: bool,
+        password: Option<String>,
         period: Duration,
     ) -> Option<AuthentResponse> {
         let v = self.get_client_state_mut(addr)?;
@@ -108,25 +175,130 @@ impl Authent {
                 });
             }
 
+            if mods != self.mods {
+                let missing = self
+                    .mods
+                    .iter()
+                    .filter(|m| !mods.contains(m))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let extra = mods
+                    .iter()
+                    .filter(|m| !self.mods.contains(m))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                return Some(AuthentResponse::Refused {
+                    reason: format!(
+                        "Incompatible mods: missing on client: [{}], not on server: [{}]",
+                        missing.join(", "),
+                        extra.join(", ")
+                    ),
+                });
+            }
+
+            let role = if spectate {
+                Role::Spectator
+            } else {
+                password
+                    .and_then(|p| self.passwords.get(&p).copied())
+                    .unwrap_or(self.default_role)
+            };
+
+            let resume_token = new_resume_token();
+
             // Unwrap ok: already checked right before
             *self.get_client_state_mut(tcp_addr).unwrap() = ClientConnectState::Connected(Client {
                 id,
                 uid: UserID(hash),
                 name,
+                role,
                 ack,
 
                 udp_addr,
                 tcp_addr,
                 state: ClientGameState::Downloading,
+                resume_token,
             });
 
             self.n_connected_clients += 1;
 
-            return Some(AuthentResponse::Accepted { id, period });
+            return Some(AuthentResponse::Accepted {
+                id,
+                period,
+                role,
+                resume_token,
+            });
         }
         None
     }
 
+    /// Gives back its session to a player coming back with its resume token.
+    /// `resume` is called with the old and new ids of the client and tells if the inputs the
+    /// client missed since `frame` are still available.
+    pub fn tcp_client_resume(
+        &mut self,
+        addr: SocketAddr,
+        token: u64,
+        frame: Frame,
+        resume: impl FnOnce(AuthentID, AuthentID) -> bool,
+    ) -> Option<AuthentResponse> {
+        let ClientConnectState::Connecting {
+            id,
+            tcp_addr,
+            udp_addr: Some(udp_addr),
+        } = *self.get_client_state_mut(addr)?
+        else {
+            return None;
+        };
+
+        let Some(away) = self.away.remove(&token) else {
+            return Some(AuthentResponse::Refused {
+                reason: "session expired".to_string(),
+            });
+        };
+        let old = away.client;
+
+        if !resume(old.id, id) {
+            self.names.remove(&old.name);
+            return Some(AuthentResponse::Refused {
+                reason: format!("cannot resume from frame {}", frame.0),
+            });
+        }
+        log::info!("client resumed: {}@{}", old.name, addr);
+
+        let resume_token = new_resume_token();
+        let role = old.role;
+
+        // Unwrap ok: already checked right before
+        *self.get_client_state_mut(tcp_addr).unwrap() = ClientConnectState::Connected(Client {
+            id,
+            uid: old.uid,
+            name: old.name,
+            role,
+            ack: frame,
+
+            udp_addr,
+            tcp_addr,
+            state: ClientGameState::CatchingUp,
+            resume_token,
+        });
+
+        self.n_connected_clients += 1;
+
+        Some(AuthentResponse::Resumed {
+            id,
+            role,
+            resume_token,
+        })
+    }
+
+    /// The connection of the client still connected with this resume token
+    pub fn connected_with_token(&self, token: u64) -> Option<SocketAddr> {
+        self.iter()
+            .find(|c| c.resume_token == token)
+            .map(|c| c.tcp_addr)
+    }
+
     pub fn udp_connect(&mut self, addr: SocketAddr, id: AuthentID, net: &Connections) {
         log::info!("udp connect: {}", addr);
         self.addr_to_client.insert(addr, id);
@@ -158,6 +330,7 @@ impl Authent {
         net.send_tcp(tcp_addr, encode(&ServerReliablePacket::Challenge(id)));
     }
 
+    /// The name of the client stays reserved until it is either kept away or released
     pub fn disconnected(&mut self, tcp_addr: SocketAddr) -> Option<Client> {
         let id = self.addr_to_client.remove(&tcp_addr)?;
         let client = self.clients.remove(&id)?;
@@ -168,13 +341,46 @@ impl Authent {
         if let ClientConnectState::Connected(c) = client {
             self.addr_to_client.remove(&c.udp_addr);
             self.n_connected_clients -= 1;
-            self.names.remove(&c.name);
 
             return Some(c);
         }
         None
     }
 
+    /// Keeps the session of a disconnected client for [`RESUME_GRACE_PERIOD`]
+    pub fn keep_away(&mut self, client: Client) {
+        self.away.insert(
+            client.resume_token,
+            AwayClient {
+                client,
+                left_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn release_name(&mut self, name: &str) {
+        self.names.remove(name);
+    }
+
+    /// Forgets the clients that did not come back in time, releasing their names
+    pub fn expire_away(&mut self) -> Vec<Client> {
+        let expired = self
+            .away
+            .iter()
+            .filter(|(_, a)| a.left_at.elapsed() > RESUME_GRACE_PERIOD)
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>();
+
+        expired
+            .into_iter()
+            .filter_map(|token| self.away.remove(&token))
+            .map(|a| {
+                self.names.remove(&a.client.name);
+                a.client
+            })
+            .collect()
+    }
+
     pub fn get_client(&self, addr: SocketAddr) -> Option<&Client> {
         self.addr_to_client
             .get(&addr)
@@ -216,6 +422,13 @@ impl Authent {
     }
 }
 
+/// The token lets a client take back its seat after a disconnection, so it must not be guessable
+fn new_resume_token() -> u64 {
+    let mut bytes = [0; 8];
+    getrandom::getrandom(&mut bytes).expect("the OS could not provide randomness");
+    u64::from_le_bytes(bytes)
+}
+
 impl ClientConnectState {
     pub fn as_connected(&self) -> Option<&Client> {
         if let ClientConnectState::Connected(c) = self {
@@ -33,6 +33,35 @@ impl CatchUp {
         }
     }
 
+    /// Remembers the inputs a disconnected client misses, from `from` which it already consumed.
+    /// `past` are the inputs consumed by the server since then
+    pub fn remember_away(&mut self, id: AuthentID, from: Frame, past: Vec<MergedInputs>) {
+        self.frame_history.insert(
+            id,
+            CatchUpState {
+                inputs: past,
+                sent: 0,
+                from,
+                ready: false,
+            },
+        );
+    }
+
+    /// Hands the inputs remembered for a disconnected client to its new connection,
+    /// starting from the frame the client reached. Returns false if they are not available
+    pub fn resume(&mut self, old: AuthentID, new: AuthentID, frame: Frame) -> bool {
+        let Some(mut state) = self.frame_history.remove(&old) else {
+            return false;
+        };
+        if frame < state.from || frame.0 > state.from.0 + state.inputs.len() as u64 {
+            return false;
+        }
+        state.inputs.drain(..(frame.0 - state.from.0) as usize);
+        state.from = frame;
+        self.frame_history.insert(new, state);
+        true
+    }
+
     pub fn add_merged_inputs(&mut self, frame: Frame, inp: MergedInputs) {
         for v in self.frame_history.values_mut() {
             if frame.0 != v.from.0 + 1 + v.inputs.len() as u64 {
@@ -90,3 +119,28 @@ impl CatchUp {
         self.frame_history.remove(&id);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_skips_consumed_inputs() {
+        let mut catchup = CatchUp::default();
+        let (old, new) = (AuthentID(2), AuthentID(3));
+
+        catchup.remember_away(old, Frame(10), vec![vec![]; 3]);
+        catchup.add_merged_inputs(Frame(14), vec![]);
+        assert!(catchup.resume(old, new, Frame(12)));
+        assert!(!catchup.frame_history.contains_key(&old));
+
+        let state = &catchup.frame_history[&new];
+        assert_eq!(state.from, Frame(12));
+        assert_eq!(state.inputs.len(), 2);
+
+        catchup.remember_away(AuthentID(4), Frame(20), vec![]);
+        assert!(!catchup.resume(AuthentID(4), AuthentID(5), Frame(21)));
+        catchup.remember_away(AuthentID(6), Frame(20), vec![]);
+        assert!(!catchup.resume(AuthentID(6), AuthentID(7), Frame(19)));
+    }
+}
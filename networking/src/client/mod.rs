@@ -1,24 +1,34 @@
 use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use client_playout::ClientPlayoutBuffer;
 
+use crate::authent::RESUME_GRACE_PERIOD;
 use crate::connection_client::ConnectionClient;
 use crate::connections::ConnectionsError;
 use crate::packets::{
     AuthentResponse, ClientReliablePacket, ClientUnreliablePacket, ServerReliablePacket,
     ServerUnreliablePacket,
 };
-use crate::worldsend::WorldReceive;
+use crate::worldsend::{WorldCache, WorldParts, WorldReceive};
 use crate::{
-    decode, decode_merged, encode, AuthentID, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT,
+    decode, decode_merged, encode, AuthentID, Frame, PhantomSendSync, PlayerInput, Role,
+    DEFAULT_PORT,
 };
 use common::timestep::Timestep;
 
 mod client_playout;
 
+/// Time between two attempts to reach the server again after losing the connection
+const RECONNECT_PERIOD: Duration = Duration::from_secs(2);
+
+type WorldFromParts<W> = Box<dyn Fn(WorldParts) -> Option<W> + Send + Sync>;
+
 #[derive(Debug)]
 pub struct FrameInputs<I> {
     pub inputs: Vec<ServerInput<I>>,
@@ -55,6 +65,14 @@ enum ClientState<W, I> {
         buffer: ClientPlayoutBuffer,
         final_inputs: Option<Vec<FrameInputs<I>>>,
     },
+    /// Lost the connection while playing, trying to resume the session from `frame`
+    Reconnecting {
+        frame: Frame,
+        since: Instant,
+        last_try: Instant,
+        /// Connecting in the background not to block the game
+        pending: Option<Receiver<Result<ConnectionClient, ConnectionsError>>>,
+    },
     Disconnected {
         reason: String,
     },
@@ -62,9 +80,18 @@ enum ClientState<W, I> {
 
 pub struct Client<WORLD: DeserializeOwned, INPUT: Serialize + DeserializeOwned + Default> {
     net: ConnectionClient,
+    addr: SocketAddr,
 
     name: String,
     version: String,
+    mods: Vec<String>,
+    spectate: bool,
+    password: Option<String>,
+    role: Option<Role>,
+    /// Given by the server once authenticated
+    resume_token: Option<u64>,
+    cache: WorldCache,
+    world_from_parts: Option<WorldFromParts<WORLD>>,
 
     state: ClientState<WORLD, INPUT>,
 
@@ -80,6 +107,13 @@ pub struct ConnectConf {
     pub port: Option<u16>,
     pub frame_buffer_advance: u64,
     pub version: String,
+    pub mods: Vec<String>,
+    /// Join as a spectator, whatever role the server would give
+    pub spectate: bool,
+    /// Secret given by the server owner to get a role other than the default one
+    pub password: Option<String>,
+    /// Where to keep the downloaded world, so that joining again only downloads what changed
+    pub world_cache: Option<PathBuf>,
 }
 
 impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I> {
@@ -92,23 +126,57 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
 
         Ok(Self {
             net,
+            addr: saddr,
             state: ClientState::Connecting,
             name: conf.name,
             lag_compensate: conf.frame_buffer_advance,
             step: Timestep::default(),
             _phantom: Default::default(),
             version: conf.version,
+            mods: conf.mods,
+            spectate: conf.spectate,
+            password: conf.password,
+            role: None,
+            resume_token: None,
+            cache: WorldCache::load(conf.world_cache),
+            world_from_parts: None,
         })
     }
 
+    /// Assembles the world from the parts given by [`crate::Server::set_world_parts`].
+    /// Without it, the world is expected as a single part.
+    pub fn set_world_from_parts(
+        &mut self,
+        world_from_parts: impl Fn(WorldParts) -> Option<W> + Send + Sync + 'static,
+    ) {
+        self.world_from_parts = Some(Box::new(world_from_parts));
+    }
+
     #[allow(clippy::collapsible_if)]
     pub fn poll(&mut self, input: I) -> PollResult<W, I> {
         //log::info!("{:?}", &self.state);
         if self.net.is_disconnected() {
-            if !matches!(self.state, ClientState::Disconnected { .. }) {
-                self.state = ClientState::Disconnected {
-                    reason: "connection lost".to_string(),
-                };
+            match self.state {
+                ClientState::Playing {
+                    ref buffer,
+                    final_inputs: None,
+                    ..
+                } if self.resume_token.is_some() => {
+                    log::warn!("{}: connection lost, trying to resume", self.name);
+                    let frame = buffer.consumed_frame();
+                    self.state = ClientState::Reconnecting {
+                        frame,
+                        since: Instant::now(),
+                        last_try: Instant::now(),
+                        pending: None,
+                    };
+                }
+                ClientState::Reconnecting { .. } | ClientState::Disconnected { .. } => {}
+                _ => {
+                    self.state = ClientState::Disconnected {
+                        reason: "connection lost".to_string(),
+                    };
+                }
             }
         }
 
@@ -141,6 +209,48 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
             ClientState::Connecting => {
                 return PollResult::Wait(input);
             }
+            ClientState::Reconnecting {
+                since,
+                ref mut last_try,
+                ref mut pending,
+                ..
+            } => {
+                if since.elapsed() > RESUME_GRACE_PERIOD {
+                    let reason = "connection lost".to_string();
+                    self.state = ClientState::Disconnected {
+                        reason: reason.clone(),
+                    };
+                    return PollResult::Disconnect(reason);
+                }
+
+                let connected = pending.as_ref().and_then(|rx| rx.try_recv().ok());
+                match connected {
+                    Some(Ok(net)) => {
+                        log::info!("{}: reached the server again", self.name);
+                        self.net = net;
+                        *pending = None;
+                    }
+                    Some(Err(e)) => {
+                        log::info!("{}: could not reach the server: {:?}", self.name, e);
+                        *pending = None;
+                    }
+                    None => {}
+                }
+
+                if pending.is_none()
+                    && self.net.is_disconnected()
+                    && last_try.elapsed() >= RECONNECT_PERIOD
+                {
+                    *last_try = Instant::now();
+                    let (tx, rx) = channel();
+                    let addr = self.addr;
+                    std::thread::spawn(move || {
+                        let _ = tx.send(ConnectionClient::new(addr));
+                    });
+                    *pending = Some(rx);
+                }
+                return PollResult::Wait(input);
+            }
             ClientState::Downloading {
                 wr: WorldReceive::Errored,
                 ..
@@ -246,11 +356,26 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
 
     fn message_reliable(&mut self, p: ServerReliablePacket) -> Option<()> {
         match p {
+            ServerReliablePacket::WorldManifest(manifest) => {
+                log::info!("{}: received world manifest", self.name);
+
+                if let ClientState::Downloading { ref mut wr, .. } = self.state {
+                    let from_parts = &self.world_from_parts;
+                    wr.handle_manifest(manifest, &mut self.cache, &self.net, |parts| {
+                        world_from_parts(from_parts, parts)
+                    });
+                } else {
+                    log::error!("received world manifest but was not downloading.. weird");
+                }
+            }
             ServerReliablePacket::WorldSend(fragment) => {
                 log::info!("{}: received world fragment", self.name);
 
                 if let ClientState::Downloading { ref mut wr, .. } = self.state {
-                    wr.handle(fragment, &self.net);
+                    let from_parts = &self.world_from_parts;
+                    wr.handle_fragment(fragment, &self.cache, &self.net, |parts| {
+                        world_from_parts(from_parts, parts)
+                    });
                 } else {
                     log::error!("received world but was not downloading.. weird");
                 }
@@ -261,11 +386,19 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                     .send_udp(encode(&ClientUnreliablePacket::Connection(challenge)));
             }
             ServerReliablePacket::AuthentResponse(r) => match r {
-                AuthentResponse::Accepted { id, period: step } => {
+                AuthentResponse::Accepted {
+                    id,
+                    period: step,
+                    role,
+                    resume_token,
+                } => {
                     log::info!(
-                        "{}: authent response is accepted. asking for world",
-                        self.name
+                        "{}: authent response is accepted as {:?}. asking for world",
+                        self.name,
+                        role
                     );
+                    self.role = Some(role);
+                    self.resume_token = Some(resume_token);
                     self.state = ClientState::Downloading {
                         wr: WorldReceive::default(),
                         id,
@@ -273,6 +406,26 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                     self.step = Timestep::new(step);
                     self.net.send_tcp(encode(&ClientReliablePacket::WorldAck));
                 }
+                AuthentResponse::Resumed {
+                    id,
+                    role,
+                    resume_token,
+                } => {
+                    let ClientState::Reconnecting { frame, .. } = self.state else {
+                        log::error!("resumed but was not reconnecting.. weird");
+                        return None;
+                    };
+                    log::info!("{}: session resumed at {:?}. catching up", self.name, frame);
+                    self.role = Some(role);
+                    self.resume_token = Some(resume_token);
+                    self.state = ClientState::CatchingUp {
+                        id,
+                        consumed_frame: frame,
+                        next_inputs: None,
+                    };
+                    self.net
+                        .send_tcp(encode(&ClientReliablePacket::BeginCatchUp));
+                }
                 AuthentResponse::Refused { reason } => {
                     log::error!("authent refused :( reason: {}", reason);
                     self.state = ClientState::Disconnected { reason };
@@ -364,15 +517,33 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
             }
             ServerUnreliablePacket::ReadyForAuth => {
                 log::info!("{}: received ready for auth", self.name);
-                let connect = ClientReliablePacket::Connect {
-                    name: self.name.clone(),
-                    version: self.version.clone(),
+                let connect = match (&self.state, self.resume_token) {
+                    (ClientState::Reconnecting { frame, .. }, Some(token)) => {
+                        ClientReliablePacket::Resume {
+                            token,
+                            frame: *frame,
+                        }
+                    }
+                    _ => ClientReliablePacket::Connect {
+                        name: self.name.clone(),
+                        version: self.version.clone(),
+                        mods: self.mods.clone(),
+                        spectate: self.spectate,
+                        password: self.password.clone(),
+                        cached: self.cache.checksums(),
+                    },
                 };
                 self.net.send_tcp(encode(&connect));
             }
+            ServerUnreliablePacket::Announce(_) => {}
         }
     }
 
+    /// The role given by the server, once authenticated
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+
     pub fn describe(&self) -> String {
         match self.state {
             ClientState::Connecting => "Connecting...".to_string(),
@@ -388,12 +559,211 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                 }
             }
             ClientState::CatchingUp { .. } => "Catching up...".to_string(),
+            ClientState::Reconnecting { .. } => "Connection lost, reconnecting...".to_string(),
             ClientState::Playing {
                 buffer: ref buf, ..
             } => {
-                format!("Playing! Buffer advance: {}", buf.advance())
+                format!(
+                    "Playing as {:?}! Buffer advance: {}",
+                    self.role.unwrap_or(Role::Builder),
+                    buf.advance()
+                )
             }
             ClientState::Disconnected { ref reason } => reason.clone(),
         }
     }
 }
+
+fn world_from_parts<W: DeserializeOwned>(
+    f: &Option<WorldFromParts<W>>,
+    mut parts: WorldParts,
+) -> Option<W> {
+    match f {
+        Some(f) => f(parts),
+        None => {
+            let i = parts.iter().position(|(name, _)| name == "world")?;
+            decode(&parts.swap_remove(i).1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Server, ServerConfiguration, ServerPollResult};
+    use std::net::Ipv4Addr;
+
+    /// A server and a client playing a world that counts its frames
+    struct Game {
+        server: Server<u64, u32>,
+        server_frames: u64,
+        client: Client<u64, u32>,
+        client_frames: Option<u64>,
+    }
+
+    impl Game {
+        fn start(port: u16) -> Self {
+            Self::start_as(port, "player", None)
+        }
+
+        /// The server gives the admin role to the players knowing "secret"
+        fn start_as(port: u16, name: &str, password: Option<&str>) -> Self {
+            let server = Server::start(ServerConfiguration {
+                start_frame: Frame(0),
+                period: Duration::from_millis(5),
+                port: Some(port),
+                name: "test".to_string(),
+                virtual_client: None,
+                version: "v1".to_string(),
+                mods: vec![],
+                default_role: Role::Builder,
+                passwords: vec![("secret".to_string(), Role::Admin)],
+                always_run: true,
+            })
+            .unwrap();
+
+            let client = Client::connect(ConnectConf {
+                name: name.to_string(),
+                addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                port: Some(port),
+                frame_buffer_advance: 1,
+                version: "v1".to_string(),
+                mods: vec![],
+                spectate: false,
+                password: password.map(str::to_string),
+                world_cache: None,
+            })
+            .unwrap();
+
+            Self {
+                server,
+                server_frames: 0,
+                client,
+                client_frames: None,
+            }
+        }
+
+        fn poll_server(&mut self) {
+            let frame = Frame(self.server_frames);
+            if let ServerPollResult::Input(inputs) =
+                self.server.poll(&self.server_frames, frame, None)
+            {
+                self.server_frames += inputs.len() as u64;
+            }
+        }
+
+        fn poll_client(&mut self) {
+            match self.client.poll(0) {
+                PollResult::GameWorld(_, world) => self.client_frames = Some(world),
+                PollResult::Input(inputs) => {
+                    *self.client_frames.as_mut().unwrap() += inputs.len() as u64;
+                }
+                PollResult::Disconnect(reason) => panic!("client disconnected: {}", reason),
+                PollResult::Wait(_) => {}
+            }
+        }
+
+        /// Polls both sides until `cond` holds, panicking after a while
+        fn run_until(&mut self, what: &str, cond: impl Fn(&Self) -> bool) {
+            let start = Instant::now();
+            while !cond(self) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(15),
+                    "timed out waiting for {}",
+                    what
+                );
+                self.poll_server();
+                self.poll_client();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        /// The frame consumed by the client, if it is playing
+        fn consumed_frame(&self) -> Option<Frame> {
+            match self.client.state {
+                ClientState::Playing {
+                    ref buffer,
+                    final_inputs: None,
+                    ..
+                } => Some(buffer.consumed_frame()),
+                _ => None,
+            }
+        }
+
+        fn server_sees_playing(&self) -> bool {
+            let players = self.server.players();
+            players.len() == 1 && players[0].state == "Playing"
+        }
+
+        fn assert_in_sync(&self) {
+            let frame = self.consumed_frame().expect("client is not playing");
+            assert_eq!(self.client_frames, Some(frame.0));
+        }
+    }
+
+    #[test]
+    fn resume_drops_the_old_connection() {
+        let mut game = Game::start(23529);
+        game.run_until("playing", |g| g.consumed_frame().is_some());
+        let frame = game.consumed_frame().unwrap();
+
+        // the client lost its connection and comes back before the server noticed
+        let old_net = std::mem::replace(
+            &mut game.client.net,
+            ConnectionClient::new(game.client.addr).unwrap(),
+        );
+        game.client.state = ClientState::Reconnecting {
+            frame,
+            since: Instant::now(),
+            last_try: Instant::now(),
+            pending: None,
+        };
+
+        game.run_until("resumed", |g| {
+            g.consumed_frame().is_some_and(|f| f.0 > frame.0 + 10) && g.server_sees_playing()
+        });
+        game.assert_in_sync();
+        drop(old_net);
+    }
+
+    #[test]
+    fn lagged_out_client_resumes() {
+        let mut game = Game::start(23530);
+        game.run_until("playing", |g| g.consumed_frame().is_some());
+        let frame = game.consumed_frame().unwrap();
+
+        // the client stops answering until the server drops it
+        let start = Instant::now();
+        while !game.server.players().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(15),
+                "never lagged out"
+            );
+            game.poll_server();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        game.run_until("resumed", |g| {
+            g.consumed_frame().is_some_and(|f| f.0 > frame.0 + 200) && g.server_sees_playing()
+        });
+        game.assert_in_sync();
+    }
+
+    #[test]
+    fn roles_need_the_password() {
+        // anyone can pick the name of the admin
+        let mut game = Game::start_as(23531, "admin", None);
+        game.run_until("playing", |g| g.consumed_frame().is_some());
+        assert_eq!(game.client.role(), Some(Role::Builder));
+        assert_eq!(game.server.players()[0].role, Role::Builder);
+
+        let mut game = Game::start_as(23532, "admin", Some("wrong"));
+        game.run_until("playing", |g| g.consumed_frame().is_some());
+        assert_eq!(game.client.role(), Some(Role::Builder));
+
+        let mut game = Game::start_as(23533, "player", Some("secret"));
+        game.run_until("playing", |g| g.consumed_frame().is_some());
+        assert_eq!(game.client.role(), Some(Role::Admin));
+        assert_eq!(game.server.players()[0].role, Role::Admin);
+    }
+}
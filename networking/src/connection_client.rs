@@ -1,6 +1,6 @@
 use crate::connections::{ConnectionsError, FramedTcpReceiver};
 use std::io::{Read, Write};
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
@@ -120,6 +120,11 @@ impl ConnectionClient {
             let mut buf = [0u8; 65536];
             loop {
                 match stream_cpy.read(&mut buf) {
+                    Ok(0) => {
+                        log::info!("tcp connection closed by the server");
+                        disconnected.store(true, Ordering::SeqCst);
+                        break;
+                    }
                     Ok(size) => {
                         let data = buf[..size].to_vec();
                         tcp_send.send(data).unwrap();
@@ -149,7 +154,10 @@ impl ConnectionClient {
                         }
                     }
                 }
-                Err(_) => break,
+                Err(_) => {
+                    let _ = tcp_stream.shutdown(Shutdown::Both);
+                    break;
+                }
                 _ => {}
             }
         });
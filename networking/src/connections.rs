@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::io::{Read, Write};
-use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::net::{Shutdown, SocketAddr, TcpListener, UdpSocket};
 use std::sync::mpsc::{channel, Receiver, Sender};
 
 pub struct Packet {
@@ -168,6 +168,10 @@ impl Connections {
                         let mut buf = [0u8; 65536];
                         loop {
                             match stream_cpy.read(&mut buf) {
+                                Ok(0) => {
+                                    let _ = send_cpy.send(TcpConnEvent::Killed { addr });
+                                    break;
+                                }
                                 Ok(size) => {
                                     let data = buf[..size].to_vec();
                                     send_from_tcp.send(data).unwrap();
@@ -197,7 +201,11 @@ impl Connections {
                                     }
                                 }
                             }
-                            Err(_) => break,
+                            // the connection was removed, close it so that the client notices
+                            Err(_) => {
+                                let _ = stream.shutdown(Shutdown::Both);
+                                break;
+                            }
                             _ => {}
                         }
                     });
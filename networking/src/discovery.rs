@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::packets::{ClientUnreliablePacket, ServerUnreliablePacket};
+use crate::{decode, encode, DEFAULT_PORT};
+
+/// Servers are looked for on this many ports starting from the default port,
+/// so that several servers can run on the same machine
+pub const DISCOVERY_PORTS: u16 = 8;
+
+const PROBE_PERIOD: Duration = Duration::from_secs(2);
+/// Servers that did not answer the last probes are forgotten
+const SERVER_TIMEOUT: Duration = Duration::from_secs(7);
+
+/// Whether a discovery probe from this address is answered.
+/// Probes need no connection so their source can be spoofed, and the answer is much bigger than
+/// the probe: only the local network is answered so that servers can't be used to flood others.
+pub(crate) fn answers_discovery(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return answers_discovery(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            // unique local fc00::/7 and link local fe80::/10
+            ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+/// What a server tells about itself when probed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerAnnounce {
+    pub name: String,
+    pub version: String,
+    pub players: u32,
+    /// Short description of the world, as given by [`crate::Server::set_map_info`]
+    pub map: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer {
+    /// Address to connect to, the game port of the server
+    pub addr: SocketAddr,
+    pub announce: ServerAnnounce,
+    last_seen: Instant,
+}
+
+/// Finds the servers of the local network by broadcasting probes on their UDP game port.
+/// The probes are also sent to localhost in case broadcasting is not possible.
+pub struct Discovery {
+    sock: UdpSocket,
+    ports: Range<u16>,
+    last_probe: Option<Instant>,
+    servers: BTreeMap<SocketAddr, DiscoveredServer>,
+}
+
+impl Discovery {
+    pub fn new() -> std::io::Result<Self> {
+        Self::with_ports(DEFAULT_PORT..DEFAULT_PORT + DISCOVERY_PORTS)
+    }
+
+    pub fn with_ports(ports: Range<u16>) -> std::io::Result<Self> {
+        let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        sock.set_broadcast(true)?;
+        sock.set_nonblocking(true)?;
+        Ok(Self {
+            sock,
+            ports,
+            last_probe: None,
+            servers: BTreeMap::new(),
+        })
+    }
+
+    /// Probes the network periodically and collects the answers. Never blocks
+    pub fn poll(&mut self) {
+        if self
+            .last_probe
+            .map_or(true, |t| t.elapsed() >= PROBE_PERIOD)
+        {
+            self.probe();
+        }
+
+        let mut buf = [0u8; 4096];
+        while let Ok((size, addr)) = self.sock.recv_from(&mut buf) {
+            let Some(ServerUnreliablePacket::Announce(announce)) = decode(&buf[..size]) else {
+                continue;
+            };
+
+            // servers of this machine answer both the broadcast and the localhost probes,
+            // only the loopback address is kept so that they are listed once
+            let same = |s: &DiscoveredServer| {
+                s.addr.port() == addr.port() && s.announce.name == announce.name
+            };
+            if addr.ip().is_loopback() {
+                self.servers.retain(|_, s| s.addr == addr || !same(s));
+            } else if self
+                .servers
+                .values()
+                .any(|s| s.addr.ip().is_loopback() && same(s))
+            {
+                continue;
+            }
+
+            self.servers.insert(
+                addr,
+                DiscoveredServer {
+                    addr,
+                    announce,
+                    last_seen: Instant::now(),
+                },
+            );
+        }
+
+        self.servers
+            .retain(|_, s| s.last_seen.elapsed() < SERVER_TIMEOUT);
+    }
+
+    /// Sends a probe right away instead of waiting for the next period
+    pub fn probe(&mut self) {
+        let probe = encode(&ClientUnreliablePacket::Discover);
+        for port in self.ports.clone() {
+            for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                if let Err(e) = self.sock.send_to(&probe, (ip, port)) {
+                    log::debug!("could not send discovery probe to {}:{}: {}", ip, port, e);
+                }
+            }
+        }
+        self.last_probe = Some(Instant::now());
+    }
+
+    /// The servers that answered recently, sorted by address
+    pub fn servers(&self) -> impl Iterator<Item = &DiscoveredServer> + '_ {
+        self.servers.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Frame, Role, Server, ServerConfiguration};
+
+    fn start_server(name: &str, port: u16) -> Server<(), ()> {
+        let mut server = Server::start(ServerConfiguration {
+            start_frame: Frame(0),
+            period: Duration::from_millis(20),
+            port: Some(port),
+            name: name.to_string(),
+            virtual_client: None,
+            version: "v1".to_string(),
+            mods: vec![],
+            default_role: Role::Builder,
+            passwords: vec![],
+            always_run: false,
+        })
+        .unwrap();
+        server.set_map_info(move |_| format!("map of {}", port));
+        server
+    }
+
+    #[test]
+    fn only_local_probes_are_answered() {
+        for local in [
+            "127.0.0.1",
+            "192.168.1.12",
+            "10.0.0.3",
+            "172.16.4.1",
+            "169.254.0.7",
+            "::1",
+            "fd12::1",
+            "fe80::1",
+            "::ffff:192.168.1.12",
+        ] {
+            assert!(answers_discovery(local.parse().unwrap()), "{}", local);
+        }
+        for public in ["8.8.8.8", "172.32.0.1", "2001:db8::1", "::ffff:8.8.8.8"] {
+            assert!(!answers_discovery(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[test]
+    fn discovers_servers_on_loopback() {
+        let ports = 23419..23422;
+        let mut a = start_server("a", ports.start);
+        let mut b = start_server("b", ports.start + 1);
+        let mut discovery = Discovery::with_ports(ports).unwrap();
+
+        let found = |d: &Discovery| {
+            let mut found = d
+                .servers()
+                .map(|s| (s.addr.port(), s.announce.clone()))
+                .collect::<Vec<_>>();
+            found.sort_by_key(|(port, _)| *port);
+            found
+        };
+
+        let start = Instant::now();
+        while found(&discovery).len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            a.poll(&(), Frame(0), None);
+            b.poll(&(), Frame(0), None);
+            discovery.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(
+            found(&discovery),
+            vec![
+                (
+                    23419,
+                    ServerAnnounce {
+                        name: "a".to_string(),
+                        version: "v1".to_string(),
+                        players: 0,
+                        map: "map of 23419".to_string(),
+                    }
+                ),
+                (
+                    23420,
+                    ServerAnnounce {
+                        name: "b".to_string(),
+                        version: "v1".to_string(),
+                        players: 0,
+                        map: "map of 23420".to_string(),
+                    }
+                ),
+            ]
+        );
+    }
+
+    /// Serves discovery for [`discovers_servers_of_other_processes`] in a child process
+    #[test]
+    #[ignore]
+    fn discovery_server_process() {
+        let Ok(port) = std::env::var("DISCOVERY_SERVER_PORT") else {
+            return;
+        };
+        let mut server = start_server("child", port.parse().unwrap());
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            server.poll(&(), Frame(0), None);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn discovers_servers_of_other_processes() {
+        let port = 23429;
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--ignored",
+                "--exact",
+                "discovery::tests::discovery_server_process",
+            ])
+            .env("DISCOVERY_SERVER_PORT", port.to_string())
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut discovery = Discovery::with_ports(port..port + 1).unwrap();
+        let start = Instant::now();
+        while discovery.servers().next().is_none() && start.elapsed() < Duration::from_secs(5) {
+            discovery.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        // give the answers to the broadcast probe some time to come in too
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(100) {
+            discovery.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let servers = discovery.servers().collect::<Vec<_>>();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].announce.name, "child");
+        assert_eq!(servers[0].addr.port(), port);
+        assert!(servers[0].addr.ip().is_loopback());
+    }
+}
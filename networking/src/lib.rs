@@ -14,14 +14,18 @@ mod catchup;
 mod client;
 mod connection_client;
 mod connections;
+mod discovery;
 mod packets;
 mod ring;
 mod server;
 mod worldsend;
 
 use crate::client::FrameInputs;
+pub use authent::Role;
 pub use client::{Client, ConnectConf, PollResult, ServerInput};
-pub use server::{Server, ServerConfiguration, ServerPollResult, VirtualClientConf};
+pub use discovery::{DiscoveredServer, Discovery, ServerAnnounce, DISCOVERY_PORTS};
+pub use server::{PlayerInfo, Server, ServerConfiguration, ServerPollResult, VirtualClientConf};
+pub use worldsend::WorldParts;
 
 pub(crate) const MAX_WORLDSEND_PACKET_SIZE: usize = 262144; //32 ko at least 1.3Mo per s at 50FPS
 pub(crate) const DEFAULT_PORT: u16 = 23019;
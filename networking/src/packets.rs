@@ -1,4 +1,5 @@
-use crate::authent::AuthentID;
+use crate::authent::{AuthentID, Role};
+use crate::discovery::ServerAnnounce;
 use crate::{Frame, MergedInputs, PlayerInput};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -7,6 +8,7 @@ use std::time::Duration;
 pub(crate) enum ServerUnreliablePacket {
     Input(Vec<(Frame, MergedInputs)>),
     ReadyForAuth,
+    Announce(ServerAnnounce),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -20,32 +22,79 @@ pub(crate) enum ServerReliablePacket {
     CatchUp {
         inputs: Vec<MergedInputs>,
     },
+    WorldManifest(WorldManifest),
     WorldSend(WorldDataFragment),
 }
 
 #[derive(Serialize, Deserialize)]
 pub(crate) enum ClientUnreliablePacket {
     Connection(AuthentID),
-    Input { input: Vec<(Frame, PlayerInput)> },
+    Input {
+        input: Vec<(Frame, PlayerInput)>,
+    },
+    /// Sent by [`crate::Discovery`] to find the servers, no connection needed
+    Discover,
 }
 
 #[derive(Serialize, Deserialize)]
 pub(crate) enum ClientReliablePacket {
-    Connect { name: String, version: String },
+    Connect {
+        name: String,
+        version: String,
+        mods: Vec<String>,
+        spectate: bool,
+        /// Secret giving a role other than the default one, see [`crate::ServerConfiguration`]
+        password: Option<String>,
+        /// Checksums of the world parts the client already has
+        cached: Vec<(String, u64)>,
+    },
     BeginCatchUp,
     CatchUpAck,
     WorldAck,
+    /// Sent instead of `Connect` by a client that lost its connection, to continue playing
+    /// from the frame it reached without downloading the world again
+    Resume {
+        token: u64,
+        frame: Frame,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum AuthentResponse {
-    Accepted { id: AuthentID, period: Duration },
-    Refused { reason: String },
+    Accepted {
+        id: AuthentID,
+        period: Duration,
+        role: Role,
+        resume_token: u64,
+    },
+    Resumed {
+        id: AuthentID,
+        role: Role,
+        resume_token: u64,
+    },
+    Refused {
+        reason: String,
+    },
+}
+
+/// The parts of the world at `frame`, sent before their data
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct WorldManifest {
+    pub frame: Frame,
+    pub parts: Vec<WorldPartInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct WorldPartInfo {
+    pub name: String,
+    pub checksum: u64,
+    /// Size of the compressed data to download, None if the client has the part in its cache
+    pub download: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct WorldDataFragment {
-    pub is_over: Option<Frame>,
-    pub data_size: usize,
+    /// Index of the part in the manifest
+    pub part: usize,
     pub data: Vec<u8>,
 }
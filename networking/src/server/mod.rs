@@ -2,16 +2,17 @@ use std::time::Duration;
 
 use serde::Serialize;
 
-use crate::authent::{Authent, AuthentID, ClientGameState};
+use crate::authent::{Authent, AuthentID, ClientGameState, Role, RESUME_GRACE_PERIOD};
 use crate::catchup::CatchUp;
 use crate::client::FrameInputs;
 use crate::connections::{Connections, ConnectionsError};
+use crate::discovery::{answers_discovery, ServerAnnounce};
 use crate::packets::{
     AuthentResponse, ClientReliablePacket, ClientUnreliablePacket, ServerReliablePacket,
     ServerUnreliablePacket,
 };
 use crate::server::server_playout::ServerPlayoutBuffer;
-use crate::worldsend::WorldSend;
+use crate::worldsend::{WorldParts, WorldSend};
 use crate::{decode, decode_merged, encode, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT};
 use common::timestep::Timestep;
 use serde::de::DeserializeOwned;
@@ -23,9 +24,18 @@ pub struct ServerConfiguration {
     pub start_frame: Frame,
     pub period: Duration,
     pub port: Option<u16>,
+    /// Name shown to the players discovering the server on the local network
+    pub name: String,
     pub virtual_client: Option<VirtualClientConf>,
     /// Checks if client has same version or refuses authent otherwise
     pub version: String,
+    /// Checks if client has the same mods loaded or refuses authent otherwise
+    pub mods: Vec<String>,
+    /// Role of the players joining without one of the `passwords`
+    pub default_role: Role,
+    /// Role given to the players joining with the password.
+    /// Roles are never given by name as players choose their own name
+    pub passwords: Vec<(String, Role)>,
     /// Always run, even when everyone is disconnected
     pub always_run: bool,
 }
@@ -39,6 +49,18 @@ pub enum ServerPollResult<I> {
     Input(Vec<FrameInputs<I>>),
 }
 
+type InputFilter<INPUT> = Box<dyn Fn(Role, &mut INPUT) + Send + Sync>;
+type MapInfo<WORLD> = Box<dyn Fn(&WORLD) -> String + Send + Sync>;
+type WorldPartsFn<WORLD> = Box<dyn Fn(&WORLD) -> WorldParts + Send + Sync>;
+
+pub struct PlayerInfo {
+    pub name: String,
+    pub role: Role,
+    pub state: String,
+    /// How many frames behind the server the player is, None if too late
+    pub lag: Option<u64>,
+}
+
 struct VirtualClient {
     name: String,
 }
@@ -55,6 +77,12 @@ pub struct Server<WORLD: Serialize, INPUT> {
 
     step: Timestep,
     always_run: bool,
+    paused: bool,
+    speed: u32,
+    input_filter: Option<InputFilter<INPUT>>,
+    name: String,
+    map_info: Option<MapInfo<WORLD>>,
+    world_parts: Option<WorldPartsFn<WORLD>>,
 
     _phantom: PhantomSendSync<(WORLD, INPUT)>,
 }
@@ -64,7 +92,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         let port = conf.port.unwrap_or(DEFAULT_PORT);
         let net = Connections::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port))?;
 
-        let mut authent = Authent::new(conf.version);
+        let mut authent = Authent::new(conf.version, conf.mods, conf.passwords, conf.default_role);
         let v_client = conf.virtual_client.map(|c| VirtualClient { name: c.name });
         if let Some(ref v_client) = v_client {
             authent.register(v_client.name.clone());
@@ -80,6 +108,12 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
             worldsend: Default::default(),
             _phantom: Default::default(),
             always_run: conf.always_run,
+            paused: false,
+            speed: 1,
+            input_filter: None,
+            name: conf.name,
+            map_info: None,
+            world_parts: None,
             next_inputs: vec![],
         })
     }
@@ -97,6 +131,10 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         for addr in deleted {
             self.tcp_disconnected(addr);
         }
+        for c in self.authent.expire_away() {
+            log::info!("player {} did not come back", c.name);
+            self.catchup.disconnected(c.id);
+        }
 
         loop {
             let v = self.net.recv_tcp();
@@ -114,7 +152,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
 
         while let Some(p) = self.net.recv_udp() {
             if let Some(packet) = decode(&p.data) {
-                let _ = self.message_unreliable(p.addr, packet);
+                let _ = self.message_unreliable(p.addr, packet, world);
             } else {
                 log::error!("client sent invalid unreliable packet");
             }
@@ -124,14 +162,13 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         self.send_long_running();
 
         if !self.next_inputs.is_empty() {
-            if self.v_client.is_some() {
-                if let Some(inp) = local_inputs {
-                    self.buffer.insert_input(
-                        AuthentID::VIRTUAL_ID,
-                        self.buffer.consumed_frame.incred(),
-                        PlayerInput(encode(&inp)),
-                    );
-                }
+            // local inputs come from the virtual client if there is one, or from the server itself (e.g admin commands)
+            if let Some(inp) = local_inputs {
+                self.buffer.insert_input(
+                    AuthentID::VIRTUAL_ID,
+                    self.buffer.consumed_frame.incred(),
+                    PlayerInput(encode(&inp)),
+                );
             }
             return ServerPollResult::Input(std::mem::take(&mut self.next_inputs));
         }
@@ -141,11 +178,11 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
     fn send_merged_inputs(&mut self) {
         let n_playing = self.authent.iter_playing().count() + self.v_client.is_some() as usize;
 
-        if n_playing == 0 && !self.always_run {
+        if (n_playing == 0 && !self.always_run) || self.paused {
             return;
         }
 
-        self.step.prepare_frame(1);
+        self.step.prepare_frame(self.speed);
 
         while self.step.tick() {
             let buffer = &self.buffer;
@@ -162,7 +199,9 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                     self.buffer.consumed_frame,
                     ack,
                 );
-                self.disconnect(tcp_addr);
+                // closing the connection lets the client notice and resume its session
+                self.net.remove_tcp(tcp_addr);
+                self.disconnect(tcp_addr, true);
             }
 
             let clients_playing = self.authent.iter_playing();
@@ -206,6 +245,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         &mut self,
         addr: SocketAddr,
         packet: ClientUnreliablePacket,
+        world: &WORLD,
     ) -> Option<()> {
         match packet {
             ClientUnreliablePacket::Input { input } => {
@@ -215,12 +255,38 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
 
                 for (frame, input) in input {
                     client.ack = client.ack.max(frame);
+                    let input = match self.input_filter {
+                        Some(ref filter) => {
+                            let Some(mut inp) = decode::<INPUT>(&input.0) else {
+                                log::error!("client {} sent invalid input", client.name);
+                                continue;
+                            };
+                            filter(client.role, &mut inp);
+                            PlayerInput(encode(&inp))
+                        }
+                        None if client.role == Role::Spectator => continue,
+                        None => input,
+                    };
                     self.buffer.insert_input(client.id, frame, input);
                 }
             }
             ClientUnreliablePacket::Connection(id) => {
                 self.authent.udp_connect(addr, id, &self.net);
             }
+            ClientUnreliablePacket::Discover => {
+                if !answers_discovery(addr.ip()) {
+                    log::debug!("ignoring discovery probe from {}", addr);
+                    return None;
+                }
+                let announce = ServerAnnounce {
+                    name: self.name.clone(),
+                    version: self.authent.version().to_string(),
+                    players: self.players().len() as u32,
+                    map: self.map_info.as_ref().map(|f| f(world)).unwrap_or_default(),
+                };
+                self.net
+                    .send_udp(addr, encode(&ServerUnreliablePacket::Announce(announce)));
+            }
         }
         Some(())
     }
@@ -233,13 +299,23 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         w_frame: Frame,
     ) -> Option<()> {
         match packet {
-            ClientReliablePacket::Connect { name, version } => {
+            ClientReliablePacket::Connect {
+                name,
+                version,
+                mods,
+                spectate,
+                password,
+                cached,
+            } => {
                 log::info!("received tcp game handshake: {} {}", name, version);
                 let auth_r = self.authent.tcp_client_auth(
                     addr,
                     self.buffer.consumed_frame,
                     name,
                     version,
+                    mods,
+                    spectate,
+                    password,
                     self.step.period,
                 )?;
 
@@ -252,7 +328,11 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                     AuthentResponse::Accepted { .. } => {
                         let c = self.authent.get_client(addr)?;
                         assert_eq!(self.buffer.consumed_frame, w_frame);
-                        self.worldsend.begin_send(c, encode(&w), w_frame);
+                        let parts = match self.world_parts {
+                            Some(ref f) => f(w),
+                            None => vec![("world".to_string(), encode(w))],
+                        };
+                        self.worldsend.begin_send(c, parts, &cached, w_frame);
                         self.catchup
                             .begin_remembering(self.buffer.consumed_frame, c);
 
@@ -262,6 +342,32 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                         log::error!("refused authent because: {}", reason);
                         self.net.remove_tcp(addr);
                     }
+                    AuthentResponse::Resumed { .. } => unreachable!(),
+                }
+            }
+            ClientReliablePacket::Resume { token, frame } => {
+                // the previous connection may be lost without the server having noticed yet
+                if let Some(old_addr) = self.authent.connected_with_token(token) {
+                    log::info!("dropping the previous connection of a resuming client");
+                    self.net.remove_tcp(old_addr);
+                    self.disconnect(old_addr, true);
+                }
+
+                let catchup = &mut self.catchup;
+                let auth_r = self
+                    .authent
+                    .tcp_client_resume(addr, token, frame, |old, new| {
+                        catchup.resume(old, new, frame)
+                    })?;
+
+                self.net.send_tcp(
+                    addr,
+                    encode(&ServerReliablePacket::AuthentResponse(auth_r.clone())),
+                );
+
+                if let AuthentResponse::Refused { reason } = auth_r {
+                    log::error!("refused resume because: {}", reason);
+                    self.net.remove_tcp(addr);
                 }
             }
             ClientReliablePacket::BeginCatchUp => {
@@ -289,7 +395,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
     }
 
     fn tcp_disconnected(&mut self, tcp_addr: SocketAddr) {
-        self.disconnect(tcp_addr);
+        self.disconnect(tcp_addr, true);
     }
 
     pub fn describe(&self) -> String {
@@ -299,17 +405,117 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
             s += &*format!("{}: Playing...\n", c.name)
         }
         for c in self.authent.iter() {
-            s += &*format!("{}: {:?}...\n", c.name, c.state);
+            s += &*format!("{} ({:?}): {:?}...\n", c.name, c.role, c.state);
         }
         s
     }
 
-    fn disconnect(&mut self, tcp_addr: SocketAddr) {
-        if let Some(c) = self.authent.disconnected(tcp_addr) {
-            log::info!("player {} disconnected", c.name);
-            self.buffer.disconnected(c.id);
-            self.catchup.disconnected(c.id);
-            self.worldsend.disconnected(c.id);
+    /// Filters the inputs of each player according to its role before they are sent to everyone.
+    /// Without a filter, the inputs of spectators are dropped and the others are kept as is.
+    pub fn set_input_filter(&mut self, filter: impl Fn(Role, &mut INPUT) + Send + Sync + 'static) {
+        self.input_filter = Some(Box::new(filter));
+    }
+
+    /// Describes the world to the players discovering the server, e.g. its size or population
+    pub fn set_map_info(&mut self, map_info: impl Fn(&WORLD) -> String + Send + Sync + 'static) {
+        self.map_info = Some(Box::new(map_info));
+    }
+
+    /// Splits the world in parts sent separately to the joining players, so that they only
+    /// download the parts that changed since they last joined.
+    /// Without it, the world is sent as a single part.
+    pub fn set_world_parts(
+        &mut self,
+        world_parts: impl Fn(&WORLD) -> WorldParts + Send + Sync + 'static,
+    ) {
+        self.world_parts = Some(Box::new(world_parts));
+    }
+
+    /// The players connected to the server, including the virtual client
+    pub fn players(&self) -> Vec<PlayerInfo> {
+        let mut v = vec![];
+        if let Some(ref c) = self.v_client {
+            v.push(PlayerInfo {
+                name: c.name.clone(),
+                role: Role::Admin,
+                state: "Playing".to_string(),
+                lag: Some(0),
+            });
+        }
+        for c in self.authent.iter() {
+            v.push(PlayerInfo {
+                name: c.name.clone(),
+                role: c.role,
+                state: format!("{:?}", c.state),
+                lag: self.buffer.lag(c.ack),
+            });
+        }
+        v
+    }
+
+    /// Disconnects the player with the given name. Returns false if no such player is connected
+    pub fn kick(&mut self, name: &str) -> bool {
+        let Some(tcp_addr) = self
+            .authent
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.tcp_addr)
+        else {
+            return false;
+        };
+        log::info!("kicking {}", name);
+        self.net.remove_tcp(tcp_addr);
+        self.disconnect(tcp_addr, false);
+        true
+    }
+
+    /// Stops producing frames until resumed. Clients wait for the server so everyone is paused
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Produces frames `speed` times faster than the configured period
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed.max(1);
+    }
+
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// A `resumable` player that was playing can come back during the grace period
+    /// and only catch up with the inputs it missed
+    fn disconnect(&mut self, tcp_addr: SocketAddr, resumable: bool) {
+        let Some(c) = self.authent.disconnected(tcp_addr) else {
+            return;
+        };
+        self.buffer.disconnected(c.id);
+        self.catchup.disconnected(c.id);
+        self.worldsend.disconnected(c.id);
+
+        let missed = if resumable && c.state == ClientGameState::Playing {
+            self.buffer.inputs_since(c.ack)
+        } else {
+            None
+        };
+        match missed {
+            Some(missed) => {
+                log::info!(
+                    "player {} disconnected, keeping their session for {}s",
+                    c.name,
+                    RESUME_GRACE_PERIOD.as_secs()
+                );
+                self.catchup.remember_away(c.id, c.ack, missed);
+                self.authent.keep_away(c);
+            }
+            None => {
+                log::info!("player {} disconnected", c.name);
+                self.authent.release_name(&c.name);
+            }
         }
     }
 }
@@ -57,6 +57,20 @@ impl ServerPlayoutBuffer {
         }
     }
 
+    /// The inputs consumed after the given frame, None if they are not remembered anymore.
+    /// They are still remembered right when the client becomes too late.
+    pub fn inputs_since(&self, f: Frame) -> Option<Vec<MergedInputs>> {
+        let lag = self.consumed_frame.0 - f.0;
+        if lag >= self.past.len() as u64 {
+            return None;
+        }
+        Some(
+            (1..=lag)
+                .map(|i| self.past.get(f + Frame(i)).clone())
+                .collect(),
+        )
+    }
+
     // call when a user has disconnected
     pub fn disconnected(&mut self, user: AuthentID) {
         self.dedup.remove(&user);
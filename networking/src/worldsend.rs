@@ -1,10 +1,37 @@
 use crate::authent::{Client, ClientGameState};
 use crate::connection_client::ConnectionClient;
 use crate::connections::Connections;
-use crate::packets::{ClientReliablePacket, ServerReliablePacket, WorldDataFragment};
+use crate::packets::{
+    ClientReliablePacket, ServerReliablePacket, WorldDataFragment, WorldManifest, WorldPartInfo,
+};
 use crate::{decode, encode, AuthentID, Frame, MAX_WORLDSEND_PACKET_SIZE};
 use common::FastMap;
-use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+
+/// The world split in named parts, serialized independently
+pub type WorldParts = Vec<(String, Vec<u8>)>;
+
+const CACHE_EXTENSION: &str = "part";
+
+/// Checksum of the serialized part, the same on every machine.
+/// The data is read as little endian 64 bits words so that it doesn't depend on the pointer width.
+fn part_checksum(data: &[u8]) -> u64 {
+    const K: u64 = 0x517c_c1b7_2722_0a95;
+    let mix = |hash: u64, word: u64| (hash.rotate_left(5) ^ word).wrapping_mul(K);
+
+    let mut hash = (data.len() as u64).wrapping_mul(K);
+    let mut words = data.chunks_exact(8);
+    for word in &mut words {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(word);
+        hash = mix(hash, u64::from_le_bytes(bytes));
+    }
+    for &byte in words.remainder() {
+        hash = mix(hash, byte as u64);
+    }
+    hash
+}
 
 #[derive(Eq, PartialEq)]
 enum WorldSendStatus {
@@ -14,10 +41,9 @@ enum WorldSendStatus {
 }
 
 struct WorldSendState {
-    data: Vec<u8>,
-    sent: usize,
+    manifest: Option<WorldManifest>,
+    fragments: VecDeque<WorldDataFragment>,
     status: WorldSendStatus,
-    frame: Frame,
 }
 
 #[derive(Default)]
@@ -26,14 +52,58 @@ pub(crate) struct WorldSend {
 }
 
 impl WorldSend {
-    pub fn begin_send(&mut self, c: &Client, data: Vec<u8>, frame: Frame) {
+    /// Sends the list of parts, then only the parts the client doesn't have in its cache
+    pub fn begin_send(
+        &mut self,
+        c: &Client,
+        parts: WorldParts,
+        cached: &[(String, u64)],
+        frame: Frame,
+    ) {
+        let mut manifest = WorldManifest {
+            frame,
+            parts: Vec::with_capacity(parts.len()),
+        };
+        let mut fragments = VecDeque::new();
+
+        for (i, (name, data)) in parts.into_iter().enumerate() {
+            let checksum = part_checksum(&data);
+            let download = if cached.iter().any(|(n, c)| *n == name && *c == checksum) {
+                None
+            } else {
+                let compressed = encode(&data);
+                for chunk in compressed.chunks(MAX_WORLDSEND_PACKET_SIZE) {
+                    fragments.push_back(WorldDataFragment {
+                        part: i,
+                        data: chunk.to_vec(),
+                    });
+                }
+                Some(compressed.len())
+            };
+            manifest.parts.push(WorldPartInfo {
+                name,
+                checksum,
+                download,
+            });
+        }
+
+        log::info!(
+            "sending {}/{} world parts to {}",
+            manifest
+                .parts
+                .iter()
+                .filter(|p| p.download.is_some())
+                .count(),
+            manifest.parts.len(),
+            c.name
+        );
+
         self.send_state.insert(
             c.id,
             WorldSendState {
-                data,
-                sent: 0,
+                manifest: Some(manifest),
+                fragments,
                 status: WorldSendStatus::ReadyToSend,
-                frame,
             },
         );
     }
@@ -61,26 +131,23 @@ impl WorldSend {
                 return;
             }
 
-            let to_send = MAX_WORLDSEND_PACKET_SIZE.min(state.data.len() - state.sent);
-            let is_over = (to_send < MAX_WORLDSEND_PACKET_SIZE).then_some(state.frame);
-
-            net.send_tcp(
-                c.tcp_addr,
-                encode(&ServerReliablePacket::WorldSend(WorldDataFragment {
-                    is_over,
-                    data_size: state.data.len(),
-                    data: Vec::from(&state.data[state.sent..state.sent + to_send]),
-                })),
-            );
-
-            if is_over.is_some() {
-                log::info!("sending final world fragment to {}", c.name);
-                state.status = WorldSendStatus::WaitingForFinalAck;
-            } else {
+            if let Some(manifest) = state.manifest.take() {
+                net.send_tcp(
+                    c.tcp_addr,
+                    encode(&ServerReliablePacket::WorldManifest(manifest)),
+                );
+            } else if let Some(fragment) = state.fragments.pop_front() {
                 log::info!("sending world fragment to {}", c.name);
+                net.send_tcp(
+                    c.tcp_addr,
+                    encode(&ServerReliablePacket::WorldSend(fragment)),
+                );
             }
 
-            state.sent += to_send;
+            if state.fragments.is_empty() {
+                log::info!("sent the whole world to {}", c.name);
+                state.status = WorldSendStatus::WaitingForFinalAck;
+            }
         } else {
             log::error!("updating a non existing world send");
         }
@@ -91,11 +158,81 @@ impl WorldSend {
     }
 }
 
-#[derive(Debug)]
+/// The parts of the worlds received before, kept on disk so that joining again only
+/// downloads the parts that changed, and an interrupted download continues where it stopped
+pub(crate) struct WorldCache {
+    dir: Option<PathBuf>,
+    parts: BTreeMap<String, (u64, Vec<u8>)>,
+}
+
+impl WorldCache {
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let mut parts = BTreeMap::new();
+        if let Some(dir) = dir.as_ref().filter(|d| d.is_dir()) {
+            for path in common::saveload::walkdir(dir) {
+                if path.extension().map_or(true, |e| e != CACHE_EXTENSION) {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let Ok(data) = std::fs::read(&path) else {
+                    continue;
+                };
+                parts.insert(name.to_string(), (part_checksum(&data), data));
+            }
+            log::info!("{} world parts in cache {}", parts.len(), dir.display());
+        }
+        Self { dir, parts }
+    }
+
+    pub fn checksums(&self) -> Vec<(String, u64)> {
+        self.parts
+            .iter()
+            .map(|(name, (checksum, _))| (name.clone(), *checksum))
+            .collect()
+    }
+
+    fn take(&mut self, name: &str, checksum: u64) -> Option<Vec<u8>> {
+        match self.parts.remove(name) {
+            Some((c, data)) if c == checksum => Some(data),
+            _ => None,
+        }
+    }
+
+    fn store(&self, name: &str, data: &[u8]) {
+        let Some(ref dir) = self.dir else {
+            return;
+        };
+        // the name comes from the server, don't let it write anywhere else
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return;
+        }
+        let res = std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(dir.join(format!("{name}.{CACHE_EXTENSION}")), data));
+        if let Err(e) = res {
+            log::error!("could not cache world part {}: {}", name, e);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub(crate) enum WorldReceive<W> {
+    /// Waiting for the list of parts
+    #[default]
+    Manifest,
     Downloading {
-        datasize: usize,
-        data_so_far: Vec<u8>,
+        frame: Frame,
+        parts: Vec<WorldPartInfo>,
+        /// Serialized parts, from the cache or downloaded
+        data: Vec<Option<Vec<u8>>>,
+        /// Compressed data received so far for the part being downloaded
+        current: Vec<u8>,
+        downloaded: usize,
+        total: usize,
     },
     Finished {
         frame: Frame,
@@ -108,52 +245,258 @@ impl<W> WorldReceive<W> {
     pub fn progress(&self) -> Option<(usize, usize)> {
         match self {
             WorldReceive::Downloading {
-                datasize,
-                data_so_far,
-            } => Some((data_so_far.len(), *datasize)),
+                downloaded, total, ..
+            } => Some((*downloaded, *total)),
             _ => None,
         }
     }
-}
 
-impl<W> Default for WorldReceive<W> {
-    fn default() -> Self {
-        Self::Downloading {
-            datasize: 0,
-            data_so_far: vec![],
+    pub fn handle_manifest(
+        &mut self,
+        manifest: WorldManifest,
+        cache: &mut WorldCache,
+        net: &ConnectionClient,
+        from_parts: impl FnOnce(WorldParts) -> Option<W>,
+    ) {
+        if !matches!(self, WorldReceive::Manifest) {
+            log::warn!("received world manifest twice");
+            return;
         }
-    }
-}
 
-impl<W: DeserializeOwned> WorldReceive<W> {
-    pub fn handle(&mut self, fragment: WorldDataFragment, net: &ConnectionClient) {
-        if let WorldReceive::Downloading {
-            ref mut datasize,
-            ref mut data_so_far,
-        } = self
-        {
-            *datasize = fragment.data_size;
-            if data_so_far.capacity() == 0 {
-                data_so_far.reserve(fragment.data_size)
+        let mut data = Vec::with_capacity(manifest.parts.len());
+        for part in &manifest.parts {
+            if part.download.is_some() {
+                data.push(None);
+                continue;
             }
-            data_so_far.extend(fragment.data);
-            if let Some(frame) = fragment.is_over {
-                log::info!("received last fragment at {:?}", frame);
-                net.send_tcp(encode(&ClientReliablePacket::WorldAck));
+            let Some(cached) = cache.take(&part.name, part.checksum) else {
+                log::error!("world part {} is not in the cache anymore", part.name);
+                *self = WorldReceive::Errored;
+                return;
+            };
+            data.push(Some(cached));
+        }
 
-                let d = decode(data_so_far);
+        let total = manifest.parts.iter().filter_map(|p| p.download).sum();
+        log::info!(
+            "received world manifest at {:?}: {} parts, {} bytes to download",
+            manifest.frame,
+            manifest.parts.len(),
+            total
+        );
 
-                if let Some(w) = d {
-                    *self = WorldReceive::Finished { frame, world: w }
-                } else {
-                    *self = WorldReceive::Errored;
-                }
-            }
-        } else {
+        *self = WorldReceive::Downloading {
+            frame: manifest.frame,
+            parts: manifest.parts,
+            data,
+            current: vec![],
+            downloaded: 0,
+            total,
+        };
+        self.try_finish(net, from_parts);
+    }
+
+    pub fn handle_fragment(
+        &mut self,
+        fragment: WorldDataFragment,
+        cache: &WorldCache,
+        net: &ConnectionClient,
+        from_parts: impl FnOnce(WorldParts) -> Option<W>,
+    ) {
+        let WorldReceive::Downloading {
+            ref parts,
+            ref mut data,
+            ref mut current,
+            ref mut downloaded,
+            ..
+        } = *self
+        else {
             log::warn!(
                 "received fragment but was not downloading (errored: {:?})",
                 matches!(self, WorldReceive::Errored)
             );
+            return;
+        };
+
+        let Some(part) = parts.get(fragment.part) else {
+            log::error!("received fragment of unknown world part {}", fragment.part);
+            *self = WorldReceive::Errored;
+            return;
+        };
+
+        *downloaded += fragment.data.len();
+        current.extend(fragment.data);
+        if Some(current.len()) != part.download {
+            return;
+        }
+
+        let received = decode::<Vec<u8>>(current.as_slice());
+        current.clear();
+        match received {
+            Some(received) if part_checksum(&received) == part.checksum => {
+                cache.store(&part.name, &received);
+                data[fragment.part] = Some(received);
+            }
+            _ => {
+                log::error!("world part {} is corrupted", part.name);
+                *self = WorldReceive::Errored;
+                return;
+            }
+        }
+
+        self.try_finish(net, from_parts);
+    }
+
+    /// Builds the world once every part is known
+    fn try_finish(
+        &mut self,
+        net: &ConnectionClient,
+        from_parts: impl FnOnce(WorldParts) -> Option<W>,
+    ) {
+        let WorldReceive::Downloading {
+            frame,
+            ref mut parts,
+            ref mut data,
+            ..
+        } = *self
+        else {
+            return;
+        };
+        if data.iter().any(Option::is_none) {
+            return;
+        }
+
+        log::info!("received the whole world at {:?}", frame);
+        net.send_tcp(encode(&ClientReliablePacket::WorldAck));
+
+        let parts = std::mem::take(parts)
+            .into_iter()
+            .zip(std::mem::take(data))
+            .map(|(part, data)| (part.name, data.unwrap_or_default()))
+            .collect();
+
+        *self = match from_parts(parts) {
+            Some(world) => WorldReceive::Finished { frame, world },
+            None => WorldReceive::Errored,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Role, UserID};
+    use std::net::{SocketAddr, TcpListener};
+
+    fn client() -> Client {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        Client {
+            id: AuthentID(2),
+            uid: UserID(0),
+            name: "player".to_string(),
+            role: Role::Builder,
+            ack: Frame(0),
+            udp_addr: addr,
+            tcp_addr: addr,
+            state: ClientGameState::Downloading,
+            resume_token: 0,
+        }
+    }
+
+    fn parts() -> WorldParts {
+        vec![
+            ("map".to_string(), vec![1; 1000]),
+            ("world".to_string(), vec![2; 10]),
+        ]
+    }
+
+    /// The manifest and fragments sent by the server to a client with the `cached` parts
+    fn sent(cached: &[(String, u64)]) -> (WorldManifest, Vec<WorldDataFragment>) {
+        let c = client();
+        let mut send = WorldSend::default();
+        send.begin_send(&c, parts(), cached, Frame(7));
+        let state = send.send_state.remove(&c.id).unwrap();
+        (state.manifest.unwrap(), state.fragments.into())
+    }
+
+    /// The client only sends its ack on the connection, nobody needs to read it
+    fn connection() -> (TcpListener, ConnectionClient) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let net = ConnectionClient::new(listener.local_addr().unwrap()).unwrap();
+        (listener, net)
+    }
+
+    fn cache_with(parts: &[(String, Vec<u8>)]) -> WorldCache {
+        WorldCache {
+            dir: None,
+            parts: parts
+                .iter()
+                .map(|(name, data)| (name.clone(), (part_checksum(data), data.clone())))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn checksum_is_portable() {
+        assert_eq!(part_checksum(b"egregoria world"), 0xee16_0b8f_cf8e_090b);
+        assert_ne!(part_checksum(&[0; 8]), part_checksum(&[0; 9]));
+    }
+
+    #[test]
+    fn cached_parts_are_skipped() {
+        let map = parts().swap_remove(0);
+        let mut cache = cache_with(&[map.clone()]);
+        let (manifest, fragments) = sent(&cache.checksums());
+
+        assert_eq!(manifest.parts[0].download, None);
+        assert!(manifest.parts[1].download.is_some());
+        assert!(fragments.iter().all(|f| f.part == 1));
+
+        let (_listener, net) = connection();
+        let mut wr = WorldReceive::default();
+        wr.handle_manifest(manifest, &mut cache, &net, Some);
+        for fragment in fragments {
+            wr.handle_fragment(fragment, &cache, &net, Some);
+        }
+
+        let WorldReceive::Finished { frame, world } = wr else {
+            panic!("the world should be received");
+        };
+        assert_eq!(frame, Frame(7));
+        assert_eq!(world, parts());
+    }
+
+    #[test]
+    fn corrupted_part_errors() {
+        let (mut manifest, fragments) = sent(&[]);
+        manifest.parts[0].checksum ^= 1;
+
+        let mut cache = cache_with(&[]);
+        let (_listener, net) = connection();
+        let mut wr = WorldReceive::default();
+        wr.handle_manifest(manifest, &mut cache, &net, Some);
+        for fragment in fragments {
+            wr.handle_fragment(fragment, &cache, &net, Some);
+        }
+
+        assert!(matches!(wr, WorldReceive::Errored));
+    }
+
+    #[test]
+    fn cache_miss_after_manifest_errors() {
+        // the client announced a part that it lost since
+        let (manifest, fragments) = sent(&cache_with(&parts()[..1]).checksums());
+
+        let mut cache = cache_with(&[]);
+        let (_listener, net) = connection();
+        let mut wr = WorldReceive::default();
+        wr.handle_manifest(manifest, &mut cache, &net, Some);
+        assert!(matches!(wr, WorldReceive::Errored));
+
+        // the rest of the download is ignored
+        for fragment in fragments {
+            wr.handle_fragment(fragment, &cache, &net, Some);
         }
+        assert!(matches!(wr, WorldReceive::Errored));
     }
 }
@@ -9,12 +9,14 @@ use std::hash::Hash;
 mod macros;
 
 mod load;
+mod mods;
 mod prototypes;
 mod tests;
 mod types;
 mod validation;
 
 pub use load::*;
+pub use mods::*;
 pub use prototypes::*;
 pub use types::*;
 
@@ -1,53 +1,155 @@
 use crate::validation::ValidationError;
-use crate::{validation, Prototypes, PROTOTYPES};
+use crate::{
+    depends_on, discover_mods, field_write_conflicts, validation, FieldWrite, ModManifest,
+    Prototypes, BASE_MOD, PROTOTYPES,
+};
 use common::error::MultiError;
-use mlua::{Lua, Table};
+use mlua::{Function, Lua, Table};
+use std::collections::BTreeMap;
 use std::io;
 use thiserror::Error;
 
 pub fn test_prototypes(lua: &str) {
     let l = Lua::new();
 
-    unsafe { load_prototypes_str(l, lua).unwrap() };
+    let test_mod = ModManifest {
+        name: BASE_MOD.to_string(),
+        version: "test".to_string(),
+        dependencies: vec![],
+        dir: String::new(),
+    };
+
+    unsafe {
+        l.load(include_str!("prototype_init.lua")).exec().unwrap();
+        l.load(lua).exec().unwrap();
+        let n = l.globals().get::<_, Table>("data").unwrap().raw_len();
+        load_prototypes_lua(l, vec![test_mod], &[n], &[]).unwrap()
+    };
 }
 
-/// Loads the prototypes from the data.lua file
+/// Loads the prototypes from the base mod and the mods in the `mods/` folder, in dependency order.
+/// Mods are loaded by running their data.lua file. A mod can override a prototype of a mod it depends on
+/// by defining one with the same name, or modify it in place using `data:get(type, name)`.
+/// Two mods modifying the same field are a conflict unless one depends on the other.
 /// # Safety
 /// This function is not thread safe, and should only be called once at the start of the program.
 pub unsafe fn load_prototypes(base: &str) -> Result<(), PrototypeLoadError> {
     log::info!("loading prototypes from {}", base);
-    let l = Lua::new();
+    let mods = discover_mods(base)?;
 
-    let base = base.to_string();
+    let l = Lua::new();
+    l.load(include_str!("prototype_init.lua")).exec()?;
 
-    l.globals()
-        .get::<_, Table>("package")?
-        .set("path", base.clone() + "base_mod/?.lua")?;
+    // the number of prototypes defined and the fields modified once each mod is loaded
+    let (ends, writes) = load_mods_data(&l, &mods)?;
 
-    load_prototypes_str(
-        l,
-        &common::saveload::load_string(base + "base_mod/data.lua")?,
-    )
+    load_prototypes_lua(l, mods, &ends, &writes)
 }
 
-unsafe fn load_prototypes_str(l: Lua, main: &str) -> Result<(), PrototypeLoadError> {
-    l.load(include_str!("prototype_init.lua")).exec()?;
+fn load_mods_data(
+    l: &Lua,
+    mods: &[ModManifest],
+) -> Result<(Vec<usize>, Vec<Vec<FieldWrite>>), PrototypeLoadError> {
+    let data_table = l.globals().get::<_, Table>("data")?;
+    let package = l.globals().get::<_, Table>("package")?;
+    let loaded = package.get::<_, Table>("loaded")?;
+    let builtin_modules = table_keys(&loaded);
+
+    let take_writes = data_table.get::<_, Function>("take_writes")?;
+
+    let mut ends = Vec::with_capacity(mods.len());
+    let mut writes = Vec::with_capacity(mods.len());
+
+    for m in mods {
+        log::info!("loading mod {}", m);
+        let data_path = format!("{}/data.lua", m.dir);
+        if std::fs::metadata(&data_path).is_ok() {
+            package.set("path", format!("{}/?.lua", m.dir))?;
+
+            l.load(&common::saveload::load_string(&data_path)?)
+                .set_name(&data_path)
+                .exec()?;
+
+            // forget the modules required by this mod so the next mods can use the same file names
+            for k in table_keys(&loaded) {
+                if !builtin_modules.contains(&k) {
+                    loaded.set(k, mlua::Value::Nil)?;
+                }
+            }
+        }
+        ends.push(data_table.raw_len());
+        writes.push(
+            take_writes
+                .call::<_, Vec<Table>>(data_table.clone())?
+                .into_iter()
+                .map(|w| Ok((w.get(1)?, w.get(2)?, w.get(3)?)))
+                .collect::<mlua::Result<Vec<FieldWrite>>>()?,
+        );
+    }
+
+    Ok((ends, writes))
+}
 
-    l.load(main).exec()?;
+fn table_keys(t: &Table) -> Vec<String> {
+    t.clone()
+        .pairs::<String, mlua::Value>()
+        .filter_map(|kv| kv.ok())
+        .map(|(k, _)| k)
+        .collect()
+}
 
+/// `ends` is the number of entries in the data table after each mod was loaded, used to know which mod defined what
+/// `writes` are the fields each mod modified through `data:get`
+unsafe fn load_prototypes_lua(
+    l: Lua,
+    mods: Vec<ModManifest>,
+    ends: &[usize],
+    writes: &[Vec<FieldWrite>],
+) -> Result<(), PrototypeLoadError> {
     let mut p = Box::<Prototypes>::default();
 
-    let mut errors = Vec::new();
+    let mut errors = field_write_conflicts(&mods, writes);
+
+    // (type, name) -> index of the mod that defined it
+    let mut owners: BTreeMap<(String, String), usize> = BTreeMap::new();
 
     let data_table = l.globals().get::<_, Table>("data")?;
 
-    let _ = data_table.for_each(|_: String, t: Table| {
-        let r = p.parse_prototype(t);
-        if let Err(e) = r {
+    for (i, t) in data_table.sequence_values::<Table>().enumerate() {
+        let t = t?;
+        let owner = ends
+            .iter()
+            .position(|&end| i < end)
+            .unwrap_or(mods.len() - 1);
+
+        if let (Ok(ty), Ok(name)) = (t.get::<_, String>("type"), t.get::<_, String>("name")) {
+            if let Some(prev) = owners.insert((ty.clone(), name.clone()), owner) {
+                if prev == owner {
+                    log::warn!("duplicate {} with name: {}", ty, name);
+                } else if depends_on(&mods, owner, prev) {
+                    log::info!(
+                        "{} {} from {} is overridden by {}",
+                        ty,
+                        name,
+                        mods[prev].name,
+                        mods[owner].name
+                    );
+                } else {
+                    errors.push(PrototypeLoadError::Conflict(
+                        ty,
+                        name,
+                        mods[prev].name.clone(),
+                        mods[owner].name.clone(),
+                    ));
+                    continue;
+                }
+            }
+        }
+
+        if let Err(e) = p.parse_prototype(t) {
             errors.push(e);
         }
-        Ok(())
-    });
+    }
 
     if !errors.is_empty() {
         return Err(PrototypeLoadError::MultiError(MultiError(errors)));
@@ -57,6 +159,7 @@ unsafe fn load_prototypes_str(l: Lua, main: &str) -> Result<(), PrototypeLoadErr
 
     p.compute_orderings();
     p.print_stats();
+    p.mods = mods;
 
     unsafe {
         PROTOTYPES = Some(Box::leak(p));
@@ -77,4 +180,18 @@ pub enum PrototypeLoadError {
     MultiError(MultiError<PrototypeLoadError>),
     #[error("validation errors: {0}")]
     ValidationErrors(#[from] MultiError<ValidationError>),
+    #[error("invalid mod manifest {0}: {1}")]
+    InvalidManifest(String, mlua::Error),
+    #[error("mod {0} is defined twice (also in {1})")]
+    DuplicateMod(String, String),
+    #[error("mod {0} depends on {1} which is not installed")]
+    MissingDependency(String, String),
+    #[error("dependency cycle between mods: {0:?}")]
+    DependencyCycle(Vec<String>),
+    #[error("{0} {1} is defined by both {2} and {3}, which do not depend on each other")]
+    Conflict(String, String, String, String),
+    #[error(
+        "field {2} of {0} {1} is modified by both {3} and {4}, which do not depend on each other"
+    )]
+    FieldConflict(String, String, String, String, String),
 }
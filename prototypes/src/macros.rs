@@ -38,6 +38,7 @@ macro_rules! gen_prototypes {
                 pub(crate) $name: common::TransparentMap<$id, $t>,
             )+
             pub(crate) orderings: Orderings,
+            pub(crate) mods: Vec<$crate::ModManifest>,
         }
 
         $(
@@ -106,9 +107,8 @@ macro_rules! gen_prototypes {
 
                             <$t as $crate::ConcretePrototype>::insert_parents(&proto, self);
 
-                            if let Some(v) = self.$name.insert((&proto.name).into(), proto) {
-                                log::warn!("duplicate {} with name: {}", <$t as $crate::Prototype>::NAME, v.name);
-                            }
+                            // duplicates and mod overrides are reported by the loader
+                            self.$name.insert((&proto.name).into(), proto);
                         }
                     ),+
                     _ => {
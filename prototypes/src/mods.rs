@@ -0,0 +1,329 @@
+use crate::PrototypeLoadError;
+use common::error::MultiError;
+use mlua::{Lua, Table};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// The name of the mod living in the `base_mod` folder. Every other mod implicitly depends on it.
+pub const BASE_MOD: &str = "base";
+
+/// The manifest of a mod, read from the `info.lua` file at the root of its folder
+///
+/// ```lua
+/// return {
+///     name = "more-bakeries",
+///     version = "0.1.0",
+///     dependencies = { "some-other-mod" },
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModManifest {
+    pub name: String,
+    pub version: String,
+    pub dependencies: Vec<String>,
+    /// The folder of the mod, including the base path given to [`load_prototypes`](crate::load_prototypes)
+    pub dir: String,
+}
+
+impl Display for ModManifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+impl ModManifest {
+    fn from_lua(table: &Table, dir: String) -> mlua::Result<Self> {
+        Ok(Self {
+            name: crate::get_lua(table, "name")?,
+            version: crate::get_lua(table, "version")?,
+            dependencies: crate::get_lua_opt(table, "dependencies")?.unwrap_or_default(),
+            dir,
+        })
+    }
+
+    /// Reads the `info.lua` manifest in the given folder
+    pub fn read(dir: String) -> Result<Self, PrototypeLoadError> {
+        let path = format!("{dir}/info.lua");
+        let src = common::saveload::load_string(&path)?;
+
+        // the manifest is pure data, no need for any library
+        let l = Lua::new_with(mlua::StdLib::NONE, mlua::LuaOptions::default())?;
+        l.load(&src)
+            .set_name(&path)
+            .eval::<Table>()
+            .and_then(|t| Self::from_lua(&t, dir))
+            .map_err(|e| PrototypeLoadError::InvalidManifest(path, e))
+    }
+}
+
+/// The mods the prototypes were loaded from, in load order
+pub fn loaded_mods() -> &'static [ModManifest] {
+    match crate::try_prototypes() {
+        Some(p) => &p.mods,
+        None => &[],
+    }
+}
+
+/// A description of the loaded mods (`name@version`, in load order)
+/// Two machines with a different mod set cannot play together
+pub fn mod_set() -> Vec<String> {
+    loaded_mods().iter().map(|m| m.to_string()).collect()
+}
+
+/// Finds the base mod and all the mods in the `mods/` folder, and returns them in load order
+pub fn discover_mods(base: &str) -> Result<Vec<ModManifest>, PrototypeLoadError> {
+    let mut mods = vec![ModManifest::read(format!("{base}base_mod"))?];
+
+    if let Ok(dir) = std::fs::read_dir(format!("{base}mods")) {
+        let mut folders = dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        folders.sort();
+
+        for folder in folders {
+            let dir = format!("{base}mods/{folder}");
+            if std::fs::metadata(format!("{dir}/info.lua")).is_err() {
+                log::warn!("ignoring {}: no info.lua manifest", dir);
+                continue;
+            }
+            mods.push(ModManifest::read(dir)?);
+        }
+    }
+
+    resolve_load_order(mods)
+}
+
+/// Sorts the mods so that every mod is loaded after its dependencies.
+/// The base mod always comes first, and independent mods are loaded in alphabetical order
+/// so that every machine ends up with the same order.
+pub fn resolve_load_order(mods: Vec<ModManifest>) -> Result<Vec<ModManifest>, PrototypeLoadError> {
+    let mut by_name = BTreeMap::new();
+    for m in mods {
+        let name = m.name.clone();
+        if let Some(old) = by_name.insert(name.clone(), m) {
+            return Err(PrototypeLoadError::DuplicateMod(name, old.dir));
+        }
+    }
+
+    let mut errors = vec![];
+    let mut deps: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (name, m) in &by_name {
+        let mut d = BTreeSet::new();
+        if name != BASE_MOD && by_name.contains_key(BASE_MOD) {
+            d.insert(BASE_MOD.to_string());
+        }
+        for dep in &m.dependencies {
+            if !by_name.contains_key(dep) {
+                errors.push(PrototypeLoadError::MissingDependency(
+                    name.clone(),
+                    dep.clone(),
+                ));
+                continue;
+            }
+            d.insert(dep.clone());
+        }
+        deps.insert(name.clone(), d);
+    }
+    if !errors.is_empty() {
+        return Err(PrototypeLoadError::MultiError(MultiError(errors)));
+    }
+
+    let mut order = Vec::with_capacity(by_name.len());
+    while !deps.is_empty() {
+        let Some(next) = deps
+            .iter()
+            .find(|(_, d)| d.is_empty())
+            .map(|(name, _)| name.clone())
+        else {
+            return Err(PrototypeLoadError::DependencyCycle(
+                deps.keys().cloned().collect(),
+            ));
+        };
+        deps.remove(&next);
+        for d in deps.values_mut() {
+            d.remove(&next);
+        }
+        order.push(by_name.remove(&next).unwrap());
+    }
+
+    Ok(order)
+}
+
+/// A field modified through `data:get`: (type, name, field)
+pub(crate) type FieldWrite = (String, String, String);
+
+/// Reports the fields modified by two mods where neither depends on the other,
+/// as the result would depend on the load order
+/// `writes` are the fields modified by each mod, in load order
+pub(crate) fn field_write_conflicts(
+    mods: &[ModManifest],
+    writes: &[Vec<FieldWrite>],
+) -> Vec<PrototypeLoadError> {
+    let mut writers: BTreeMap<&FieldWrite, Vec<usize>> = BTreeMap::new();
+    for (i, w) in writes.iter().enumerate() {
+        for field in w {
+            let v = writers.entry(field).or_default();
+            if !v.contains(&i) {
+                v.push(i);
+            }
+        }
+    }
+
+    let mut errors = vec![];
+    for ((ty, name, field), by) in writers {
+        for (k, &b) in by.iter().enumerate() {
+            for &a in &by[..k] {
+                if depends_on(mods, b, a) {
+                    continue;
+                }
+                errors.push(PrototypeLoadError::FieldConflict(
+                    ty.clone(),
+                    name.clone(),
+                    field.clone(),
+                    mods[a].name.clone(),
+                    mods[b].name.clone(),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Returns true if `a` depends on `b`, directly or through other mods
+/// `mods` must be in load order
+pub(crate) fn depends_on(mods: &[ModManifest], a: usize, b: usize) -> bool {
+    if a == b {
+        return false;
+    }
+    if mods[b].name == BASE_MOD {
+        return true;
+    }
+    let mut stack = vec![a];
+    let mut seen = BTreeSet::new();
+    while let Some(i) = stack.pop() {
+        for dep in &mods[i].dependencies {
+            let Some(j) = mods.iter().position(|m| &m.name == dep) else {
+                continue;
+            };
+            if j == b {
+                return true;
+            }
+            if seen.insert(j) {
+                stack.push(j);
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(name: &str, deps: &[&str]) -> ModManifest {
+        ModManifest {
+            name: name.to_string(),
+            version: "0.1.0".to_string(),
+            dependencies: deps.iter().map(|x| x.to_string()).collect(),
+            dir: format!("mods/{name}"),
+        }
+    }
+
+    #[test]
+    fn load_order() {
+        let order = resolve_load_order(vec![
+            manifest("zebra", &[]),
+            manifest("alpha", &["zebra"]),
+            manifest(BASE_MOD, &[]),
+            manifest("beta", &[]),
+        ])
+        .unwrap();
+
+        let names = order.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![BASE_MOD, "beta", "zebra", "alpha"]);
+
+        assert!(depends_on(&order, 3, 2));
+        assert!(depends_on(&order, 3, 0));
+        assert!(!depends_on(&order, 3, 1));
+    }
+
+    #[test]
+    fn load_order_errors() {
+        assert!(matches!(
+            resolve_load_order(vec![manifest(BASE_MOD, &[]), manifest("a", &["missing"])]),
+            Err(PrototypeLoadError::MultiError(_))
+        ));
+        assert!(matches!(
+            resolve_load_order(vec![
+                manifest(BASE_MOD, &[]),
+                manifest("a", &["b"]),
+                manifest("b", &["a"])
+            ]),
+            Err(PrototypeLoadError::DependencyCycle(_))
+        ));
+    }
+
+    #[test]
+    fn field_writes() {
+        let l = Lua::new();
+        l.load(include_str!("prototype_init.lua")).exec().unwrap();
+        l.load(
+            r#"
+            data:extend { type = "item", name = "bread", label = "Bread", stats = { weight = 1 } }
+            local bread = data:get("item", "bread")
+            bread.label = "Baguette"
+            bread.stats.weight = 2
+            bread.stats = bread.stats
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let data = l.globals().get::<_, Table>("data").unwrap();
+        let take_writes = data.get::<_, mlua::Function>("take_writes").unwrap();
+        let mut writes = take_writes
+            .call::<_, Vec<Vec<String>>>(data.clone())
+            .unwrap();
+        writes.sort();
+        assert_eq!(
+            writes,
+            vec![
+                vec!["item", "bread", "label"],
+                vec!["item", "bread", "stats"]
+            ]
+        );
+        assert!(take_writes
+            .call::<_, Vec<Vec<String>>>(data)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn field_write_conflicts_follow_dependencies() {
+        let mods = resolve_load_order(vec![
+            manifest(BASE_MOD, &[]),
+            manifest("a", &[]),
+            manifest("b", &["a"]),
+            manifest("c", &[]),
+        ])
+        .unwrap();
+        let w = |field: &str| ("item".to_string(), "bread".to_string(), field.to_string());
+
+        // b depends on a
+        let writes = vec![vec![w("label")], vec![w("label")], vec![w("label")], vec![]];
+        assert!(field_write_conflicts(&mods, &writes).is_empty());
+
+        // c does not depend on a, different fields are fine
+        let writes = vec![vec![], vec![w("label")], vec![], vec![w("stats")]];
+        assert!(field_write_conflicts(&mods, &writes).is_empty());
+
+        let writes = vec![vec![], vec![w("label")], vec![], vec![w("label")]];
+        assert!(matches!(
+            &field_write_conflicts(&mods, &writes)[..],
+            [PrototypeLoadError::FieldConflict(_, _, field, a, c)]
+                if field == "label" && a == "a" && c == "c"
+        ));
+    }
+}
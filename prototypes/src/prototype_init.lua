@@ -11,6 +11,68 @@ function data:extend (t)
     end
 end
 
+-- prototypes returned by data:get since the last call to data:take_writes, with a snapshot of their fields
+local gotten = {}
+
+-- a string describing the value, deep for tables so that nested changes are seen too
+local function fingerprint (v)
+    if type(v) ~= "table" then
+        return type(v) .. ":" .. tostring(v)
+    end
+    local keys = {}
+    for k in pairs(v) do
+        table.insert(keys, k)
+    end
+    table.sort(keys, function (a, b) return tostring(a) < tostring(b) end)
+    local parts = {}
+    for _, k in ipairs(keys) do
+        table.insert(parts, tostring(k) .. "=" .. fingerprint(v[k]))
+    end
+    return "{" .. table.concat(parts, ",") .. "}"
+end
+
+-- returns the last defined prototype with the given type and name, so that mods can modify it in place
+function data:get (type, name)
+    for i = rawlen(self), 1, -1 do
+        local v = rawget(self, i)
+        if v.type == type and v.name == name then
+            if gotten[v] == nil then
+                local fields = {}
+                for k, field in pairs(v) do
+                    fields[k] = fingerprint(field)
+                end
+                gotten[v] = fields
+            end
+            return v
+        end
+    end
+    return nil
+end
+
+-- returns the { type, name, field } modified through data:get since the last call, the loader uses it
+-- to find mods modifying the same field
+function data:take_writes ()
+    local writes = {}
+    for v, fields in pairs(gotten) do
+        local changed = {}
+        for k, field in pairs(v) do
+            if fields[k] ~= fingerprint(field) then
+                changed[k] = true
+            end
+        end
+        for k in pairs(fields) do
+            if v[k] == nil then
+                changed[k] = true
+            end
+        end
+        for k in pairs(changed) do
+            table.insert(writes, { v.type, v.name, tostring(k) })
+        end
+    end
+    gotten = {}
+    return writes
+end
+
 setmetatable(data, {
     __index = data,
     __newindex = function (t, k, v) end,
@@ -0,0 +1,45 @@
+use crate::{get_lua, BatteryID, Energy, GoodsCompanyPrototype, Power, Prototype};
+use std::ops::Deref;
+
+/// A battery stores the surplus of power of its network and gives it back when the network lacks power
+#[derive(Debug, Clone)]
+pub struct BatteryPrototype {
+    pub base: GoodsCompanyPrototype,
+    pub id: BatteryID,
+    /// The maximum energy that can be stored
+    pub capacity: Energy,
+    /// The maximum power at which the battery charges or discharges
+    pub max_power: Power,
+}
+
+impl Prototype for BatteryPrototype {
+    type Parent = GoodsCompanyPrototype;
+    type ID = BatteryID;
+    const NAME: &'static str = "battery";
+
+    fn from_lua(table: &mlua::Table) -> mlua::Result<Self> {
+        let base = GoodsCompanyPrototype::from_lua(table)?;
+        Ok(Self {
+            id: BatteryID::new(&base.name),
+            base,
+            capacity: get_lua(table, "capacity")?,
+            max_power: get_lua(table, "max_power")?,
+        })
+    }
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn parent(&self) -> &Self::Parent {
+        &self.base
+    }
+}
+
+impl Deref for BatteryPrototype {
+    type Target = GoodsCompanyPrototype;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     get_lua, get_v2, Money, NoParent, Power, Prototype, PrototypeBase, RenderAsset, Size2D,
+    WaterRate,
 };
 use egui_inspect::debug_inspect_impl;
 use geom::Vec2;
@@ -33,6 +34,12 @@ pub struct BuildingPrototype {
     pub price: Money,
     pub power_consumption: Option<Power>,
     pub power_production: Option<Power>,
+    /// Fresh water drawn from the water mains, also released as sewage
+    pub water_consumption: Option<WaterRate>,
+    /// Fresh water pumped into the water mains
+    pub water_production: Option<WaterRate>,
+    /// Sewage the building can treat
+    pub sewage_treatment: Option<WaterRate>,
 }
 
 impl Prototype for BuildingPrototype {
@@ -51,6 +58,9 @@ impl Prototype for BuildingPrototype {
             price: get_lua(table, "price")?,
             power_consumption: get_lua(table, "power_consumption")?,
             power_production: get_lua(table, "power_production")?,
+            water_consumption: get_lua(table, "water_consumption")?,
+            water_production: get_lua(table, "water_production")?,
+            sewage_treatment: get_lua(table, "sewage_treatment")?,
         })
     }
 
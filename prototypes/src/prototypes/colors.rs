@@ -30,6 +30,9 @@ pub struct ColorsPrototype {
 
     pub lot_unassigned_col: Color,
     pub lot_residential_col: Color,
+    pub lot_commercial_col: Color,
+    pub lot_industrial_col: Color,
+    pub lot_mixed_col: Color,
 }
 
 impl Prototype for ColorsPrototype {
@@ -62,6 +65,9 @@ impl Prototype for ColorsPrototype {
 
             lot_unassigned_col: get_color(table, "lot_unassigned_col")?,
             lot_residential_col: get_color(table, "lot_residential_col")?,
+            lot_commercial_col: get_color(table, "lot_commercial_col")?,
+            lot_industrial_col: get_color(table, "lot_industrial_col")?,
+            lot_mixed_col: get_color(table, "lot_mixed_col")?,
         })
     }
 
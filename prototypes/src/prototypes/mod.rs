@@ -5,6 +5,7 @@ crate::gen_prototypes!(
     mod goods_company: GoodsCompanyID      = GoodsCompanyPrototype => BuildingPrototypeID,
     mod leisure:       LeisurePrototypeID  = LeisurePrototype => BuildingPrototypeID,
     mod solar:         SolarPanelID        = SolarPanelPrototype => GoodsCompanyID,
+    mod battery:       BatteryID           = BatteryPrototype => GoodsCompanyID,
 
     mod vehicle:       VehiclePrototypeID = VehiclePrototype,
     mod road_vehicle:  RoadVehicleID      = RoadVehiclePrototype => VehiclePrototypeID,
@@ -12,6 +13,7 @@ crate::gen_prototypes!(
 
     mod colors:         ColorsPrototypeID   = ColorsPrototype,
     mod freightstation: FreightStationPrototypeID = FreightStationPrototype,
+    mod scenario:       ScenarioPrototypeID = ScenarioPrototype,
 );
 
 mod base;
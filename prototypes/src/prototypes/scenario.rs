@@ -0,0 +1,136 @@
+use crate::{
+    get_lua, get_lua_opt, GameDuration, ItemID, Money, NoParent, Prototype, PrototypeBase,
+};
+use mlua::{FromLua, Lua, Table, Value};
+use std::ops::Deref;
+
+use super::*;
+
+/// The map the scenario starts on
+#[derive(Debug, Clone)]
+pub enum ScenarioStartMap {
+    /// Only the generated terrain
+    Empty,
+    /// The Paris road network
+    Paris,
+    /// A grid of roads centered on the terrain
+    TestField { size: u32, spacing: f32 },
+    /// A previously saved world, loaded from disk
+    Save(String),
+}
+
+/// A goal the player has to reach to win the scenario
+#[derive(Debug, Clone)]
+pub enum ScenarioObjective {
+    /// Reach at least this many inhabitants
+    Population(u32),
+    /// Export at least `amount` of `item` during the last in-game hour
+    ExportPerHour { item: ItemID, amount: u32 },
+    /// Have at least this much money in the bank
+    Money(Money),
+}
+
+/// ScenarioPrototype describes a starting situation and the objectives to reach to win
+#[derive(Clone, Debug)]
+pub struct ScenarioPrototype {
+    pub base: PrototypeBase,
+    pub id: ScenarioPrototypeID,
+    pub description: String,
+    pub start_map: ScenarioStartMap,
+    pub starting_money: Money,
+    pub objectives: Vec<ScenarioObjective>,
+    /// The scenario is lost if the objectives are not reached in time
+    pub time_limit: Option<GameDuration>,
+}
+
+impl Prototype for ScenarioPrototype {
+    type Parent = NoParent;
+    type ID = ScenarioPrototypeID;
+    const NAME: &'static str = "scenario";
+
+    fn from_lua(table: &Table) -> mlua::Result<Self> {
+        let base = PrototypeBase::from_lua(table)?;
+        Ok(Self {
+            id: Self::ID::new(&base.name),
+            base,
+            description: get_lua(table, "description").unwrap_or_default(),
+            start_map: get_lua(table, "start_map")?,
+            starting_money: get_lua(table, "starting_money")?,
+            objectives: get_lua(table, "objectives")?,
+            time_limit: get_lua_opt(table, "time_limit")?,
+        })
+    }
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn parent(&self) -> &Self::Parent {
+        &NoParent
+    }
+}
+
+impl Deref for ScenarioPrototype {
+    type Target = PrototypeBase;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<'a> FromLua<'a> for ScenarioStartMap {
+    fn from_lua(value: Value<'a>, _: &'a Lua) -> mlua::Result<Self> {
+        let table = match value {
+            Value::String(s) => {
+                let s = s.to_str()?;
+                return match s {
+                    "empty" => Ok(Self::Empty),
+                    "paris" => Ok(Self::Paris),
+                    _ => Err(mlua::Error::external(format!(
+                        "Unknown start map kind: {}",
+                        s
+                    ))),
+                };
+            }
+            Value::Table(t) => t,
+            _ => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ScenarioStartMap",
+                message: Some("expected string or table".into()),
+            })?,
+        };
+        let kind = get_lua::<String>(&table, "kind")?;
+        match kind.as_str() {
+            "empty" => Ok(Self::Empty),
+            "paris" => Ok(Self::Paris),
+            "testfield" => Ok(Self::TestField {
+                size: get_lua(&table, "size")?,
+                spacing: get_lua(&table, "spacing")?,
+            }),
+            "save" => Ok(Self::Save(get_lua(&table, "path")?)),
+            _ => Err(mlua::Error::external(format!(
+                "Unknown start map kind: {}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl<'a> FromLua<'a> for ScenarioObjective {
+    fn from_lua(value: Value<'a>, lua: &'a Lua) -> mlua::Result<Self> {
+        let table: Table = FromLua::from_lua(value, lua)?;
+        let kind = get_lua::<String>(&table, "kind")?;
+        match kind.as_str() {
+            "population" => Ok(Self::Population(get_lua(&table, "amount")?)),
+            "export_per_hour" => Ok(Self::ExportPerHour {
+                item: get_lua(&table, "item")?,
+                amount: get_lua(&table, "amount")?,
+            }),
+            "money" => Ok(Self::Money(get_lua(&table, "amount")?)),
+            _ => Err(mlua::Error::external(format!(
+                "Unknown objective kind: {}",
+                kind
+            ))),
+        }
+    }
+}
@@ -1,12 +1,31 @@
-use crate::{GoodsCompanyPrototype, Prototype, SolarPanelID};
+use crate::{GoodsCompanyPrototype, Prototype, SolarPanelID, SECONDS_PER_HOUR};
 use std::ops::Deref;
 
+/// Time of the day at which the sun rises, in seconds
+pub const SUNRISE: i32 = 6 * SECONDS_PER_HOUR;
+
+/// Time of the day at which the sun sets, in seconds
+pub const SUNSET: i32 = 20 * SECONDS_PER_HOUR;
+
+/// A solar panel produces its `power_production` when the sun is highest, and nothing at night
 #[derive(Debug, Clone)]
 pub struct SolarPanelPrototype {
     pub base: GoodsCompanyPrototype,
     pub id: SolarPanelID,
 }
 
+impl SolarPanelPrototype {
+    /// The share of the peak production given by the sun at the given time of the day, in [0; 1] range.
+    /// It follows a half sine between sunrise and sunset.
+    pub fn sun_intensity(daysec: i32) -> f64 {
+        if daysec <= SUNRISE || daysec >= SUNSET {
+            return 0.0;
+        }
+        let t = (daysec - SUNRISE) as f64 / (SUNSET - SUNRISE) as f64;
+        (t * std::f64::consts::PI).sin()
+    }
+}
+
 impl Prototype for SolarPanelPrototype {
     type Parent = GoodsCompanyPrototype;
     type ID = SolarPanelID;
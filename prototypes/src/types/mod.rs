@@ -1,3 +1,6 @@
+#[macro_use]
+mod unit;
+
 mod asset;
 mod geom;
 mod money;
@@ -5,6 +8,7 @@ mod power;
 mod recipe;
 mod size;
 mod time;
+mod water;
 mod zone;
 
 pub use asset::*;
@@ -14,4 +18,6 @@ pub use power::*;
 pub use recipe::*;
 pub use size::*;
 pub use time::*;
+pub use unit::*;
+pub use water::*;
 pub use zone::*;
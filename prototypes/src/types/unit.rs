@@ -0,0 +1,209 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum UnitParseError {
+    #[error("Invalid unit: {0} (accepted: {1})")]
+    InvalidUnit(String, String),
+    #[error("Invalid number")]
+    InvalidNumber,
+    #[error("Value is too big")]
+    TooBig,
+}
+
+/// Defines a quantity stored as an integer amount of its base unit, that is parsed from and
+/// displayed as a number followed by a unit, like "1.5kW".
+///
+/// `parse` lists the accepted units and how much of the base unit they are worth (as a f64),
+/// `display` lists the units used for display from the smallest to the biggest.
+macro_rules! unit_type {
+    (
+        $(#[$meta:meta])*
+        $name:ident,
+        parse: [$($parse_unit:literal => $parse_factor:expr),+ $(,)?],
+        display: [$($display_unit:literal => $display_factor:expr),+ $(,)?] $(,)?
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            Clone,
+            Copy,
+            Default,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            serde::Serialize,
+            serde::Deserialize,
+        )]
+        pub struct $name(pub i64);
+        egui_inspect::debug_inspect_impl!($name);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const MAX: $name = $name(i64::MAX);
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = $crate::UnitParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let s = s.trim();
+                let (number, rest) =
+                    common::parse_f64(s).map_err(|_| $crate::UnitParseError::InvalidNumber)?;
+
+                let unit = rest.trim();
+
+                let factor = match unit {
+                    $($parse_unit => $parse_factor,)+
+                    _ => {
+                        return Err($crate::UnitParseError::InvalidUnit(
+                            unit.to_string(),
+                            [$($parse_unit),+].join(", "),
+                        ))
+                    }
+                };
+
+                let number = number * factor;
+                if number > i64::MAX as f64 {
+                    return Err($crate::UnitParseError::TooBig);
+                }
+
+                Ok(Self(number as i64))
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let units: &[(&str, f64)] = &[$(($display_unit, $display_factor)),+];
+                let abs = (self.0 as f64).abs();
+                let (unit, div) = units
+                    .iter()
+                    .rev()
+                    .find(|(_, div)| abs >= *div)
+                    .unwrap_or(&units[0]);
+
+                let v = self.0 as f64 / div;
+
+                if (v.round() - v).abs() < 0.01 {
+                    write!(f, "{}{}", v.round(), unit)
+                } else {
+                    write!(f, "{:.2}{}", v, unit)
+                }
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(self, f)
+            }
+        }
+
+        impl<'lua> mlua::FromLua<'lua> for $name {
+            fn from_lua(value: mlua::Value<'lua>, _: &'lua mlua::Lua) -> mlua::Result<Self> {
+                match value {
+                    mlua::Value::Integer(i) => Ok(Self(i as i64)),
+                    mlua::Value::Number(n) => {
+                        if n > i64::MAX as f64 {
+                            return Err(mlua::Error::external($crate::UnitParseError::TooBig));
+                        }
+                        Ok(Self(n as i64))
+                    }
+                    mlua::Value::String(s) => {
+                        let s = s.to_str()?.trim();
+                        if s.is_empty() {
+                            return Ok(Self(0));
+                        }
+                        <Self as std::str::FromStr>::from_str(s).map_err(mlua::Error::external)
+                    }
+                    _ => Err(mlua::Error::FromLuaConversionError {
+                        from: value.type_name(),
+                        to: stringify!($name),
+                        message: Some("expected string or number".into()),
+                    }),
+                }
+            }
+        }
+
+        impl std::ops::Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl std::ops::AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl std::ops::Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl std::ops::SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl std::ops::Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(-self.0)
+            }
+        }
+
+        impl std::iter::Sum for $name {
+            fn sum<I: Iterator<Item = $name>>(iter: I) -> Self {
+                iter.fold($name::ZERO, |a, b| a + b)
+            }
+        }
+
+        impl std::ops::Mul<i64> for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: i64) -> $name {
+                $name(self.0 * rhs)
+            }
+        }
+
+        impl std::ops::Mul<f64> for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: f64) -> $name {
+                $name((self.0 as f64 * rhs) as i64)
+            }
+        }
+
+        impl std::ops::Mul<$name> for i64 {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> $name {
+                $name(self * rhs.0)
+            }
+        }
+
+        impl std::ops::Mul<$name> for f64 {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> $name {
+                $name((self * rhs.0 as f64) as i64)
+            }
+        }
+
+        impl std::ops::Div<i64> for $name {
+            type Output = $name;
+
+            fn div(self, rhs: i64) -> $name {
+                $name(self.0 / rhs)
+            }
+        }
+    };
+}
@@ -0,0 +1,53 @@
+unit_type!(
+    /// Water flow in liters per hour, used for both fresh water and sewage
+    WaterRate,
+    parse: ["L/h" => 1.0, "m3/h" => 1000.0],
+    display: ["L/h" => 1.0, "m3/h" => 1000.0],
+);
+
+impl WaterRate {
+    pub const fn new(liters_per_hour: i64) -> Self {
+        Self(liters_per_hour)
+    }
+
+    pub const fn liters_per_hour(&self) -> i64 {
+        self.0
+    }
+
+    pub fn cubic_meters_per_hour(&self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WaterRate;
+    use crate::UnitParseError;
+
+    #[test]
+    fn water_rate_units() {
+        assert_eq!("20L/h".parse::<WaterRate>().unwrap(), WaterRate::new(20));
+        assert_eq!(
+            " 1.5 m3/h".parse::<WaterRate>().unwrap(),
+            WaterRate::new(1500)
+        );
+        assert!(matches!(
+            "3kW".parse::<WaterRate>(),
+            Err(UnitParseError::InvalidUnit(..))
+        ));
+
+        assert_eq!(WaterRate::new(20).to_string(), "20L/h");
+        assert_eq!(WaterRate::new(2000).to_string(), "2m3/h");
+        assert_eq!(WaterRate::new(2500).to_string(), "2.50m3/h");
+        assert_eq!(WaterRate::new(1500).cubic_meters_per_hour(), 1.5);
+
+        assert_eq!(3 * WaterRate::new(20), WaterRate::new(60));
+        assert_eq!(WaterRate::new(20) * 0.5, WaterRate::new(10));
+        assert_eq!(
+            [WaterRate::new(20), WaterRate::new(30)]
+                .into_iter()
+                .sum::<WaterRate>(),
+            WaterRate::new(50)
+        );
+    }
+}
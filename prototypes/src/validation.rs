@@ -4,7 +4,7 @@ use thiserror::Error;
 
 use common::error::MultiError;
 
-use crate::{CompanyKind, Prototypes};
+use crate::{CompanyKind, Prototypes, ScenarioObjective};
 
 #[derive(Debug, Error)]
 pub enum ValidationError {
@@ -73,6 +73,51 @@ pub(crate) fn validate(proto: &Prototypes) -> Result<(), MultiError<ValidationEr
                 "must not be negative".to_string(),
             ));
         }
+
+        for (field, rate) in [
+            ("water_consumption", comp.water_consumption),
+            ("water_production", comp.water_production),
+            ("sewage_treatment", comp.sewage_treatment),
+        ] {
+            if rate.map_or(false, |v| v.0 < 0) {
+                errors.push(ValidationError::InvalidField(
+                    comp.name.clone(),
+                    field,
+                    "must not be negative".to_string(),
+                ));
+            }
+        }
+    }
+
+    for battery in proto.battery.values() {
+        if battery.capacity.0 <= 0 {
+            errors.push(ValidationError::InvalidField(
+                battery.name.clone(),
+                "capacity",
+                "must be positive".to_string(),
+            ));
+        }
+
+        if battery.max_power.0 <= 0 {
+            errors.push(ValidationError::InvalidField(
+                battery.name.clone(),
+                "max_power",
+                "must be positive".to_string(),
+            ));
+        }
+    }
+
+    for scenario in proto.scenario.values() {
+        for objective in &scenario.objectives {
+            if let ScenarioObjective::ExportPerHour { item, .. } = objective {
+                if !proto.item.contains_key(item) {
+                    errors.push(ValidationError::ReferencedProtoNotFound(
+                        scenario.name.clone(),
+                        "objectives",
+                    ));
+                }
+            }
+        }
     }
 
     if !errors.is_empty() {
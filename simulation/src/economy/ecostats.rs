@@ -76,6 +76,26 @@ impl ItemHistories {
             .filter_map(move |(id, history)| Some((*id, history.levels.get(level)?)))
     }
 
+    /// Sums the quantity of `item` over the `n_bins` most recent complete bins of the given level,
+    /// the current (partial) bin excluded
+    pub fn sum_items_recent(&self, item: ItemID, level: usize, n_bins: usize) -> i64 {
+        let Some(h) = self.m.get(&item) else {
+            return 0;
+        };
+        let cursor = self.cursors[level];
+        (1..=n_bins.min(HISTORY_SIZE - 1))
+            .map(|i| h.levels[level].past_ring_items[(cursor + HISTORY_SIZE - i) % HISTORY_SIZE])
+            .sum()
+    }
+
+    /// Sums the quantity of all items over the `n_bins` most recent complete bins of the given level
+    pub fn total_items_recent(&self, level: usize, n_bins: usize) -> i64 {
+        self.m
+            .keys()
+            .map(|item| self.sum_items_recent(*item, level, n_bins))
+            .sum()
+    }
+
     pub fn handle_trade(&mut self, trade: &Trade) {
         if trade.qty <= 0 {
             return;
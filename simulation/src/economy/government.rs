@@ -1,9 +1,17 @@
-use crate::map::{LanePattern, MapProject, MAX_ZONE_AREA};
+use crate::map::{LanePattern, Map, MapProject, MAX_ZONE_AREA};
+use crate::map_dynamic::{BuildingInfos, LandValue};
+use crate::utils::resources::Resources;
 use crate::world_command::WorldCommand;
-use crate::{BuildingKind, Simulation};
-use prototypes::Money;
+use crate::{BuildingKind, Simulation, World};
+use prototypes::{GameTime, Money, TICKS_PER_HOUR};
 use serde::{Deserialize, Serialize};
 
+/// Property tax paid every hour by each household, for a land value of 0.5
+const HOUSEHOLD_PROPERTY_TAX: Money = Money::new_cents(20);
+
+/// Property tax paid every hour by each company, for a land value of 0.5
+const COMPANY_PROPERTY_TAX: Money = Money::new_bucks(1);
+
 /// The government represents the player.
 #[derive(Serialize, Deserialize)]
 pub struct Government {
@@ -49,6 +57,25 @@ impl Government {
                 return (newarea - oldarea) as i64 * zonedescr.price_per_area
                     / MAX_ZONE_AREA as i64;
             }
+            WorldCommand::MapUpdateRoadPattern { road, pattern } => {
+                let m = sim.map();
+                let Some(r) = m.roads.get(*road) else {
+                    return Money::ZERO;
+                };
+                let len = r.length();
+                let old = Self::lanes_cost(len, &r.pattern(&m.lanes));
+                let new = Self::lanes_cost(len, pattern);
+                (new - old).max(0)
+            }
+            WorldCommand::MapBuildPowerLine { src, dst, kind } => {
+                let m = sim.map();
+                let (Some(src), Some(dst)) =
+                    (m.power_line_end_pos(*src), m.power_line_end_pos(*dst))
+                else {
+                    return Money::ZERO;
+                };
+                (src.distance(dst) * kind.price_per_meter()) as i64
+            }
             WorldCommand::MapMakeMultipleConnections(ref projs, ref links) => {
                 let mut total = 0;
                 for (from, to, _, pat) in links.iter() {
@@ -68,6 +95,9 @@ impl Government {
                 BuildingKind::RailFreightStation(x) => {
                     return x.prototype().price;
                 }
+                BuildingKind::Leisure(x) => {
+                    return x.prototype().price;
+                }
                 BuildingKind::TrainStation => 1000,
                 _ => 0,
             },
@@ -77,7 +107,44 @@ impl Government {
 
     fn connection_cost(p1: &MapProject, p2: &MapProject, pat: &LanePattern) -> i64 {
         let dist = p1.pos.distance(p2.pos);
-        50 + ((0.03 * dist) as i64).max(1)
+        50 + Self::lanes_cost(dist, pat)
+    }
+
+    /// The cost of the lanes of a road of the given length, without the fixed part
+    fn lanes_cost(length: f32, pat: &LanePattern) -> i64 {
+        ((0.03 * length) as i64).max(1)
             * (pat.lanes_forward.len() + pat.lanes_backward.len()) as i64
     }
 }
+
+/// Collects the property tax of houses and companies, which grows with the land value
+pub fn property_tax_system(world: &mut World, resources: &mut Resources) {
+    profiling::scope!("economy::property_tax_system");
+    if resources.read::<GameTime>().tick.0 % TICKS_PER_HOUR != 0 {
+        return;
+    }
+
+    let map = resources.read::<Map>();
+    let binfos = resources.read::<BuildingInfos>();
+    let lv = resources.read::<LandValue>();
+
+    let factor = |p| 0.5 + lv.value(p) as f64;
+
+    let mut total = Money::ZERO;
+    for (id, b) in map.buildings() {
+        if b.kind != BuildingKind::House {
+            continue;
+        }
+        let households = binfos.get(id).map_or(0, |info| info.residents.len());
+        total += households as i64 * HOUSEHOLD_PROPERTY_TAX * factor(b.obb.center());
+    }
+
+    for c in world.companies.values() {
+        let Some(b) = map.buildings().get(c.comp.building) else {
+            continue;
+        };
+        total += COMPANY_PROPERTY_TAX * factor(b.obb.center());
+    }
+
+    resources.write::<Government>().money += total;
+}
@@ -60,6 +60,14 @@ impl SingleMarket {
     pub fn capital_map(&self) -> &BTreeMap<SoulID, i32> {
         &self.capital
     }
+
+    pub fn buy_orders(&self) -> &BTreeMap<SoulID, BuyOrder> {
+        &self.buy_orders
+    }
+
+    pub fn sell_orders(&self) -> &BTreeMap<SoulID, SellOrder> {
+        &self.sell_orders
+    }
 }
 
 /// Market handles good exchanging between souls themselves and the external market.
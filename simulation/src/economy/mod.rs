@@ -7,6 +7,9 @@
 //! - The market, which is the place where goods are exchanged.
 //! - The government, which is the entity representing the player
 //!
+//! It also contains the zoning demand, which makes zoned lots grow buildings,
+//! and the recorder of the city statistics over time.
+//!
 use crate::utils::resources::Resources;
 use crate::SoulID;
 use crate::World;
@@ -19,13 +22,18 @@ use std::fmt::Debug;
 mod ecostats;
 mod government;
 mod market;
+mod stats_recorder;
+mod zoning;
 
+use crate::events::{SimEvent, SimEvents};
 use crate::map::Map;
 use crate::world::HumanID;
 pub use ecostats::*;
 pub use government::*;
 pub use market::*;
 use prototypes::{GameTime, ItemID, Money, TICKS_PER_MINUTE};
+pub use stats_recorder::*;
+pub use zoning::*;
 
 const WORKER_CONSUMPTION_PER_MINUTE: Money = Money::new_cents(10);
 
@@ -68,6 +76,12 @@ pub fn market_update(world: &mut World, resources: &mut Resources) {
 
     resources.write::<EcoStats>().advance(tick.0, trades);
 
+    let mut events = resources.write::<SimEvents>();
+    for &trade in trades.iter() {
+        events.push(SimEvent::TradeMade(trade));
+    }
+    drop(events);
+
     for &trade in trades.iter() {
         log::debug!("A trade was made! {:?}", trade);
 
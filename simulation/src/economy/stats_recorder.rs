@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use prototypes::{GameTime, Tick, TICKS_PER_HOUR};
+
+use crate::economy::{EcoStats, Government, LEVEL_FREQS};
+use crate::map_dynamic::ElectricityFlow;
+use crate::utils::resources::Resources;
+use crate::World;
+
+/// A city statistic that can be recorded over time
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Metric {
+    Population,
+    Money,
+    Vehicles,
+    Trains,
+    Companies,
+    /// Items exported during the last hour
+    Exports,
+    /// Items imported during the last hour
+    Imports,
+    /// Items traded inside the city during the last hour
+    InternalTrade,
+    /// Power produced by all networks, in watts
+    PowerProduced,
+    /// Power consumed by all networks, in watts
+    PowerConsumed,
+    /// Number of networks in blackout
+    Blackouts,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 11] = [
+        Metric::Population,
+        Metric::Money,
+        Metric::Vehicles,
+        Metric::Trains,
+        Metric::Companies,
+        Metric::Exports,
+        Metric::Imports,
+        Metric::InternalTrade,
+        Metric::PowerProduced,
+        Metric::PowerConsumed,
+        Metric::Blackouts,
+    ];
+
+    /// Name used as column in the exports
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Population => "population",
+            Metric::Money => "money",
+            Metric::Vehicles => "vehicles",
+            Metric::Trains => "trains",
+            Metric::Companies => "companies",
+            Metric::Exports => "exports",
+            Metric::Imports => "imports",
+            Metric::InternalTrade => "internal_trade",
+            Metric::PowerProduced => "power_produced",
+            Metric::PowerConsumed => "power_consumed",
+            Metric::Blackouts => "blackouts",
+        }
+    }
+
+    fn sample(self, world: &World, resources: &Resources) -> f64 {
+        let hour_bins = (TICKS_PER_HOUR / LEVEL_FREQS[0]) as usize;
+        match self {
+            Metric::Population => world.humans.len() as f64,
+            Metric::Money => resources.read::<Government>().money.cents() as f64 / 100.0,
+            Metric::Vehicles => world.vehicles.len() as f64,
+            Metric::Trains => world.trains.len() as f64,
+            Metric::Companies => world.companies.len() as f64,
+            Metric::Exports => resources
+                .read::<EcoStats>()
+                .exports
+                .total_items_recent(0, hour_bins) as f64,
+            Metric::Imports => resources
+                .read::<EcoStats>()
+                .imports
+                .total_items_recent(0, hour_bins) as f64,
+            Metric::InternalTrade => resources
+                .read::<EcoStats>()
+                .internal_trade
+                .total_items_recent(0, hour_bins) as f64,
+            Metric::PowerProduced => resources
+                .read::<ElectricityFlow>()
+                .networks()
+                .map(|(_, f)| f.produced_power.watts() as f64)
+                .sum(),
+            Metric::PowerConsumed => resources
+                .read::<ElectricityFlow>()
+                .networks()
+                .map(|(_, f)| f.consumed_power.watts() as f64)
+                .sum(),
+            Metric::Blackouts => resources
+                .read::<ElectricityFlow>()
+                .networks()
+                .filter(|(_, f)| f.blackout)
+                .count() as f64,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL
+            .into_iter()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| format!("unknown metric: {}", s))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSample {
+    pub tick: Tick,
+    pub values: BTreeMap<Metric, f64>,
+}
+
+/// Samples the chosen metrics every in-game hour, to be exported and analysed elsewhere
+#[derive(Serialize, Deserialize)]
+pub struct StatsRecorder {
+    metrics: Vec<Metric>,
+    samples: Vec<StatsSample>,
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        Self {
+            metrics: Metric::ALL.to_vec(),
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl StatsRecorder {
+    pub fn metrics(&self) -> &[Metric] {
+        &self.metrics
+    }
+
+    /// Changes the metrics recorded from now on, the past samples are kept
+    pub fn set_metrics(&mut self, metrics: &[Metric]) {
+        self.metrics = Metric::ALL
+            .into_iter()
+            .filter(|m| metrics.contains(m))
+            .collect();
+    }
+
+    pub fn samples(&self) -> &[StatsSample] {
+        &self.samples
+    }
+
+    /// Metrics present in at least one sample
+    fn columns(&self) -> Vec<Metric> {
+        Metric::ALL
+            .into_iter()
+            .filter(|m| self.samples.iter().any(|s| s.values.contains_key(m)))
+            .collect()
+    }
+
+    /// One line per sample, metrics that were not recorded at that time are left empty
+    pub fn to_csv(&self) -> String {
+        let columns = self.columns();
+        let mut out = String::from("tick,hour");
+        for m in &columns {
+            out.push(',');
+            out.push_str(m.name());
+        }
+        out.push('\n');
+
+        for s in &self.samples {
+            out.push_str(&format!("{},{}", s.tick.0, s.tick.0 / TICKS_PER_HOUR));
+            for m in &columns {
+                out.push(',');
+                if let Some(v) = s.values.get(m) {
+                    out.push_str(&v.to_string());
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// One JSON object per line and per sample
+    pub fn to_jsonl(&self) -> String {
+        let mut out = String::new();
+        for s in &self.samples {
+            let mut obj = serde_json::Map::new();
+            obj.insert("tick".to_string(), s.tick.0.into());
+            obj.insert("hour".to_string(), (s.tick.0 / TICKS_PER_HOUR).into());
+            for (m, v) in &s.values {
+                obj.insert(m.name().to_string(), (*v).into());
+            }
+            out.push_str(&serde_json::Value::Object(obj).to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Writes the samples as CSV if the extension is .csv, and as JSON lines otherwise
+    pub fn export(&self, path: &Path) -> std::io::Result<()> {
+        let content = if path.extension().is_some_and(|e| e == "csv") {
+            self.to_csv()
+        } else {
+            self.to_jsonl()
+        };
+        std::fs::write(path, content)
+    }
+}
+
+pub fn stats_recorder_system(world: &mut World, resources: &mut Resources) {
+    profiling::scope!("economy::stats_recorder_system");
+    let tick = resources.read::<GameTime>().tick;
+    if tick.0 % TICKS_PER_HOUR != 0 {
+        return;
+    }
+
+    let mut recorder = resources.write::<StatsRecorder>();
+    if recorder.metrics.is_empty() {
+        return;
+    }
+    let values = recorder
+        .metrics
+        .iter()
+        .map(|m| (*m, m.sample(world, resources)))
+        .collect();
+    recorder.samples.push(StatsSample { tick, values });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_missing_values() {
+        let mut rec = StatsRecorder::default();
+        rec.set_metrics(&[Metric::Money, Metric::Population]);
+        rec.samples.push(StatsSample {
+            tick: Tick(TICKS_PER_HOUR),
+            values: [(Metric::Population, 3.0), (Metric::Money, 10.5)].into(),
+        });
+        rec.samples.push(StatsSample {
+            tick: Tick(2 * TICKS_PER_HOUR),
+            values: [(Metric::Population, 4.0)].into(),
+        });
+
+        let t = TICKS_PER_HOUR;
+        assert_eq!(
+            rec.to_csv(),
+            format!(
+                "tick,hour,population,money\n{},1,3,10.5\n{},2,4,\n",
+                t,
+                2 * t
+            )
+        );
+        let lines = rec
+            .to_jsonl()
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["money"], 10.5);
+        assert_eq!(lines[1]["hour"], 2);
+        assert!(lines[1].get("money").is_none());
+        assert_eq!("power_produced".parse(), Ok(Metric::PowerProduced));
+    }
+}
@@ -0,0 +1,242 @@
+//! Zoning demand
+//!
+//! Lots painted with a zone grow buildings on their own, depending on what the city lacks:
+//! - Residential lots get houses when there are more jobs than workers
+//! - Commercial lots get stores when there are not enough shops for the population
+//! - Industrial lots get factories when there are not enough industrial jobs for the population
+//! - Mixed lots accept both residential and commercial growth
+//!
+//! Lots with the highest land value grow first, and pricier businesses settle on valuable land.
+
+use crate::events::{SimEvent, SimEvents};
+use crate::map::{BuildingKind, LotID, LotKind};
+use crate::map_dynamic::{BuildingInfos, LandValue};
+use crate::utils::rand_provider::RandProvider;
+use crate::{Simulation, World};
+use geom::OBB;
+use ordered_float::OrderedFloat;
+use prototypes::{
+    prototypes_iter, try_prototype, BatteryID, CompanyKind, GameTime, GoodsCompanyPrototype,
+    SolarPanelID, TICKS_PER_MINUTE,
+};
+use serde::{Deserialize, Serialize};
+
+/// How often the demand is recomputed and zoned lots get a chance to grow
+pub const ZONE_GROWTH_PERIOD: u64 = TICKS_PER_MINUTE;
+
+/// How many buildings of one zone can grow per period when the demand is at its maximum
+const MAX_GROWTH_PER_PERIOD: f32 = 3.0;
+
+/// Population under which the city is considered empty, so that the first lots can grow
+const BASE_POPULATION: f32 = 10.0;
+
+/// How many commercial jobs are wanted per inhabitant
+const SHOPPERS_RATIO: f32 = 0.25;
+
+/// How many industrial jobs are wanted per inhabitant
+const INDUSTRY_RATIO: f32 = 0.5;
+
+/// The demand for each zone, between -1 (oversupplied) and 1 (strongly wanted)
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct ZoneDemand {
+    pub residential: f32,
+    pub commercial: f32,
+    pub industrial: f32,
+}
+
+impl ZoneDemand {
+    pub fn compute(population: usize, commercial_jobs: u32, industrial_jobs: u32) -> Self {
+        let pop = population as f32;
+        let jobs = (commercial_jobs + industrial_jobs) as f32;
+
+        let wanted_shops = pop * SHOPPERS_RATIO + BASE_POPULATION * SHOPPERS_RATIO;
+        let wanted_industry = pop * INDUSTRY_RATIO + BASE_POPULATION * INDUSTRY_RATIO;
+
+        Self {
+            residential: ((jobs + BASE_POPULATION - pop) / (pop + BASE_POPULATION))
+                .clamp(-1.0, 1.0),
+            commercial: ((wanted_shops - commercial_jobs as f32) / wanted_shops).clamp(-1.0, 1.0),
+            industrial: ((wanted_industry - industrial_jobs as f32) / wanted_industry)
+                .clamp(-1.0, 1.0),
+        }
+    }
+
+    pub fn from_world(world: &World) -> Self {
+        let mut commercial_jobs = 0;
+        let mut industrial_jobs = 0;
+        for company in world.companies.values() {
+            match company.comp.proto.prototype().kind {
+                CompanyKind::Store => commercial_jobs += company.comp.max_workers,
+                CompanyKind::Factory => industrial_jobs += company.comp.max_workers,
+            }
+        }
+        Self::compute(world.humans.len(), commercial_jobs, industrial_jobs)
+    }
+
+    /// The demand for buildings on a lot of the given kind
+    pub fn get(&self, kind: LotKind) -> f32 {
+        match kind {
+            LotKind::Unassigned => 0.0,
+            LotKind::Residential => self.residential,
+            LotKind::Commercial => self.commercial,
+            LotKind::Industrial => self.industrial,
+            LotKind::Mixed => self.residential.max(self.commercial),
+        }
+    }
+}
+
+/// Whether a lot of kind `lot` can grow buildings of zone `zone`
+fn accepts(lot: LotKind, zone: LotKind) -> bool {
+    lot == zone || (lot == LotKind::Mixed && zone != LotKind::Industrial)
+}
+
+pub fn zone_growth_system(sim: &mut Simulation) {
+    profiling::scope!("economy::zone_growth_system");
+    if sim.read::<GameTime>().tick.0 % ZONE_GROWTH_PERIOD != 0 {
+        return;
+    }
+
+    let demand = ZoneDemand::from_world(sim.world());
+    *sim.write::<ZoneDemand>() = demand;
+
+    for zone in [
+        LotKind::Residential,
+        LotKind::Commercial,
+        LotKind::Industrial,
+    ] {
+        let d = demand.get(zone);
+        if d <= 0.0 {
+            continue;
+        }
+        let n_wanted = (d * MAX_GROWTH_PER_PERIOD).ceil() as usize;
+
+        let mut lots = sim
+            .map()
+            .lots()
+            .values()
+            .filter(|lot| accepts(lot.kind, zone))
+            .map(|lot| (lot.id, lot.shape.center()))
+            .collect::<Vec<_>>();
+        if lots.is_empty() {
+            continue;
+        }
+
+        // start at a random lot so that growth is spread over lots of equal value
+        let start = sim.write::<RandProvider>().next_u32() as usize % lots.len();
+        lots.rotate_left(start);
+
+        let lv = sim.read::<LandValue>();
+        lots.sort_by_key(|&(_, pos)| std::cmp::Reverse(OrderedFloat(lv.value(pos))));
+        drop(lv);
+
+        let mut n_built = 0;
+        for &(lot, _) in &lots {
+            if n_built >= n_wanted {
+                break;
+            }
+            if grow_lot(sim, lot, zone) {
+                n_built += 1;
+            }
+        }
+    }
+}
+
+/// Builds a building fitting the zone on the lot, returns true if something was built
+fn grow_lot(sim: &mut Simulation, lot_id: LotID, zone: LotKind) -> bool {
+    let company_kind = match zone {
+        LotKind::Residential => {
+            let Some(build) = sim.map_mut().build_house(lot_id) else {
+                return false;
+            };
+            sim.write::<BuildingInfos>().insert(build);
+            sim.write::<SimEvents>()
+                .push(SimEvent::BuildingBuilt(build));
+            return true;
+        }
+        LotKind::Commercial => CompanyKind::Store,
+        LotKind::Industrial => CompanyKind::Factory,
+        LotKind::Unassigned | LotKind::Mixed => return false,
+    };
+
+    let map = sim.map();
+    let Some(lot) = map.lots().get(lot_id) else {
+        return false;
+    };
+    let [_, along] = lot.shape.axis();
+    let lot_size = along.mag();
+    let Some(side) = along.try_normalize() else {
+        return false;
+    };
+    let edge = lot.shape.center() - side * lot_size * 0.5;
+    let road = lot.parent;
+    let value = sim.read::<LandValue>().value(lot.shape.center());
+    drop(map);
+
+    let mut candidates = prototypes_iter::<GoodsCompanyPrototype>()
+        .filter(|proto| {
+            proto.kind == company_kind
+                && proto.zone.is_none()
+                && proto.power_production.is_none()
+                && proto.water_production.is_none()
+                && proto.sewage_treatment.is_none()
+                && try_prototype(BatteryID::from(proto.id)).is_none()
+                && try_prototype(SolarPanelID::from(proto.id)).is_none()
+                && proto.size.w <= lot_size
+                && proto.size.h <= lot_size
+        })
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return false;
+    }
+
+    // the more valuable the land, the more expensive the business
+    candidates.sort_by_key(|proto| proto.price);
+    let rank = (value * candidates.len() as f32) as usize;
+    let proto = candidates[rank.min(candidates.len() - 1)];
+
+    let obb = OBB::new(
+        edge + side * proto.size.w * 0.5,
+        side,
+        proto.size.w,
+        proto.size.h,
+    );
+
+    let Some(build) = sim.map_mut().build_special_building(
+        &obb,
+        BuildingKind::GoodsCompany(proto.id),
+        proto.bgen,
+        None,
+        Some(road),
+    ) else {
+        return false;
+    };
+    sim.write::<BuildingInfos>().insert(build);
+    sim.write::<SimEvents>()
+        .push(SimEvent::BuildingBuilt(build));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZoneDemand;
+
+    #[test]
+    fn demand_follows_population() {
+        let empty = ZoneDemand::compute(0, 0, 0);
+        assert_eq!(empty.residential, 1.0);
+        assert!(empty.commercial > 0.0);
+        assert!(empty.industrial > 0.0);
+
+        // lots of people and no jobs: businesses are wanted, houses are not
+        let dormitory = ZoneDemand::compute(1000, 0, 0);
+        assert!(dormitory.residential < 0.0);
+        assert!(dormitory.commercial > 0.5);
+        assert!(dormitory.industrial > 0.5);
+
+        // lots of jobs and nobody to take them: only houses are wanted
+        let business_park = ZoneDemand::compute(10, 200, 400);
+        assert_eq!(business_park.residential, 1.0);
+        assert!(business_park.commercial < 0.0);
+        assert!(business_park.industrial < 0.0);
+    }
+}
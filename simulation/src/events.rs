@@ -0,0 +1,150 @@
+//! Simulation events
+//!
+//! Systems push [`SimEvent`]s in the [`SimEvents`] buffer of the current tick instead of leaving
+//! the UI to notice changes by itself. The buffer is read by the mod hooks, then emptied at the
+//! end of the tick, where notable events are:
+//! - kept in [`SimEvents::recent`] so that the UI can show them as toasts,
+//! - announced in the chat as info or warning messages,
+//! - recorded in the replay.
+//!
+//! Events are only emitted by the simulation itself, so they are the same on every peer.
+//!
+//! There is no event for a company going bankrupt: companies don't hold any money, only the
+//! [`crate::economy::Government`] does, so they never go out of business.
+
+use std::collections::VecDeque;
+
+use geom::Color;
+use prototypes::{GameTime, Tick};
+use serde::{Deserialize, Serialize};
+
+use crate::economy::Trade;
+use crate::map::{BuildingID, BuildingKind};
+use crate::multiplayer::chat::{Message, MessageKind};
+use crate::multiplayer::MultiplayerState;
+use crate::world::TrainID;
+use crate::{Replay, Simulation};
+
+/// Number of notable events kept for the UI
+const RECENT_EVENTS: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SimEvent {
+    BuildingBuilt(BuildingID),
+    TradeMade(Trade),
+    /// A power network doesn't get enough power anymore.
+    /// The network is identified by its first producer, as its id changes when it is edited.
+    BlackoutStarted(BuildingID),
+    BlackoutEnded(BuildingID),
+    /// A train has not moved for [`crate::transportation::train::TRAIN_STUCK_SECONDS`]
+    TrainStuck(TrainID),
+    /// Residents moved out of a house that lost density
+    CitizensLeft {
+        house: BuildingID,
+        n: u32,
+    },
+}
+
+impl SimEvent {
+    /// Name used by mod scripts in `on_event`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimEvent::BuildingBuilt(_) => "building_built",
+            SimEvent::TradeMade(_) => "trade_made",
+            SimEvent::BlackoutStarted(_) => "blackout_started",
+            SimEvent::BlackoutEnded(_) => "blackout_ended",
+            SimEvent::TrainStuck(_) => "train_stuck",
+            SimEvent::CitizensLeft { .. } => "citizens_left",
+        }
+    }
+
+    /// The chat message announcing the event, if it is worth telling the players about it
+    pub fn notice(&self, sim: &Simulation) -> Option<(MessageKind, String)> {
+        Some(match *self {
+            SimEvent::BuildingBuilt(id) => {
+                let map = sim.map();
+                let label = match map.buildings.get(id)?.kind {
+                    // houses grow on their own all the time
+                    BuildingKind::House => return None,
+                    BuildingKind::GoodsCompany(id) => id.prototype().label.clone(),
+                    BuildingKind::RailFreightStation(id) => id.prototype().label.clone(),
+                    BuildingKind::Leisure(id) => id.prototype().label.clone(),
+                    BuildingKind::TrainStation => "Train station".to_string(),
+                    BuildingKind::ExternalTrading => "External trading".to_string(),
+                };
+                (MessageKind::Info, format!("{} completed", label))
+            }
+            SimEvent::TradeMade(_) => return None,
+            SimEvent::BlackoutStarted(_) => (
+                MessageKind::Warning,
+                "Blackout: a power network lacks power".to_string(),
+            ),
+            SimEvent::BlackoutEnded(_) => {
+                (MessageKind::Info, "Power is back on a network".to_string())
+            }
+            SimEvent::TrainStuck(_) => (MessageKind::Warning, "A train is stuck".to_string()),
+            SimEvent::CitizensLeft { n, .. } => {
+                (MessageKind::Info, format!("{} citizens left the city", n))
+            }
+        })
+    }
+}
+
+/// The events of the current tick, and the notable ones of the last ticks
+#[derive(Default, Serialize, Deserialize)]
+pub struct SimEvents {
+    events: Vec<SimEvent>,
+    recent: VecDeque<(Tick, SimEvent)>,
+}
+
+impl SimEvents {
+    pub fn push(&mut self, event: SimEvent) {
+        self.events.push(event);
+    }
+
+    /// The events emitted so far during the current tick
+    pub fn current(&self) -> &[SimEvent] {
+        &self.events
+    }
+
+    /// The last notable events, oldest first
+    pub fn recent(&self) -> impl Iterator<Item = &(Tick, SimEvent)> + '_ {
+        self.recent.iter()
+    }
+}
+
+/// Empties the buffer of the tick, announcing and recording the notable events
+pub(crate) fn end_tick(sim: &mut Simulation) {
+    profiling::scope!("events::end_tick");
+    let events = std::mem::take(&mut sim.write::<SimEvents>().events);
+    let time = *sim.read::<GameTime>();
+
+    for event in events {
+        let Some((kind, text)) = event.notice(sim) else {
+            continue;
+        };
+
+        sim.write::<MultiplayerState>().chat.add_message(Message {
+            name: "City".to_string(),
+            text,
+            sent_at: time.instant(),
+            color: match kind {
+                MessageKind::Warning => Color::ORANGE,
+                _ => Color::WHITE,
+            },
+            kind,
+        });
+
+        let mut rep = sim.write::<Replay>();
+        if rep.enabled {
+            rep.push_event(time.tick, event.clone());
+        }
+        drop(rep);
+
+        let mut events = sim.write::<SimEvents>();
+        if events.recent.len() == RECENT_EVENTS {
+            events.recent.pop_front();
+        }
+        events.recent.push_back((time.tick, event));
+    }
+}
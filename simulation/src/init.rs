@@ -1,17 +1,27 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::sync::Once;
 
 #[allow(unused_imports)]
 use common::saveload::{Bincode, Encoder, JSONPretty, JSON};
 use prototypes::{GameTime, Tick};
 
-use crate::economy::{market_update, EcoStats, Government, Market};
+use crate::economy::{
+    market_update, property_tax_system, stats_recorder_system, zone_growth_system, EcoStats,
+    Government, Market, StatsRecorder, ZoneDemand,
+};
+use crate::events::SimEvents;
 use crate::map::Map;
 use crate::map_dynamic::{
-    dispatch_system, electricity_flow_system, itinerary_update, routing_changed_system,
-    routing_update_system, BuildingInfos, Dispatcher, ElectricityFlow, ParkingManagement,
+    dispatch_system, electricity_flow_system, itinerary_update, land_value_update_system,
+    pollution_update_system, routing_changed_system, routing_update_system, water_flow_system,
+    BuildingInfos, Dispatcher, ElectricityFlow, LandValue, ParkingManagement, PollutionField,
+    WaterFlow,
 };
+use crate::modding::{ModCommands, ModHooks};
 use crate::multiplayer::MultiplayerState;
+use crate::scenario::{scenario_update_system, ScenarioState};
+use crate::souls::density::density_system;
 use crate::souls::freight_station::freight_station_system;
 use crate::souls::goods_company::company_system;
 use crate::souls::human::update_decision_system;
@@ -23,6 +33,7 @@ use crate::transportation::train::{
 };
 use crate::transportation::{transport_grid_synchronize, TransportGrid};
 use crate::utils::resources::Resources;
+use crate::utils::scheduler::{ParSystem, SystemAccess, SystemResources, SystemWorld};
 use crate::world::{CompanyEnt, FreightStationEnt, HumanEnt, TrainEnt, VehicleEnt, WagonEnt};
 use crate::World;
 use crate::{
@@ -30,7 +41,14 @@ use crate::{
     Simulation, SimulationOptions, RNG_SEED,
 };
 
+/// Loads the prototypes and registers the systems and resources of the simulation.
+/// Only the first call does something, so that tests can create several simulations.
 pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(init_once);
+}
+
+fn init_once() {
     //crate::rerun::init_rerun();
 
     // # Safety
@@ -49,25 +67,72 @@ pub fn init() {
         }
     }
 
-    register_system("electricity_flow_system", electricity_flow_system);
+    // the flows don't depend on each other and run concurrently
+    register_par_system(
+        "electricity_flow_system",
+        SystemAccess::new()
+            .read::<Map>()
+            .read::<BuildingInfos>()
+            .read::<GameTime>()
+            .write::<ElectricityFlow>()
+            .write::<SimEvents>()
+            .read_store::<CompanyEnt>(),
+        electricity_flow_system,
+    );
+    register_par_system(
+        "water_flow_system",
+        SystemAccess::new()
+            .read::<Map>()
+            .read::<BuildingInfos>()
+            .read::<GameTime>()
+            .write::<WaterFlow>()
+            .read_store::<CompanyEnt>(),
+        water_flow_system,
+    );
     register_system("dispatch_system", dispatch_system);
     register_system("update_decision_system", update_decision_system);
     register_system("company_system", company_system);
-    register_system("pedestrian_decision_system", pedestrian_decision_system);
+    // pedestrians and trains don't see each other, both are synchronized in the grid afterwards
+    register_par_system(
+        "pedestrian_decision_system",
+        SystemAccess::new().write_store::<HumanEnt>(),
+        pedestrian_decision_system,
+    );
+    register_par_system(
+        "locomotive_system",
+        SystemAccess::new()
+            .read::<Map>()
+            .read::<TrainReservations>()
+            .write::<SimEvents>()
+            .write_store::<TrainEnt>(),
+        locomotive_system,
+    );
     register_system("transport_grid_synchronize", transport_grid_synchronize);
-    register_system("locomotive_system", locomotive_system);
     register_system("vehicle_decision_system", vehicle_decision_system);
     register_system("vehicle_state_update_system", vehicle_state_update_system);
     register_system("routing_changed_system", routing_changed_system);
     register_system("routing_update_system", routing_update_system);
     register_system("itinerary_update", itinerary_update);
     register_system("market_update", market_update);
+    register_system("pollution_update", pollution_update_system);
+    register_system("property_tax", property_tax_system);
     register_system("train_reservations_update", train_reservations_update);
     register_system("freight_station", freight_station_system);
     register_system("random_vehicles", random_vehicles_update);
     register_system("update_map", |_, res| res.write::<Map>().update());
+    register_system("update_water", |_, res| {
+        let tick = res.read::<GameTime>().tick;
+        res.write::<Map>().update_water(tick)
+    });
+    register_system("scenario_update", scenario_update_system);
+    register_system("stats_recorder", stats_recorder_system);
 
+    register_system_sim("zone_growth", zone_growth_system);
+    register_system_sim("house_density", density_system);
     register_system_sim("add_souls_to_empty_buildings", add_souls_to_empty_buildings);
+    // last so that the map changes of the tick are collected before a potential save
+    register_system("land_value_update", land_value_update_system);
+    register_system_boxed(|| Box::new(ModHooks::load()));
 
     register_resource_noserialize::<ParCommandBuffer<VehicleEnt>>();
     register_resource_noserialize::<ParCommandBuffer<TrainEnt>>();
@@ -78,19 +143,27 @@ pub fn init() {
     register_resource_noinit::<SimulationOptions, Bincode>("simoptions");
 
     register_resource_default::<ElectricityFlow, Bincode>("electricity_flow");
+    register_resource_default::<WaterFlow, Bincode>("water_flow");
+    register_resource_default::<PollutionField, Bincode>("pollution");
+    register_resource_default::<LandValue, Bincode>("land_value");
     register_resource_default::<Market, Bincode>("market");
     register_resource_default::<EcoStats, Bincode>("ecostats");
+    register_resource_default::<StatsRecorder, Bincode>("stats_recorder");
+    register_resource_default::<ZoneDemand, Bincode>("zone_demand");
     register_resource_default::<MultiplayerState, Bincode>("multiplayer_state");
     register_resource_default::<RandomVehicles, Bincode>("random_vehicles");
     register_resource_default::<Map, Bincode>("map");
     register_resource_default::<TrainReservations, Bincode>("train_reservations");
     register_resource_default::<Government, Bincode>("government");
+    register_resource_default::<ScenarioState, Bincode>("scenario");
     register_resource_default::<ParkingManagement, Bincode>("pmanagement");
     register_resource_default::<BuildingInfos, Bincode>("binfos");
     register_resource::<GameTime, Bincode>("game_time", || GameTime::new(Tick(1)));
     register_resource::<TransportGrid, Bincode>("transport_grid", || TransportGrid::new(100));
     register_resource::<RandProvider, Bincode>("randprovider", || RandProvider::new(RNG_SEED));
     register_resource_default::<Dispatcher, Bincode>("dispatcher");
+    register_resource_default::<SimEvents, Bincode>("sim_events");
+    register_resource_default::<ModCommands, Bincode>("mod_commands");
     register_resource_default::<Replay, JSON>("replay");
 }
 
@@ -102,10 +175,13 @@ pub(crate) struct SaveLoadFunc {
     pub name: &'static str,
     pub save: Box<dyn Fn(&Simulation) -> Vec<u8> + 'static>,
     pub load: Box<dyn Fn(&mut Simulation, Vec<u8>) + 'static>,
+    /// Human readable version of the resource, to find what changed between two simulations
+    pub debug: Box<dyn Fn(&Simulation) -> std::io::Result<Vec<u8>> + 'static>,
 }
 
-pub(crate) struct GSystem {
-    pub(crate) s: Box<dyn Fn() -> Box<dyn RunnableSystem>>,
+pub(crate) enum GSystem {
+    Exclusive(Box<dyn Fn() -> Box<dyn RunnableSystem>>),
+    Par(ParSystem),
 }
 
 pub(crate) static mut INIT_FUNCS: Vec<InitFunc> = Vec::new();
@@ -122,22 +198,37 @@ pub(crate) static mut GSYSTEMS: Vec<GSystem> = Vec::new();
 
 fn register_system(name: &'static str, s: fn(&mut World, &mut Resources)) {
     unsafe {
-        GSYSTEMS.push(GSystem {
-            s: Box::new(move || {
-                Box::new(utils::scheduler::RunnableFn {
-                    f: move |sim| s(&mut sim.world, &mut sim.resources),
-                    name,
-                })
-            }),
-        });
+        GSYSTEMS.push(GSystem::Exclusive(Box::new(move || {
+            Box::new(utils::scheduler::RunnableFn {
+                f: move |sim| s(&mut sim.world, &mut sim.resources),
+                name,
+            })
+        })));
+    }
+}
+
+/// Registers a system that declares what it accesses, see [`SystemAccess`]
+fn register_par_system(
+    name: &'static str,
+    access: SystemAccess,
+    f: fn(&mut SystemWorld, &SystemResources),
+) {
+    unsafe {
+        GSYSTEMS.push(GSystem::Par(ParSystem { name, access, f }));
     }
 }
 
 fn register_system_sim(name: &'static str, s: fn(&mut Simulation)) {
     unsafe {
-        GSYSTEMS.push(GSystem {
-            s: Box::new(move || Box::new(utils::scheduler::RunnableFn { f: s, name })),
-        });
+        GSYSTEMS.push(GSystem::Exclusive(Box::new(move || {
+            Box::new(utils::scheduler::RunnableFn { f: s, name })
+        })));
+    }
+}
+
+fn register_system_boxed(s: impl Fn() -> Box<dyn RunnableSystem> + 'static) {
+    unsafe {
+        GSYSTEMS.push(GSystem::Exclusive(Box::new(s)));
     }
 }
 
@@ -185,6 +276,7 @@ fn register_resource_noinit<T: 'static + Send + Sync + Serialize + DeserializeOw
                     log::error!("Error loading resource {}: {}", name, e);
                 }
             }),
+            debug: Box::new(move |uiworld| JSONPretty::encode(&*uiworld.read::<T>())),
         });
     }
 }
@@ -2,9 +2,10 @@
 #![allow(clippy::type_complexity)]
 #![warn(clippy::iter_over_hash_type)]
 
-use crate::init::{GSYSTEMS, INIT_FUNCS, SAVELOAD_FUNCS};
+use crate::init::{GSystem, GSYSTEMS, INIT_FUNCS, SAVELOAD_FUNCS};
 use crate::map::{BuildingKind, Map};
 use crate::map_dynamic::{Itinerary, ItineraryLeader};
+use crate::modding::ModCommands;
 use crate::souls::add_souls_to_empty_buildings;
 use crate::utils::resources::{Ref, RefMut, Resources};
 use crate::utils::scheduler::RunnableSystem;
@@ -14,7 +15,10 @@ use common::saveload::Encoder;
 use common::FastMap;
 use derive_more::{From, TryInto};
 use geom::Vec3;
-use prototypes::{prototype, ColorsPrototype, ColorsPrototypeID, GameTime, Tick};
+use prototypes::{
+    prototype, try_prototype, ColorsPrototype, ColorsPrototypeID, GameTime, ScenarioPrototypeID,
+    ScenarioStartMap, Tick,
+};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::Any;
 use std::collections::BTreeMap;
@@ -23,7 +27,7 @@ use std::hash::Hash;
 use std::ptr::addr_of;
 use std::time::{Duration, Instant};
 use utils::rand_provider::RandProvider;
-use utils::scheduler::SeqSchedule;
+use utils::scheduler::Schedule;
 
 #[macro_use]
 extern crate common;
@@ -39,11 +43,14 @@ extern crate egui_inspect;
 extern crate log as extern_log;
 
 pub mod economy;
+pub mod events;
 pub mod init;
 pub mod map;
 pub mod map_dynamic;
+pub mod modding;
 pub mod multiplayer;
 mod rerun;
+pub mod scenario;
 pub mod souls;
 #[cfg(test)]
 mod tests;
@@ -129,12 +136,15 @@ impl Default for SimulationOptions {
 }
 
 impl Simulation {
-    pub fn schedule() -> SeqSchedule {
-        let mut schedule = SeqSchedule::default();
+    pub fn schedule() -> Schedule {
+        let mut schedule = Schedule::default();
+        schedule.set_parallel(true);
         unsafe {
             for s in &*addr_of!(GSYSTEMS) {
-                let s = (s.s)();
-                schedule.add_system(s);
+                match s {
+                    GSystem::Exclusive(s) => schedule.add_system(s()),
+                    GSystem::Par(s) => schedule.add_par_system(s.clone()),
+                };
             }
         }
         schedule
@@ -161,16 +171,7 @@ impl Simulation {
             }
         }
 
-        (
-            sim,
-            SimulationReplayLoader {
-                replay,
-                pastt: Tick::default(),
-                idx: 0,
-                speed: 1,
-                advance_n_ticks: 0,
-            },
-        )
+        (sim, SimulationReplayLoader::new(replay))
     }
 
     pub fn new_with_options(opts: SimulationOptions) -> Simulation {
@@ -200,6 +201,18 @@ impl Simulation {
         sim
     }
 
+    /// Creates the simulation for the given scenario, ready to be played
+    /// Returns None if the scenario does not exist or if its saved world could not be loaded
+    pub fn new_scenario(id: ScenarioPrototypeID) -> Option<Simulation> {
+        let proto = try_prototype(id)?;
+        let mut sim = match proto.start_map {
+            ScenarioStartMap::Save(ref path) => Self::load_from_disk(path)?,
+            _ => Self::new(true),
+        };
+        WorldCommand::StartScenario(id).apply(&mut sim);
+        Some(sim)
+    }
+
     pub fn world_res(&mut self) -> (&mut World, &mut Resources) {
         (&mut self.world, &mut self.resources)
     }
@@ -236,8 +249,26 @@ impl Simulation {
 
     pub fn tick<'a>(
         &mut self,
-        game_schedule: &mut SeqSchedule,
+        game_schedule: &mut Schedule,
+        commands: impl IntoIterator<Item = &'a WorldCommand>,
+    ) -> Duration {
+        self.tick_inner(game_schedule, commands, false)
+    }
+
+    /// Plays a tick of a replay, see [`WorldCommand::validate_replayed`]
+    pub fn tick_replayed<'a>(
+        &mut self,
+        game_schedule: &mut Schedule,
         commands: impl IntoIterator<Item = &'a WorldCommand>,
+    ) -> Duration {
+        self.tick_inner(game_schedule, commands, true)
+    }
+
+    fn tick_inner<'a>(
+        &mut self,
+        game_schedule: &mut Schedule,
+        commands: impl IntoIterator<Item = &'a WorldCommand>,
+        replayed: bool,
     ) -> Duration {
         profiling::scope!("simulation::tick");
         let t = Instant::now();
@@ -246,8 +277,26 @@ impl Simulation {
         {
             profiling::scope!("applying commands");
             for command in commands {
+                let valid = if replayed {
+                    command.validate_replayed(self)
+                } else {
+                    command.validate(self)
+                };
+                if let Err(e) = valid {
+                    log::warn!("rejected command {:?}: {}", command, e);
+                    continue;
+                }
                 command.apply(self);
             }
+
+            let mod_commands = std::mem::take(&mut self.write::<ModCommands>().commands);
+            for command in &mod_commands {
+                if let Err(e) = command.validate(self) {
+                    log::warn!("rejected mod command {:?}: {}", command, e);
+                    continue;
+                }
+                command.apply_unrecorded(self);
+            }
         }
 
         {
@@ -256,6 +305,7 @@ impl Simulation {
         }
 
         game_schedule.execute(self);
+        events::end_tick(self);
 
         self.resources.write::<Replay>().last_tick_recorded =
             self.resources.read::<GameTime>().tick;
@@ -267,6 +317,17 @@ impl Simulation {
         self.resources.read::<GameTime>().tick.0
     }
 
+    /// One line description of the city, e.g. for the players looking for a server
+    pub fn summary(&self) -> String {
+        let map = self.map();
+        format!(
+            "{} citizens, {} buildings, {} roads",
+            self.world.humans.len(),
+            map.buildings().len(),
+            map.roads().len()
+        )
+    }
+
     pub fn hashes(&self) -> BTreeMap<String, u64> {
         let mut hashes = BTreeMap::new();
         let ser = common::saveload::Bincode::encode(&self.world).unwrap();
@@ -282,6 +343,79 @@ impl Simulation {
         hashes
     }
 
+    /// The world or a saved resource, as named in [`Simulation::hashes`], in pretty JSON
+    pub fn debug_dump(&self, name: &str) -> Option<String> {
+        let v = if name == "world" {
+            common::saveload::JSONPretty::encode(&self.world)
+        } else {
+            unsafe {
+                let l = (*addr_of!(SAVELOAD_FUNCS))
+                    .iter()
+                    .find(|l| l.name == name)?;
+                (l.debug)(self)
+            }
+        };
+        Some(match v {
+            Ok(v) => String::from_utf8_lossy(&v).into_owned(),
+            Err(e) => format!("could not encode {name} as json: {e}"),
+        })
+    }
+
+    /// The world and each saved resource serialized separately,
+    /// so that a client with an older copy only downloads the parts that changed
+    pub fn to_parts(&self) -> Vec<(String, Vec<u8>)> {
+        let mut parts = vec![(
+            "world".to_string(),
+            common::saveload::Bincode::encode(&self.world).unwrap(),
+        )];
+        unsafe {
+            for l in &*addr_of!(SAVELOAD_FUNCS) {
+                parts.push((l.name.to_string(), (l.save)(self)));
+            }
+        }
+        parts
+    }
+
+    /// Inverse of [`Simulation::to_parts`], None if the world part is missing or invalid
+    pub fn from_parts(parts: Vec<(String, Vec<u8>)>) -> Option<Self> {
+        let mut res: FastMap<String, Vec<u8>> = parts.into_iter().collect();
+        let world = res.remove("world")?;
+        let world = match common::saveload::Bincode::decode(&world) {
+            Ok(world) => world,
+            Err(e) => {
+                log::error!("could not decode world part: {}", e);
+                return None;
+            }
+        };
+        Some(Self::from_saved(world, res))
+    }
+
+    /// Initializes the resources then loads the saved ones
+    fn from_saved(world: World, mut res: FastMap<String, Vec<u8>>) -> Self {
+        let mut sim = Self {
+            world: World::default(),
+            resources: Resources::default(),
+        };
+
+        unsafe {
+            for s in &*addr_of!(INIT_FUNCS) {
+                (s.f)(&mut sim);
+            }
+        }
+
+        sim.world = world;
+
+        unsafe {
+            for l in &*addr_of!(SAVELOAD_FUNCS) {
+                if let Some(data) = res.remove(l.name) {
+                    (l.load)(&mut sim, data);
+                }
+            }
+        }
+
+        sim
+    }
+
     pub fn load_replay_from_disk(save_name: &str) -> Option<Replay> {
         let path = format!("{save_name}_replay");
         let replay: Replay = common::saveload::JSON::load(&path).ok()?;
@@ -400,7 +534,7 @@ impl<'de> Deserialize<'de> for Simulation {
         log::info!("deserializing sim state");
         let t = Instant::now();
 
-        let mut simdeser = <SimulationDeser as Deserialize>::deserialize(deserializer)?;
+        let simdeser = <SimulationDeser as Deserialize>::deserialize(deserializer)?;
 
         log::info!(
             "took {}s to deserialize base deser",
@@ -420,26 +554,7 @@ impl<'de> Deserialize<'de> for Simulation {
             );
         }
 
-        let mut sim = Self {
-            world: World::default(),
-            resources: Resources::default(),
-        };
-
-        unsafe {
-            for s in &*addr_of!(INIT_FUNCS) {
-                (s.f)(&mut sim);
-            }
-        }
-
-        sim.world = simdeser.world;
-
-        unsafe {
-            for l in &*addr_of!(SAVELOAD_FUNCS) {
-                if let Some(data) = simdeser.res.remove(l.name) {
-                    (l.load)(&mut sim, data);
-                }
-            }
-        }
+        let sim = Simulation::from_saved(simdeser.world, simdeser.res);
 
         log::info!(
             "took {}s to deserialize in total",
@@ -135,9 +135,12 @@ pub fn find_overrides(map: &mut Map, chunk: SubscriberChunkID) {
 
                     setter.set_override(r.points.bbox().flatten().expand(expand + 3.0), |pos| {
                         let proj = r.points.project_2d(pos);
-                        proj.xy()
-                            .is_close(pos, expand)
-                            .then_some(proj.z - ROAD_Z_OFFSET)
+                        let h = proj.z - ROAD_Z_OFFSET;
+                        // don't cut the surface above tunnels, only dig the portals
+                        if map.environment.is_underground(proj.xy().z(h)) {
+                            return None;
+                        }
+                        proj.xy().is_close(pos, expand).then_some(h)
                     })
                 }
                 ProjectKind::Building(b) => {
@@ -1,13 +1,15 @@
-use crate::map::electricity_cache::ElectricityCache;
 use crate::map::height_override::find_overrides;
+use crate::map::network_cache::{NetworkCache, NetworkKind, NetworkObjectID};
 use crate::map::serializing::SerializedMap;
 use crate::map::{
-    Building, BuildingID, BuildingKind, Environment, Intersection, IntersectionID, Lane, LaneID,
-    LaneKind, LanePattern, Lot, LotID, LotKind, MapSubscriber, MapSubscribers, ParkingSpotID,
-    ParkingSpots, ProjectFilter, ProjectKind, Road, RoadID, RoadSegmentKind, SpatialMap,
-    SubscriberChunkID, TerraformKind, UpdateType, Zone, ROAD_Z_OFFSET,
+    Building, BuildingID, BuildingKind, Density, Environment, Intersection, IntersectionID, Lane,
+    LaneID, LaneKind, LanePattern, Lot, LotID, LotKind, MapSubscriber, MapSubscribers,
+    ParkingSpotID, ParkingSpots, PowerLine, PowerLineEnd, PowerLineID, PowerLineKind,
+    ProjectFilter, ProjectKind, Pylon, PylonID, Road, RoadID, RoadSegmentKind, SpatialMap,
+    SubscriberChunkID, TerraformKind, UpdateType, Zone, PYLON_HEIGHT, ROAD_Z_OFFSET,
+    WATER_BUILD_MARGIN, WATER_UPDATE_PERIOD,
 };
-use geom::OBB;
+use geom::{BoldLine, OBB};
 use geom::{Vec2, Vec3};
 use ordered_float::OrderedFloat;
 use prototypes::{BuildingGen, Tick};
@@ -19,6 +21,8 @@ pub type Lanes = HopSlotMap<LaneID, Lane>;
 pub type Intersections = HopSlotMap<IntersectionID, Intersection>;
 pub type Buildings = HopSlotMap<BuildingID, Building>;
 pub type Lots = HopSlotMap<LotID, Lot>;
+pub type Pylons = HopSlotMap<PylonID, Pylon>;
+pub type PowerLines = HopSlotMap<PowerLineID, PowerLine>;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct MapProject {
@@ -32,14 +36,19 @@ pub struct Map {
     pub(crate) intersections: Intersections,
     pub(crate) buildings: Buildings,
     pub(crate) lots: Lots,
+    pub(crate) pylons: Pylons,
+    pub(crate) power_lines: PowerLines,
     pub(crate) spatial_map: SpatialMap,
     pub(crate) external_train_stations: Vec<BuildingID>,
 
-    pub electricity: ElectricityCache,
+    pub electricity: NetworkCache,
+    pub water_mains: NetworkCache,
     pub environment: Environment,
     pub parking: ParkingSpots,
     pub subscribers: MapSubscribers,
     pub(crate) override_subscriber: MapSubscriber,
+    /// Kept inside the map so that no change is missed between a load and the first land value update
+    pub(crate) land_value_subscriber: MapSubscriber,
 }
 
 defer_serialize!(Map, SerializedMap);
@@ -61,11 +70,16 @@ impl Map {
             parking: ParkingSpots::default(),
             buildings: Buildings::default(),
             lots: Lots::default(),
+            pylons: Pylons::default(),
+            power_lines: PowerLines::default(),
             environment: Environment::default(),
             spatial_map: SpatialMap::default(),
             external_train_stations: Default::default(),
-            electricity: Default::default(),
+            electricity: NetworkCache::new(NetworkKind::Electricity),
+            water_mains: NetworkCache::new(NetworkKind::WaterMains),
             override_subscriber: subscribers.subscribe(UpdateType::Road | UpdateType::Building),
+            land_value_subscriber: subscribers
+                .subscribe(UpdateType::Road | UpdateType::Building | UpdateType::Terrain),
             subscribers,
         }
     }
@@ -108,7 +122,7 @@ impl Map {
             self.invalidate(o);
         }
 
-        self.electricity.remove_object(src);
+        self.remove_network_object(src);
         self.spatial_map.remove(src);
     }
 
@@ -128,6 +142,10 @@ impl Map {
     pub fn remove_building(&mut self, b: BuildingID) -> Option<Building> {
         info!("remove_building {:?}", b);
 
+        for line in self.buildings.get(b)?.power_lines.clone() {
+            self.remove_power_line(line);
+        }
+
         let b = self.buildings.remove(b)?;
         self.subscribers.dispatch(UpdateType::Building, &b);
 
@@ -139,7 +157,7 @@ impl Map {
             self.roads[r].connected_buildings.retain(|x| *x != b.id);
         }
 
-        self.electricity.remove_object(b.id);
+        self.remove_network_object(b.id);
         self.spatial_map.remove(b.id);
 
         self.check_invariants();
@@ -147,12 +165,116 @@ impl Map {
         Some(b)
     }
 
+    /// Builds a power line between two buildings or pylons, placing new pylons on the ground if needed.
+    /// Power lines only carry electricity and don't need roads.
+    pub fn build_power_line(
+        &mut self,
+        src: PowerLineEnd,
+        dst: PowerLineEnd,
+        kind: PowerLineKind,
+    ) -> Option<PowerLineID> {
+        info!("build_power_line {:?} {:?} {:?}", src, dst, kind);
+
+        let src_pos = self.power_line_end_pos(src)?;
+        let dst_pos = self.power_line_end_pos(dst)?;
+        if src_pos.distance(dst_pos) < 1.0 {
+            return None;
+        }
+
+        let mut mk_end = |end: PowerLineEnd| match end {
+            PowerLineEnd::Building(b) => NetworkObjectID::Building(b),
+            PowerLineEnd::Pylon(p) => NetworkObjectID::Pylon(p),
+            PowerLineEnd::Ground(pos) => {
+                let id = self.pylons.insert_with_key(|id| Pylon {
+                    id,
+                    pos,
+                    lines: Vec::new(),
+                });
+                self.electricity.add_object(id);
+                NetworkObjectID::Pylon(id)
+            }
+        };
+
+        let src = mk_end(src);
+        let dst = mk_end(dst);
+
+        let id = self.power_lines.insert_with_key(|id| PowerLine {
+            id,
+            kind,
+            src,
+            dst,
+            src_pos,
+            dst_pos,
+        });
+
+        for end in [src, dst] {
+            match end {
+                NetworkObjectID::Building(b) => self.buildings[b].power_lines.push(id),
+                NetworkObjectID::Pylon(p) => self.pylons[p].lines.push(id),
+                _ => {}
+            }
+        }
+
+        self.electricity.add_object(id);
+        self.electricity.add_edge(id, src);
+        self.electricity.add_edge(id, dst);
+
+        self.check_invariants();
+        Some(id)
+    }
+
+    /// Removes a power line, and the pylons that don't hold any line anymore
+    pub fn remove_power_line(&mut self, id: PowerLineID) -> Option<PowerLine> {
+        info!("remove_power_line {:?}", id);
+
+        let line = self.power_lines.remove(id)?;
+        self.electricity.remove_object(id);
+
+        for end in [line.src, line.dst] {
+            match end {
+                NetworkObjectID::Building(b) => {
+                    if let Some(b) = self.buildings.get_mut(b) {
+                        b.power_lines.retain(|l| *l != id);
+                    }
+                }
+                NetworkObjectID::Pylon(p) => {
+                    let Some(pylon) = self.pylons.get_mut(p) else {
+                        continue;
+                    };
+                    pylon.lines.retain(|l| *l != id);
+                    if pylon.lines.is_empty() {
+                        self.pylons.remove(p);
+                        self.electricity.remove_object(p);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.check_invariants();
+        Some(line)
+    }
+
+    /// Where a power line would be attached: on the roof of buildings and on top of pylons.
+    /// None if the building or pylon doesn't exist
+    pub fn power_line_end_pos(&self, end: PowerLineEnd) -> Option<Vec3> {
+        match end {
+            PowerLineEnd::Building(b) => {
+                let b = self.buildings.get(b)?;
+                Some(b.obb.center().z(b.door_pos.z + b.height))
+            }
+            PowerLineEnd::Pylon(p) => self.pylons.get(p).map(|p| p.pos.up(PYLON_HEIGHT)),
+            PowerLineEnd::Ground(pos) => Some(pos.up(PYLON_HEIGHT)),
+        }
+    }
+
     pub fn make_connection(
         &mut self,
         from: MapProject,
         to: MapProject,
         interpoint: Option<Vec2>,
         pattern: &LanePattern,
+        tunnel: bool,
     ) -> Option<(IntersectionID, RoadID)> {
         if !from.kind.check_valid(self)
             || !to.kind.check_valid(self)
@@ -160,7 +282,10 @@ impl Map {
         {
             return None;
         }
-        info!("make_connection {:?} {:?} {:?}", from, to, interpoint);
+        info!(
+            "make_connection {:?} {:?} {:?} tunnel: {}",
+            from, to, interpoint, tunnel
+        );
 
         let connection_segment = match interpoint {
             Some(x) => RoadSegmentKind::from_elbow(from.pos.xy(), to.pos.xy(), x),
@@ -183,7 +308,7 @@ impl Map {
             return None;
         };
 
-        let Some(r) = self.connect(from_id, to_id, pattern, connection_segment) else {
+        let Some(r) = self.connect(from_id, to_id, pattern, connection_segment, tunnel) else {
             self.invalidate(from_id);
             self.invalidate(to_id);
             self.check_invariants();
@@ -254,6 +379,10 @@ impl Map {
             log::warn!("did not build {:?}: building overlaps", kind);
             return None;
         }
+        if self.environment.water.overlaps(obb, WATER_BUILD_MARGIN) {
+            log::warn!("did not build {:?}: too close to water", kind);
+            return None;
+        }
         log::info!(
             "build special {:?} with shape {:?} and gen {:?} and zone {:?}",
             kind,
@@ -273,7 +402,6 @@ impl Map {
         let Some(id) = Building::make(
             &mut self.buildings,
             &mut self.spatial_map,
-            &mut self.electricity,
             &mut self.roads,
             &self.environment,
             *obb,
@@ -288,6 +416,7 @@ impl Map {
 
         self.subscribers
             .dispatch(UpdateType::Building, &self.buildings[id]);
+        self.add_building_to_networks(id);
 
         if kind == BuildingKind::ExternalTrading {
             self.external_train_stations.push(id);
@@ -300,6 +429,14 @@ impl Map {
     pub fn build_house(&mut self, lot_id: LotID) -> Option<BuildingID> {
         info!("build house on {:?}", lot_id);
 
+        if self
+            .environment
+            .water
+            .overlaps(&self.lots.get(lot_id)?.shape, WATER_BUILD_MARGIN)
+        {
+            return None;
+        }
+
         let lot = self.lots.remove(lot_id)?;
         self.subscribers.dispatch(UpdateType::Road, &lot);
         self.spatial_map.remove(lot.id);
@@ -307,7 +444,6 @@ impl Map {
         let Some(id) = Building::make(
             &mut self.buildings,
             &mut self.spatial_map,
-            &mut self.electricity,
             &mut self.roads,
             &self.environment,
             lot.shape,
@@ -322,7 +458,7 @@ impl Map {
 
         self.subscribers
             .dispatch(UpdateType::Building, &self.buildings[id]);
-        self.electricity.add_object(id);
+        self.add_building_to_networks(id);
 
         self.check_invariants();
         Some(id)
@@ -336,6 +472,92 @@ impl Map {
         v
     }
 
+    /// Changes the lane pattern of a road without demolishing it.
+    /// Lots along the road are regenerated if its width changed, connected buildings are kept.
+    /// Returns the removed lanes, or None if the road doesn't exist
+    pub fn update_road_pattern(
+        &mut self,
+        road_id: RoadID,
+        pattern: &LanePattern,
+    ) -> Option<Vec<LaneID>> {
+        info!("update_road_pattern {:?} {:?}", road_id, pattern);
+
+        let road = self.roads.get_mut(road_id)?;
+        self.subscribers.dispatch(UpdateType::Road, road);
+
+        let old_width = road.width;
+        let had_sidewalks = road.has_sidewalks();
+        let removed = road.set_pattern(
+            pattern,
+            &mut self.lanes,
+            &mut self.parking,
+            &self.environment,
+        );
+        let (src, dst) = (road.src, road.dst);
+        let width_changed = road.width != old_width || road.has_sidewalks() != had_sidewalks;
+
+        self.spatial_map.update(&*road);
+
+        if width_changed {
+            let smap = &mut self.spatial_map;
+            let mut old_lots = vec![];
+            self.lots.retain(|_, lot| {
+                let to_remove = lot.parent == road_id;
+                if to_remove {
+                    self.subscribers.dispatch(UpdateType::Road, lot);
+                    smap.remove(lot.id);
+                    old_lots.push((lot.shape, lot.kind));
+                }
+                !to_remove
+            });
+
+            Lot::remove_intersecting_lots(self, road_id);
+            Lot::generate_along_road(self, road_id);
+
+            // the new lots keep the zoning of the lots they replace
+            for lot in self.lots.values_mut().filter(|lot| lot.parent == road_id) {
+                let Some(&(_, kind)) = old_lots
+                    .iter()
+                    .find(|(shape, _)| shape.contains(lot.shape.center()))
+                else {
+                    continue;
+                };
+                lot.kind = kind;
+                self.subscribers.dispatch(UpdateType::Road, lot);
+            }
+        }
+
+        self.invalidate(src);
+        self.invalidate(dst);
+
+        #[allow(clippy::indexing_slicing)]
+        for &b in &self.roads[road_id].connected_buildings {
+            if let Some(b) = self.buildings.get(b) {
+                self.subscribers.dispatch(UpdateType::Building, b);
+            }
+        }
+
+        self.check_invariants();
+
+        Some(removed)
+    }
+
+    /// Returns true if changing the road to the given pattern would run over a building
+    /// it doesn't already touch
+    pub fn road_pattern_overlaps_building(&self, road: RoadID, pattern: &LanePattern) -> bool {
+        let Some(r) = self.roads.get(road) else {
+            return false;
+        };
+        let touched = self
+            .spatial_map
+            .query(r.boldline(), ProjectFilter::BUILDING)
+            .collect::<Vec<_>>();
+        let widened = BoldLine::new(r.points.flatten(), pattern.width() * 0.5);
+        self.spatial_map
+            .query(widened, ProjectFilter::BUILDING)
+            .any(|b| !touched.contains(&b))
+    }
+
     pub fn subscribe(&self, filter: UpdateType) -> MapSubscriber {
         self.subscribers.subscribe(filter)
     }
@@ -382,6 +604,25 @@ impl Map {
         Some(road)
     }
 
+    /// Changes the density of a house, regenerating its mesh if the level changed
+    pub fn set_building_density(&mut self, id: BuildingID, density: Density) {
+        let Some(b) = self.buildings.get_mut(id) else {
+            log::warn!("trying to set density of non-existing building {:?}", id);
+            return;
+        };
+        let old_level = b.density.level;
+        b.density = density;
+        if b.kind != BuildingKind::House || old_level == density.level {
+            return;
+        }
+
+        let (mesh, door_pos, _) =
+            Building::gen_mesh(&self.environment, b.obb, BuildingGen::House, density.level);
+        b.mesh = mesh;
+        b.door_pos = door_pos;
+        self.subscribers.dispatch(UpdateType::Building, b);
+    }
+
     pub fn set_lot_kind(&mut self, lot: LotID, kind: LotKind) {
         match self.lots.get_mut(lot) {
             Some(lot) => {
@@ -411,16 +652,91 @@ impl Map {
         }
     }
 
+    /// Recomputes lakes and rivers after the terrain changed.
+    /// Lots and buildings that end up under water are flooded and removed.
+    /// Roads that end up under water are raised into bridges.
+    pub fn update_water(&mut self, tick: Tick) {
+        if tick.0 % WATER_UPDATE_PERIOD != 0 || !self.environment.water.is_dirty() {
+            return;
+        }
+        profiling::scope!("map::update_water");
+        let before = self.environment.water.clone();
+        self.environment.update_water();
+
+        let water = &self.environment.water;
+        let flooded_roads = self
+            .roads
+            .values()
+            .filter(|r| !r.tunnel && water.overlaps_new(&before, &r.boldline()))
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+        let flooded_lots = self
+            .lots
+            .values()
+            .filter(|lot| water.overlaps(&lot.shape, 0.0))
+            .map(|lot| ProjectKind::Lot(lot.id))
+            .collect();
+        let flooded_buildings = self
+            .buildings
+            .values()
+            .filter(|b| water.overlaps(&b.obb, 0.0))
+            .map(|b| b.id)
+            .collect::<Vec<_>>();
+
+        self.clean_lots_inner(flooded_lots);
+        for b in flooded_buildings {
+            info!("building {:?} was flooded", b);
+            self.remove_building(b);
+        }
+
+        for id in flooded_roads {
+            info!("road {:?} was flooded", id);
+            #[allow(clippy::indexing_slicing)]
+            let road = &mut self.roads[id];
+            self.subscribers.dispatch(UpdateType::Road, road);
+            road.update_heights(&mut self.lanes, &mut self.parking, &self.environment);
+            let (src, dst) = (road.src, road.dst);
+            self.spatial_map.update(&*road);
+            self.invalidate(src);
+            self.invalidate(dst);
+        }
+    }
+
     // Private mutating
 
     pub(crate) fn add_intersection(&mut self, pos: Vec3) -> IntersectionID {
         let id = Intersection::make(&mut self.intersections, &mut self.spatial_map, pos);
         self.subscribers
             .dispatch(UpdateType::Building, &self.intersections[id]);
-        self.electricity.add_object(id);
+        self.add_network_object(id);
         id
     }
 
+    fn add_network_object(&mut self, id: impl Into<NetworkObjectID>) {
+        let id = id.into();
+        self.electricity.add_object(id);
+        self.water_mains.add_object(id);
+    }
+
+    fn remove_network_object(&mut self, id: impl Into<NetworkObjectID>) {
+        let id = id.into();
+        self.electricity.remove_object(id);
+        self.water_mains.remove_object(id);
+    }
+
+    fn add_building_to_networks(&mut self, id: BuildingID) {
+        self.add_network_object(id);
+        let Some(road) = self.buildings[id]
+            .connected_road
+            .and_then(|r| self.roads.get(r))
+        else {
+            return;
+        };
+        for net in [&mut self.electricity, &mut self.water_mains] {
+            net.connect_road(id, road);
+        }
+    }
+
     fn invalidate(&mut self, id: IntersectionID) {
         info!("invalidate {:?}", id);
 
@@ -467,7 +783,7 @@ impl Map {
         let road = self.roads.remove(road_id)?;
 
         self.spatial_map.remove(road_id);
-        self.electricity.remove_object(road_id);
+        self.remove_network_object(road_id);
 
         for (id, _) in road.lanes_iter() {
             self.lanes.remove(id);
@@ -509,8 +825,14 @@ impl Map {
         let dist_along = r.points.length_at_proj(pos);
         let (before, after) = r.points.split(dist_along);
 
-        let r1 = self.connect(r.src, id, &pat, RoadSegmentKind::Arbitrary(before))?;
-        let r2 = self.connect(id, r.dst, &pat, RoadSegmentKind::Arbitrary(after))?;
+        let r1 = self.connect(
+            r.src,
+            id,
+            &pat,
+            RoadSegmentKind::Arbitrary(before),
+            r.tunnel,
+        )?;
+        let r2 = self.connect(id, r.dst, &pat, RoadSegmentKind::Arbitrary(after), r.tunnel)?;
 
         self.invalidate(r.src);
         self.invalidate(r.dst);
@@ -570,7 +892,9 @@ impl Map {
             let road_id = b.connected_road.unwrap();
             self.roads[road_id].connected_buildings.push(b.id);
 
-            self.electricity.add_edge(b.id, road_id);
+            for net in [&mut self.electricity, &mut self.water_mains] {
+                net.connect_road(b.id, &self.roads[road_id]);
+            }
         }
 
         Some(id)
@@ -607,6 +931,11 @@ impl Map {
             return None;
         }
 
+        if r1.tunnel != r2.tunnel {
+            log::info!("merge refused because only one of the roads is a tunnel");
+            return None;
+        }
+
         let r1_extremity = if r1.src == same_inter { r1.dst } else { r1.src };
         let r2_extremity = if r2.src == same_inter { r2.dst } else { r2.src };
 
@@ -643,6 +972,7 @@ impl Map {
             r2_extremity,
             &pat1,
             RoadSegmentKind::Arbitrary(new_polyline),
+            r1.tunnel,
         )?;
 
         let new_r = &mut self.roads[new_r];
@@ -662,7 +992,9 @@ impl Map {
             b.connected_road = Some(new_r.id);
             new_r.connected_buildings.push(b.id);
 
-            self.electricity.add_edge(b.id, new_r.id);
+            for net in [&mut self.electricity, &mut self.water_mains] {
+                net.connect_road(b.id, new_r);
+            }
         }
 
         for lot in &mut self.lots.values_mut() {
@@ -686,6 +1018,7 @@ impl Map {
         dst_id: IntersectionID,
         pattern: &LanePattern,
         segment: RoadSegmentKind,
+        tunnel: bool,
     ) -> Option<RoadID> {
         let src = self.intersections.get(src_id)?;
         let dst = self.intersections.get(dst_id)?;
@@ -697,6 +1030,7 @@ impl Map {
             dst,
             segment,
             pattern,
+            tunnel,
             &self.environment,
             &mut self.roads,
             &mut self.lanes,
@@ -704,13 +1038,16 @@ impl Map {
             &mut self.spatial_map,
         );
 
-        self.electricity.add_object(rid);
-        self.electricity.add_edge(src_id, rid);
-        self.electricity.add_edge(dst_id, rid);
+        self.add_network_object(rid);
 
         #[allow(clippy::indexing_slicing)]
         let r = &self.roads[rid];
 
+        for net in [&mut self.electricity, &mut self.water_mains] {
+            net.connect_road(src_id, r);
+            net.connect_road(dst_id, r);
+        }
+
         self.intersections.get_mut(src_id)?.add_road(&self.roads, r);
         self.intersections.get_mut(dst_id)?.add_road(&self.roads, r);
 
@@ -758,6 +1095,13 @@ impl Map {
                         "Road does not exist anymore, you seem to have forgotten to remove it from the spatial map.");
 
                     let projected = road.points.project(pos);
+                    // roads cannot be connected to from inside a tunnel
+                    if self
+                        .environment
+                        .is_underground(projected.up(-ROAD_Z_OFFSET))
+                    {
+                        continue;
+                    }
                     qroad = Some((id, projected));
                 }
                 ProjectKind::Building(id) => {
@@ -800,6 +1144,12 @@ impl Map {
     pub fn lots(&self) -> &Lots {
         &self.lots
     }
+    pub fn pylons(&self) -> &Pylons {
+        &self.pylons
+    }
+    pub fn power_lines(&self) -> &PowerLines {
+        &self.power_lines
+    }
     pub fn spatial_map(&self) -> &SpatialMap {
         &self.spatial_map
     }
@@ -944,7 +1294,7 @@ impl Map {
             }
         }
 
-        crate::map::electricity_cache::check_electricity_coherency(self);
+        crate::map::network_cache::check_network_coherency(self);
 
         for b in self.external_train_stations.iter() {
             assert!(self.buildings.contains_key(*b));
@@ -1032,6 +1382,22 @@ impl Map {
             }
         }
 
+        for line in self.power_lines.values() {
+            for end in [line.src, line.dst] {
+                match end {
+                    NetworkObjectID::Building(b) => {
+                        assert!(self.buildings[b].power_lines.contains(&line.id))
+                    }
+                    NetworkObjectID::Pylon(p) => assert!(self.pylons[p].lines.contains(&line.id)),
+                    _ => unreachable!("power lines end on buildings or pylons"),
+                }
+            }
+        }
+
+        for pylon in self.pylons.values() {
+            assert!(!pylon.lines.is_empty());
+        }
+
         for lot in self.lots.values() {
             log::debug!("{:?}", lot.id);
             assert!(lot.shape.axis().iter().all(|x| x.mag() > 0.0));
@@ -1107,3 +1473,17 @@ impl MapObj for LotID {
         map.lots.get(self)
     }
 }
+
+impl MapObj for PylonID {
+    type Obj = Pylon;
+    fn get(self, map: &Map) -> Option<&Pylon> {
+        map.pylons.get(self)
+    }
+}
+
+impl MapObj for PowerLineID {
+    type Obj = PowerLine;
+    fn get(self, map: &Map) -> Option<&PowerLine> {
+        map.power_lines.get(self)
+    }
+}
@@ -4,6 +4,7 @@ mod objects {
     mod lane;
     mod lot;
     mod parking;
+    mod power_line;
     mod road;
     mod turn;
 
@@ -12,6 +13,7 @@ mod objects {
     pub use lane::*;
     pub use lot::*;
     pub use parking::*;
+    pub use power_line::*;
     pub use road::*;
     pub use turn::*;
 }
@@ -28,11 +30,11 @@ pub mod procgen {
 }
 
 mod change_detection;
-mod electricity_cache;
 mod height_override;
 mod light_policy;
 #[allow(clippy::module_inception)]
 mod map;
+mod network_cache;
 mod pathfinding;
 mod serializing;
 mod spatial_map;
@@ -40,21 +42,27 @@ pub mod terrain;
 mod traffic_control;
 mod traversable;
 mod turn_policy;
+mod water;
 
 // Use self or else it would be ambiguous with "pathfinding" crate
 pub use self::pathfinding::*;
 pub use change_detection::*;
-pub use electricity_cache::*;
 pub use light_policy::*;
 pub use map::*;
+pub use network_cache::*;
 pub use spatial_map::*;
 pub use terrain::*;
 pub use traffic_control::*;
 pub use traversable::*;
 pub use turn_policy::*;
+pub use water::*;
 
 pub use ::pathfinding as pathfinding_crate;
 
 pub const CROSSWALK_WIDTH: f32 = 2.0;
 pub const ROAD_Z_OFFSET: f32 = 0.3;
 pub const MAX_SLOPE: f32 = 0.25; // 25% grade
+/// Minimum depth of a road below the natural terrain for it to be considered in a tunnel
+pub const TUNNEL_MIN_DEPTH: f32 = 6.0;
+/// Depth below the lowest natural terrain at which tunnels built on purpose are dug
+pub const TUNNEL_DEPTH: f32 = TUNNEL_MIN_DEPTH + 4.0;
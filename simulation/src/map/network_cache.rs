@@ -1,14 +1,16 @@
-use crate::map::{BuildingID, Buildings, IntersectionID, Intersections, Map, RoadID, Roads};
+use crate::map::{BuildingID, IntersectionID, Map, PowerLineID, PylonID, Road, RoadID};
 use serde::{Deserialize, Serialize};
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
 
-/// A network object is an object that can be connected to an electricity network
+/// A network object is an object that can be connected to a utility network
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum NetworkObjectID {
     Building(BuildingID),
     Intersection(IntersectionID),
     Road(RoadID),
+    Pylon(PylonID),
+    PowerLine(PowerLineID),
 }
 
 impl From<BuildingID> for NetworkObjectID {
@@ -29,15 +31,27 @@ impl From<RoadID> for NetworkObjectID {
     }
 }
 
+impl From<PylonID> for NetworkObjectID {
+    fn from(v: PylonID) -> Self {
+        Self::Pylon(v)
+    }
+}
+
+impl From<PowerLineID> for NetworkObjectID {
+    fn from(v: PowerLineID) -> Self {
+        Self::PowerLine(v)
+    }
+}
+
 /// The id of a network is the id of its lowest object. This is necessary to keep everything
-/// deterministic even though we don't serialize the electricity cache
+/// deterministic even though we don't serialize the network caches
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct ElectricityNetworkID(NetworkObjectID);
+pub struct NetworkID(NetworkObjectID);
 
 /// A network is a set of sources and sinks that is connected together
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct ElectricityNetwork {
-    pub id: ElectricityNetworkID,
+pub struct Network {
+    pub id: NetworkID,
 
     /// The sources/sinks of the network must be buildings. For efficient iteration,
     /// we store them separately from the road graph
@@ -47,22 +61,55 @@ pub struct ElectricityNetwork {
     pub objects: BTreeSet<NetworkObjectID>,
 }
 
-/// The electricity cache is a cache of all the electricity networks in the map
+/// What a network carries, which decides the roads it follows
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum NetworkKind {
+    /// Power lines follow every road and rail track, and can also be built on pylons
+    #[default]
+    Electricity,
+    /// Water and sewage mains are buried under roads, but not under rail tracks
+    WaterMains,
+}
+
+impl NetworkKind {
+    pub fn follows(self, road: &Road) -> bool {
+        match self {
+            NetworkKind::Electricity => true,
+            NetworkKind::WaterMains => road.lanes_iter().any(|(_, kind)| !kind.is_rail()),
+        }
+    }
+
+    /// Whether pylons and power lines are part of this kind of network
+    pub fn carries_power_lines(self) -> bool {
+        self == NetworkKind::Electricity
+    }
+}
+
+/// The network cache is a cache of all the networks of one kind in the map
 /// It maintains a mapping from network objects to network ids that are connected to each other
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
-pub struct ElectricityCache {
-    pub(crate) networks: BTreeMap<ElectricityNetworkID, ElectricityNetwork>,
+pub struct NetworkCache {
+    pub kind: NetworkKind,
+
+    pub(crate) networks: BTreeMap<NetworkID, Network>,
 
     /// The network that each intersection is connected to
-    pub(crate) ids: BTreeMap<NetworkObjectID, ElectricityNetworkID>,
+    pub(crate) ids: BTreeMap<NetworkObjectID, NetworkID>,
 
-    /// The memoized graph of electricity edges
+    /// The memoized graph of network edges
     /// This is used to decouple the adding/removal of edges and actual removal in map
     /// Note that the ordering of the Vec isn't deterministic so shouldn't be used for anything
     pub(crate) graph: BTreeMap<NetworkObjectID, Vec<NetworkObjectID>>,
 }
 
-impl ElectricityCache {
+impl NetworkCache {
+    pub fn new(kind: NetworkKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+
     /// Add a new network object. Must be called before adding or removing edges.
     pub fn add_object(&mut self, object_id: impl Into<NetworkObjectID>) {
         let object_id = object_id.into();
@@ -76,8 +123,8 @@ impl ElectricityCache {
             Entry::Occupied(_) => return,
         };
 
-        let network_id = ElectricityNetworkID(object_id);
-        let network = ElectricityNetwork {
+        let network_id = NetworkID(object_id);
+        let network = Network {
             id: network_id,
             buildings: match object_id {
                 NetworkObjectID::Building(b) => BTreeSet::from([b]),
@@ -114,7 +161,7 @@ impl ElectricityCache {
     }
 
     /// Add an edge between two network objects (symmetric)
-    /// Must agree with the electricity_edge function
+    /// Must agree with the map_network_edges function
     pub fn add_edge(&mut self, src: impl Into<NetworkObjectID>, dst: impl Into<NetworkObjectID>) {
         let src = src.into();
         let dst = dst.into();
@@ -129,18 +176,25 @@ impl ElectricityCache {
         self.graph.get_mut(dst).unwrap().push(*src);
 
         let Some(src) = self.ids.get(src) else {
-            log::error!("electricity add_edge src {:?} not found", src);
+            log::error!("{:?} add_edge src {:?} not found", self.kind, src);
             return;
         };
         let Some(dst) = self.ids.get(dst) else {
-            log::error!("electricity add_edge dst {:?} not found", dst);
+            log::error!("{:?} add_edge dst {:?} not found", self.kind, dst);
             return;
         };
         self.merge(*src, *dst);
     }
 
+    /// Connect an object to a road, if this kind of network follows it
+    pub fn connect_road(&mut self, obj: impl Into<NetworkObjectID>, road: &Road) {
+        if self.kind.follows(road) {
+            self.add_edge(obj, road.id);
+        }
+    }
+
     /// Remove an edge between two network objects (symmetric)
-    /// Must agree with the electricity_edge function
+    /// Must agree with the map_network_edges function
     /// Must be called _after_ removing the edge from the map
     pub fn remove_edge(
         &mut self,
@@ -163,11 +217,11 @@ impl ElectricityCache {
         self.graph.get_mut(dst).unwrap().retain(|v| v != src);
 
         let Some(src_net) = self.ids.get(src) else {
-            log::error!("electricity remove_edge src {:?} not found", src);
+            log::error!("{:?} remove_edge src {:?} not found", self.kind, src);
             return;
         };
         let Some(dst_net) = self.ids.get(dst) else {
-            log::error!("electricity remove_edge dst {:?} not found", dst);
+            log::error!("{:?} remove_edge dst {:?} not found", self.kind, dst);
             return;
         };
         debug_assert!(src_net == dst_net);
@@ -188,11 +242,11 @@ impl ElectricityCache {
 
     /// Gets the network id of a network object
     /// Note that network ids change all the time, so this should not be kept as state
-    pub fn net_id(&self, object_id: impl Into<NetworkObjectID>) -> Option<ElectricityNetworkID> {
+    pub fn net_id(&self, object_id: impl Into<NetworkObjectID>) -> Option<NetworkID> {
         self.ids.get(&object_id.into()).copied()
     }
 
-    pub fn networks(&self) -> impl Iterator<Item = &ElectricityNetwork> {
+    pub fn networks(&self) -> impl Iterator<Item = &Network> {
         self.networks.values()
     }
 
@@ -200,30 +254,28 @@ impl ElectricityCache {
         &self.graph
     }
 
-    /// Build the electricity cache from a map. Should give the same result as the current cache in the map
-    pub fn build(map: &Map) -> ElectricityCache {
-        let mut e = ElectricityCache::default();
+    /// Build a network cache of the given kind from a map. Should give the same result as the current cache in the map
+    pub fn build(map: &Map, kind: NetworkKind) -> NetworkCache {
+        let mut e = NetworkCache::new(kind);
 
-        for b in map.buildings.keys() {
-            e.add_object(NetworkObjectID::Building(b));
-        }
+        let mut objects = common::iter::chain((
+            map.buildings.keys().map(NetworkObjectID::Building),
+            map.intersections.keys().map(NetworkObjectID::Intersection),
+            map.roads.keys().map(NetworkObjectID::Road),
+        ))
+        .collect::<Vec<_>>();
 
-        for i in map.intersections.keys() {
-            e.add_object(NetworkObjectID::Intersection(i));
+        if kind.carries_power_lines() {
+            objects.extend(map.pylons.keys().map(NetworkObjectID::Pylon));
+            objects.extend(map.power_lines.keys().map(NetworkObjectID::PowerLine));
         }
 
-        for r in map.roads.keys() {
-            e.add_object(NetworkObjectID::Road(r));
+        for &n_id in &objects {
+            e.add_object(n_id);
         }
 
-        for n_id in common::iter::chain((
-            map.buildings.keys().map(NetworkObjectID::Building),
-            map.intersections.keys().map(NetworkObjectID::Intersection),
-            map.roads.keys().map(NetworkObjectID::Road),
-        )) {
-            for neighbor in
-                Self::map_electricity_edges(&map.roads, &map.buildings, &map.intersections, n_id)
-            {
+        for &n_id in &objects {
+            for neighbor in Self::map_network_edges(kind, map, n_id) {
                 e.add_edge(n_id, neighbor);
             }
         }
@@ -231,50 +283,80 @@ impl ElectricityCache {
         e
     }
 
-    /// Must agree with the map_eletricity_edges function in the end
+    /// Must agree with the map_network_edges function in the end
     fn edges(&self, id: NetworkObjectID) -> impl Iterator<Item = NetworkObjectID> + '_ {
         self.graph.get(&id).unwrap().iter().copied()
     }
 
     /// Iterate over the edges of a network object
     ///
-    /// Buildings -> 1 road
+    /// Buildings -> 1 road + n power lines
     /// Intersections -> n roads
     /// Roads -> 2 intersections + n buildings
-    fn map_electricity_edges<'a>(
-        roads: &'a Roads,
-        buildings: &'a Buildings,
-        intersections: &'a Intersections,
+    /// Pylons -> n power lines
+    /// Power lines -> 2 buildings or pylons
+    ///
+    /// Roads the network kind doesn't follow have no edges, and only electricity goes through power lines
+    fn map_network_edges(
+        kind: NetworkKind,
+        map: &Map,
         obj: NetworkObjectID,
-    ) -> impl Iterator<Item = NetworkObjectID> + 'a {
-        use itertools::Either::{Left, Right};
+    ) -> Vec<NetworkObjectID> {
+        let follows = |r: RoadID| map.roads.get(r).is_some_and(|road| kind.follows(road));
+        let lines = kind.carries_power_lines();
+
         match obj {
             NetworkObjectID::Building(b) => {
-                let Some(b) = buildings.get(b) else {
-                    return Left(Left(std::iter::empty()));
-                };
-                let Some(r) = b.connected_road else {
-                    return Left(Left(std::iter::empty()));
+                let Some(b) = map.buildings.get(b) else {
+                    return vec![];
                 };
-                Left(Right(Some(NetworkObjectID::Road(r)).into_iter()))
+                let mut edges = Vec::new();
+                if let Some(r) = b.connected_road.filter(|r| follows(*r)) {
+                    edges.push(NetworkObjectID::Road(r));
+                }
+                if lines {
+                    edges.extend(b.power_lines.iter().map(|l| NetworkObjectID::PowerLine(*l)));
+                }
+                edges
             }
             NetworkObjectID::Intersection(i) => {
-                let Some(i) = intersections.get(i) else {
-                    return Left(Left(std::iter::empty()));
+                let Some(i) = map.intersections.get(i) else {
+                    return vec![];
                 };
-                Right(Left(i.roads.iter().map(|v| NetworkObjectID::Road(*v))))
+                i.roads
+                    .iter()
+                    .filter(|r| follows(**r))
+                    .map(|r| NetworkObjectID::Road(*r))
+                    .collect()
             }
             NetworkObjectID::Road(r) => {
-                let Some(r) = roads.get(r) else {
-                    return Left(Left(std::iter::empty()));
-                };
-                Right(Right(common::iter::chain((
+                if !follows(r) {
+                    return vec![];
+                }
+                let r = &map.roads[r];
+                common::iter::chain((
                     Some(NetworkObjectID::Intersection(r.src)).into_iter(),
                     Some(NetworkObjectID::Intersection(r.dst)).into_iter(),
                     r.connected_buildings
                         .iter()
                         .map(|v| NetworkObjectID::Building(*v)),
-                ))))
+                ))
+                .collect()
+            }
+            NetworkObjectID::Pylon(p) => {
+                let Some(p) = map.pylons.get(p).filter(|_| lines) else {
+                    return vec![];
+                };
+                p.lines
+                    .iter()
+                    .map(|l| NetworkObjectID::PowerLine(*l))
+                    .collect()
+            }
+            NetworkObjectID::PowerLine(l) => {
+                let Some(l) = map.power_lines.get(l).filter(|_| lines) else {
+                    return vec![];
+                };
+                vec![l.src, l.dst]
             }
         }
     }
@@ -286,7 +368,7 @@ impl ElectricityCache {
     ///  - Find out the network_id of the new network (smallest object within it)
     ///  - Update the network_id of all objects in the new network if needed
     ///
-    fn merge(&mut self, mut a: ElectricityNetworkID, mut b: ElectricityNetworkID) {
+    fn merge(&mut self, mut a: NetworkID, mut b: NetworkID) {
         if a == b {
             return;
         }
@@ -298,7 +380,7 @@ impl ElectricityCache {
         let src = self.networks.remove(&a).unwrap();
         let dst = self.networks.get(&b).unwrap();
 
-        let new_id = ElectricityNetworkID(
+        let new_id = NetworkID(
             *dst.objects
                 .first()
                 .unwrap()
@@ -333,12 +415,12 @@ impl ElectricityCache {
     /// The two given ids are hints of elements in disjoint connected components
     fn split(
         &mut self,
-        network_to_split_id: ElectricityNetworkID,
+        network_to_split_id: NetworkID,
         id1: NetworkObjectID,
         id2: NetworkObjectID,
     ) {
         fn explore(
-            cache: &ElectricityCache,
+            cache: &NetworkCache,
             visited: &mut BTreeSet<NetworkObjectID>,
             id: NetworkObjectID,
             early_stop: NetworkObjectID, // we can stop when we find the old network since we won't touch it
@@ -364,15 +446,15 @@ impl ElectricityCache {
         explore(self, &mut visited1, id1, network_to_split_id.0);
         explore(self, &mut visited2, id2, network_to_split_id.0);
 
-        let network_1_id = ElectricityNetworkID(*visited1.first().unwrap());
-        let network_2_id = ElectricityNetworkID(*visited2.first().unwrap());
+        let network_1_id = NetworkID(*visited1.first().unwrap());
+        let network_2_id = NetworkID(*visited2.first().unwrap());
 
         debug_assert!(network_1_id != network_2_id, "path existed");
 
         fn apply_split(
-            cache: &mut ElectricityCache,
-            kept_network_id: ElectricityNetworkID,
-            new_network_id: ElectricityNetworkID,
+            cache: &mut NetworkCache,
+            kept_network_id: NetworkID,
+            new_network_id: NetworkID,
             new_network_objects: BTreeSet<NetworkObjectID>,
         ) {
             let kept_net = cache.networks.get_mut(&kept_network_id).unwrap();
@@ -386,7 +468,7 @@ impl ElectricityCache {
                 cache.ids.insert(*v, new_network_id);
             }
 
-            let new_network = ElectricityNetwork {
+            let new_network = Network {
                 id: new_network_id,
                 buildings: new_buildings,
                 objects: new_network_objects,
@@ -405,22 +487,26 @@ impl ElectricityCache {
     }
 }
 
-pub fn check_electricity_coherency(map: &Map) {
-    let mut e_from_map = ElectricityCache::build(map);
-    let mut e = map.electricity.clone();
-    for v in e.graph.values_mut() {
-        v.sort();
-    }
-    for v in e_from_map.graph.values_mut() {
-        v.sort();
+pub fn check_network_coherency(map: &Map) {
+    for cache in [&map.electricity, &map.water_mains] {
+        let mut e_from_map = NetworkCache::build(map, cache.kind);
+        let mut e = cache.clone();
+        for v in e.graph.values_mut() {
+            v.sort();
+        }
+        for v in e_from_map.graph.values_mut() {
+            v.sort();
+        }
+        assert_eq!(e, e_from_map);
     }
-    assert_eq!(e, e_from_map);
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::map::{check_electricity_coherency, ElectricityCache};
-    use crate::map::{BuildingKind, LanePatternBuilder, Map, MapProject, NetworkObjectID, RoadID};
+    use crate::map::{check_network_coherency, NetworkCache, NetworkKind};
+    use crate::map::{
+        BuildingKind, LanePatternBuilder, Map, MapProject, NetworkObjectID, ProjectKind, RoadID,
+    };
     use common::logger::MyLog;
     use geom::{vec3, Vec2, OBB};
     use prototypes::BuildingGen;
@@ -430,7 +516,7 @@ mod tests {
     fn test_loop_removal() {
         MyLog::init();
 
-        let mut e = ElectricityCache::default();
+        let mut e = NetworkCache::default();
 
         let mk_ent = |i| NetworkObjectID::Road(RoadID::from(KeyData::from_ffi(i)));
 
@@ -472,6 +558,7 @@ mod tests {
                 MapProject::ground(vec3(1.0, 0.0, 0.0)),
                 None,
                 &LanePatternBuilder::new().build(),
+                false,
             )
             .unwrap();
 
@@ -487,8 +574,8 @@ mod tests {
             )
             .unwrap();
 
-        let mut e = ElectricityCache::build(&m);
-        check_electricity_coherency(&m);
+        let mut e = NetworkCache::build(&m, NetworkKind::Electricity);
+        check_network_coherency(&m);
 
         assert_eq!(e.networks.len(), 1);
         assert_eq!(e.networks[&e.net_id(b).unwrap()].objects.len(), 4);
@@ -508,4 +595,41 @@ mod tests {
         assert_eq!(e.networks.len(), 1);
         assert_eq!(e.networks[&e.net_id(b).unwrap()].buildings.len(), 1);
     }
+
+    #[test]
+    fn test_water_mains_skip_rails() {
+        MyLog::init();
+        let mut m = Map::empty();
+
+        let (i, road) = m
+            .make_connection(
+                MapProject::ground(vec3(0.0, 0.0, 0.0)),
+                MapProject::ground(vec3(100.0, 0.0, 0.0)),
+                None,
+                &LanePatternBuilder::new().build(),
+                false,
+            )
+            .unwrap();
+
+        let (_, rail) = m
+            .make_connection(
+                MapProject {
+                    pos: vec3(100.0, 0.0, 0.0),
+                    kind: ProjectKind::Intersection(i),
+                },
+                MapProject::ground(vec3(100.0, 100.0, 0.0)),
+                None,
+                &LanePatternBuilder::new().rail(true).build(),
+                false,
+            )
+            .unwrap();
+        check_network_coherency(&m);
+
+        let elec = &m.electricity;
+        assert_eq!(elec.net_id(road), elec.net_id(rail));
+
+        let water = &m.water_mains;
+        assert_ne!(water.net_id(road), water.net_id(rail));
+        assert_eq!(water.net_id(road), water.net_id(i));
+    }
 }
@@ -1,10 +1,8 @@
 use crate::map::procgen::{gen_exterior_farm, gen_exterior_house, ColoredMesh};
-use crate::map::{
-    Buildings, ElectricityCache, Environment, LanePattern, RoadID, Roads, SpatialMap,
-};
+use crate::map::{Buildings, Environment, LanePattern, PowerLineID, RoadID, Roads, SpatialMap};
 use egui_inspect::debug_inspect_impl;
 use geom::{Color, Polygon, Vec2, Vec3, OBB};
-use prototypes::{BuildingGen, FreightStationPrototypeID, GoodsCompanyID};
+use prototypes::{BuildingGen, FreightStationPrototypeID, GoodsCompanyID, LeisurePrototypeID};
 use serde::{Deserialize, Serialize};
 use slotmapd::new_key_type;
 
@@ -19,6 +17,8 @@ pub enum BuildingKind {
     House,
     GoodsCompany(GoodsCompanyID),
     RailFreightStation(FreightStationPrototypeID),
+    /// Cinemas, parks and the like, where people go to relax
+    Leisure(LeisurePrototypeID),
     TrainStation,
     ExternalTrading,
 }
@@ -34,6 +34,18 @@ impl BuildingKind {
     pub fn is_cached_in_bkinds(&self) -> bool {
         matches!(self, BuildingKind::ExternalTrading)
     }
+
+    /// Name used by mod scripts and external tools, the prototype name for companies and stations
+    pub fn name(&self) -> String {
+        match self {
+            BuildingKind::House => "house".to_string(),
+            BuildingKind::GoodsCompany(id) => id.prototype().name.clone(),
+            BuildingKind::RailFreightStation(id) => id.prototype().name.clone(),
+            BuildingKind::Leisure(id) => id.prototype().name.clone(),
+            BuildingKind::TrainStation => "train-station".to_string(),
+            BuildingKind::ExternalTrading => "external-trading".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +79,41 @@ impl Zone {
     }
 }
 
+/// The highest density level a house can reach
+pub const MAX_DENSITY_LEVEL: u8 = 4;
+
+/// How dense a building is. Denser houses host more households and are taller.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Density {
+    /// 0 means the building is abandoned
+    pub level: u8,
+    /// Accumulates good (positive) or bad (negative) conditions, the level changes when it reaches 1 or -1
+    pub progress: f32,
+}
+
+impl Default for Density {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            progress: 0.0,
+        }
+    }
+}
+
+impl Density {
+    /// The number of households living in a house of this density
+    pub fn households(&self) -> usize {
+        match self.level {
+            0 => 0,
+            l => 1 << (l - 1),
+        }
+    }
+
+    pub fn is_abandoned(&self) -> bool {
+        self.level == 0
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Building {
     pub id: BuildingID,
@@ -77,13 +124,14 @@ pub struct Building {
     pub height: f32,
     pub zone: Option<Zone>,
     pub connected_road: Option<RoadID>,
+    pub density: Density,
+    pub power_lines: Vec<PowerLineID>,
 }
 
 impl Building {
     pub fn make(
         buildings: &mut Buildings,
         spatial_map: &mut SpatialMap,
-        electricity: &mut ElectricityCache,
         roads: &mut Roads,
         env: &Environment,
         obb: OBB,
@@ -92,6 +140,44 @@ impl Building {
         zone: Option<Zone>,
         mut connected_road: Option<RoadID>,
     ) -> Option<BuildingID> {
+        let density = Density::default();
+        let (mesh, door_pos, height) = Self::gen_mesh(env, obb, gen, density.level);
+
+        let b = buildings.insert_with_key(move |id| {
+            if let Some(r) = connected_road {
+                if let Some(r) = roads.get_mut(r) {
+                    r.connected_buildings.push(id);
+                } else {
+                    connected_road = None;
+                }
+            }
+
+            Self {
+                id,
+                mesh,
+                kind,
+                door_pos,
+                obb,
+                height,
+                zone,
+                connected_road,
+                density,
+                power_lines: Vec::new(),
+            }
+        });
+
+        spatial_map.insert(&buildings[b]);
+
+        Some(b)
+    }
+
+    /// Generates the mesh of a building, returns the mesh, the door position and the ground height
+    pub fn gen_mesh(
+        env: &Environment,
+        obb: OBB,
+        gen: BuildingGen,
+        level: u8,
+    ) -> (ColoredMesh, Vec3, f32) {
         let at = obb.center().z(env.height(obb.center()).unwrap_or(0.0));
         let axis = (obb.corners[1] - obb.corners[0]).normalize();
         let size = obb.corners[0].distance(obb.corners[1]);
@@ -99,7 +185,7 @@ impl Building {
         let r = common::rand::rand2(obb.center().x, obb.center().y).to_bits();
 
         let (mut mesh, door_pos) = match gen {
-            BuildingGen::House => gen_exterior_house(size, r as u64),
+            BuildingGen::House => gen_exterior_house(size, r as u64, level),
             BuildingGen::Farm => gen_exterior_farm(size, r as u64),
             BuildingGen::CenteredDoor {
                 vertical_factor, ..
@@ -129,32 +215,6 @@ impl Building {
             mesh.faces.push((walkway, Color::gray(0.4).into()));
         }
 
-        let b = buildings.insert_with_key(move |id| {
-            electricity.add_object(id);
-            if let Some(r) = connected_road {
-                electricity.add_edge(id, r);
-
-                if let Some(r) = roads.get_mut(r) {
-                    r.connected_buildings.push(id);
-                } else {
-                    connected_road = None;
-                }
-            }
-
-            Self {
-                id,
-                mesh,
-                kind,
-                door_pos,
-                obb,
-                height: at.z,
-                zone,
-                connected_road,
-            }
-        });
-
-        spatial_map.insert(&buildings[b]);
-
-        Some(b)
+        (mesh, door_pos, at.z)
     }
 }
@@ -1,4 +1,4 @@
-use crate::map::{Map, ProjectFilter, ProjectKind, RoadID, UpdateType};
+use crate::map::{Map, ProjectFilter, ProjectKind, RoadID, UpdateType, WATER_BUILD_MARGIN};
 use geom::Vec2;
 use geom::OBB;
 use geom::{Circle, Vec3};
@@ -10,10 +10,14 @@ new_key_type! {
     pub struct LotID;
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LotKind {
     Unassigned,
     Residential,
+    Commercial,
+    Industrial,
+    /// Accepts both residential and commercial buildings
+    Mixed,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -40,6 +44,10 @@ impl Lot {
 
         let shape = OBB::new(at.xy() + axis * size * 0.5, axis, size, size);
 
+        if map.environment.water.overlaps(&shape, WATER_BUILD_MARGIN) {
+            return None;
+        }
+
         let proj = map.project(shape.center().z0(), size * 0.5 - 0.5, ProjectFilter::ALL);
         if !matches!(proj.kind, ProjectKind::Ground) {
             return None;
@@ -0,0 +1,77 @@
+use crate::map::{BuildingID, NetworkObjectID};
+use geom::Vec3;
+use prototypes::Power;
+use serde::{Deserialize, Serialize};
+use slotmapd::new_key_type;
+
+new_key_type! {
+    pub struct PylonID;
+    pub struct PowerLineID;
+}
+
+/// Height at which power lines hang on pylons
+pub const PYLON_HEIGHT: f32 = 20.0;
+
+/// A pylon holds power lines anywhere on the map, away from roads and buildings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pylon {
+    pub id: PylonID,
+    /// Position of the foot of the pylon
+    pub pos: Vec3,
+    pub lines: Vec<PowerLineID>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerLineKind {
+    LowVoltage,
+    HighVoltage,
+}
+
+impl PowerLineKind {
+    /// The maximum power the line can carry
+    pub fn capacity(self) -> Power {
+        match self {
+            PowerLineKind::LowVoltage => Power::new(500_000),
+            PowerLineKind::HighVoltage => Power::new(5_000_000),
+        }
+    }
+
+    /// Price in bucks per meter of line
+    pub fn price_per_meter(self) -> f32 {
+        match self {
+            PowerLineKind::LowVoltage => 0.5,
+            PowerLineKind::HighVoltage => 2.0,
+        }
+    }
+}
+
+/// Where a new power line starts or ends
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum PowerLineEnd {
+    Building(BuildingID),
+    Pylon(PylonID),
+    /// A new pylon is placed at this position
+    Ground(Vec3),
+}
+
+/// A power line carries electricity between two buildings or pylons, independently of roads.
+/// Its ends are either [`NetworkObjectID::Building`] or [`NetworkObjectID::Pylon`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerLine {
+    pub id: PowerLineID,
+    pub kind: PowerLineKind,
+    pub src: NetworkObjectID,
+    pub dst: NetworkObjectID,
+    pub src_pos: Vec3,
+    pub dst_pos: Vec3,
+}
+
+impl PowerLine {
+    pub fn capacity(&self) -> Power {
+        self.kind.capacity()
+    }
+
+    pub fn length(&self) -> f32 {
+        self.src_pos.distance(self.dst_pos)
+    }
+}
@@ -7,7 +7,8 @@ use geom::{Vec2, Vec3};
 
 use crate::map::{
     BuildingID, Environment, Intersection, IntersectionID, Lane, LaneDirection, LaneID, LaneKind,
-    LanePattern, Lanes, ParkingSpots, Roads, SpatialMap, MAX_SLOPE, ROAD_Z_OFFSET,
+    LanePattern, Lanes, ParkingSpots, Roads, SpatialMap, BRIDGE_CLEARANCE, MAX_SLOPE,
+    ROAD_Z_OFFSET, TUNNEL_DEPTH, TUNNEL_MIN_DEPTH,
 };
 
 new_key_type! {
@@ -41,6 +42,9 @@ pub struct Road {
     pub points: PolyLine3,
     pub interfaced_points: PolyLine3,
     pub width: f32,
+    /// Whether the road was built as a tunnel, digging under the terrain between its two portals
+    /// even when the terrain doesn't force it
+    pub tunnel: bool,
 
     pub connected_buildings: Vec<BuildingID>,
 
@@ -75,6 +79,7 @@ impl Road {
         dst: &Intersection,
         segment: RoadSegmentKind,
         lane_pattern: &LanePattern,
+        tunnel: bool,
         env: &Environment,
         roads: &mut Roads,
         lanes: &mut Lanes,
@@ -87,6 +92,7 @@ impl Road {
             dst.pos,
             segment,
             lane_pattern.lanes().any(|(a, _, _)| a.is_rail()),
+            tunnel,
             env,
         );
 
@@ -97,6 +103,7 @@ impl Road {
             src_interface: 9.0,
             dst_interface: 9.0,
             width,
+            tunnel,
             lanes_forward: vec![],
             lanes_backward: vec![],
             interfaced_points: PolyLine3::new(vec![points.first()]),
@@ -211,6 +218,88 @@ impl Road {
         parking.clean_reuse();
     }
 
+    /// Rebuilds the lanes of the road in place to match the new pattern
+    /// Lanes of the same kind and direction are kept (in order) so that their ids stay valid,
+    /// the others are removed along with their parking spots.
+    /// Returns the removed lanes
+    pub fn set_pattern(
+        &mut self,
+        pattern: &LanePattern,
+        lanes: &mut Lanes,
+        parking: &mut ParkingSpots,
+        env: &Environment,
+    ) -> Vec<LaneID> {
+        let mut old: Vec<(LaneID, LaneKind, LaneDirection)> = self
+            .lanes_forward
+            .iter()
+            .rev()
+            .map(|&(id, kind)| (id, kind, LaneDirection::Forward))
+            .chain(
+                self.lanes_backward
+                    .iter()
+                    .map(|&(id, kind)| (id, kind, LaneDirection::Backward)),
+            )
+            .collect();
+
+        self.lanes_forward.clear();
+        self.lanes_backward.clear();
+        self.width = pattern.width();
+
+        let mut dist_from_bottom = 0.0;
+        for (lane_k, dir, limit) in pattern.lanes() {
+            let reused = old
+                .iter()
+                .position(|&(_, kind, d)| kind == lane_k && d == dir)
+                .map(|i| old.remove(i).0);
+
+            let id = match reused.and_then(|id| lanes.get_mut(id)) {
+                Some(lane) => {
+                    lane.speed_limit = limit;
+                    lane.dist_from_bottom = dist_from_bottom;
+                    lane.id
+                }
+                None => Lane::make(self, lanes, lane_k, limit, dir, dist_from_bottom),
+            };
+
+            match dir {
+                LaneDirection::Forward => self.lanes_forward.insert(0, (id, lane_k)),
+                LaneDirection::Backward => self.lanes_backward.push((id, lane_k)),
+            }
+
+            dist_from_bottom += lane_k.width();
+        }
+
+        let removed = old.into_iter().map(|(id, _, _)| id).collect::<Vec<_>>();
+        for &id in &removed {
+            parking.remove_spots(id);
+            lanes.remove(id);
+        }
+
+        self.update_lanes(lanes, parking, env);
+
+        removed
+    }
+
+    /// Runs the heightfinder again along the same path, e.g. so that the road becomes a bridge
+    /// when a lake or a river appears under it
+    pub fn update_heights(
+        &mut self,
+        lanes: &mut Lanes,
+        parking: &mut ParkingSpots,
+        env: &Environment,
+    ) {
+        let (points, _) = Self::heightfinder(
+            &PolyLine::new(self.points.iter().map(|v| v.xy()).collect::<Vec<_>>()),
+            self.points.first().z - ROAD_Z_OFFSET,
+            self.points.last().z - ROAD_Z_OFFSET,
+            MAX_SLOPE,
+            self.tunnel,
+            env,
+        );
+        self.points = points;
+        self.update_lanes(lanes, parking, env);
+    }
+
     pub fn length(&self) -> f32 {
         self.points.length()
     }
@@ -260,7 +349,8 @@ impl Road {
             .equipoints_dir(80.0, true)
             .filter_map(move |(pos, dir)| {
                 let h = env.true_height(pos.xy())?;
-                if (h - pos.z).abs() <= 2.0 {
+                // no pylons on the ground or in tunnels
+                if pos.z - h <= 2.0 {
                     return None;
                 }
                 Some(PylonPosition {
@@ -271,6 +361,36 @@ impl Road {
             })
     }
 
+    /// Returns the parts of the road that go through a tunnel, as distances along the interfaced points
+    pub fn tunnels(&self, env: &Environment) -> Vec<(f32, f32)> {
+        const STEP: f32 = 2.0;
+
+        let p = &self.interfaced_points;
+        let l = p.length();
+        let n = (l / STEP) as u32;
+
+        let mut tunnels = vec![];
+        let mut start = None;
+        for (i, (pos, _)) in p
+            .points_dirs_along((0..=n).map(|i| i as f32 * STEP).chain(std::iter::once(l)))
+            .enumerate()
+        {
+            let d = (i as f32 * STEP).min(l);
+            match (start, env.is_underground(pos.up(-ROAD_Z_OFFSET))) {
+                (None, true) => start = Some(d),
+                (Some(s), false) => {
+                    tunnels.push((s, d));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            tunnels.push((s, l));
+        }
+        tunnels
+    }
+
     pub fn points(&self) -> &PolyLine3 {
         &self.points
     }
@@ -292,6 +412,7 @@ impl Road {
             z_beg,
             z_end,
             MAX_SLOPE,
+            self.tunnel,
             env,
         );
 
@@ -303,17 +424,22 @@ impl Road {
     // The algorithm is as follow:
     // - First compute the terrain contour every meter
     // - Then find out which points are airborn (according to maxslope)
+    //   Lakes and rivers raise the contour so that the road goes over them on a bridge
     // - Then find the interface points where points become airborn
     // - Then linear interpolate the points between the interface points
     // - Then smooth out the result to avoid huge derivative changes
     // - Then simplify the result to avoid too many points
     //
+    // When tunnel is set, the road ignores the terrain and digs down from both ends to
+    // TUNNEL_DEPTH below the lowest natural terrain it goes under.
+    //
     // maxslope is the maximum meter of height difference per meter of distance (1.0 is a 45° slope)
     pub fn heightfinder(
         p: &PolyLine,
         start_height: f32,
         end_height: f32,
         maxslope: f32,
+        tunnel: bool,
         env: &Environment,
     ) -> (PolyLine3, Option<PointGenerateError>) {
         // first calculate the contour
@@ -322,6 +448,7 @@ impl Road {
         let mut points = Vec::with_capacity(contour.len());
 
         let mut height_error = false;
+        let mut lowest_natural = f32::INFINITY;
         for pos in std::iter::once(p.first())
             .chain(
                 p.points_dirs_along((1..p.length() as u32).map(|v| v as f32))
@@ -329,10 +456,15 @@ impl Road {
             )
             .chain(std::iter::once(p.last()))
         {
-            let h = env.height(pos).unwrap_or_else(|| {
+            let mut h = env.height(pos).unwrap_or_else(|| {
                 height_error = true;
                 0.0
             });
+            lowest_natural = lowest_natural.min(env.natural_height(pos).unwrap_or(h));
+            // lakes and rivers can only be crossed by bridges
+            if let Some(level) = env.water.level(pos) {
+                h = h.max(level + BRIDGE_CLEARANCE);
+            }
             contour.push(h);
             points.push(pos.z(h));
         }
@@ -340,6 +472,17 @@ impl Road {
         contour[0] = start_height;
         *contour.last_mut().unwrap() = end_height;
 
+        if tunnel {
+            let floor = lowest_natural - TUNNEL_DEPTH;
+            let last = contour.len() - 1;
+            for (i, h) in contour.iter_mut().enumerate() {
+                let from_start = start_height - maxslope * i as f32;
+                let from_end = end_height - maxslope * (last - i) as f32;
+                *h = floor.max(from_start).max(from_end);
+            }
+            return Self::finish_points(contour, points, height_error, false);
+        }
+
         // Calculate the expected road height at each point (linear interpolation)
         // This is used to determine which points can follow the terrain and which must be airborn
         let mut expected_road_height = vec![0.0; contour.len()];
@@ -380,6 +523,27 @@ impl Road {
             }
         }
 
+        // Then find out which points are buried: the terrain rises faster than the road can climb
+        // so it has to go through a tunnel. Same two passes, the road climbing at most maxslope
+        // per meter from each end. Buried points are then handled like airborn ones.
+        let mut buried = vec![false; contour.len()];
+
+        let mut cur_height = contour[0];
+        for i in 1..contour.len() {
+            cur_height = contour[i].clamp(cur_height - maxslope, cur_height + maxslope);
+            buried[i] = contour[i] - cur_height > TUNNEL_MIN_DEPTH;
+        }
+
+        let mut cur_height = contour[contour.len() - 1];
+        for i in (0..contour.len() - 1).rev() {
+            cur_height = contour[i].clamp(cur_height - maxslope, cur_height + maxslope);
+            buried[i] |= contour[i] - cur_height > TUNNEL_MIN_DEPTH;
+        }
+
+        for (a, b) in airborn.iter_mut().zip(buried) {
+            *a |= b;
+        }
+
         // Then find the interface points where points become airborn
         // To do that, we just find the points where airborn changes
 
@@ -465,6 +629,16 @@ impl Road {
         //    std::mem::swap(&mut smoothed, &mut contour);
         //}
 
+        Self::finish_points(contour, points, height_error, slope_was_too_steep)
+    }
+
+    /// Puts the points at the heights found by the heightfinder and simplifies them
+    fn finish_points(
+        contour: Vec<f32>,
+        mut points: Vec<Vec3>,
+        height_error: bool,
+        slope_was_too_steep: bool,
+    ) -> (PolyLine3, Option<PointGenerateError>) {
         for (h, v) in contour.into_iter().zip(points.iter_mut()) {
             v.z = h + ROAD_Z_OFFSET;
         }
@@ -489,12 +663,13 @@ impl Road {
         to: Vec3,
         segment: RoadSegmentKind,
         precise: bool,
+        tunnel: bool,
         env: &Environment,
     ) -> (PolyLine3, Option<PointGenerateError>) {
         let spline = match segment {
             RoadSegmentKind::Straight => {
                 let p = PolyLine::new(vec![from.xy(), to.xy()]);
-                return Self::heightfinder(&p, from.z, to.z, MAX_SLOPE, env);
+                return Self::heightfinder(&p, from.z, to.z, MAX_SLOPE, tunnel, env);
             }
             RoadSegmentKind::Curved((from_derivative, to_derivative)) => Spline {
                 from: from.xy(),
@@ -524,7 +699,7 @@ impl Road {
         }
         p.push(to.xy());
 
-        Self::heightfinder(&p, from.z, to.z, MAX_SLOPE, env)
+        Self::heightfinder(&p, from.z, to.z, MAX_SLOPE, tunnel, env)
     }
 
     pub fn interface_point(&self, id: IntersectionID) -> Vec3 {
@@ -27,7 +27,12 @@ impl ColoredMesh {
     }
 }
 
-pub fn gen_exterior_house(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
+/// Height added to a house for each level above the first
+const FLOOR_HEIGHT: f32 = 3.5;
+
+/// Generates a house with as many floors as its density level.
+/// Level 0 is an abandoned house: a single floor with faded walls.
+pub fn gen_exterior_house(size: f32, seed: u64, level: u8) -> (ColoredMesh, Vec2) {
     let mut retry_cnt = 0;
     'retry: loop {
         let mut ri = 0.0;
@@ -138,7 +143,7 @@ pub fn gen_exterior_house(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
         let mut roofs = ColoredMesh::default();
         let roof_col = LinearColor::from(crate::colors().roof_col);
 
-        let height = 4.0 + gen_range(0.0, 2.0);
+        let height = 4.0 + gen_range(0.0, 2.0) + FLOOR_HEIGHT * level.saturating_sub(1) as f32;
 
         for mut face in faces {
             if face.len() < 3 {
@@ -173,9 +178,14 @@ pub fn gen_exterior_house(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
             walls.push(b + d.z0() * 0.8 + Vec3::z(height));
         }
 
+        let mut wall_col = crate::colors().house_col;
+        if level == 0 {
+            wall_col = wall_col.adjust_luminosity(0.6);
+        }
+
         for (&a, &b, _) in geom::skeleton::window(&walls) {
             let face = vec![a, b, b.xy().z0(), a.xy().z0()];
-            roofs.faces.push((face, crate::colors().house_col.into()));
+            roofs.faces.push((face, wall_col.into()));
         }
 
         return (roofs, lowest_segment.middle());
@@ -188,7 +198,7 @@ pub fn gen_exterior_house(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
 ///     |
 pub fn gen_exterior_farm(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
     let h_size = 30.0;
-    let (mut mesh, mut door_pos) = gen_exterior_house(h_size, seed);
+    let (mut mesh, mut door_pos) = gen_exterior_house(h_size, seed, 1);
 
     let gen_range = |a, b| -> f32 { common::rand::rand(seed as f32 + 7.0) * (b - a) + a };
 
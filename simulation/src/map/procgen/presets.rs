@@ -122,6 +122,7 @@ pub fn load_parismap(map: &mut Map) {
                 .parking(true)
                 .build(),
             RoadSegmentKind::Straight,
+            false,
         )
         .unwrap();
     }
@@ -158,6 +159,7 @@ pub fn add_doublecircle(pos: Vec2, m: &mut Map) {
                 .parking(false)
                 .build(),
             RoadSegmentKind::Straight,
+            false,
         );
     }
     m.connect(
@@ -165,6 +167,7 @@ pub fn add_doublecircle(pos: Vec2, m: &mut Map) {
         first_circle[0],
         &LanePatternBuilder::new().one_way(true).build(),
         RoadSegmentKind::Straight,
+        false,
     );
 
     for x in second_circle.windows(2) {
@@ -176,6 +179,7 @@ pub fn add_doublecircle(pos: Vec2, m: &mut Map) {
                 .parking(false)
                 .build(),
             RoadSegmentKind::Straight,
+            false,
         );
     }
     m.connect(
@@ -183,6 +187,7 @@ pub fn add_doublecircle(pos: Vec2, m: &mut Map) {
         *second_circle.last().unwrap(), // Unwrap ok: n_points > 0
         &LanePatternBuilder::new().one_way(true).build(),
         RoadSegmentKind::Straight,
+        false,
     );
 
     for (a, b) in first_circle.into_iter().zip(second_circle) {
@@ -191,6 +196,7 @@ pub fn add_doublecircle(pos: Vec2, m: &mut Map) {
             b,
             &LanePatternBuilder::new().build(),
             RoadSegmentKind::Straight,
+            false,
         );
     }
 }
@@ -213,12 +219,36 @@ pub fn add_grid(m: &mut Map, pos: Vec2, size: u32, spacing: f32) {
     let pat = LanePatternBuilder::new().build();
     let l = size - 1;
     for x in 0..l {
-        m.connect(grid[l][x], grid[l][x + 1], &pat, RoadSegmentKind::Straight);
-        m.connect(grid[x][l], grid[x + 1][l], &pat, RoadSegmentKind::Straight);
+        m.connect(
+            grid[l][x],
+            grid[l][x + 1],
+            &pat,
+            RoadSegmentKind::Straight,
+            false,
+        );
+        m.connect(
+            grid[x][l],
+            grid[x + 1][l],
+            &pat,
+            RoadSegmentKind::Straight,
+            false,
+        );
 
         for y in 0..l {
-            m.connect(grid[y][x], grid[y][x + 1], &pat, RoadSegmentKind::Straight);
-            m.connect(grid[y][x], grid[y + 1][x], &pat, RoadSegmentKind::Straight);
+            m.connect(
+                grid[y][x],
+                grid[y][x + 1],
+                &pat,
+                RoadSegmentKind::Straight,
+                false,
+            );
+            m.connect(
+                grid[y][x],
+                grid[y + 1][x],
+                &pat,
+                RoadSegmentKind::Straight,
+                false,
+            );
         }
     }
 }
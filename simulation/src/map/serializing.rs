@@ -1,8 +1,8 @@
 use serde::{Deserialize, Serialize};
 
 use crate::map::{
-    BuildingID, Buildings, ElectricityCache, Environment, Intersections, Lanes, Lots, Map,
-    ParkingSpots, Roads, SpatialMap,
+    BuildingID, Buildings, Environment, Intersections, Lanes, Lots, Map, NetworkCache, NetworkKind,
+    ParkingSpots, PowerLines, Pylons, Roads, SpatialMap,
 };
 
 #[derive(Default, Serialize, Deserialize)]
@@ -15,6 +15,8 @@ pub(crate) struct SerializedMap {
     pub lots: Lots,
     pub environment: Environment,
     pub external_train_stations: Vec<BuildingID>,
+    pub pylons: Pylons,
+    pub power_lines: PowerLines,
 }
 
 impl From<&Map> for SerializedMap {
@@ -28,6 +30,8 @@ impl From<&Map> for SerializedMap {
             lots: m.lots.clone(),
             environment: m.environment.clone(),
             external_train_stations: m.external_train_stations.clone(),
+            pylons: m.pylons.clone(),
+            power_lines: m.power_lines.clone(),
         }
     }
 }
@@ -45,9 +49,12 @@ impl From<SerializedMap> for Map {
             parking: sel.parking,
             environment: sel.environment,
             external_train_stations: sel.external_train_stations,
+            pylons: sel.pylons,
+            power_lines: sel.power_lines,
             ..Self::empty()
         };
-        m.electricity = ElectricityCache::build(&m);
+        m.electricity = NetworkCache::build(&m, NetworkKind::Electricity);
+        m.water_mains = NetworkCache::build(&m, NetworkKind::WaterMains);
         m
     }
 }
@@ -12,6 +12,7 @@ use prototypes::{Tick, DELTA};
 
 use crate::map::procgen::heightmap;
 use crate::map::procgen::heightmap::tree_density;
+use crate::map::{Water, TUNNEL_MIN_DEPTH, WATER_CELLS_PER_CHUNK};
 
 pub type TerrainChunkID = common::ChunkID_512;
 
@@ -36,6 +37,7 @@ pub struct Tree {
 pub struct Environment {
     heightmap: Heightmap,
     pub trees: Grid<Tree, Vec2>,
+    pub water: Water,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -60,6 +62,7 @@ impl Environment {
         let mut me = Self {
             heightmap: Heightmap::new(w, h),
             trees: Grid::new(TREE_GRID_SIZE as i32),
+            water: Water::default(),
         };
         for y in 0..h {
             let chunks: Vec<_> = (0..w)
@@ -75,9 +78,20 @@ impl Environment {
                 }
             }
         }
+        me.update_water();
         me
     }
 
+    /// Recomputes lakes and rivers from the natural terrain
+    pub fn update_water(&mut self) {
+        let (w, h) = self.size();
+        self.water = Water::compute(
+            w as u32 * WATER_CELLS_PER_CHUNK,
+            h as u32 * WATER_CELLS_PER_CHUNK,
+            |p| self.heightmap.natural_height(p),
+        );
+    }
+
     /// Returns the height of the terrain at the given position in meters, capped at 0
     pub fn height(&self, pos: Vec2) -> Option<f32> {
         self.heightmap.height(pos).map(|x| x.max(0.0))
@@ -88,6 +102,17 @@ impl Environment {
         self.heightmap.height(pos)
     }
 
+    /// Returns the height of the terrain before roads and buildings flattened it, capped at 0
+    pub fn natural_height(&self, pos: Vec2) -> Option<f32> {
+        self.heightmap.natural_height(pos).map(|x| x.max(0.0))
+    }
+
+    /// Returns true if the given point is deep enough below the natural terrain to be in a tunnel
+    pub fn is_underground(&self, pos: Vec3) -> bool {
+        self.natural_height(pos.xy())
+            .map_or(false, |h| h - pos.z > TUNNEL_MIN_DEPTH)
+    }
+
     pub fn remove_trees_near(
         &mut self,
         obj: impl Intersect<Vec2>,
@@ -177,7 +202,7 @@ impl Environment {
         slope: Option<(Vec3, Vec3)>,
     ) -> Vec<TerrainChunkID> {
         let bbox = AABB::centered(center, Vec2::splat(radius * 2.0));
-        match kind {
+        let modified = match kind {
             TerraformKind::Elevation => self.terrain_apply(bbox, |pos| {
                 let dist = pos.xy().distance(center) / radius;
                 if dist >= 1.0 {
@@ -253,7 +278,12 @@ impl Environment {
                     .map(|(x, y)| TerrainChunkID::new_i16(x as i16, y as i16))
                     .collect()
             }
+        };
+
+        if !modified.is_empty() {
+            self.water.mark_dirty();
         }
+        modified
     }
 
     fn generate_chunk(&self, (x, y): (u16, u16)) -> Option<(Chunk, Vec<Tree>)> {
@@ -354,12 +384,14 @@ pub fn decode_pos(encoded: SmolTree, chunk: CellIdx) -> Vec2 {
 struct SerializedEnvironment {
     h: Heightmap,
     trees: Vec<(CellIdx, Vec<SmolTree>)>,
+    water: Water,
 }
 
 impl From<SerializedEnvironment> for Environment {
     fn from(ser: SerializedEnvironment) -> Self {
         let mut terrain = Environment {
             heightmap: ser.h,
+            water: ser.water,
             ..Self::default()
         };
 
@@ -378,6 +410,7 @@ impl From<&Environment> for SerializedEnvironment {
         let mut t = SerializedEnvironment {
             h: ter.heightmap.clone(),
             trees: Vec::new(),
+            water: ter.water.clone(),
         };
 
         let tree_cells = &ter.trees.storage().cells;
@@ -0,0 +1,270 @@
+//! Inland water: lakes and rivers derived from the heightmap.
+//!
+//! Water follows the natural terrain: each cell drains towards its lowest neighbour, depressions
+//! fill up into lakes until they overflow, and cells draining a large enough area become rivers.
+//! The sea (terrain below 0) is not stored here.
+
+use crate::map::TerrainChunkID;
+use geom::{vec2, Intersect, Shape, Vec2, AABB};
+use ordered_float::OrderedFloat;
+use prototypes::TICKS_PER_SECOND;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+/// Size of a water cell in meters
+pub const WATER_CELL_SIZE: f32 = 32.0;
+
+pub const WATER_CELLS_PER_CHUNK: u32 = (TerrainChunkID::SIZE_F32 / WATER_CELL_SIZE) as u32;
+
+/// How often water is recomputed when the terrain changed
+pub const WATER_UPDATE_PERIOD: u64 = TICKS_PER_SECOND * 2;
+
+/// Nothing can be built closer than this to a lake or a river
+pub const WATER_BUILD_MARGIN: f32 = 5.0;
+
+/// Height of roads above the water level when crossing a lake or a river
+pub const BRIDGE_CLEARANCE: f32 = 5.0;
+
+/// Depth under which a filled depression is considered a puddle and not a lake
+const LAKE_MIN_DEPTH: f32 = 1.0;
+
+/// Number of cells a cell must drain for it to become a river
+const RIVER_MIN_FLOW: f32 = 150.0;
+
+/// A cell only gathers rain when it drops at least this much towards its neighbour,
+/// so that flat land does not form rivers
+const MIN_DROP: f32 = 0.05;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WaterKind {
+    Lake,
+    River,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct WaterCell {
+    pub kind: WaterKind,
+    /// Height of the water surface
+    pub level: f32,
+    /// Number of upstream cells draining through this one
+    pub flow: f32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Water {
+    w: u32,
+    h: u32,
+    cells: BTreeMap<u32, WaterCell>, // indexed with (x + y * w), only cells with water
+    dirty: bool,
+}
+
+impl Water {
+    /// Computes lakes and rivers over a grid of `w` by `h` cells from the natural terrain height
+    pub fn compute(w: u32, h: u32, height: impl Fn(Vec2) -> Option<f32>) -> Self {
+        let n = (w * h) as usize;
+        let center = |i: usize| {
+            vec2((i % w as usize) as f32 + 0.5, (i / w as usize) as f32 + 0.5) * WATER_CELL_SIZE
+        };
+        let heights = (0..n)
+            .map(|i| height(center(i)).unwrap_or(0.0))
+            .collect::<Vec<_>>();
+
+        // priority flood from the borders and the sea: every cell drains into the cell it was
+        // reached from, and depressions are filled up to their spill height
+        let mut filled = heights.clone();
+        let mut receiver = (0..n).collect::<Vec<_>>();
+        let mut visited = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        let mut heap = BinaryHeap::new();
+
+        for i in 0..n {
+            let (x, y) = (i as u32 % w, i as u32 / w);
+            if x == 0 || y == 0 || x == w - 1 || y == h - 1 || heights[i] < 0.0 {
+                visited[i] = true;
+                heap.push(Reverse((OrderedFloat(heights[i]), i)));
+            }
+        }
+
+        while let Some(Reverse((_, i))) = heap.pop() {
+            order.push(i);
+            let (x, y) = (i as u32 % w, i as u32 / w);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w as usize),
+                (y + 1 < h).then(|| i + w as usize),
+            ];
+            for j in neighbours.into_iter().flatten() {
+                if visited[j] {
+                    continue;
+                }
+                visited[j] = true;
+                filled[j] = heights[j].max(filled[i]);
+                receiver[j] = i;
+                heap.push(Reverse((OrderedFloat(filled[j]), j)));
+            }
+        }
+
+        // flow accumulation, upstream cells first
+        let mut flow = (0..n)
+            .map(|i| {
+                let drop = filled[i] - filled[receiver[i]];
+                if drop > MIN_DROP || filled[i] > heights[i] {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<f32>>();
+        for &i in order.iter().rev() {
+            if receiver[i] != i {
+                flow[receiver[i]] += flow[i];
+            }
+        }
+
+        let mut cells = BTreeMap::new();
+        for i in 0..n {
+            if heights[i] < 0.0 {
+                continue;
+            }
+            let kind = if filled[i] - heights[i] > LAKE_MIN_DEPTH {
+                WaterKind::Lake
+            } else if flow[i] >= RIVER_MIN_FLOW {
+                WaterKind::River
+            } else {
+                continue;
+            };
+            cells.insert(
+                i as u32,
+                WaterCell {
+                    kind,
+                    level: filled[i],
+                    flow: flow[i],
+                },
+            );
+        }
+
+        Self {
+            w,
+            h,
+            cells,
+            dirty: false,
+        }
+    }
+
+    /// The terrain changed, water must be recomputed
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn cell(&self, p: Vec2) -> Option<&WaterCell> {
+        if p.x < 0.0 || p.y < 0.0 {
+            return None;
+        }
+        let x = (p.x / WATER_CELL_SIZE) as u32;
+        let y = (p.y / WATER_CELL_SIZE) as u32;
+        if x >= self.w || y >= self.h {
+            return None;
+        }
+        self.cells.get(&(x + y * self.w))
+    }
+
+    pub fn kind(&self, p: Vec2) -> Option<WaterKind> {
+        self.cell(p).map(|c| c.kind)
+    }
+
+    /// Height of the water surface at the given position, if there is a lake or a river
+    pub fn level(&self, p: Vec2) -> Option<f32> {
+        self.cell(p).map(|c| c.level)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (AABB, &WaterCell)> + '_ {
+        self.cells.iter().map(|(&i, c)| (self.cell_aabb(i), c))
+    }
+
+    /// Returns true if the shape is closer than `margin` to a lake or a river
+    pub fn overlaps<T: Shape + Intersect<AABB>>(&self, shape: &T, margin: f32) -> bool {
+        self.any_overlapping(shape, margin, |_, _| true)
+    }
+
+    /// Returns true if the shape overlaps a lake or a river that was not in `before`,
+    /// or whose level rose since then
+    pub fn overlaps_new<T: Shape + Intersect<AABB>>(&self, before: &Water, shape: &T) -> bool {
+        self.any_overlapping(shape, 0.0, |i, cell| {
+            before
+                .cells
+                .get(&i)
+                .map_or(true, |old| old.level < cell.level)
+        })
+    }
+
+    fn any_overlapping<T: Shape + Intersect<AABB>>(
+        &self,
+        shape: &T,
+        margin: f32,
+        f: impl Fn(u32, &WaterCell) -> bool,
+    ) -> bool {
+        let bbox = shape.bbox().expand(margin);
+        let ll = (bbox.ll / WATER_CELL_SIZE).floor();
+        let ur = (bbox.ur / WATER_CELL_SIZE).ceil();
+        for y in ll.y.max(0.0) as u32..(ur.y.max(0.0) as u32).min(self.h) {
+            for x in ll.x.max(0.0) as u32..(ur.x.max(0.0) as u32).min(self.w) {
+                let i = x + y * self.w;
+                let Some(cell) = self.cells.get(&i) else {
+                    continue;
+                };
+                if f(i, cell) && shape.intersects(&self.cell_aabb(i).expand(margin)) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn cell_aabb(&self, i: u32) -> AABB {
+        let ll = vec2((i % self.w) as f32, (i / self.w) as f32) * WATER_CELL_SIZE;
+        AABB::new_ll_size(ll, Vec2::splat(WATER_CELL_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Water, WaterKind, WATER_CELL_SIZE};
+    use geom::{vec2, AABB};
+
+    #[test]
+    fn lakes_and_rivers() {
+        // a bowl in the middle of a slope going down towards x = 0
+        let lake_center = vec2(40.0, 20.0) * WATER_CELL_SIZE;
+        let water = Water::compute(64, 40, |p| {
+            let bowl = (30.0 - 3.0 * p.distance(lake_center) / WATER_CELL_SIZE).max(0.0);
+            Some(p.x * 0.05 + 5.0 - bowl)
+        });
+
+        assert_eq!(water.kind(lake_center), Some(WaterKind::Lake));
+        let level = water.level(lake_center).unwrap();
+        let bottom = lake_center.x * 0.05 + 5.0 - 30.0;
+        assert!(level > bottom + 1.0);
+
+        // the lake overflows downhill into a river
+        let river = water
+            .cells()
+            .filter(|(_, c)| c.kind == WaterKind::River)
+            .count();
+        assert!(river > 0);
+
+        let far = AABB::new_ll_size(vec2(0.0, 0.0), vec2(10.0, 10.0));
+        let near = AABB::new_ll_size(lake_center, vec2(10.0, 10.0));
+        assert!(!water.overlaps(&far, 5.0));
+        assert!(water.overlaps(&near, 5.0));
+
+        // flat land has no water at all
+        let flat = Water::compute(32, 32, |_| Some(0.0));
+        assert_eq!(flat.cells().count(), 0);
+    }
+}
@@ -8,6 +8,8 @@ use std::collections::BTreeMap;
 pub struct BuildingInfo {
     pub owner: Option<SoulID>,
     pub inside: Vec<SoulID>,
+    /// The souls living in the building, the owner included
+    pub residents: Vec<SoulID>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
@@ -44,6 +46,29 @@ impl BuildingInfos {
         self.assignment.get(building).and_then(|x| x.owner)
     }
 
+    /// Adds a resident to the building, who becomes its owner if it has none
+    pub fn add_resident(&mut self, building: BuildingID, soul: SoulID) {
+        let b = unwrap_ret!(self.get_mut(building));
+        b.residents.push(soul);
+        if b.owner.is_none() {
+            self.set_owner(building, soul);
+        }
+    }
+
+    /// Removes a resident from the building, another resident becomes the owner if needed
+    pub fn remove_resident(&mut self, building: BuildingID, soul: SoulID) {
+        let b = unwrap_ret!(self.get_mut(building));
+        b.residents.retain(|x| *x != soul);
+        if b.owner != Some(soul) {
+            return;
+        }
+        b.owner = b.residents.first().copied();
+        self.owners.remove(&soul);
+        if let Some(owner) = self.owner(building) {
+            self.owners.insert(owner, building);
+        }
+    }
+
     pub fn get_in(&mut self, building: BuildingID, e: SoulID) {
         let b = unwrap_ret!(self.get_mut(building));
         if cfg!(debug_assertions) && b.inside.contains(&e) {
@@ -317,6 +317,7 @@ mod tests {
                 MapProject::ground(Vec3::x(100.0)),
                 None,
                 &LanePatternBuilder::new().rail(true).build(),
+                false,
             )
             .unwrap();
 
@@ -386,6 +387,7 @@ mod tests {
                 MapProject::ground(Vec3::x(100.0)),
                 None,
                 &LanePatternBuilder::new().one_way(true).rail(true).build(),
+                false,
             )
             .unwrap();
 
@@ -459,6 +461,7 @@ mod tests {
                 MapProject::ground(Vec3::x(100.0)),
                 None,
                 &LanePatternBuilder::new().one_way(true).rail(true).build(),
+                false,
             )
             .unwrap();
 
@@ -471,6 +474,7 @@ mod tests {
                 MapProject::ground(Vec3::new(200.0, 50.0, 0.0)),
                 None,
                 &LanePatternBuilder::new().one_way(true).rail(true).build(),
+                false,
             )
             .unwrap();
 
@@ -480,6 +484,7 @@ mod tests {
             MapProject::ground(Vec3::new(100.0, 10.0, 0.0)),
             None,
             &LanePatternBuilder::new().one_way(true).rail(true).build(),
+            false,
         )
         .unwrap();
 
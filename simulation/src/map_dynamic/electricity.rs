@@ -1,57 +1,152 @@
-use crate::map::{BuildingKind, ElectricityNetworkID, Map};
+use crate::events::{SimEvent, SimEvents};
+use crate::map::{BuildingID, BuildingKind, Map, Network, NetworkID, NetworkObjectID, PowerLineID};
 use crate::map_dynamic::BuildingInfos;
-use crate::utils::resources::Resources;
-use crate::{SoulID, World};
-use prototypes::Power;
+use crate::utils::scheduler::{SystemResources, SystemWorld};
+use crate::{CompanyEnt, SoulID};
+use prototypes::{
+    try_prototype, BatteryID, Energy, GameTime, Power, SolarPanelID, SolarPanelPrototype,
+    TICKS_PER_MINUTE, TICKS_PER_SECOND,
+};
 use serde::Deserialize;
 use slotmapd::__impl::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Ticks between two samples of the history of a network
+pub const FLOW_HISTORY_PERIOD: u64 = TICKS_PER_MINUTE * 10;
+
+/// Number of samples kept in the history of a network, a whole day
+pub const FLOW_HISTORY_SIZE: usize = 144;
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct ElectricityFlow {
-    flowmap: BTreeMap<ElectricityNetworkID, NetworkFlow>,
+    flowmap: BTreeMap<NetworkID, NetworkFlow>,
+    /// Power going through each power line
+    line_flows: BTreeMap<PowerLineID, Power>,
+    /// Power lines carrying their capacity while their other side still lacks power
+    overloaded: BTreeSet<PowerLineID>,
+    /// Buildings cut off from enough power, behind overloaded lines or in a network lacking power
+    blacked_out: BTreeSet<BuildingID>,
+    /// Producers of the networks in a blackout, which identify the blackout across map edits
+    dark_producers: BTreeSet<BuildingID>,
+    /// Producers of the networks that produced power at least once, only their blackouts are announced
+    powered_producers: BTreeSet<BuildingID>,
+    /// Energy stored in each battery
+    battery_charge: BTreeMap<BuildingID, Energy>,
+    /// Past flows of each network, oldest first, sampled every [`FLOW_HISTORY_PERIOD`]
+    history: BTreeMap<NetworkID, VecDeque<NetworkFlow>>,
 }
 
 impl ElectricityFlow {
-    pub fn blackout(&self, network: ElectricityNetworkID) -> bool {
+    pub fn blackout(&self, network: NetworkID) -> bool {
         self.flowmap
             .get(&network)
             .map(|f| f.blackout)
             .unwrap_or(false)
     }
 
-    pub fn network_stats(&self, network: ElectricityNetworkID) -> NetworkFlow {
-        self.flowmap.get(&network).cloned().unwrap_or(NetworkFlow {
-            consumed_power: Power::ZERO,
-            produced_power: Power::ZERO,
-            blackout: false,
-        })
+    /// Whether the building doesn't get enough power.
+    /// Only part of a network can be in a blackout when its power lines are overloaded.
+    pub fn building_blackout(&self, building: BuildingID) -> bool {
+        self.blacked_out.contains(&building)
+    }
+
+    pub fn network_stats(&self, network: NetworkID) -> NetworkFlow {
+        self.flowmap.get(&network).cloned().unwrap_or_default()
+    }
+
+    /// The current flow of every network
+    pub fn networks(&self) -> impl Iterator<Item = (NetworkID, &NetworkFlow)> + '_ {
+        self.flowmap.iter().map(|(id, f)| (*id, f))
+    }
+
+    pub fn line_flow(&self, line: PowerLineID) -> Power {
+        self.line_flows.get(&line).copied().unwrap_or(Power::ZERO)
+    }
+
+    pub fn is_overloaded(&self, line: PowerLineID) -> bool {
+        self.overloaded.contains(&line)
+    }
+
+    pub fn overloaded_lines(&self) -> impl Iterator<Item = PowerLineID> + '_ {
+        self.overloaded.iter().copied()
+    }
+
+    pub fn battery_charge(&self, building: BuildingID) -> Energy {
+        self.battery_charge
+            .get(&building)
+            .copied()
+            .unwrap_or(Energy::ZERO)
+    }
+
+    /// The flows of the network over the last day, oldest first
+    pub fn history(&self, network: NetworkID) -> impl Iterator<Item = &NetworkFlow> + '_ {
+        self.history.get(&network).into_iter().flatten()
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct NetworkFlow {
     pub consumed_power: Power,
     pub produced_power: Power,
-    /// Whether the network is in a blackout
+    /// Power that could not go through overloaded power lines
+    pub undelivered_power: Power,
+    /// Power given by the batteries, negative when they are charging
+    pub battery_power: Power,
+    /// Energy stored in the batteries of the network
+    pub stored_energy: Energy,
+    pub storage_capacity: Energy,
+    /// Whether part of the network is in a blackout
     pub blackout: bool,
 }
 
+/// State of a battery while dispatching the power of its network
+struct BatteryState {
+    capacity: Energy,
+    max_power: Power,
+    charge: Energy,
+}
+
 /// Compute the electricity flow of the map and store it in the [`ElectricityFlow`] resource
 /// All producing buildings will produce power, and all consuming buildings will consume power
-/// If a network produces less power than it consumes, a blackout will occur
-pub fn electricity_flow_system(world: &mut World, resources: &mut Resources) {
+/// Solar panels only produce during the day, following the sun
+/// Power lines carry the power between their two sides, up to their capacity
+/// Batteries store the surplus of their network and give it back when it lacks power
+/// The buildings of a part of a network that gets less power than it consumes are in a blackout
+/// and [`SimEvent::BlackoutStarted`] is emitted, if the network has already produced power
+pub fn electricity_flow_system(world: &mut SystemWorld, resources: &SystemResources) {
     profiling::scope!("map_dynamic::electricity_flow");
 
     let map = resources.read::<Map>();
     let binfos = resources.read::<BuildingInfos>();
+    let time = resources.read::<GameTime>();
     let mut flow = resources.write::<ElectricityFlow>();
 
+    let sun = SolarPanelPrototype::sun_intensity(time.daytime.daysec());
+
+    let old_dark_producers = std::mem::take(&mut flow.dark_producers);
+    let old_powered_producers = std::mem::take(&mut flow.powered_producers);
+    let mut announcements = Vec::new();
+
     flow.flowmap.clear();
+    flow.line_flows.clear();
+    flow.overloaded.clear();
+    flow.blacked_out.clear();
+    let old_charges = std::mem::take(&mut flow.battery_charge);
+
+    let mut networks_with_lines = BTreeSet::new();
+    for line in map.power_lines().keys() {
+        if let Some(net_id) = map.electricity.net_id(line) {
+            networks_with_lines.insert(net_id);
+        }
+    }
 
     for network in map.electricity.networks.values() {
         let mut consumed_power: Power = Power::ZERO;
         let mut produced_power: Power = Power::ZERO;
+        let has_lines = networks_with_lines.contains(&network.id);
+        let mut balance: BTreeMap<BuildingID, Power> = BTreeMap::new();
+        let mut batteries: Vec<(BuildingID, BatteryState)> = Vec::new();
+        let mut producers: Vec<BuildingID> = Vec::new();
 
         for building in network.buildings.iter() {
             let building = map.buildings.get(*building).unwrap();
@@ -59,21 +154,59 @@ pub fn electricity_flow_system(world: &mut World, resources: &mut Resources) {
             match building.kind {
                 BuildingKind::House => {
                     consumed_power += Power::new(100);
+                    if has_lines {
+                        balance.insert(building.id, -Power::new(100));
+                    }
                 }
                 BuildingKind::GoodsCompany(comp) => {
+                    if let Some(battery) = try_prototype(BatteryID::from(comp)) {
+                        producers.push(building.id);
+                        batteries.push((
+                            building.id,
+                            BatteryState {
+                                capacity: battery.capacity,
+                                max_power: battery.max_power,
+                                charge: old_charges
+                                    .get(&building.id)
+                                    .copied()
+                                    .unwrap_or(Energy::ZERO)
+                                    .min(battery.capacity),
+                            },
+                        ));
+                        continue;
+                    }
+
                     let proto = comp.prototype();
+                    if proto.power_production.is_some() {
+                        producers.push(building.id);
+                    }
 
                     let Some(SoulID::GoodsCompany(owner)) = binfos.owner(building.id) else {
                         continue;
                     };
 
-                    let Some(ent) = world.companies.get(owner) else {
+                    let Some(ent) = world.store::<CompanyEnt>().get(owner) else {
                         continue;
                     };
                     let productivity = ent.raw_productivity(proto, building.zone.as_ref()) as f64;
 
-                    consumed_power += proto.power_consumption.unwrap_or(Power::ZERO) * productivity;
-                    produced_power += proto.power_production.unwrap_or(Power::ZERO) * productivity;
+                    let consumed = proto.power_consumption.unwrap_or(Power::ZERO) * productivity;
+                    let mut produced = proto.power_production.unwrap_or(Power::ZERO) * productivity;
+                    if try_prototype(SolarPanelID::from(comp)).is_some() {
+                        produced = produced * sun;
+                    }
+                    consumed_power += consumed;
+                    produced_power += produced;
+                    if has_lines {
+                        balance.insert(building.id, produced - consumed);
+                    }
+                }
+                BuildingKind::Leisure(id) => {
+                    let consumed = id.prototype().power_consumption.unwrap_or(Power::ZERO);
+                    consumed_power += consumed;
+                    if has_lines {
+                        balance.insert(building.id, Power::ZERO - consumed);
+                    }
                 }
                 BuildingKind::RailFreightStation(_) => {}
                 BuildingKind::TrainStation => {}
@@ -81,13 +214,608 @@ pub fn electricity_flow_system(world: &mut World, resources: &mut Resources) {
             }
         }
 
+        let regions = if has_lines {
+            let routing = route_power(&map, network, &balance);
+            flow.line_flows.extend(routing.flows);
+            flow.overloaded.extend(routing.overloaded);
+            routing.regions
+        } else {
+            vec![Region {
+                buildings: network.buildings.iter().copied().collect(),
+                balance: produced_power - consumed_power,
+            }]
+        };
+
+        let settlement = settle(
+            &regions,
+            batteries.iter_mut().map(|(_, b)| b),
+            1.0 / TICKS_PER_SECOND as f64,
+        );
+        let blackout = !settlement.blacked_out.is_empty();
+        flow.blacked_out.extend(settlement.blacked_out);
+
+        // networks are renumbered on every merge or split, so blackouts are followed through
+        // their producers. A network that never produced power, like a new power plant without
+        // workers yet, never had power to lose so it is not announced.
+        let has_produced = produced_power > Power::ZERO
+            || producers.iter().any(|p| old_powered_producers.contains(p));
+        if has_produced {
+            flow.powered_producers.extend(producers.iter().copied());
+        }
+        if let Some(&first_producer) = producers.first().filter(|_| has_produced) {
+            let was_dark = producers.iter().any(|p| old_dark_producers.contains(p));
+            match (was_dark, blackout) {
+                (false, true) => announcements.push(SimEvent::BlackoutStarted(first_producer)),
+                (true, false) => announcements.push(SimEvent::BlackoutEnded(first_producer)),
+                _ => {}
+            }
+            if blackout {
+                flow.dark_producers.extend(producers);
+            }
+        }
+
+        let mut stored_energy = Energy::ZERO;
+        let mut storage_capacity = Energy::ZERO;
+        for (id, battery) in batteries {
+            stored_energy += battery.charge;
+            storage_capacity += battery.capacity;
+            flow.battery_charge.insert(id, battery.charge);
+        }
+
         flow.flowmap.insert(
             network.id,
             NetworkFlow {
                 consumed_power,
                 produced_power,
-                blackout: consumed_power > produced_power,
+                undelivered_power: settlement.undelivered_power,
+                battery_power: settlement.battery_power,
+                stored_energy,
+                storage_capacity,
+                blackout,
             },
         );
     }
+
+    let mut events = resources.write::<SimEvents>();
+    for event in announcements {
+        events.push(event);
+    }
+    drop(events);
+
+    if time.tick.0 % FLOW_HISTORY_PERIOD == 0 {
+        let flow = &mut *flow;
+        flow.history
+            .retain(|net_id, _| flow.flowmap.contains_key(net_id));
+        for (net_id, net_flow) in &flow.flowmap {
+            let history = flow.history.entry(*net_id).or_default();
+            if history.len() >= FLOW_HISTORY_SIZE {
+                history.pop_front();
+            }
+            history.push_back(net_flow.clone());
+        }
+    }
+}
+
+/// Charges the batteries with the surplus of power of their network, or discharges them to cover
+/// its deficit, one after the other, during `seconds` (game) seconds.
+/// Returns the power given by the batteries, negative when they are charging.
+fn dispatch_batteries<'a>(
+    surplus: Power,
+    batteries: impl Iterator<Item = &'a mut BatteryState>,
+    seconds: f64,
+) -> Power {
+    let mut remaining = surplus;
+    let mut given = Power::ZERO;
+
+    for battery in batteries {
+        if remaining > Power::ZERO {
+            let room = (battery.capacity - battery.charge).to_power(seconds);
+            let p = remaining.min(battery.max_power).min(room);
+            battery.charge += Energy::from_power(p, seconds);
+            remaining -= p;
+            given -= p;
+        } else if remaining < Power::ZERO {
+            let available = battery.charge.to_power(seconds);
+            let p = (-remaining).min(battery.max_power).min(available);
+            battery.charge -= Energy::from_power(p, seconds);
+            remaining += p;
+            given += p;
+        }
+        battery.charge = battery.charge.clamp(Energy::ZERO, battery.capacity);
+    }
+
+    given
+}
+
+/// Part of a network cut off from the rest by overloaded power lines
+struct Region {
+    buildings: Vec<BuildingID>,
+    /// Production - consumption of the region, including what the overloaded lines bring in
+    balance: Power,
+}
+
+/// How the power of a network goes through its power lines
+struct Routing {
+    /// Power carried by each power line, at most its capacity
+    flows: Vec<(PowerLineID, Power)>,
+    /// Lines carrying their capacity while the other side still lacks power
+    overloaded: Vec<PowerLineID>,
+    regions: Vec<Region>,
+}
+
+/// An arc of the flow graph, undirected links are two arcs that are each other's reverse
+struct FlowArc {
+    to: usize,
+    /// Power that can still be sent through the arc
+    residual: i64,
+    rev: usize,
+}
+
+/// Adds the arcs a -> b and b -> a, returning where the first one is
+fn add_link(
+    arcs: &mut [Vec<FlowArc>],
+    a: usize,
+    b: usize,
+    forward: i64,
+    backward: i64,
+) -> (usize, usize) {
+    let (ia, ib) = (arcs[a].len(), arcs[b].len());
+    arcs[a].push(FlowArc {
+        to: b,
+        residual: forward,
+        rev: ib,
+    });
+    arcs[b].push(FlowArc {
+        to: a,
+        residual: backward,
+        rev: ia,
+    });
+    (a, ia)
+}
+
+/// Routes the power balance (production - consumption) of the buildings of the network through
+/// its power lines.
+///
+/// Roads and the links between buildings and roads carry any power, so the objects they link
+/// are grouped first and only the power lines between groups are limited by their capacity.
+/// The power is then routed as a maximum flow from the groups with a surplus to the groups lacking
+/// power, so parallel lines share the load.
+/// When some groups still lack power, the lines that are full on the way to them are overloaded
+/// and cut the network in regions that each have to balance on their own.
+fn route_power(map: &Map, network: &Network, balance: &BTreeMap<BuildingID, Power>) -> Routing {
+    let graph = map.electricity.graph();
+
+    // flood fill the objects linked without power lines
+    let mut group_of: BTreeMap<NetworkObjectID, usize> = BTreeMap::new();
+    let mut group_buildings: Vec<Vec<BuildingID>> = Vec::new();
+    let mut lines = Vec::new();
+    let mut stack = Vec::new();
+    for &obj in &network.objects {
+        if let NetworkObjectID::PowerLine(line) = obj {
+            lines.push(line);
+            continue;
+        }
+        if group_of.contains_key(&obj) {
+            continue;
+        }
+        let group = group_buildings.len();
+        group_buildings.push(vec![]);
+        group_of.insert(obj, group);
+        stack.push(obj);
+        while let Some(obj) = stack.pop() {
+            if let NetworkObjectID::Building(b) = obj {
+                group_buildings[group].push(b);
+            }
+            for &n in graph.get(&obj).into_iter().flatten() {
+                if !matches!(n, NetworkObjectID::PowerLine(_)) && !group_of.contains_key(&n) {
+                    group_of.insert(n, group);
+                    stack.push(n);
+                }
+            }
+        }
+    }
+    for buildings in &mut group_buildings {
+        buildings.sort();
+    }
+
+    let group_balance: Vec<Power> = group_buildings
+        .iter()
+        .map(|buildings| {
+            buildings
+                .iter()
+                .filter_map(|b| balance.get(b))
+                .copied()
+                .sum()
+        })
+        .collect();
+
+    // flow graph between the groups, with a source giving the surpluses and a sink taking the
+    // deficits
+    let n_groups = group_buildings.len();
+    let (source, sink) = (n_groups, n_groups + 1);
+    let mut arcs: Vec<Vec<FlowArc>> = (0..n_groups + 2).map(|_| Vec::new()).collect();
+
+    // each line is the arc from its source group, or None if it links a group to itself
+    let line_arcs: Vec<Option<(usize, usize)>> = lines
+        .iter()
+        .map(|&line| {
+            let l = &map.power_lines()[line];
+            let a = *group_of.get(&l.src)?;
+            let b = *group_of.get(&l.dst)?;
+            if a == b {
+                return None;
+            }
+            let capacity = l.capacity().0;
+            Some(add_link(&mut arcs, a, b, capacity, capacity))
+        })
+        .collect();
+
+    let mut demand = 0;
+    for (group, &p) in group_balance.iter().enumerate() {
+        if p > Power::ZERO {
+            add_link(&mut arcs, source, group, p.0, 0);
+        } else if p < Power::ZERO {
+            add_link(&mut arcs, group, sink, -p.0, 0);
+            demand -= p.0;
+        }
+    }
+
+    // Edmonds-Karp: augment along the shortest paths with some room left
+    let mut delivered = 0;
+    let mut reached = vec![None; arcs.len()];
+    loop {
+        reached.fill(None);
+        reached[source] = Some((source, 0));
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for (i, arc) in arcs[node].iter().enumerate() {
+                if arc.residual > 0 && reached[arc.to].is_none() {
+                    reached[arc.to] = Some((node, i));
+                    queue.push_back(arc.to);
+                }
+            }
+        }
+        if reached[sink].is_none() {
+            break;
+        }
+
+        let mut room = i64::MAX;
+        let mut node = sink;
+        while node != source {
+            let (prev, i) = reached[node].unwrap();
+            room = room.min(arcs[prev][i].residual);
+            node = prev;
+        }
+        let mut node = sink;
+        while node != source {
+            let (prev, i) = reached[node].unwrap();
+            arcs[prev][i].residual -= room;
+            let rev = arcs[prev][i].rev;
+            arcs[node][rev].residual += room;
+            node = prev;
+        }
+        delivered += room;
+    }
+
+    // after the last search, the groups still reached from the source are on the supply side
+    // of the lines limiting the flow
+    let mut flows = Vec::with_capacity(lines.len());
+    let mut overloaded = Vec::new();
+    let mut transfers = Vec::new();
+    let mut linked: Vec<Vec<usize>> = vec![Vec::new(); n_groups];
+    for (&line, &arc) in lines.iter().zip(&line_arcs) {
+        let Some((a, i)) = arc else {
+            flows.push((line, Power::ZERO));
+            continue;
+        };
+        let b = arcs[a][i].to;
+        let capacity = map.power_lines()[line].capacity().0;
+        let from_a = capacity - arcs[a][i].residual;
+        flows.push((line, Power(from_a.abs())));
+
+        if delivered < demand && reached[a].is_some() != reached[b].is_some() {
+            overloaded.push(line);
+            transfers.push((a, b, Power(from_a)));
+        } else {
+            linked[a].push(b);
+            linked[b].push(a);
+        }
+    }
+
+    // the groups still linked by lines that are not overloaded balance together
+    let mut region_of_group = vec![usize::MAX; n_groups];
+    let mut regions = Vec::new();
+    for start in 0..n_groups {
+        if region_of_group[start] != usize::MAX {
+            continue;
+        }
+        let region = regions.len();
+        let mut buildings = Vec::new();
+        let mut region_balance = Power::ZERO;
+        region_of_group[start] = region;
+        let mut to_visit = vec![start];
+        while let Some(group) = to_visit.pop() {
+            buildings.extend_from_slice(&group_buildings[group]);
+            region_balance += group_balance[group];
+            for &other in &linked[group] {
+                if region_of_group[other] == usize::MAX {
+                    region_of_group[other] = region;
+                    to_visit.push(other);
+                }
+            }
+        }
+        regions.push(Region {
+            buildings,
+            balance: region_balance,
+        });
+    }
+    for (a, b, transfer) in transfers {
+        regions[region_of_group[a]].balance -= transfer;
+        regions[region_of_group[b]].balance += transfer;
+    }
+
+    Routing {
+        flows,
+        overloaded,
+        regions,
+    }
+}
+
+/// How the power of a network was shared between its regions
+struct Settlement {
+    /// Power that could not go through the overloaded power lines
+    undelivered_power: Power,
+    /// Power given by the batteries, negative when they are charging
+    battery_power: Power,
+    blacked_out: Vec<BuildingID>,
+}
+
+/// Covers the deficit of the regions of a network with its batteries, or charges them with the
+/// surplus, during `seconds` (game) seconds.
+/// Batteries are not routed through power lines, they directly balance their network.
+/// The regions still lacking power are in a blackout.
+fn settle<'a>(
+    regions: &[Region],
+    batteries: impl Iterator<Item = &'a mut BatteryState>,
+    seconds: f64,
+) -> Settlement {
+    let deficit: Power = regions.iter().map(|r| (-r.balance).max(Power::ZERO)).sum();
+    let surplus: Power = regions.iter().map(|r| r.balance.max(Power::ZERO)).sum();
+
+    let battery_power = if deficit > Power::ZERO {
+        dispatch_batteries(-deficit, batteries, seconds)
+    } else {
+        dispatch_batteries(surplus, batteries, seconds)
+    };
+
+    let mut remaining = battery_power.max(Power::ZERO);
+    let mut blacked_out = Vec::new();
+    for region in regions {
+        if region.balance >= Power::ZERO {
+            continue;
+        }
+        if remaining >= -region.balance {
+            remaining += region.balance;
+            continue;
+        }
+        blacked_out.extend_from_slice(&region.buildings);
+    }
+
+    Settlement {
+        // without the power lines, the surplus of some regions would cover the others
+        undelivered_power: deficit.min(surplus),
+        battery_power,
+        blacked_out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch_batteries, route_power, settle, BatteryState};
+    use crate::map::{check_network_coherency, BuildingKind, Map, PowerLineEnd, PowerLineKind};
+    use common::logger::MyLog;
+    use geom::{vec2, vec3, Vec2, OBB};
+    use prototypes::{BuildingGen, Energy, Power, SolarPanelPrototype, SECONDS_PER_HOUR};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn power_line_flow() {
+        MyLog::init();
+        let mut m = Map::empty();
+
+        let mut mk_building = |pos: Vec2| {
+            m.build_special_building(
+                &OBB::new(pos, vec2(1.0, 0.0), 10.0, 10.0),
+                BuildingKind::ExternalTrading,
+                BuildingGen::NoWalkway { door_pos: pos },
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let plant = mk_building(vec2(0.0, 0.0));
+        let city = mk_building(vec2(1000.0, 0.0));
+
+        let elec = &m.electricity;
+        assert_ne!(elec.net_id(plant), elec.net_id(city));
+
+        let a = m
+            .build_power_line(
+                PowerLineEnd::Building(plant),
+                PowerLineEnd::Ground(vec3(500.0, 0.0, 0.0)),
+                PowerLineKind::LowVoltage,
+            )
+            .unwrap();
+        let pylon = m.power_lines()[a].dst;
+        let crate::map::NetworkObjectID::Pylon(pylon) = pylon else {
+            panic!("a pylon should have been placed");
+        };
+        let b = m
+            .build_power_line(
+                PowerLineEnd::Pylon(pylon),
+                PowerLineEnd::Building(city),
+                PowerLineKind::LowVoltage,
+            )
+            .unwrap();
+        check_network_coherency(&m);
+
+        let net_id = m.electricity.net_id(plant).unwrap();
+        assert_eq!(m.electricity.net_id(city), Some(net_id));
+
+        let network = m.electricity.networks().find(|n| n.id == net_id).unwrap();
+        let settle_city = |city_power: i64| {
+            let balance = BTreeMap::from([
+                (plant, Power::new(1_000_000)),
+                (city, Power::new(-city_power)),
+            ]);
+            let routing = route_power(&m, network, &balance);
+            let settlement = settle(&routing.regions, std::iter::empty(), 1.0);
+            (routing, settlement)
+        };
+
+        // the lines can carry the whole consumption of the city
+        let (routing, settlement) = settle_city(400_000);
+        assert_eq!(routing.flows.len(), 2);
+        for &(line, flow) in &routing.flows {
+            assert!(line == a || line == b);
+            assert_eq!(flow, Power::new(400_000));
+        }
+        assert_eq!(routing.regions.len(), 1);
+        assert_eq!(settlement.undelivered_power, Power::ZERO);
+        assert!(settlement.blacked_out.is_empty());
+
+        // the city gets 500kW out of 600kW through the lines, the plant has enough power
+        // but only the city is in a blackout, the missing power is only counted once
+        let (routing, settlement) = settle_city(600_000);
+        for &(_, flow) in &routing.flows {
+            assert_eq!(flow, Power::new(500_000));
+        }
+        assert_eq!(routing.overloaded, vec![a]);
+        assert_eq!(routing.regions.len(), 2);
+        assert_eq!(settlement.undelivered_power, Power::new(100_000));
+        assert_eq!(settlement.blacked_out, vec![city]);
+
+        m.remove_power_line(b);
+        check_network_coherency(&m);
+        assert_ne!(m.electricity.net_id(plant), m.electricity.net_id(city));
+        assert!(m.pylons().contains_key(pylon));
+
+        m.remove_building(plant);
+        check_network_coherency(&m);
+        assert!(m.power_lines().is_empty());
+        assert!(m.pylons().is_empty());
+    }
+
+    #[test]
+    fn parallel_power_lines() {
+        MyLog::init();
+        let mut m = Map::empty();
+
+        let mut mk_building = |pos: Vec2| {
+            m.build_special_building(
+                &OBB::new(pos, vec2(1.0, 0.0), 10.0, 10.0),
+                BuildingKind::ExternalTrading,
+                BuildingGen::NoWalkway { door_pos: pos },
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let plant = mk_building(vec2(0.0, 0.0));
+        let city = mk_building(vec2(1000.0, 0.0));
+
+        // two lines going around each side, through their own pylon
+        let mut lines = vec![];
+        for y in [-200.0, 200.0] {
+            let a = m
+                .build_power_line(
+                    PowerLineEnd::Building(plant),
+                    PowerLineEnd::Ground(vec3(500.0, y, 0.0)),
+                    PowerLineKind::LowVoltage,
+                )
+                .unwrap();
+            let crate::map::NetworkObjectID::Pylon(pylon) = m.power_lines()[a].dst else {
+                panic!("a pylon should have been placed");
+            };
+            let b = m
+                .build_power_line(
+                    PowerLineEnd::Pylon(pylon),
+                    PowerLineEnd::Building(city),
+                    PowerLineKind::LowVoltage,
+                )
+                .unwrap();
+            lines.extend([a, b]);
+        }
+        check_network_coherency(&m);
+
+        let net_id = m.electricity.net_id(plant).unwrap();
+        let network = m.electricity.networks().find(|n| n.id == net_id).unwrap();
+        let settle_city = |city_power: i64| {
+            let balance = BTreeMap::from([
+                (plant, Power::new(2_000_000)),
+                (city, Power::new(-city_power)),
+            ]);
+            let routing = route_power(&m, network, &balance);
+            let settlement = settle(&routing.regions, std::iter::empty(), 1.0);
+            (routing, settlement)
+        };
+
+        // neither line could carry 800kW alone, together they can
+        let (routing, settlement) = settle_city(800_000);
+        assert!(routing.overloaded.is_empty());
+        let flow_of = |line| routing.flows.iter().find(|&&(l, _)| l == line).unwrap().1;
+        assert_eq!(flow_of(lines[0]), flow_of(lines[1]));
+        assert_eq!(flow_of(lines[2]), flow_of(lines[3]));
+        assert!(flow_of(lines[0]) <= Power::new(500_000));
+        assert!(flow_of(lines[2]) <= Power::new(500_000));
+        assert_eq!(flow_of(lines[0]) + flow_of(lines[2]), Power::new(800_000));
+        assert_eq!(routing.regions.len(), 1);
+        assert_eq!(settlement.undelivered_power, Power::ZERO);
+        assert!(settlement.blacked_out.is_empty());
+
+        // both paths are full
+        let (routing, settlement) = settle_city(1_200_000);
+        assert_eq!(routing.overloaded.len(), 2);
+        for &(_, flow) in &routing.flows {
+            assert_eq!(flow, Power::new(500_000));
+        }
+        assert_eq!(settlement.undelivered_power, Power::new(200_000));
+        assert_eq!(settlement.blacked_out, vec![city]);
+    }
+
+    #[test]
+    fn batteries_and_sun() {
+        let mk = |charge: i64| BatteryState {
+            capacity: Energy::new(10_000),
+            max_power: Power::new(1000),
+            charge: Energy::new(charge),
+        };
+
+        // surplus charges the first battery up to its max power, then the next one
+        let mut batteries = [mk(0), mk(0)];
+        let given = dispatch_batteries(Power::new(1500), batteries.iter_mut(), 2.0);
+        assert_eq!(given, Power::new(-1500));
+        assert_eq!(batteries[0].charge, Energy::new(2000));
+        assert_eq!(batteries[1].charge, Energy::new(1000));
+
+        // a full battery doesn't charge anymore
+        let mut batteries = [mk(9_500)];
+        let given = dispatch_batteries(Power::new(1000), batteries.iter_mut(), 1.0);
+        assert_eq!(given, Power::new(-500));
+        assert_eq!(batteries[0].charge, Energy::new(10_000));
+
+        // deficit is covered until the battery is empty
+        let mut batteries = [mk(300)];
+        let given = dispatch_batteries(Power::new(-800), batteries.iter_mut(), 1.0);
+        assert_eq!(given, Power::new(300));
+        assert_eq!(batteries[0].charge, Energy::ZERO);
+
+        assert_eq!(SolarPanelPrototype::sun_intensity(0), 0.0);
+        assert_eq!(
+            SolarPanelPrototype::sun_intensity(22 * SECONDS_PER_HOUR),
+            0.0
+        );
+        assert!(SolarPanelPrototype::sun_intensity(13 * SECONDS_PER_HOUR) > 0.99);
+        assert!(SolarPanelPrototype::sun_intensity(8 * SECONDS_PER_HOUR) > 0.0);
+    }
 }
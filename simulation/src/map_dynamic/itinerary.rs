@@ -1,4 +1,4 @@
-use crate::map::{Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind};
+use crate::map::{LaneID, Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind};
 use crate::utils::resources::Resources;
 use crate::world::TrainID;
 use crate::World;
@@ -92,6 +92,26 @@ impl Itinerary {
         }
     }
 
+    /// Returns true if the route goes through one of the given lanes or a turn connected to them
+    pub fn uses_lanes(&self, lanes: &[LaneID]) -> bool {
+        let ItineraryKind::Route(ref r, _) = self.kind else {
+            return false;
+        };
+        std::iter::once(&r.cur)
+            .chain(r.reversed_route.iter())
+            .any(|t| match t.kind {
+                TraverseKind::Lane(id) => lanes.contains(&id),
+                TraverseKind::Turn(id) => lanes.contains(&id.src) || lanes.contains(&id.dst),
+            })
+    }
+
+    /// Forgets the current route and computes a new one to the same destination on the next update
+    pub fn reroute(&mut self) {
+        if let ItineraryKind::Route(ref r, kind) = self.kind {
+            *self = Self::wait_for_reroute(kind, r.end_pos);
+        }
+    }
+
     pub fn route(
         tick: Tick,
         start: Vec3,
@@ -0,0 +1,411 @@
+use crate::map::{BuildingKind, IntersectionID, Map, ProjectFilter, ProjectKind, TerrainChunkID};
+use crate::map_dynamic::PollutionField;
+use crate::utils::resources::Resources;
+use crate::World;
+use common::ChunkID_128;
+use geom::{vec2, Vec2};
+use ordered_float::OrderedFloat;
+use prototypes::{GameTime, TICKS_PER_SECOND};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+
+/// Land value is stored per 128m chunk, 4x4 per terrain chunk
+pub type LandValueChunkID = ChunkID_128;
+
+const CHUNKS_PER_TERRAIN_CHUNK: u16 = (TerrainChunkID::SIZE / LandValueChunkID::SIZE) as u16;
+
+/// How often dirty chunks are recomputed
+pub const LAND_VALUE_UPDATE_PERIOD: u64 = TICKS_PER_SECOND * 10;
+
+/// Maximum number of dirty chunks recomputed per update
+const MAX_CHUNKS_PER_UPDATE: usize = 1024;
+
+/// Number of chunks refreshed per update regardless of map changes, to follow pollution
+const REFRESH_PER_UPDATE: usize = 64;
+
+/// Intersections within this radius of a chunk center give it access to the road network
+const ACCESS_SEARCH_RADIUS: f32 = 300.0;
+
+/// Distance to jobs and shops at which access is divided by e
+const ACCESS_DISTANCE_SCALE: f32 = 1000.0;
+
+/// Number of trees in a chunk for it to be considered a full leisure area
+const TREES_FOR_FULL_LEISURE: f32 = 20.0;
+
+/// Leisure buildings make the land attractive up to this distance, fading with it
+const LEISURE_BUILDING_RADIUS: f32 = 400.0;
+
+/// Height above the surroundings giving the best view
+const VIEW_HEIGHT_SCALE: f32 = 30.0;
+
+/// Land value between 0 and 1, computed per chunk from
+/// - accessibility: distance along roads to jobs and shops
+/// - leisure: trees and leisure buildings nearby
+/// - pollution and noise
+/// - view: elevation compared to the surroundings
+///
+/// It is updated incrementally from the chunks the map reports as changed,
+/// and slowly refreshed everywhere to follow the pollution.
+#[derive(Default, Serialize, Deserialize)]
+pub struct LandValue {
+    values: Vec<f32>, // values is an array of length w * h, indexed with (x + y * w)
+    w: u16,
+    h: u16,
+    /// Distance along roads from each intersection to the closest company
+    job_distance: BTreeMap<IntersectionID, f32>,
+    dirty: BTreeSet<LandValueChunkID>,
+    distances_dirty: bool,
+    refresh_cursor: usize,
+}
+
+impl LandValue {
+    pub fn new(w: u16, h: u16) -> Self {
+        Self {
+            values: vec![0.0; w as usize * h as usize],
+            w,
+            h,
+            job_distance: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            distances_dirty: true,
+            refresh_cursor: 0,
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.w, self.h)
+    }
+
+    /// The land value at the given position, 0 outside of the map
+    pub fn value(&self, p: Vec2) -> f32 {
+        self.chunk_value(LandValueChunkID::new(p))
+    }
+
+    pub fn chunk_value(&self, id: LandValueChunkID) -> f32 {
+        self.index(id).map_or(0.0, |i| self.values[i])
+    }
+
+    fn index(&self, id: LandValueChunkID) -> Option<usize> {
+        if id.0 < 0 || id.1 < 0 || id.0 as u16 >= self.w || id.1 as u16 >= self.h {
+            return None;
+        }
+        Some(id.0 as usize + id.1 as usize * self.w as usize)
+    }
+
+    fn chunk_at(&self, i: usize) -> LandValueChunkID {
+        let w = self.w as usize;
+        LandValueChunkID::new_i16((i % w) as i16, (i / w) as i16)
+    }
+
+    fn mark_dirty_around(&mut self, p: Vec2, radius: f32) {
+        let ll = LandValueChunkID::new(p - Vec2::splat(radius));
+        let ur = LandValueChunkID::new(p + Vec2::splat(radius));
+        for y in ll.1..=ur.1 {
+            for x in ll.0..=ur.0 {
+                let id = LandValueChunkID::new_i16(x, y);
+                if self.index(id).is_some() {
+                    self.dirty.insert(id);
+                }
+            }
+        }
+    }
+
+    fn recompute(&mut self, map: &Map, pollution: &PollutionField, id: LandValueChunkID) {
+        let Some(i) = self.index(id) else {
+            return;
+        };
+        self.values[i] = compute_chunk(map, pollution, &self.job_distance, id);
+    }
+
+    /// Recomputes the distances to jobs and marks the chunks where they changed as dirty
+    fn update_job_distances(&mut self, map: &Map) {
+        let new = job_distances(map);
+        let radius = ACCESS_SEARCH_RADIUS + LandValueChunkID::SIZE_F32;
+
+        for (id, inter) in map.intersections() {
+            let old = self.job_distance.get(&id);
+            let changed = match (old, new.get(&id)) {
+                (Some(a), Some(b)) => (a - b).abs() > 1.0,
+                (None, None) => false,
+                _ => true,
+            };
+            if changed {
+                self.mark_dirty_around(inter.pos.xy(), radius);
+            }
+        }
+
+        self.job_distance = new;
+    }
+}
+
+/// Combines the components of land value, all between 0 and 1 except pollution and noise
+/// which are raw field values
+pub fn land_value_score(access: f32, leisure: f32, view: f32, air: f32, noise: f32) -> f32 {
+    let air = air / (air + 20.0);
+    let noise = noise / (noise + 1.0);
+    (0.2 + 0.5 * access + 0.15 * leisure + 0.15 * view - 0.3 * air - 0.2 * noise).clamp(0.0, 1.0)
+}
+
+fn compute_chunk(
+    map: &Map,
+    pollution: &PollutionField,
+    job_distance: &BTreeMap<IntersectionID, f32>,
+    id: LandValueChunkID,
+) -> f32 {
+    let env = &map.environment;
+    let center = id.center();
+    let Some(height) = env.height(center) else {
+        return 0.0;
+    };
+
+    let mut closest = f32::INFINITY;
+    for proj in map
+        .spatial_map()
+        .query_around(center, ACCESS_SEARCH_RADIUS, ProjectFilter::INTER)
+    {
+        let ProjectKind::Intersection(inter_id) = proj else {
+            continue;
+        };
+        let (Some(d), Some(inter)) = (
+            job_distance.get(&inter_id),
+            map.intersections().get(inter_id),
+        ) else {
+            continue;
+        };
+        closest = closest.min(d + inter.pos.xy().distance(center));
+    }
+    let access = (-closest / ACCESS_DISTANCE_SCALE).exp();
+
+    let bbox = id.bbox();
+    let n_trees = env.trees.query(bbox.ll, bbox.ur).count();
+    let mut leisure = n_trees as f32 / TREES_FOR_FULL_LEISURE;
+    let nearby =
+        map.spatial_map()
+            .query_around(center, LEISURE_BUILDING_RADIUS, ProjectFilter::BUILDING);
+    for proj in nearby {
+        let ProjectKind::Building(b_id) = proj else {
+            continue;
+        };
+        let Some(b) = map.buildings().get(b_id) else {
+            continue;
+        };
+        if !matches!(b.kind, BuildingKind::Leisure(_)) {
+            continue;
+        }
+        leisure += (1.0 - b.obb.center().distance(center) / LEISURE_BUILDING_RADIUS).max(0.0);
+    }
+    let leisure = leisure.min(1.0);
+
+    let d = LandValueChunkID::SIZE_F32 * 2.0;
+    let surroundings = [vec2(d, 0.0), vec2(-d, 0.0), vec2(0.0, d), vec2(0.0, -d)]
+        .iter()
+        .map(|&off| env.height(center + off).unwrap_or(height))
+        .sum::<f32>()
+        * 0.25;
+    let view = ((height - surroundings) / VIEW_HEIGHT_SCALE).clamp(0.0, 1.0);
+
+    land_value_score(
+        access,
+        leisure,
+        view,
+        pollution.air(center),
+        pollution.noise(center),
+    )
+}
+
+/// Multi-source dijkstra over the road graph, starting from the roads companies are connected to.
+/// Rail-only roads are ignored.
+fn job_distances(map: &Map) -> BTreeMap<IntersectionID, f32> {
+    let mut dist: BTreeMap<IntersectionID, f32> = BTreeMap::new();
+    let mut heap = BinaryHeap::new();
+
+    for b in map.buildings().values() {
+        if b.kind.as_goods_company().is_none() {
+            continue;
+        }
+        let Some(road) = b.connected_road.and_then(|r| map.roads().get(r)) else {
+            continue;
+        };
+        for inter_id in [road.src, road.dst] {
+            let Some(inter) = map.intersections().get(inter_id) else {
+                continue;
+            };
+            relax(
+                &mut dist,
+                &mut heap,
+                inter_id,
+                inter.pos.distance(b.door_pos),
+            );
+        }
+    }
+
+    while let Some(Reverse((OrderedFloat(d), id))) = heap.pop() {
+        if dist.get(&id).is_some_and(|&best| d > best) {
+            continue;
+        }
+        let Some(inter) = map.intersections().get(id) else {
+            continue;
+        };
+        for &road_id in &inter.roads {
+            let Some(road) = map.roads().get(road_id) else {
+                continue;
+            };
+            if road.lanes_iter().all(|(_, kind)| kind.is_rail()) {
+                continue;
+            }
+            let Some(other) = road.other_end(id) else {
+                continue;
+            };
+            relax(&mut dist, &mut heap, other, d + road.length());
+        }
+    }
+
+    dist
+}
+
+fn relax(
+    dist: &mut BTreeMap<IntersectionID, f32>,
+    heap: &mut BinaryHeap<Reverse<(OrderedFloat<f32>, IntersectionID)>>,
+    id: IntersectionID,
+    d: f32,
+) {
+    if dist.get(&id).map_or(true, |&old| d < old) {
+        dist.insert(id, d);
+        heap.push(Reverse((OrderedFloat(d), id)));
+    }
+}
+
+/// Collects the chunks changed on the map and recomputes the land value of dirty chunks
+pub fn land_value_update_system(_: &mut World, resources: &mut Resources) {
+    profiling::scope!("map_dynamic::land_value_update");
+    let mut map = resources.write::<Map>();
+    let mut lv = resources.write::<LandValue>();
+
+    // changes are collected every tick so that none are lost when saving between two updates
+    let mut changed = false;
+    for chunk in map.land_value_subscriber.take_updated_chunks() {
+        // a leisure building changes the value of the land around it too
+        chunk.convert().for_each(|id: LandValueChunkID| {
+            lv.mark_dirty_around(id.center(), LEISURE_BUILDING_RADIUS);
+        });
+        changed = true;
+    }
+    if map.land_value_subscriber.take_cleared() {
+        changed = true;
+    }
+    lv.distances_dirty |= changed;
+
+    if resources.read::<GameTime>().tick.0 % LAND_VALUE_UPDATE_PERIOD != 0 {
+        return;
+    }
+
+    let map = &*map;
+    let pollution = resources.read::<PollutionField>();
+
+    let (w, h) = map.environment.size();
+    let size = (w * CHUNKS_PER_TERRAIN_CHUNK, h * CHUNKS_PER_TERRAIN_CHUNK);
+    if lv.size() != size {
+        *lv = LandValue::new(size.0, size.1);
+        lv.update_job_distances(map);
+        lv.distances_dirty = false;
+        lv.dirty.clear();
+        for i in 0..lv.values.len() {
+            let id = lv.chunk_at(i);
+            lv.recompute(map, &pollution, id);
+        }
+        return;
+    }
+
+    if lv.distances_dirty {
+        lv.update_job_distances(map);
+        lv.distances_dirty = false;
+    }
+
+    let n = lv.values.len();
+    if n > 0 {
+        for _ in 0..REFRESH_PER_UPDATE.min(n) {
+            let id = lv.chunk_at(lv.refresh_cursor);
+            lv.dirty.insert(id);
+            lv.refresh_cursor = (lv.refresh_cursor + 1) % n;
+        }
+    }
+
+    for _ in 0..MAX_CHUNKS_PER_UPDATE {
+        let Some(id) = lv.dirty.pop_first() else {
+            break;
+        };
+        lv.recompute(map, &pollution, id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{land_value_score, LandValue, LandValueChunkID, LAND_VALUE_UPDATE_PERIOD};
+    use crate::map::BuildingKind;
+    use crate::tests::TestCtx;
+    use crate::world_command::WorldCommand;
+    use geom::{vec2, vec3, OBB};
+    use prototypes::{BuildingGen, GameTime, LeisurePrototypeID, Tick};
+
+    #[test]
+    fn land_value_components() {
+        let nothing = land_value_score(0.0, 0.0, 0.0, 0.0, 0.0);
+        let accessible = land_value_score(1.0, 0.0, 0.0, 0.0, 0.0);
+        let nice = land_value_score(1.0, 1.0, 1.0, 0.0, 0.0);
+        let polluted = land_value_score(1.0, 0.0, 0.0, 100.0, 5.0);
+
+        assert!(accessible > nothing);
+        assert!(nice > accessible);
+        assert!(polluted < accessible);
+        assert!(nice > 0.99);
+        assert_eq!(land_value_score(0.0, 0.0, 0.0, 1000.0, 1000.0), 0.0);
+    }
+
+    #[test]
+    fn map_changes_update_land_value() {
+        let mut test = TestCtx::new();
+        test.build_roads(&[vec3(50.0, 200.0, 0.0), vec3(350.0, 200.0, 0.0)]);
+        let bounds = test.g.map().environment.bounds();
+        test.g
+            .map_mut()
+            .environment
+            .remove_trees_near(bounds, |_| {});
+
+        let update_at = |test: &mut TestCtx, tick: u64| {
+            *test.g.write::<GameTime>() = GameTime::new(Tick(tick - 1));
+            test.tick();
+        };
+
+        update_at(&mut test, LAND_VALUE_UPDATE_PERIOD);
+        let pos = vec2(200.0, 140.0);
+        let before = test.g.read::<LandValue>().value(pos);
+
+        let road = test.g.map().roads().keys().next().unwrap();
+        test.apply(&[WorldCommand::MapBuildSpecialBuilding {
+            pos: OBB::new(pos, vec2(1.0, 0.0), 40.0, 40.0),
+            kind: BuildingKind::Leisure(LeisurePrototypeID::new("cinema")),
+            gen: BuildingGen::CenteredDoor {
+                vertical_factor: 1.0,
+            },
+            zone: None,
+            connected_road: Some(road),
+        }]);
+
+        // the chunks around the cinema are marked dirty right away
+        test.tick();
+        {
+            let lv = test.g.read::<LandValue>();
+            assert!(lv.dirty.contains(&LandValueChunkID::new(pos)));
+            assert!(lv
+                .dirty
+                .contains(&LandValueChunkID::new(pos + vec2(200.0, 0.0))));
+            assert_eq!(lv.value(pos), before);
+        }
+
+        // and recomputed on the next update
+        update_at(&mut test, 2 * LAND_VALUE_UPDATE_PERIOD);
+        let lv = test.g.read::<LandValue>();
+        assert!(lv.dirty.is_empty());
+        assert!(lv.value(pos) > before, "{} <= {}", lv.value(pos), before);
+    }
+}
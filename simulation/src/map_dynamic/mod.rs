@@ -2,12 +2,18 @@ mod binfos;
 mod dispatch;
 mod electricity;
 mod itinerary;
+mod land_value;
 mod parking;
+mod pollution;
 mod router;
+mod water_supply;
 
 pub use binfos::*;
 pub use dispatch::*;
 pub use electricity::*;
 pub use itinerary::*;
+pub use land_value::*;
 pub use parking::*;
+pub use pollution::*;
 pub use router::*;
+pub use water_supply::*;
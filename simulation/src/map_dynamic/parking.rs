@@ -109,6 +109,20 @@ impl ParkingManagement {
         }
         Err(E::NoSpotFoundAfterSearch)
     }
+
+    /// Moves a reservation whose spot was removed from the map to a free spot near the given position.
+    /// Returns false if the spot doesn't exist anymore and no other spot could be found
+    pub fn relocate(&mut self, spot: &mut SpotReservation, near: Vec3, map: &Map) -> bool {
+        if spot.exists(&map.parking) {
+            return true;
+        }
+        let Ok(new) = self.reserve_near(near, map) else {
+            return false;
+        };
+        let old = std::mem::replace(spot, new);
+        self.free(old);
+        true
+    }
 }
 
 impl SpotReservation {
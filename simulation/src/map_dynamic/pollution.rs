@@ -0,0 +1,344 @@
+use crate::map::{Map, TerrainChunkID, Traversable, TraverseDirection, TraverseKind};
+use crate::transportation::{VehicleKind, VehicleState};
+use crate::utils::resources::Resources;
+use crate::World;
+use geom::{PolyLine3, Vec2};
+use prototypes::{CompanyKind, GameTime, TICKS_PER_SECOND};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Pollution chunks have the same layout as terrain chunks
+pub type PollutionChunkID = TerrainChunkID;
+
+pub const POLLUTION_CHUNK_RESOLUTION: usize = 16;
+
+/// Size of a pollution cell in meters (512 / 16 = 32)
+pub const POLLUTION_CELL_SIZE: f32 = PollutionChunkID::SIZE_F32 / POLLUTION_CHUNK_RESOLUTION as f32;
+
+/// How often sources are accumulated and the field diffuses
+pub const POLLUTION_UPDATE_PERIOD: u64 = TICKS_PER_SECOND * 10;
+
+const FACTORY_AIR: f32 = 2.0;
+const FACTORY_NOISE: f32 = 1.0;
+const STORE_NOISE: f32 = 0.3;
+const CAR_AIR: f32 = 0.05;
+const CAR_NOISE: f32 = 0.1;
+const TRUCK_FACTOR: f32 = 3.0;
+const TRAIN_AIR: f32 = 0.1;
+const TRAIN_NOISE: f32 = 0.5;
+
+/// Values under this threshold are set to zero so that the field stays sparse
+const EPSILON: f32 = 0.001;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PollutionKind {
+    /// Air pollution lingers and spreads far
+    Air,
+    /// Noise does not accumulate and fades quickly away from its sources
+    Noise,
+}
+
+impl PollutionKind {
+    /// How much of the difference with the neighbours is exchanged at each update
+    fn diffusion(self) -> f32 {
+        match self {
+            PollutionKind::Air => 0.2,
+            PollutionKind::Noise => 0.3,
+        }
+    }
+
+    /// Fraction of the value that disappears at each update
+    fn decay(self) -> f32 {
+        match self {
+            PollutionKind::Air => 0.02,
+            PollutionKind::Noise => 0.5,
+        }
+    }
+}
+
+type Layer = [[f32; POLLUTION_CHUNK_RESOLUTION]; POLLUTION_CHUNK_RESOLUTION];
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PollutionChunk {
+    pub air: Layer,
+    pub noise: Layer,
+}
+
+impl PollutionChunk {
+    pub fn layer(&self, kind: PollutionKind) -> &Layer {
+        match kind {
+            PollutionKind::Air => &self.air,
+            PollutionKind::Noise => &self.noise,
+        }
+    }
+
+    fn layer_mut(&mut self, kind: PollutionKind) -> &mut Layer {
+        match kind {
+            PollutionKind::Air => &mut self.air,
+            PollutionKind::Noise => &mut self.noise,
+        }
+    }
+}
+
+/// Air pollution and noise over the map, stored in chunks following the terrain layout
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PollutionField {
+    chunks: Vec<PollutionChunk>, // chunks is an array of length w * h, indexed with (x + y * w)
+    w: u16,
+    h: u16,
+    /// The field being computed by [`PollutionField::diffuse`], swapped with `chunks` afterwards
+    #[serde(skip)]
+    back: Vec<PollutionChunk>,
+}
+
+impl PollutionField {
+    pub fn new(w: u16, h: u16) -> Self {
+        Self {
+            chunks: vec![PollutionChunk::default(); w as usize * h as usize],
+            w,
+            h,
+            back: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.w, self.h)
+    }
+
+    pub fn chunk(&self, id: PollutionChunkID) -> Option<&PollutionChunk> {
+        if id.0 < 0 || id.1 < 0 || id.0 as u16 >= self.w || id.1 as u16 >= self.h {
+            return None;
+        }
+        self.chunks
+            .get(id.0 as usize + id.1 as usize * self.w as usize)
+    }
+
+    /// The value of the field at the given position, 0 outside of the map
+    pub fn value(&self, kind: PollutionKind, p: Vec2) -> f32 {
+        let Some((x, y)) = self.cell(p) else {
+            return 0.0;
+        };
+        self.get(kind, x, y)
+    }
+
+    pub fn air(&self, p: Vec2) -> f32 {
+        self.value(PollutionKind::Air, p)
+    }
+
+    pub fn noise(&self, p: Vec2) -> f32 {
+        self.value(PollutionKind::Noise, p)
+    }
+
+    /// Adds some pollution at the given position
+    pub fn emit(&mut self, kind: PollutionKind, p: Vec2, amount: f32) {
+        let Some((x, y)) = self.cell(p) else {
+            return;
+        };
+        *self.get_mut(kind, x, y) += amount;
+    }
+
+    /// Spreads the given amount evenly along a lane or a turn, one share per cell it crosses
+    pub fn emit_along(&mut self, kind: PollutionKind, points: &PolyLine3, amount: f32) {
+        let length = points.length();
+        let n = (length / POLLUTION_CELL_SIZE).ceil().max(1.0);
+        for i in 0..n as usize {
+            let pos = points.point_along((i as f32 + 0.5) * length / n).xy();
+            self.emit(kind, pos, amount / n);
+        }
+    }
+
+    /// Spreads the field to neighbouring cells and makes it decay
+    pub fn diffuse(&mut self) {
+        let mut next = std::mem::take(&mut self.back);
+        next.resize_with(self.chunks.len(), Default::default);
+
+        self.diffuse_into(PollutionKind::Air, &mut next);
+        self.diffuse_into(PollutionKind::Noise, &mut next);
+
+        self.back = std::mem::replace(&mut self.chunks, next);
+    }
+
+    /// Writes every cell of the diffused layer into `next`, which has the same layout as `chunks`
+    fn diffuse_into(&self, kind: PollutionKind, next: &mut [PollutionChunk]) {
+        let (w, h) = self.cells_size();
+        let diffusion = kind.diffusion();
+        let keep = 1.0 - kind.decay();
+
+        for y in 0..h {
+            for x in 0..w {
+                let v = self.get(kind, x, y);
+                let neighbour = |nx: Option<usize>, ny: Option<usize>| match (nx, ny) {
+                    (Some(nx), Some(ny)) if nx < w && ny < h => self.get(kind, nx, ny),
+                    _ => v,
+                };
+                let avg = (neighbour(x.checked_sub(1), Some(y))
+                    + neighbour(Some(x + 1), Some(y))
+                    + neighbour(Some(x), y.checked_sub(1))
+                    + neighbour(Some(x), Some(y + 1)))
+                    * 0.25;
+
+                let mut new = (v + diffusion * (avg - v)) * keep;
+                if new < EPSILON {
+                    new = 0.0;
+                }
+                let (chunk, cy, cx) = self.idx(x, y);
+                next[chunk].layer_mut(kind)[cy][cx] = new;
+            }
+        }
+    }
+
+    fn cells_size(&self) -> (usize, usize) {
+        (
+            self.w as usize * POLLUTION_CHUNK_RESOLUTION,
+            self.h as usize * POLLUTION_CHUNK_RESOLUTION,
+        )
+    }
+
+    fn cell(&self, p: Vec2) -> Option<(usize, usize)> {
+        if p.x < 0.0 || p.y < 0.0 {
+            return None;
+        }
+        let x = (p.x / POLLUTION_CELL_SIZE) as usize;
+        let y = (p.y / POLLUTION_CELL_SIZE) as usize;
+        let (w, h) = self.cells_size();
+        if x >= w || y >= h {
+            return None;
+        }
+        Some((x, y))
+    }
+
+    /// Index of the chunk of the cell, then the row and column of the cell inside it
+    fn idx(&self, x: usize, y: usize) -> (usize, usize, usize) {
+        let r = POLLUTION_CHUNK_RESOLUTION;
+        (x / r + (y / r) * self.w as usize, y % r, x % r)
+    }
+
+    fn get(&self, kind: PollutionKind, x: usize, y: usize) -> f32 {
+        let (chunk, cy, cx) = self.idx(x, y);
+        self.chunks[chunk].layer(kind)[cy][cx]
+    }
+
+    fn get_mut(&mut self, kind: PollutionKind, x: usize, y: usize) -> &mut f32 {
+        let (chunk, cy, cx) = self.idx(x, y);
+        &mut self.chunks[chunk].layer_mut(kind)[cy][cx]
+    }
+}
+
+/// Accumulates the pollution emitted by companies and by the traffic on each lane and rail,
+/// then diffuses the field
+pub fn pollution_update_system(world: &mut World, resources: &mut Resources) {
+    profiling::scope!("map_dynamic::pollution_update");
+    if resources.read::<GameTime>().tick.0 % POLLUTION_UPDATE_PERIOD != 0 {
+        return;
+    }
+
+    let map = resources.read::<Map>();
+    let mut field = resources.write::<PollutionField>();
+
+    let size = map.environment.size();
+    if field.size() != size {
+        *field = PollutionField::new(size.0, size.1);
+    }
+
+    for c in world.companies.values() {
+        let Some(b) = map.buildings().get(c.comp.building) else {
+            continue;
+        };
+        let proto = c.comp.proto.prototype();
+        let p = c.raw_productivity(proto, b.zone.as_ref());
+        let center = b.obb.center();
+
+        match proto.kind {
+            CompanyKind::Factory => {
+                field.emit(PollutionKind::Air, center, FACTORY_AIR * p);
+                field.emit(PollutionKind::Noise, center, FACTORY_NOISE * p);
+            }
+            CompanyKind::Store => {
+                field.emit(PollutionKind::Noise, center, STORE_NOISE * p);
+            }
+        }
+    }
+
+    // (air, noise) emitted by the traffic currently on each lane or turn
+    let mut traffic: BTreeMap<TraverseKind, (f32, f32)> = BTreeMap::new();
+
+    for v in world.vehicles.values() {
+        if matches!(v.vehicle.state, VehicleState::Parked(_)) {
+            continue;
+        }
+        let Some(travers) = v.it.get_travers() else {
+            continue;
+        };
+        let factor = match v.vehicle.kind {
+            VehicleKind::Truck => TRUCK_FACTOR,
+            _ => 1.0,
+        };
+        let e = traffic.entry(travers.kind).or_default();
+        e.0 += CAR_AIR * factor;
+        e.1 += CAR_NOISE * factor;
+    }
+
+    for t in world.trains.values() {
+        let Some(travers) = t.it.get_travers() else {
+            continue;
+        };
+        let e = traffic.entry(travers.kind).or_default();
+        e.0 += TRAIN_AIR;
+        e.1 += TRAIN_NOISE;
+    }
+
+    for (kind, (air, noise)) in traffic {
+        let travers = Traversable::new(kind, TraverseDirection::Forward);
+        let Some(points) = travers.raw_points(&map) else {
+            continue;
+        };
+        field.emit_along(PollutionKind::Air, points, air);
+        field.emit_along(PollutionKind::Noise, points, noise);
+    }
+
+    field.diffuse();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PollutionField, PollutionKind, POLLUTION_CELL_SIZE};
+    use geom::{vec2, vec3, PolyLine3};
+
+    #[test]
+    fn pollution_diffuses_and_decays() {
+        let mut field = PollutionField::new(2, 2);
+        let source = vec2(510.0, 510.0);
+        let neighbour = source + vec2(POLLUTION_CELL_SIZE, 0.0);
+
+        field.emit(PollutionKind::Air, source, 10.0);
+        assert_eq!(field.air(source), 10.0);
+        assert_eq!(field.air(neighbour), 0.0);
+
+        field.diffuse();
+        // spreads across the chunk border
+        assert!(field.air(neighbour) > 0.0);
+        assert!(field.air(source) < 10.0);
+        assert_eq!(field.noise(source), 0.0);
+
+        for _ in 0..1000 {
+            field.diffuse();
+        }
+        assert_eq!(field.air(source), 0.0);
+        assert_eq!(field.air(vec2(-1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn lane_traffic_is_spread_along_the_lane() {
+        let mut field = PollutionField::new(1, 1);
+        let lane = PolyLine3::new(vec![vec3(0.0, 16.0, 0.0), vec3(320.0, 16.0, 0.0)]);
+
+        field.emit_along(PollutionKind::Noise, &lane, 1.0);
+
+        let cells: Vec<f32> = (0..10)
+            .map(|i| field.noise(vec2((i as f32 + 0.5) * POLLUTION_CELL_SIZE, 16.0)))
+            .collect();
+        assert!(cells.iter().all(|&v| (v - 0.1).abs() < 1e-5), "{cells:?}");
+        assert_eq!(field.noise(vec2(336.0, 16.0)), 0.0);
+        assert_eq!(field.noise(vec2(16.0, 48.0)), 0.0);
+    }
+}
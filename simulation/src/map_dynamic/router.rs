@@ -317,6 +317,28 @@ impl Router {
         }
     }
 
+    /// Walks to the new position of the vehicle when it was moved before getting in it
+    pub(crate) fn vehicle_moved(&mut self, vehicle: VehicleID, pos: Vec3, it: &mut Itinerary) {
+        // steps are done from the last one
+        let gets_in =
+            |step: &RoutingStep| matches!(*step, RoutingStep::GetInVehicle(v) if v == vehicle);
+
+        if self.steps.last().is_some_and(gets_in) {
+            if let Some(RoutingStep::WalkTo(ref mut obj)) = self.cur_step {
+                *obj = pos;
+                *it = Itinerary::wait_for_reroute(PathKind::Pedestrian, pos);
+            }
+        }
+        for i in 1..self.steps.len() {
+            if !gets_in(&self.steps[i - 1]) {
+                continue;
+            }
+            if let RoutingStep::WalkTo(ref mut obj) = self.steps[i] {
+                *obj = pos;
+            }
+        }
+    }
+
     pub fn reset_dest(&mut self) {
         self.cur_dest = None;
     }
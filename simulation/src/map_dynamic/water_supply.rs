@@ -0,0 +1,175 @@
+use crate::map::{BuildingID, BuildingKind, Map, NetworkID};
+use crate::map_dynamic::BuildingInfos;
+use crate::utils::scheduler::{SystemResources, SystemWorld};
+use crate::{CompanyEnt, SoulID};
+use prototypes::{GameTime, Tick, WaterRate, TICKS_PER_HOUR};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Fresh water drawn by each household living in a house
+pub const HOUSEHOLD_WATER_CONSUMPTION: WaterRate = WaterRate::new(20);
+
+/// Time given to a city to build its water supply before buildings need it, both in new games and
+/// in saves made before water existed
+pub const WATER_GRACE_PERIOD: u64 = TICKS_PER_HOUR * 48;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct WaterFlow {
+    flowmap: BTreeMap<NetworkID, WaterNetworkFlow>,
+    /// Tick from which buildings need water, set the first time the flow is computed
+    required_from: Option<Tick>,
+    required: bool,
+}
+
+impl WaterFlow {
+    pub fn network_stats(&self, network: NetworkID) -> WaterNetworkFlow {
+        self.flowmap.get(&network).cloned().unwrap_or_default()
+    }
+
+    /// The share of the needs of the building that are met, in [0; 1] range.
+    /// Buildings that are not connected to any water mains get no service, once water is required.
+    pub fn service(&self, map: &Map, building: BuildingID) -> f32 {
+        if !self.required {
+            return 1.0;
+        }
+        let Some(net_id) = map.water_mains.net_id(building) else {
+            return 0.0;
+        };
+        self.network_stats(net_id).service()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WaterNetworkFlow {
+    pub consumed_water: WaterRate,
+    pub produced_water: WaterRate,
+    /// Sewage is the water that was consumed
+    pub sewage: WaterRate,
+    pub treatment_capacity: WaterRate,
+}
+
+impl WaterNetworkFlow {
+    /// The share of the fresh water demand that is supplied
+    pub fn water_service(&self) -> f32 {
+        if self.consumed_water <= WaterRate::ZERO {
+            return 1.0;
+        }
+        (self.produced_water.0 as f32 / self.consumed_water.0 as f32).min(1.0)
+    }
+
+    /// The share of the sewage that is treated
+    pub fn sewage_service(&self) -> f32 {
+        if self.sewage <= WaterRate::ZERO {
+            return 1.0;
+        }
+        (self.treatment_capacity.0 as f32 / self.sewage.0 as f32).min(1.0)
+    }
+
+    /// A shortage of either fresh water or sewage treatment reduces the service
+    pub fn service(&self) -> f32 {
+        self.water_service().min(self.sewage_service())
+    }
+}
+
+/// Compute the water and sewage flow of each water mains network and store it in the [`WaterFlow`] resource
+/// Pumping stations supply fresh water, which is consumed by houses and companies and comes back as sewage
+/// that treatment plants must handle
+/// Buildings only need water after [`WATER_GRACE_PERIOD`]
+pub fn water_flow_system(world: &mut SystemWorld, resources: &SystemResources) {
+    profiling::scope!("map_dynamic::water_flow");
+
+    let map = resources.read::<Map>();
+    let binfos = resources.read::<BuildingInfos>();
+    let time = resources.read::<GameTime>();
+    let mut flow = resources.write::<WaterFlow>();
+
+    let required_from = *flow
+        .required_from
+        .get_or_insert(Tick(time.tick.0 + WATER_GRACE_PERIOD));
+    flow.required = time.tick >= required_from;
+
+    flow.flowmap.clear();
+
+    for network in map.water_mains.networks.values() {
+        let mut net_flow = WaterNetworkFlow::default();
+
+        for building in network.buildings.iter() {
+            let building = map.buildings.get(*building).unwrap();
+
+            match building.kind {
+                BuildingKind::House => {
+                    let n_residents = binfos.get(building.id).map_or(0, |b| b.residents.len());
+                    net_flow.consumed_water += n_residents as i64 * HOUSEHOLD_WATER_CONSUMPTION;
+                }
+                BuildingKind::GoodsCompany(comp) => {
+                    let proto = comp.prototype();
+
+                    let Some(SoulID::GoodsCompany(owner)) = binfos.owner(building.id) else {
+                        continue;
+                    };
+
+                    let Some(ent) = world.store::<CompanyEnt>().get(owner) else {
+                        continue;
+                    };
+                    let productivity = ent.raw_productivity(proto, building.zone.as_ref()) as f64;
+
+                    net_flow.consumed_water +=
+                        proto.water_consumption.unwrap_or(WaterRate::ZERO) * productivity;
+                    net_flow.produced_water +=
+                        proto.water_production.unwrap_or(WaterRate::ZERO) * productivity;
+                    net_flow.treatment_capacity +=
+                        proto.sewage_treatment.unwrap_or(WaterRate::ZERO) * productivity;
+                }
+                BuildingKind::Leisure(id) => {
+                    net_flow.consumed_water +=
+                        id.prototype().water_consumption.unwrap_or(WaterRate::ZERO);
+                }
+                BuildingKind::RailFreightStation(_) => {}
+                BuildingKind::TrainStation => {}
+                BuildingKind::ExternalTrading => {}
+            }
+        }
+
+        net_flow.sewage = net_flow.consumed_water;
+
+        flow.flowmap.insert(network.id, net_flow);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WaterFlow, WaterNetworkFlow};
+    use crate::map::{BuildingID, Map};
+    use prototypes::WaterRate;
+
+    #[test]
+    fn water_not_required_during_grace_period() {
+        let map = Map::empty();
+        let mut flow = WaterFlow::default();
+        assert_eq!(flow.service(&map, BuildingID::default()), 1.0);
+
+        // not connected to any water mains
+        flow.required = true;
+        assert_eq!(flow.service(&map, BuildingID::default()), 0.0);
+    }
+
+    #[test]
+    fn water_service() {
+        let mut flow = WaterNetworkFlow::default();
+        assert_eq!(flow.service(), 1.0);
+
+        flow.consumed_water = WaterRate::new(1000);
+        flow.sewage = WaterRate::new(1000);
+        assert_eq!(flow.water_service(), 0.0);
+
+        flow.produced_water = WaterRate::new(500);
+        flow.treatment_capacity = WaterRate::new(2000);
+        assert_eq!(flow.water_service(), 0.5);
+        assert_eq!(flow.sewage_service(), 1.0);
+        assert_eq!(flow.service(), 0.5);
+
+        flow.produced_water = WaterRate::new(2000);
+        flow.treatment_capacity = WaterRate::new(250);
+        assert_eq!(flow.service(), 0.25);
+    }
+}
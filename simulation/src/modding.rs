@@ -0,0 +1,418 @@
+//! Runtime modding hooks
+//!
+//! Mods can provide a `control.lua` script next to their `data.lua`.
+//! Those scripts register callbacks on [`SimEvent`]s using `on_event(name, callback)`.
+//! Callbacks receive the event and a read-only snapshot of the game, and can only act on the
+//! world by emitting [`WorldCommand`]s with `emit(command)`.
+//!
+//! To keep lockstep multiplayer deterministic:
+//! - Every peer runs the same scripts on the same events in the same order.
+//! - Emitted commands are applied at the start of the next tick, and are not recorded in the
+//!   replay since replaying the scripts generates them again.
+//! - Scripts run in a sandbox without io, os, randomness or garbage collector access,
+//!   and globals are read-only once the scripts are loaded.
+//! - The Lua state is not part of the saved or sent world, so scripts are run in a fresh state
+//!   for every tick with something to dispatch. Anything kept in upvalues or tables is forgotten
+//!   at the next tick, and peers that joined late or seeked back in a replay behave the same.
+
+use mlua::{Function, Lua, LuaOptions, StdLib, Table, Value};
+use prototypes::{GameTime, Tick};
+use serde::{Deserialize, Serialize};
+
+use crate::economy::Government;
+use crate::events::{SimEvent, SimEvents};
+use crate::utils::scheduler::RunnableSystem;
+use crate::world_command::WorldCommand;
+use crate::Simulation;
+
+/// Commands emitted by mods, applied at the start of the next tick
+#[derive(Default, Serialize, Deserialize)]
+pub struct ModCommands {
+    pub(crate) commands: Vec<WorldCommand>,
+}
+
+/// The Lua scripts of the loaded mods, run as the last system of the schedule
+pub struct ModHooks {
+    /// (name, source) of the control scripts, in load order
+    scripts: Vec<(String, String)>,
+}
+
+const HOOKS_REGISTRY_KEY: &str = "egregoria_hooks";
+
+impl ModHooks {
+    /// Reads the `control.lua` scripts of the loaded mods, in load order
+    pub fn load() -> Self {
+        let scripts = find_control_scripts()
+            .into_iter()
+            .filter_map(|(name, path)| match common::saveload::load_string(&path) {
+                Ok(src) => {
+                    log::info!("loaded mod script {}", name);
+                    Some((name, src))
+                }
+                Err(e) => {
+                    log::error!("could not read {}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+        Self::from_scripts(scripts)
+    }
+
+    pub(crate) fn from_scripts(scripts: Vec<(String, String)>) -> Self {
+        Self { scripts }
+    }
+
+    /// Runs the scripts in a new sandboxed state, so that they register their callbacks
+    fn new_state(&self) -> Option<Lua> {
+        let lua = match new_sandboxed_lua() {
+            Ok(lua) => lua,
+            Err(e) => {
+                log::error!("could not create lua state for mods: {}", e);
+                return None;
+            }
+        };
+
+        for (name, src) in &self.scripts {
+            if let Err(e) = lua.load(src).set_name(name).exec() {
+                log::error!("error loading mod script {}: {}", name, e);
+            }
+        }
+
+        if let Err(e) = lua.sandbox(true) {
+            log::error!("could not sandbox mods: {}", e);
+            return None;
+        }
+
+        Some(lua)
+    }
+}
+
+fn dispatch(lua: &Lua, sim: &Simulation, events: &[SimEvent], new_day: Option<i32>) {
+    let hooks = match lua.named_registry_value::<Table>(HOOKS_REGISTRY_KEY) {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            log::error!("mod hooks registry is missing: {}", e);
+            return;
+        }
+    };
+
+    let game = match game_snapshot(lua, sim) {
+        Ok(game) => game,
+        Err(e) => {
+            log::error!("could not build game snapshot for mods: {}", e);
+            return;
+        }
+    };
+
+    let call = |event_name: &str, event: mlua::Result<Value>| {
+        let Ok(Some(callbacks)) = hooks.get::<_, Option<Table>>(event_name) else {
+            return;
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                log::error!("could not convert {} event for mods: {}", event_name, e);
+                return;
+            }
+        };
+        for callback in callbacks.sequence_values::<Function>() {
+            let r = callback.and_then(|f| f.call::<_, ()>((event.clone(), game.clone())));
+            if let Err(e) = r {
+                log::error!("error in mod callback for {}: {}", event_name, e);
+            }
+        }
+    };
+
+    for event in events {
+        call(event.name(), event_to_lua(lua, sim, event));
+    }
+
+    if let Some(day) = new_day {
+        call(
+            "new_day",
+            lua.create_table_from([("day", day)]).map(Value::Table),
+        );
+    }
+}
+
+impl RunnableSystem for ModHooks {
+    fn run(&self, sim: &mut Simulation) {
+        profiling::scope!("modding::mod_hooks");
+        if self.scripts.is_empty() {
+            return;
+        }
+
+        let time = *sim.read::<GameTime>();
+        let yesterday = GameTime::new(Tick(time.tick.0.saturating_sub(1)));
+        let new_day = (yesterday.daytime.day != time.daytime.day).then_some(time.daytime.day);
+
+        let events = sim.read::<SimEvents>();
+        if events.current().is_empty() && new_day.is_none() {
+            return;
+        }
+        let Some(lua) = self.new_state() else {
+            return;
+        };
+        dispatch(&lua, sim, events.current(), new_day);
+        drop(events);
+
+        let Some(mut emitted) = lua.app_data_mut::<Vec<WorldCommand>>() else {
+            return;
+        };
+        sim.write::<ModCommands>().commands.append(&mut emitted);
+    }
+
+    fn name(&self) -> &'static str {
+        "mod_hooks"
+    }
+}
+
+/// Returns (name, path) of the control scripts in load order, so a mod runs after its dependencies
+fn find_control_scripts() -> Vec<(String, String)> {
+    prototypes::loaded_mods()
+        .iter()
+        .map(|m| (m.name.clone(), format!("{}/control.lua", m.dir)))
+        .filter(|(_, path)| std::fs::metadata(path).is_ok())
+        .collect()
+}
+
+fn new_sandboxed_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH,
+        LuaOptions::default(),
+    )?;
+
+    {
+        let globals = lua.globals();
+
+        // Anything that is not deterministic or that touches the outside world
+        for name in [
+            "loadstring",
+            "getfenv",
+            "setfenv",
+            "collectgarbage",
+            "require",
+        ] {
+            globals.set(name, Value::Nil)?;
+        }
+        let math: Table = globals.get("math")?;
+        math.set("random", Value::Nil)?;
+        math.set("randomseed", Value::Nil)?;
+
+        globals.set(
+            "print",
+            lua.create_function(|_, msg: String| {
+                log::info!("[mod] {}", msg);
+                Ok(())
+            })?,
+        )?;
+
+        lua.set_named_registry_value(HOOKS_REGISTRY_KEY, lua.create_table()?)?;
+        globals.set(
+            "on_event",
+            lua.create_function(|lua, (name, callback): (String, Function)| {
+                let hooks: Table = lua.named_registry_value(HOOKS_REGISTRY_KEY)?;
+                let callbacks = match hooks.get::<_, Option<Table>>(name.as_str())? {
+                    Some(callbacks) => callbacks,
+                    None => {
+                        let callbacks = lua.create_table()?;
+                        hooks.set(name, callbacks.clone())?;
+                        callbacks
+                    }
+                };
+                callbacks.push(callback)
+            })?,
+        )?;
+
+        lua.set_app_data(Vec::<WorldCommand>::new());
+        globals.set(
+            "emit",
+            lua.create_function(|lua, command: Value| {
+                let json = lua_to_json(command)?;
+                let command: WorldCommand =
+                    serde_json::from_value(json).map_err(mlua::Error::external)?;
+                if !command.is_moddable() {
+                    return Err(mlua::Error::external(
+                        "this command cannot be emitted by mods",
+                    ));
+                }
+                if let Some(mut emitted) = lua.app_data_mut::<Vec<WorldCommand>>() {
+                    emitted.push(command);
+                }
+                Ok(())
+            })?,
+        )?;
+    }
+
+    Ok(lua)
+}
+
+fn game_snapshot<'lua>(lua: &'lua Lua, sim: &Simulation) -> mlua::Result<Table<'lua>> {
+    let time = sim.read::<GameTime>();
+    let game = lua.create_table()?;
+    game.set("tick", time.tick.0)?;
+    game.set("day", time.daytime.day)?;
+    game.set("hour", time.daytime.hour)?;
+    game.set("minute", time.daytime.minute)?;
+    game.set("money", sim.read::<Government>().money.bucks())?;
+    game.set("population", sim.world().humans.len())?;
+    Ok(game)
+}
+
+fn event_to_lua<'lua>(
+    lua: &'lua Lua,
+    sim: &Simulation,
+    event: &SimEvent,
+) -> mlua::Result<Value<'lua>> {
+    let json = match *event {
+        SimEvent::BuildingBuilt(id) => {
+            let map = sim.map();
+            let kind = map.buildings.get(id).map(|b| b.kind.name());
+            serde_json::json!({
+                "building": id,
+                "kind": kind,
+            })
+        }
+        SimEvent::TradeMade(ref trade) => serde_json::json!({
+            "item": trade.kind.prototype().name,
+            "qty": trade.qty,
+            "money": trade.money_delta.bucks(),
+            "buyer": trade.buyer.0,
+            "seller": trade.seller.0,
+        }),
+        SimEvent::BlackoutStarted(producer) | SimEvent::BlackoutEnded(producer) => {
+            serde_json::json!({ "producer": producer })
+        }
+        SimEvent::TrainStuck(train) => serde_json::json!({ "train": train }),
+        SimEvent::CitizensLeft { house, n } => serde_json::json!({
+            "house": house,
+            "n": n,
+        }),
+    };
+    json_to_lua(lua, json)
+}
+
+/// Converts a lua value to json, so it can be deserialized using serde.
+/// Lua numbers are all floats so integral numbers are converted to json integers.
+fn lua_to_json(v: Value) -> mlua::Result<serde_json::Value> {
+    Ok(match v {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(b),
+        Value::Integer(i) => serde_json::Value::from(i),
+        Value::Number(n) => {
+            if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 {
+                serde_json::Value::from(n as i64)
+            } else {
+                serde_json::Number::from_f64(n)
+                    .map(serde_json::Value::Number)
+                    .ok_or_else(|| mlua::Error::external("number is not finite"))?
+            }
+        }
+        Value::String(s) => serde_json::Value::String(s.to_str()?.to_string()),
+        Value::Table(t) => {
+            if t.raw_len() > 0 || t.clone().pairs::<Value, Value>().next().is_none() {
+                serde_json::Value::Array(
+                    t.sequence_values::<Value>()
+                        .map(|v| lua_to_json(v?))
+                        .collect::<mlua::Result<_>>()?,
+                )
+            } else {
+                serde_json::Value::Object(
+                    t.pairs::<String, Value>()
+                        .map(|kv| {
+                            let (k, v) = kv?;
+                            Ok((k, lua_to_json(v)?))
+                        })
+                        .collect::<mlua::Result<_>>()?,
+                )
+            }
+        }
+        _ => {
+            return Err(mlua::Error::external(format!(
+                "cannot convert {} to a command",
+                v.type_name()
+            )))
+        }
+    })
+}
+
+fn json_to_lua(lua: &Lua, v: serde_json::Value) -> mlua::Result<Value<'_>> {
+    Ok(match v {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
+        serde_json::Value::String(s) => Value::String(lua.create_string(&s)?),
+        serde_json::Value::Array(a) => {
+            let t = lua.create_table_with_capacity(a.len(), 0)?;
+            for v in a {
+                t.push(json_to_lua(lua, v)?)?;
+            }
+            Value::Table(t)
+        }
+        serde_json::Value::Object(o) => {
+            let t = lua.create_table_with_capacity(0, o.len())?;
+            for (k, v) in o {
+                t.set(k, json_to_lua(lua, v)?)?;
+            }
+            Value::Table(t)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::BuildingID;
+    use crate::tests::TestCtx;
+
+    #[test]
+    fn hooks_emit_commands_without_keeping_state() {
+        let test = TestCtx::new();
+        let mut sim = test.g;
+        let hooks = ModHooks::from_scripts(vec![(
+            "test".to_string(),
+            r#"
+            local n = 0
+            on_event("building_built", function(event, game)
+                n = n + 1
+                emit({ SpawnRandomCars = { n_cars = n } })
+            end)
+            "#
+            .to_string(),
+        )]);
+
+        // nothing to dispatch
+        hooks.run(&mut sim);
+        assert!(sim.read::<ModCommands>().commands.is_empty());
+
+        sim.write::<SimEvents>()
+            .push(SimEvent::BuildingBuilt(BuildingID::default()));
+        hooks.run(&mut sim);
+        hooks.run(&mut sim);
+
+        // the counter starts over at every tick, like on a peer that just joined
+        let commands = std::mem::take(&mut sim.write::<ModCommands>().commands);
+        assert_eq!(commands.len(), 2);
+        assert!(commands
+            .iter()
+            .all(|c| matches!(c, WorldCommand::SpawnRandomCars { n_cars: 1 })));
+    }
+
+    #[test]
+    fn lua_table_to_command() {
+        let lua = new_sandboxed_lua().unwrap();
+        let v: Value = lua
+            .load("return { SpawnRandomCars = { n_cars = 10 } }")
+            .eval()
+            .unwrap();
+        let command: WorldCommand = serde_json::from_value(lua_to_json(v).unwrap()).unwrap();
+        assert!(matches!(
+            command,
+            WorldCommand::SpawnRandomCars { n_cars: 10 }
+        ));
+
+        let v: Value = lua.load("return \"MapLoadParis\"").eval().unwrap();
+        let command: WorldCommand = serde_json::from_value(lua_to_json(v).unwrap()).unwrap();
+        assert!(!command.is_moddable());
+    }
+}
@@ -0,0 +1,173 @@
+//! Scenarios
+//!
+//! A scenario is defined in Lua as a [`ScenarioPrototype`]: a starting map, some starting money
+//! and a list of objectives.
+//! Objectives are evaluated against the simulation every in-game hour, and the outcome is
+//! stored in the [`ScenarioState`] resource and announced in the chat.
+
+use geom::Color;
+use prototypes::{
+    try_prototype, GameInstant, GameTime, ScenarioObjective, ScenarioPrototype,
+    ScenarioPrototypeID, ScenarioStartMap, TICKS_PER_HOUR,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::economy::{EcoStats, Government, LEVEL_FREQS};
+use crate::map::procgen::{load_parismap, load_testfield};
+use crate::multiplayer::chat::{Message, MessageKind};
+use crate::multiplayer::MultiplayerState;
+use crate::utils::resources::Resources;
+use crate::{Simulation, World};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScenarioOutcome {
+    Won(GameInstant),
+    Lost(GameInstant),
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct ObjectiveProgress {
+    pub current: i64,
+    pub target: i64,
+    /// Once reached, an objective stays completed
+    pub completed: bool,
+}
+
+/// The scenario currently being played, if any
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScenarioState {
+    pub scenario: Option<ScenarioPrototypeID>,
+    pub started_at: Option<GameInstant>,
+    /// One entry per objective of the scenario prototype, in the same order
+    pub progress: Vec<ObjectiveProgress>,
+    pub outcome: Option<ScenarioOutcome>,
+}
+
+impl ScenarioState {
+    pub fn prototype(&self) -> Option<&'static ScenarioPrototype> {
+        try_prototype(self.scenario?)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+}
+
+/// Sets up the map, the money and the objectives of the scenario
+/// Loading a saved world is handled by [`Simulation::new_scenario`] as it replaces the whole simulation
+pub(crate) fn start_scenario(sim: &mut Simulation, id: ScenarioPrototypeID) {
+    let Some(proto) = try_prototype(id) else {
+        log::error!("trying to start unknown scenario {:?}", id);
+        return;
+    };
+
+    match proto.start_map {
+        ScenarioStartMap::Empty | ScenarioStartMap::Save(_) => {}
+        ScenarioStartMap::Paris => load_parismap(&mut sim.map_mut()),
+        ScenarioStartMap::TestField { size, spacing } => {
+            let center = sim.map().environment.bounds().center();
+            load_testfield(&mut sim.map_mut(), center, size, spacing);
+        }
+    }
+
+    sim.write::<Government>().money = proto.starting_money;
+
+    let now = sim.read::<GameTime>().instant();
+    *sim.write::<ScenarioState>() = ScenarioState {
+        scenario: Some(id),
+        started_at: Some(now),
+        progress: proto
+            .objectives
+            .iter()
+            .map(|o| ObjectiveProgress {
+                current: 0,
+                target: objective_target(o),
+                completed: false,
+            })
+            .collect(),
+        outcome: None,
+    };
+}
+
+fn objective_target(objective: &ScenarioObjective) -> i64 {
+    match *objective {
+        ScenarioObjective::Population(n) => n as i64,
+        ScenarioObjective::ExportPerHour { amount, .. } => amount as i64,
+        ScenarioObjective::Money(m) => m.bucks(),
+    }
+}
+
+fn objective_current(objective: &ScenarioObjective, world: &World, resources: &Resources) -> i64 {
+    match *objective {
+        ScenarioObjective::Population(_) => world.humans.len() as i64,
+        ScenarioObjective::ExportPerHour { item, .. } => {
+            let n_bins = (TICKS_PER_HOUR / LEVEL_FREQS[0]) as usize;
+            resources
+                .read::<EcoStats>()
+                .exports
+                .sum_items_recent(item, 0, n_bins)
+        }
+        ScenarioObjective::Money(_) => resources.read::<Government>().money.bucks(),
+    }
+}
+
+/// Evaluates the objectives of the current scenario every in-game hour
+pub fn scenario_update_system(world: &mut World, resources: &mut Resources) {
+    profiling::scope!("scenario::scenario_update_system");
+    let time = *resources.read::<GameTime>();
+    if time.tick.0 % TICKS_PER_HOUR != 0 {
+        return;
+    }
+
+    let mut state = resources.write::<ScenarioState>();
+    if state.is_over() {
+        return;
+    }
+    let Some(proto) = state.prototype() else {
+        return;
+    };
+
+    for (objective, progress) in proto.objectives.iter().zip(state.progress.iter_mut()) {
+        progress.current = objective_current(objective, world, resources);
+        progress.completed |= progress.current >= progress.target;
+    }
+
+    let outcome = if state.progress.iter().all(|p| p.completed) {
+        ScenarioOutcome::Won(time.instant())
+    } else if let (Some(limit), Some(started_at)) = (proto.time_limit, state.started_at) {
+        if started_at.elapsed(&time) < limit {
+            return;
+        }
+        ScenarioOutcome::Lost(time.instant())
+    } else {
+        return;
+    };
+
+    state.outcome = Some(outcome);
+
+    let (text, color, kind) = match outcome {
+        ScenarioOutcome::Won(_) => (
+            format!("Scenario \"{}\" completed!", proto.label),
+            Color::GREEN,
+            MessageKind::Info,
+        ),
+        ScenarioOutcome::Lost(_) => (
+            format!("Scenario \"{}\" failed: time is up.", proto.label),
+            Color::RED,
+            MessageKind::Warning,
+        ),
+    };
+
+    log::info!("{}", text);
+
+    resources
+        .write::<MultiplayerState>()
+        .chat
+        .add_message(Message {
+            name: "Scenario".to_string(),
+            text,
+            sent_at: time.instant(),
+            color,
+            kind,
+        });
+}
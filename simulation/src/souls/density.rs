@@ -0,0 +1,214 @@
+//! Density of houses
+//!
+//! Houses densify when they are connected to a road, close to companies, on valuable land and when
+//! their residents are employed, hosting more households in a taller building.
+//! When conditions are bad they shrink, down to being abandoned.
+
+use crate::economy::ZoneDemand;
+use crate::events::{SimEvent, SimEvents};
+use crate::map::{
+    BuildingID, BuildingKind, Density, ProjectFilter, ProjectKind, MAX_DENSITY_LEVEL,
+};
+use crate::map_dynamic::{BuildingInfos, LandValue, WaterFlow};
+use crate::souls::goods_company::leave_job;
+use crate::transportation::Location;
+use crate::world::{HumanEnt, VehicleEnt};
+use crate::{ParCommandBuffer, Simulation, SoulID};
+use prototypes::{GameTime, TICKS_PER_MINUTE};
+use std::collections::BTreeMap;
+
+/// How often the conditions of houses are evaluated
+pub const DENSITY_UPDATE_PERIOD: u64 = TICKS_PER_MINUTE;
+
+/// Companies within this radius of a house count towards its access
+const ACCESS_RADIUS: f32 = 400.0;
+
+/// Number of nearby companies for a house to have full access
+const COMPANIES_FOR_FULL_ACCESS: f32 = 4.0;
+
+/// Score under which conditions are considered bad and the house shrinks
+const NEUTRAL_SCORE: f32 = 0.4;
+
+/// How much the progress towards a level change moves per evaluation for a perfect score
+const PROGRESS_RATE: f32 = 0.1;
+
+/// How good it is to live in a house, roughly between 0 and 1
+///
+/// - `access` is between 0 and 1, how close shops and jobs are
+/// - `satisfaction` is between 0 and 1, how many residents are employed and how well the house
+///   is supplied with water
+/// - `demand` is the residential demand, between -1 and 1
+/// - `land_value` is between 0 and 1
+pub fn house_score(access: f32, satisfaction: f32, demand: f32, land_value: f32) -> f32 {
+    0.35 * access + 0.35 * satisfaction + 0.15 * demand + 0.15 * land_value
+}
+
+impl Density {
+    /// Accumulates the score into the progress, changing the level when it is reached.
+    /// A house only densifies once it is full.
+    pub fn advance(&mut self, score: f32, is_full: bool) {
+        self.progress += (score - NEUTRAL_SCORE) * PROGRESS_RATE;
+
+        if self.progress >= 1.0 {
+            if self.level < MAX_DENSITY_LEVEL && (is_full || self.is_abandoned()) {
+                self.level += 1;
+                self.progress = 0.0;
+            } else {
+                self.progress = 1.0;
+            }
+        } else if self.progress <= -1.0 {
+            if self.level > 0 {
+                self.level -= 1;
+                self.progress = 0.0;
+            } else {
+                self.progress = -1.0;
+            }
+        }
+    }
+}
+
+pub fn density_system(sim: &mut Simulation) {
+    profiling::scope!("souls::density_system");
+    if sim.read::<GameTime>().tick.0 % DENSITY_UPDATE_PERIOD != 0 {
+        return;
+    }
+
+    let demand = sim.read::<ZoneDemand>().residential;
+
+    let mut updates: Vec<(BuildingID, Density)> = vec![];
+    let mut evictions: Vec<(BuildingID, SoulID)> = vec![];
+
+    {
+        let map = sim.map();
+        let binfos = sim.read::<BuildingInfos>();
+        let lv = sim.read::<LandValue>();
+        let water_flow = sim.read::<WaterFlow>();
+        let world = sim.world();
+
+        for (id, b) in map.buildings() {
+            if b.kind != BuildingKind::House {
+                continue;
+            }
+            let residents = unwrap_cont!(binfos.get(id)).residents.as_slice();
+
+            let access = if b.connected_road.is_some() {
+                let n_companies = map
+                    .spatial_map()
+                    .query_around(b.door_pos.xy(), ACCESS_RADIUS, ProjectFilter::BUILDING)
+                    .filter(|proj| {
+                        let ProjectKind::Building(other) = *proj else {
+                            return false;
+                        };
+                        map.buildings()
+                            .get(other)
+                            .is_some_and(|other| other.kind.as_goods_company().is_some())
+                    })
+                    .count();
+                (n_companies as f32 / COMPANIES_FOR_FULL_ACCESS).min(1.0)
+            } else {
+                0.0
+            };
+
+            let satisfaction = if residents.is_empty() {
+                0.5
+            } else {
+                let n_employed = residents
+                    .iter()
+                    .filter(|soul| {
+                        let SoulID::Human(h) = **soul else {
+                            return false;
+                        };
+                        world.humans.get(h).is_some_and(|h| h.work.is_some())
+                    })
+                    .count();
+                n_employed as f32 / residents.len() as f32
+            };
+            // a water or sewage shortage halves satisfaction at worst
+            let satisfaction = satisfaction * (0.5 + 0.5 * water_flow.service(&map, id));
+
+            let mut density = b.density;
+            density.advance(
+                house_score(access, satisfaction, demand, lv.value(b.obb.center())),
+                residents.len() >= density.households(),
+            );
+            updates.push((id, density));
+
+            // residents who no longer fit move out once they are back home
+            for &soul in residents.iter().skip(density.households()) {
+                let SoulID::Human(h) = soul else {
+                    continue;
+                };
+                let Some(human) = world.humans.get(h) else {
+                    continue;
+                };
+                if matches!(human.location, Location::Building(home) if home == id) {
+                    evictions.push((id, soul));
+                }
+            }
+        }
+    }
+
+    let mut map = sim.map_mut();
+    for (id, density) in updates {
+        map.set_building_density(id, density);
+    }
+    drop(map);
+
+    let mut left = BTreeMap::<BuildingID, u32>::new();
+    for (house, soul) in evictions {
+        let SoulID::Human(h) = soul else {
+            continue;
+        };
+        *left.entry(house).or_default() += 1;
+        leave_job(sim, h);
+        let mut binfos = sim.write::<BuildingInfos>();
+        binfos.get_out(house, soul);
+        binfos.remove_resident(house, soul);
+        drop(binfos);
+
+        if let Some(car) = sim
+            .world()
+            .humans
+            .get(h)
+            .and_then(|h| h.router.personal_car)
+        {
+            sim.read::<ParCommandBuffer<VehicleEnt>>().kill(car);
+        }
+        sim.read::<ParCommandBuffer<HumanEnt>>().kill(h);
+    }
+
+    let mut events = sim.write::<SimEvents>();
+    for (house, n) in left {
+        events.push(SimEvent::CitizensLeft { house, n });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::house_score;
+    use crate::map::{Density, MAX_DENSITY_LEVEL};
+
+    #[test]
+    fn density_levels() {
+        let mut d = Density::default();
+        assert_eq!(d.households(), 1);
+
+        // good conditions but the house is not full yet
+        for _ in 0..100 {
+            d.advance(house_score(1.0, 1.0, 1.0, 1.0), false);
+        }
+        assert_eq!(d.level, 1);
+
+        for _ in 0..100 {
+            d.advance(house_score(1.0, 1.0, 1.0, 1.0), true);
+        }
+        assert_eq!(d.level, MAX_DENSITY_LEVEL);
+        assert_eq!(d.households(), 8);
+
+        for _ in 0..1000 {
+            d.advance(house_score(0.0, 0.0, -1.0, 0.0), true);
+        }
+        assert!(d.is_abandoned());
+        assert_eq!(d.households(), 0);
+    }
+}
@@ -3,12 +3,12 @@ use serde::{Deserialize, Serialize};
 use egui_inspect::Inspect;
 use geom::{Transform, Vec2};
 use prototypes::{
-    CompanyKind, GoodsCompanyID, GoodsCompanyPrototype, ItemID, Power, Recipe, DELTA,
+    CompanyKind, GoodsCompanyID, GoodsCompanyPrototype, ItemID, Power, Recipe, WaterRate, DELTA,
 };
 
 use crate::economy::{find_trade_place, Market};
 use crate::map::{Building, BuildingID, Map, Zone, MAX_ZONE_AREA};
-use crate::map_dynamic::{BuildingInfos, ElectricityFlow};
+use crate::map_dynamic::{BuildingInfos, ElectricityFlow, WaterFlow};
 use crate::souls::desire::WorkKind;
 use crate::transportation::{spawn_parked_vehicle, VehicleKind};
 use crate::utils::resources::Resources;
@@ -69,7 +69,7 @@ pub struct GoodsCompanyState {
 }
 
 impl CompanyEnt {
-    /// Returns the productivity of the company, in [0; 1] range _before_ taking electricity into account
+    /// Returns the productivity of the company, in [0; 1] range _before_ taking electricity and water into account
     pub fn raw_productivity(&self, proto: &GoodsCompanyPrototype, zone: Option<&Zone>) -> f32 {
         let mut p = 1.0;
         if proto.n_workers > 0 {
@@ -89,15 +89,18 @@ impl CompanyEnt {
         zone: Option<&Zone>,
         map: &Map,
         elec_flow: &ElectricityFlow,
+        water_flow: &WaterFlow,
     ) -> f32 {
-        let p = self.raw_productivity(proto, zone);
+        let mut p = self.raw_productivity(proto, zone);
 
-        if proto.power_consumption > Some(Power::ZERO) {
-            if let Some(net_id) = map.electricity.net_id(self.comp.building) {
-                if elec_flow.blackout(net_id) {
-                    return 0.0;
-                }
-            }
+        if proto.power_consumption > Some(Power::ZERO)
+            && elec_flow.building_blackout(self.comp.building)
+        {
+            return 0.0;
+        }
+
+        if proto.water_consumption > Some(WaterRate::ZERO) {
+            p *= water_flow.service(map, self.comp.building);
         }
 
         p
@@ -171,6 +174,34 @@ pub fn company_soul(
     Some(soul)
 }
 
+/// Removes the worker from the company employing it, and offers its job again
+pub fn leave_job(sim: &mut Simulation, worker: HumanID) {
+    let Some((id, c)) = sim
+        .world
+        .companies
+        .iter_mut()
+        .find(|(_, c)| c.workers.0.contains(&worker))
+    else {
+        return;
+    };
+
+    c.workers.0.retain(|&w| w != worker);
+    if c.comp.driver == Some(worker) {
+        c.comp.driver = None;
+    }
+    let building = c.comp.building;
+
+    let Some(door_pos) = sim.map().buildings().get(building).map(|b| b.door_pos) else {
+        return;
+    };
+
+    let soul = SoulID::GoodsCompany(id);
+    let job_opening = ItemID::new("job-opening");
+    let m = &mut *sim.write::<Market>();
+    m.produce(soul, job_opening, 1);
+    m.sell_all(soul, door_pos.xy(), job_opening, 0);
+}
+
 pub fn company_system(world: &mut World, res: &mut Resources) {
     profiling::scope!("souls::company_system");
     let cbuf: &ParCommandBuffer<CompanyEnt> = &res.read();
@@ -179,6 +210,7 @@ pub fn company_system(world: &mut World, res: &mut Resources) {
     let market: &Market = &res.read();
     let map: &Map = &res.read();
     let elec_flow: &ElectricityFlow = &res.read();
+    let water_flow: &WaterFlow = &res.read();
 
     world.companies.iter_mut().for_each(|(me, c)| {
         let soul = SoulID::GoodsCompany(me);
@@ -191,7 +223,8 @@ pub fn company_system(world: &mut World, res: &mut Resources) {
 
         if let Some(recipe) = &proto.recipe {
             if recipe_should_produce(recipe, soul, market) {
-                let productivity = c.productivity(proto, b.zone.as_ref(), map, elec_flow);
+                let productivity =
+                    c.productivity(proto, b.zone.as_ref(), map, elec_flow, water_flow);
 
                 c.comp.progress += productivity * DELTA / recipe.duration.seconds() as f32;
             }
@@ -269,7 +269,7 @@ pub fn spawn_human(sim: &mut Simulation, house: BuildingID) -> Option<HumanID> {
     m.buy(soul, housepos.xy(), ItemID::new("job-opening"), 1);
 
     sim.write::<BuildingInfos>().get_in(house, soul);
-    sim.write::<BuildingInfos>().set_owner(house, soul);
+    sim.write::<BuildingInfos>().add_resident(house, soul);
 
     Some(id)
 }
@@ -8,6 +8,7 @@ use crate::Simulation;
 #[macro_use]
 pub mod desire;
 
+pub mod density;
 pub mod freight_station;
 pub mod goods_company;
 pub mod human;
@@ -20,7 +21,15 @@ pub(crate) fn add_souls_to_empty_buildings(sim: &mut Simulation) {
     let mut empty_buildings = Vec::with_capacity(16);
 
     for (id, building) in map.buildings() {
-        if unwrap_cont!(infos.get(id)).owner.is_some() {
+        let info = unwrap_cont!(infos.get(id));
+        if building.kind == BuildingKind::House {
+            // houses are filled one household at a time up to their density
+            if info.residents.len() < building.density.households() {
+                empty_buildings.push((building.kind, id));
+            }
+            continue;
+        }
+        if info.owner.is_some() {
             continue;
         }
 
@@ -0,0 +1,71 @@
+use crate::economy::Government;
+use crate::map::{LanePatternBuilder, MapProject, RoadID};
+use crate::tests::TestCtx;
+use crate::world_command::{CommandRejection, WorldCommand, WorldCommands};
+use geom::vec3;
+use prototypes::Money;
+
+#[test]
+fn command_validation() {
+    let test = TestCtx::new();
+    let sim = &test.g;
+
+    assert_eq!(
+        WorldCommand::MapRemoveRoad(RoadID::default()).validate(sim),
+        Err(CommandRejection::UnknownEntity)
+    );
+
+    let pat = LanePatternBuilder::default().build();
+    assert_eq!(
+        WorldCommand::MapMakeMultipleConnections(vec![], vec![(0, 1, None, pat.clone())])
+            .validate(sim),
+        Err(CommandRejection::InvalidArguments)
+    );
+
+    let connection = WorldCommand::MapMakeConnection {
+        from: MapProject::ground(vec3(0.0, 0.0, 0.0)),
+        to: MapProject::ground(vec3(100.0, 0.0, 0.0)),
+        inter: None,
+        pat,
+        tunnel: false,
+    };
+
+    sim.write::<Government>().money = Money::ZERO;
+    assert!(matches!(
+        connection.validate(sim),
+        Err(CommandRejection::NotEnoughMoney { .. })
+    ));
+
+    // replays may contain commands applied before their cost was checked
+    assert_eq!(connection.validate_replayed(sim), Ok(()));
+
+    sim.write::<Government>().money = Money::new_bucks(1_000_000);
+    assert_eq!(connection.validate(sim), Ok(()));
+
+    let mut commands = WorldCommands::default();
+    commands.push(connection);
+    commands.push(WorldCommand::MapLoadParis);
+    commands.retain_permitted(true, false);
+    assert_eq!(commands.iter().count(), 1);
+    commands.retain_permitted(false, false);
+    assert!(commands.is_empty());
+}
+
+#[test]
+fn replayed_commands_ignore_money() {
+    let mut test = TestCtx::new();
+    let connection = WorldCommand::MapMakeConnection {
+        from: MapProject::ground(vec3(0.0, 0.0, 0.0)),
+        to: MapProject::ground(vec3(100.0, 0.0, 0.0)),
+        inter: None,
+        pat: LanePatternBuilder::default().build(),
+        tunnel: false,
+    };
+    test.g.write::<Government>().money = Money::ZERO;
+
+    test.g.tick(&mut test.sched, [&connection]);
+    assert!(test.g.map().roads().is_empty());
+
+    test.g.tick_replayed(&mut test.sched, [&connection]);
+    assert_eq!(test.g.map().roads().len(), 1);
+}
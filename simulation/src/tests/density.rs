@@ -0,0 +1,61 @@
+use crate::economy::Market;
+use crate::map::Density;
+use crate::souls::density::{density_system, DENSITY_UPDATE_PERIOD};
+use crate::tests::TestCtx;
+use crate::transportation::Location;
+use crate::{HumanEnt, ParCommandBuffer, SoulID};
+use geom::{vec2, vec3};
+use prototypes::{GameTime, ItemID, Tick};
+
+#[test]
+fn evicted_worker_frees_job() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let house = test.build_house_near(vec2(150.0, 20.0));
+    test.build_bakery(vec2(150.0, -40.0));
+
+    for _ in 0..100 {
+        test.tick();
+        if test
+            .g
+            .world
+            .companies
+            .values()
+            .any(|c| !c.workers.0.is_empty())
+        {
+            break;
+        }
+    }
+
+    let (company, c) = test.g.world.companies.iter().next().unwrap();
+    let worker = *c
+        .workers
+        .0
+        .first()
+        .expect("the resident should have been hired");
+    let job_opening = ItemID::new("job-opening");
+    let soul = SoulID::GoodsCompany(company);
+    let openings = test.g.read::<Market>().capital(soul, job_opening);
+
+    // the house is abandoned while the worker is home
+    test.g.world.humans.get_mut(worker).unwrap().location = Location::Building(house);
+    test.g.map_mut().set_building_density(
+        house,
+        Density {
+            level: 0,
+            progress: 0.0,
+        },
+    );
+    test.g.write::<GameTime>().tick = Tick(DENSITY_UPDATE_PERIOD * 100);
+    density_system(&mut test.g);
+    ParCommandBuffer::<HumanEnt>::apply(&mut test.g);
+
+    assert!(!test.g.world.humans.contains_key(worker));
+    let c = &test.g.world.companies[company];
+    assert!(!c.workers.0.contains(&worker));
+    assert_ne!(c.comp.driver, Some(worker));
+    assert_eq!(
+        test.g.read::<Market>().capital(soul, job_opening),
+        openings + 1
+    );
+}
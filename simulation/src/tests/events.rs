@@ -0,0 +1,87 @@
+use crate::events::{SimEvent, SimEvents};
+use crate::map::{BuildingKind, Zone};
+use crate::multiplayer::chat::MessageKind;
+use crate::multiplayer::MultiplayerState;
+use crate::tests::TestCtx;
+use crate::world_command::WorldCommand;
+use geom::{vec2, vec3, Polygon, OBB};
+use prototypes::{BuildingGen, GameTime, GoodsCompanyID, Tick, TICKS_PER_HOUR};
+
+fn blackouts_started(test: &TestCtx) -> usize {
+    test.g
+        .read::<SimEvents>()
+        .recent()
+        .filter(|(_, e)| matches!(e, SimEvent::BlackoutStarted(_)))
+        .count()
+}
+
+#[test]
+fn powered_network_starts_a_blackout() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    test.build_house_near(vec2(150.0, 20.0));
+
+    // a city that never had a power plant is not in a blackout worth announcing
+    test.tick();
+    assert_eq!(blackouts_started(&test), 0);
+
+    // the plant has no workers nor coal yet, so it doesn't produce anything
+    let road = test.g.map().roads().keys().next().unwrap();
+    test.apply(&[WorldCommand::MapBuildSpecialBuilding {
+        pos: OBB::new(vec2(150.0, -60.0), vec2(1.0, 0.0), 40.0, 40.0),
+        kind: BuildingKind::GoodsCompany(GoodsCompanyID::new("coal-power-plant")),
+        gen: BuildingGen::NoWalkway {
+            door_pos: vec2(150.0, -38.0),
+        },
+        zone: None,
+        connected_road: Some(road),
+    }]);
+
+    test.tick();
+    test.tick();
+    // a network that never produced power had no power to lose
+    assert_eq!(blackouts_started(&test), 0);
+
+    // solar panels need no workers, they power the house during the day
+    let zone = Polygon(vec![
+        vec2(220.0, 30.0),
+        vec2(300.0, 30.0),
+        vec2(300.0, 130.0),
+        vec2(220.0, 130.0),
+    ]);
+    test.apply(&[WorldCommand::MapBuildSpecialBuilding {
+        pos: OBB::new(vec2(260.0, 60.0), vec2(1.0, 0.0), 40.0, 40.0),
+        kind: BuildingKind::GoodsCompany(GoodsCompanyID::new("solar-panel")),
+        gen: BuildingGen::CenteredDoor {
+            vertical_factor: 1.0,
+        },
+        zone: Some(Zone::new(zone, vec2(1.0, 0.0))),
+        connected_road: Some(road),
+    }]);
+    test.tick();
+    test.tick();
+    assert_eq!(blackouts_started(&test), 0);
+
+    // the game starts at 8am, the sun is down at 9pm
+    *test.g.write::<GameTime>() = GameTime::new(Tick(13 * TICKS_PER_HOUR));
+    test.tick();
+    assert_eq!(blackouts_started(&test), 1);
+    assert!(test
+        .g
+        .read::<MultiplayerState>()
+        .chat
+        .messages
+        .iter()
+        .any(|m| matches!(m.kind, MessageKind::Warning)));
+
+    // announced once, not every tick it lasts
+    test.tick();
+    assert_eq!(blackouts_started(&test), 1);
+
+    // editing the network renumbers it, but it is still the same blackout
+    test.build_house_near(vec2(50.0, 20.0));
+    test.build_roads(&[vec3(300.0, 0.0, 0.0), vec3(300.0, 300.0, 0.0)]);
+    test.tick();
+    test.tick();
+    assert_eq!(blackouts_started(&test), 1);
+}
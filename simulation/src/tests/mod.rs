@@ -1,21 +1,31 @@
 #![allow(dead_code)]
 #![cfg(test)]
 
-use crate::map::{BuildingID, LanePatternBuilder, ProjectFilter};
+use crate::map::{BuildingID, BuildingKind, LanePatternBuilder, ProjectFilter};
 use crate::map_dynamic::BuildingInfos;
-use crate::utils::scheduler::SeqSchedule;
+use crate::utils::scheduler::Schedule;
 use crate::world_command::{WorldCommand, WorldCommands};
 use crate::{Simulation, SimulationOptions};
 use common::logger::MyLog;
 use common::saveload::Encoder;
-use geom::{Vec2, Vec3};
+use geom::{vec2, Vec2, Vec3, OBB};
+use prototypes::{BuildingGen, GoodsCompanyID};
 
+mod command_validation;
+mod density;
+mod events;
+mod road_pattern;
+mod scenario;
+mod schedule;
 mod test_iso;
+mod tunnels;
 mod vehicles;
+mod water;
+mod zoning;
 
 pub(crate) struct TestCtx {
     pub g: Simulation,
-    sched: SeqSchedule,
+    sched: Schedule,
 }
 
 impl TestCtx {
@@ -37,7 +47,7 @@ impl TestCtx {
         for w in v.windows(2) {
             let a = m.project(w[0], 0.0, ProjectFilter::ALL);
             let b = m.project(w[1], 0.0, ProjectFilter::ALL);
-            m.make_connection(a, b, None, &LanePatternBuilder::default().build());
+            m.make_connection(a, b, None, &LanePatternBuilder::default().build(), false);
         }
     }
 
@@ -56,6 +66,19 @@ impl TestCtx {
         b
     }
 
+    /// Builds a small bakery centered on the given position, with its door on its north side
+    pub(crate) fn build_bakery(&mut self, p: Vec2) {
+        self.apply(&[WorldCommand::MapBuildSpecialBuilding {
+            pos: OBB::new(p, vec2(1.0, 0.0), 20.0, 20.0),
+            kind: BuildingKind::GoodsCompany(GoodsCompanyID::new("bakery")),
+            gen: BuildingGen::NoWalkway {
+                door_pos: p + vec2(0.0, 12.0),
+            },
+            zone: None,
+            connected_road: None,
+        }]);
+    }
+
     pub(crate) fn apply(&mut self, commands: &[WorldCommand]) {
         for c in commands {
             c.apply(&mut self.g);
@@ -0,0 +1,141 @@
+use crate::economy::Government;
+use crate::map::{LaneKind, LanePatternBuilder, LotKind, ProjectFilter, ProjectKind};
+use crate::tests::TestCtx;
+use crate::transportation::{spawn_parked_vehicle, VehicleKind, VehicleState};
+use crate::world_command::{CommandRejection, WorldCommand};
+use geom::{vec2, vec3};
+use prototypes::Money;
+
+#[test]
+fn update_road_pattern_in_place() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(200.0, 0.0, 0.0)]);
+
+    let road = test.g.map().roads().keys().next().unwrap();
+    let old_lanes = test.g.map().roads()[road].lanes_iter().collect::<Vec<_>>();
+
+    let car = spawn_parked_vehicle(&mut test.g, VehicleKind::Car, vec3(100.0, 0.0, 0.0)).unwrap();
+
+    test.g.write::<Government>().money = Money::new_bucks(1_000_000);
+
+    let wider = WorldCommand::MapUpdateRoadPattern {
+        road,
+        pattern: LanePatternBuilder::default().n_lanes(2).build(),
+    };
+    assert_eq!(wider.validate(&test.g), Ok(()));
+    let cost = Government::action_cost(&wider, &test.g);
+    assert!(cost > Money::ZERO);
+    test.apply(&[wider]);
+
+    assert_eq!(
+        test.g.read::<Government>().money,
+        Money::new_bucks(1_000_000) - cost
+    );
+
+    {
+        let map = test.g.map();
+        let r = &map.roads()[road];
+        assert_eq!(r.n_lanes(), old_lanes.len() + 2);
+        // every lane of the old pattern is still part of the new one
+        let new_lanes = r.lanes_iter().collect::<Vec<_>>();
+        for lane in &old_lanes {
+            assert!(new_lanes.contains(lane));
+        }
+
+        // the parking lane was kept so the car is still parked on an existing spot
+        let v = &test.g.world().vehicles[car];
+        let VehicleState::Parked(ref spot) = v.vehicle.state else {
+            panic!("car should still be parked");
+        };
+        assert!(spot.exists(&map.parking));
+    }
+
+    let narrower = WorldCommand::MapUpdateRoadPattern {
+        road,
+        pattern: LanePatternBuilder::default().parking(false).build(),
+    };
+    assert_eq!(Government::action_cost(&narrower, &test.g), Money::ZERO);
+    test.apply(&[narrower]);
+
+    let map = test.g.map();
+    let r = &map.roads()[road];
+    assert!(r.lanes_iter().all(|(_, kind)| kind != LaneKind::Parking));
+}
+
+#[test]
+fn parked_car_moves_with_its_spot() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[
+        vec3(0.0, 0.0, 0.0),
+        vec3(200.0, 0.0, 0.0),
+        vec3(200.0, 200.0, 0.0),
+    ]);
+
+    let ProjectKind::Road(road) = test
+        .g
+        .map()
+        .project(vec3(100.0, 0.0, 0.0), 0.0, ProjectFilter::ROAD)
+        .kind
+    else {
+        panic!("no road to park on");
+    };
+    let car = spawn_parked_vehicle(&mut test.g, VehicleKind::Car, vec3(100.0, 0.0, 0.0)).unwrap();
+
+    // removing the parking lane moves the car to the other road
+    test.apply(&[WorldCommand::MapUpdateRoadPattern {
+        road,
+        pattern: LanePatternBuilder::default().parking(false).build(),
+    }]);
+
+    let map = test.g.map();
+    let v = &test.g.world().vehicles[car];
+    let VehicleState::Parked(ref spot) = v.vehicle.state else {
+        panic!("car should still be parked");
+    };
+    let spot = spot
+        .get(&map.parking)
+        .expect("car should be on an existing spot");
+    assert_ne!(map.lanes()[spot.parent].parent, road);
+    assert_eq!(v.trans, spot.trans);
+}
+
+#[test]
+fn widening_keeps_zoning_and_spares_buildings() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(200.0, 0.0, 0.0)]);
+    let road = test.g.map().roads().keys().next().unwrap();
+
+    let lots = test.g.map().lots().keys().collect::<Vec<_>>();
+    assert!(!lots.is_empty());
+    for lot in lots {
+        test.apply(&[WorldCommand::MapSetLotKind(lot, LotKind::Residential)]);
+    }
+
+    test.apply(&[WorldCommand::MapUpdateRoadPattern {
+        road,
+        pattern: LanePatternBuilder::default().n_lanes(2).build(),
+    }]);
+
+    {
+        let map = test.g.map();
+        // the lots near the ends may move around the wider intersections
+        let middle = map
+            .lots()
+            .values()
+            .filter(|lot| (40.0..160.0).contains(&lot.shape.center().x))
+            .collect::<Vec<_>>();
+        assert!(!middle.is_empty());
+        assert!(middle.iter().all(|lot| lot.kind == LotKind::Residential));
+    }
+
+    // the lots are right next to the road, so widening it again would run over the house
+    test.build_house_near(vec2(100.0, 20.0));
+    let wider = WorldCommand::MapUpdateRoadPattern {
+        road,
+        pattern: LanePatternBuilder::default().n_lanes(3).build(),
+    };
+    assert_eq!(
+        wider.validate(&test.g),
+        Err(CommandRejection::InvalidArguments)
+    );
+}
@@ -0,0 +1,55 @@
+use crate::economy::Government;
+use crate::scenario::{ScenarioOutcome, ScenarioState};
+use crate::tests::TestCtx;
+use crate::world_command::WorldCommand;
+use prototypes::{prototype, GameTime, Money, ScenarioPrototypeID, Tick, TICKS_PER_HOUR};
+
+fn grid_tycoon() -> TestCtx {
+    let mut test = TestCtx::new();
+    test.apply(&[WorldCommand::StartScenario(ScenarioPrototypeID::new(
+        "grid-tycoon",
+    ))]);
+    assert!(!test.g.map().roads().is_empty());
+    assert_eq!(test.g.read::<ScenarioState>().progress.len(), 1);
+    test
+}
+
+/// Skips time so that the next tick is the given one, objectives are evaluated on the hour
+fn tick_at(test: &mut TestCtx, tick: u64) {
+    *test.g.write::<GameTime>() = GameTime::new(Tick(tick - 1));
+    test.tick();
+}
+
+#[test]
+fn scenario_is_won_when_objectives_are_reached() {
+    let mut test = grid_tycoon();
+
+    tick_at(&mut test, TICKS_PER_HOUR);
+    let state = test.g.read::<ScenarioState>();
+    assert!(!state.is_over());
+    assert!(!state.progress[0].completed);
+    drop(state);
+
+    test.g.write::<Government>().money = Money::new_bucks(600_000);
+    tick_at(&mut test, 2 * TICKS_PER_HOUR);
+
+    let state = test.g.read::<ScenarioState>();
+    assert!(state.progress[0].completed);
+    assert!(matches!(state.outcome, Some(ScenarioOutcome::Won(_))));
+}
+
+#[test]
+fn scenario_is_lost_when_time_is_up() {
+    let mut test = grid_tycoon();
+    let limit = prototype(ScenarioPrototypeID::new("grid-tycoon"))
+        .time_limit
+        .unwrap();
+
+    tick_at(&mut test, limit.0 .0 - TICKS_PER_HOUR);
+    assert!(!test.g.read::<ScenarioState>().is_over());
+
+    tick_at(&mut test, limit.0 .0);
+    let state = test.g.read::<ScenarioState>();
+    assert!(!state.progress[0].completed);
+    assert!(matches!(state.outcome, Some(ScenarioOutcome::Lost(_))));
+}
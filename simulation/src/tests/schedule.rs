@@ -0,0 +1,82 @@
+use crate::tests::TestCtx;
+use geom::{vec2, vec3};
+
+fn small_town() -> TestCtx {
+    let mut test = TestCtx::new();
+    test.build_roads(&[
+        vec3(0.0, 0.0, 0.0),
+        vec3(300.0, 0.0, 0.0),
+        vec3(300.0, 300.0, 0.0),
+    ]);
+
+    for x in [50.0, 150.0, 250.0] {
+        test.build_house_near(vec2(x, 20.0));
+    }
+
+    test.build_bakery(vec2(150.0, -40.0));
+
+    test
+}
+
+/// The order the systems run in when scheduled one after the other.
+/// Systems that touch the same data must keep this relative order.
+const BASELINE_ORDER: &[&str] = &[
+    "electricity_flow_system",
+    "water_flow_system",
+    "dispatch_system",
+    "update_decision_system",
+    "company_system",
+    "pedestrian_decision_system",
+    "locomotive_system",
+    "transport_grid_synchronize",
+    "vehicle_decision_system",
+    "vehicle_state_update_system",
+    "routing_changed_system",
+    "routing_update_system",
+    "itinerary_update",
+    "market_update",
+    "pollution_update",
+    "property_tax",
+    "train_reservations_update",
+    "freight_station",
+    "random_vehicles",
+    "update_map",
+    "update_water",
+    "scenario_update",
+    "stats_recorder",
+    "zone_growth",
+    "house_density",
+    "add_souls_to_empty_buildings",
+    "land_value_update",
+    "mod_hooks",
+];
+
+#[test]
+fn parallel_schedule_matches_baseline() {
+    let mut seq = small_town();
+    seq.sched.set_parallel(false);
+    let mut par = small_town();
+
+    assert_eq!(seq.sched.stages().concat(), BASELINE_ORDER);
+    assert_eq!(par.sched.stages().concat(), BASELINE_ORDER);
+    assert!(par.sched.stages().iter().any(|stage| {
+        stage.contains(&"electricity_flow_system") && stage.contains(&"water_flow_system")
+    }));
+    assert!(par.sched.stages().iter().any(|stage| {
+        stage.contains(&"pedestrian_decision_system") && stage.contains(&"locomotive_system")
+    }));
+
+    for _ in 0..300 {
+        seq.tick();
+        par.tick();
+        assert_eq!(
+            seq.g.hashes(),
+            par.g.hashes(),
+            "diverged at tick {}",
+            par.g.get_tick()
+        );
+    }
+
+    assert!(!par.g.world.humans.is_empty());
+    assert!(!par.g.world.companies.is_empty());
+}
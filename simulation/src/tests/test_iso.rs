@@ -1,10 +1,12 @@
 use crate::init::init;
 use crate::map::{LanePatternBuilder, Map, MapProject, ProjectKind};
-use crate::utils::scheduler::SeqSchedule;
+use crate::utils::scheduler::Schedule;
+use crate::world_command::WorldCommand;
 use crate::World;
 use crate::{Replay, Simulation};
 use common::saveload::{Bincode, Encoder, JSONPretty};
 use geom::vec3;
+use prototypes::Tick;
 use quickcheck::{Arbitrary, Gen, TestResult};
 
 static REPLAY: &[u8] = include_bytes!("world_replay.json");
@@ -81,6 +83,7 @@ fn quickcheck_map_ser() {
                 },
                 None,
                 &LanePatternBuilder::new().build(),
+                false,
             );
 
             m2.make_connection(
@@ -94,6 +97,7 @@ fn quickcheck_map_ser() {
                 },
                 None,
                 &LanePatternBuilder::new().build(),
+                false,
             );
 
             for (action, r, x, y) in vals {
@@ -116,6 +120,7 @@ fn quickcheck_map_ser() {
                             },
                             None,
                             &LanePatternBuilder::new().build(),
+                            false,
                         );
                         m2.make_connection(
                             MapProject {
@@ -128,6 +133,7 @@ fn quickcheck_map_ser() {
                             },
                             None,
                             &LanePatternBuilder::new().build(),
+                            false,
                         );
                     }
                     MapAction::TwoInter => {
@@ -155,6 +161,7 @@ fn quickcheck_map_ser() {
                                 },
                                 None,
                                 &LanePatternBuilder::new().build(),
+                                false,
                             );
                             m2.make_connection(
                                 MapProject {
@@ -167,6 +174,7 @@ fn quickcheck_map_ser() {
                                 },
                                 None,
                                 &LanePatternBuilder::new().build(),
+                                false,
                             );
                         }
                     }
@@ -193,6 +201,7 @@ fn quickcheck_map_ser() {
                             },
                             None,
                             &LanePatternBuilder::new().build(),
+                            false,
                         );
                         m2.make_connection(
                             MapProject {
@@ -205,6 +214,7 @@ fn quickcheck_map_ser() {
                             },
                             None,
                             &LanePatternBuilder::new().build(),
+                            false,
                         );
                     }
                     MapAction::Serde => {
@@ -244,7 +254,7 @@ fn test_world_survives_serde() {
     //common::logger::MyLog::init();
 
     let replay: Replay = JSONPretty::decode(REPLAY).unwrap();
-    let mut s = SeqSchedule::default();
+    let mut s = Schedule::default();
 
     let mut check_size = 1024;
     let mut check_start = 3;
@@ -304,3 +314,75 @@ fn test_world_survives_serde() {
         break;
     }
 }
+
+#[test]
+fn test_replay_seek_backwards() {
+    init();
+
+    let replay: Replay = JSONPretty::decode(REPLAY).unwrap();
+    let mut s = Schedule::default();
+
+    let (mut sim, mut loader) = Simulation::from_replay(replay.clone());
+    loader.seek(&mut sim, &mut s, Tick(2500));
+    assert_eq!(loader.pastt, Tick(2500));
+    assert_eq!(loader.keyframes.len(), 3);
+
+    loader.seek(&mut sim, &mut s, Tick(1200));
+    assert_eq!(loader.pastt, Tick(1200));
+
+    let (mut sim2, mut loader2) = Simulation::from_replay(replay);
+    loader2.seek(&mut sim2, &mut s, Tick(1200));
+
+    assert!(sim.is_equal(&sim2));
+    assert_eq!(sim.get_tick(), sim2.get_tick());
+}
+
+#[test]
+fn test_replay_keyframes_are_restored() {
+    init();
+
+    let replay: Replay = JSONPretty::decode(REPLAY).unwrap();
+    let mut s = Schedule::default();
+
+    let (mut sim, mut loader) = Simulation::from_replay(replay.clone());
+    loader.seek(&mut sim, &mut s, Tick(2500));
+    let saved = Bincode::encode(&loader.into_saved_keyframes()).unwrap();
+
+    // seeking in a new viewing restores the saved keyframe instead of playing the replay again
+    let (mut sim2, mut loader2) = Simulation::from_replay(replay.clone());
+    assert!(loader2.restore_keyframes(Bincode::decode(&saved).unwrap()));
+    assert_eq!(loader2.keyframes.len(), 3);
+    loader2.seek(&mut sim2, &mut s, Tick(2000));
+    assert_eq!(loader2.pastt, Tick(2000));
+
+    let (mut sim3, mut loader3) = Simulation::from_replay(replay);
+    loader3.seek(&mut sim3, &mut s, Tick(2000));
+    assert!(sim2.is_equal(&sim3));
+
+    // keyframes of another replay are ignored
+    let mut other = Replay::default();
+    other.push(Tick(10), WorldCommand::MapLoadParis);
+    other.last_tick_recorded = Tick(3000);
+    let (_, mut loader4) = Simulation::from_replay(other);
+    assert!(!loader4.restore_keyframes(Bincode::decode(&saved).unwrap()));
+    assert!(loader4.keyframes.is_empty());
+}
+
+#[test]
+fn test_world_survives_parts() {
+    init();
+
+    let replay: Replay = JSONPretty::decode(REPLAY).unwrap();
+    let mut s = Schedule::default();
+
+    let (mut sim, mut loader) = Simulation::from_replay(replay);
+    loader.seek(&mut sim, &mut s, Tick(1000));
+
+    let parts = sim.to_parts();
+    assert_eq!(parts[0].0, "world");
+    let sim2 = Simulation::from_parts(parts).unwrap();
+    assert!(sim.is_equal(&sim2));
+    assert_eq!(sim.hashes(), sim2.hashes());
+
+    assert!(Simulation::from_parts(vec![]).is_none());
+}
@@ -0,0 +1,118 @@
+use crate::map::{
+    Environment, LanePatternBuilder, Map, MapProject, Road, MAX_SLOPE, ROAD_Z_OFFSET,
+};
+use common::logger::MyLog;
+use geom::{vec2, vec3, PolyLine, PolyLine3, Vec2};
+
+/// Flat terrain at 50m, with a steep hill in the middle of the (100, 496) -> (900, 496) line
+fn test_map(hill: bool) -> Map {
+    MyLog::init();
+    let mut m = Map::empty();
+    m.environment = Environment::new(2, 2);
+
+    let bounds = m.environment.bounds();
+    let center = vec2(496.0, 496.0);
+    m.environment.terrain_apply(bounds, |pos| {
+        if !hill {
+            return 50.0;
+        }
+        50.0 + (100.0 - pos.xy().distance(center) * 0.6).max(0.0)
+    });
+    m.environment.update_water();
+    m
+}
+
+fn height_at(points: &PolyLine3, x: f32) -> f32 {
+    points.point_along(x - points.first().x).z - ROAD_Z_OFFSET
+}
+
+fn line() -> PolyLine {
+    PolyLine::new(vec![vec2(100.0, 496.0), vec2(900.0, 496.0)])
+}
+
+#[test]
+fn roads_are_buried_under_steep_hills() {
+    let m = test_map(true);
+    let env = &m.environment;
+
+    assert!((env.natural_height(vec2(496.0, 496.0)).unwrap() - 150.0).abs() < 1.0);
+    assert!(env.is_underground(vec3(496.0, 496.0, 60.0)));
+    assert!(!env.is_underground(vec3(496.0, 496.0, 149.0)));
+    assert!(!env.is_underground(vec3(100.0, 496.0, 50.0)));
+
+    // the road cannot climb the hill, it goes through it instead of over it
+    let (points, err) = Road::heightfinder(&line(), 50.0, 50.0, MAX_SLOPE, false, env);
+    assert!(err.is_none());
+    let mid = height_at(&points, 496.0);
+    assert!(mid < 60.0, "road at {mid} in the middle of the hill");
+    assert!(env.is_underground(vec3(496.0, 496.0, mid)));
+    assert!(!env.is_underground(vec3(100.0, 496.0, height_at(&points, 100.0))));
+}
+
+#[test]
+fn tunnels_can_be_asked_under_flat_ground() {
+    let m = test_map(false);
+    let env = &m.environment;
+
+    let (points, _) = Road::heightfinder(&line(), 50.0, 50.0, MAX_SLOPE, false, env);
+    assert!(!env.is_underground(vec3(496.0, 496.0, height_at(&points, 496.0))));
+
+    let (points, err) = Road::heightfinder(&line(), 50.0, 50.0, MAX_SLOPE, true, env);
+    assert!(err.is_none());
+    assert!(env.is_underground(vec3(496.0, 496.0, height_at(&points, 496.0))));
+    // the portals are at the ends, descending at most at the max slope
+    assert!((height_at(&points, 100.0) - 50.0).abs() < 0.1);
+    assert!(height_at(&points, 116.0) >= 50.0 - 16.0 * MAX_SLOPE - 0.1);
+}
+
+#[test]
+fn tunnels_dont_cut_the_surface() {
+    let mut m = test_map(false);
+
+    let pat = LanePatternBuilder::new().build();
+    let (_, r) = m
+        .make_connection(
+            MapProject::ground(vec3(96.0, 496.0, 50.0)),
+            MapProject::ground(vec3(896.0, 496.0, 50.0)),
+            None,
+            &pat,
+            true,
+        )
+        .unwrap();
+    m.update();
+
+    let road = &m.roads()[r];
+    assert!(road.tunnel);
+    let tunnels = road.tunnels(&m.environment);
+    assert_eq!(tunnels.len(), 1);
+
+    let env = &m.environment;
+
+    // the terrain above the tunnel keeps its natural height
+    let above = vec2(496.0, 496.0);
+    assert!((env.natural_height(above).unwrap() - 50.0).abs() < 0.1);
+    assert!((env.height(above).unwrap() - 50.0).abs() < 0.1);
+
+    // only the portals are dug
+    let portal = vec2(112.0, 496.0);
+    assert!((env.natural_height(portal).unwrap() - 50.0).abs() < 0.1);
+    assert!(env.height(portal).unwrap() < 48.0);
+}
+
+#[test]
+fn tunnels_are_only_merged_with_tunnels() {
+    let mut m = test_map(false);
+
+    let pat = LanePatternBuilder::new().build();
+    let mk = |m: &mut Map, from: Vec2, to: Vec2, tunnel: bool| {
+        let from = m.project(from.z(50.0), 0.0, crate::map::ProjectFilter::INTER);
+        m.make_connection(from, MapProject::ground(to.z(50.0)), None, &pat, tunnel)
+            .unwrap()
+    };
+
+    // two aligned roads would be merged, but not when only one of them is a tunnel
+    mk(&mut m, vec2(96.0, 496.0), vec2(496.0, 496.0), false);
+    mk(&mut m, vec2(496.0, 496.0), vec2(896.0, 496.0), true);
+    assert_eq!(m.roads().len(), 2);
+    assert_eq!(m.roads().values().filter(|r| r.tunnel).count(), 1);
+}
@@ -0,0 +1,62 @@
+use crate::map::{
+    BuildingKind, Environment, LanePatternBuilder, Map, MapProject, BRIDGE_CLEARANCE, ROAD_Z_OFFSET,
+};
+use common::logger::MyLog;
+use geom::{vec2, vec3, OBB};
+use prototypes::{BuildingGen, Tick};
+
+#[test]
+fn flooded_roads_become_bridges() {
+    MyLog::init();
+    let mut m = Map::empty();
+    m.environment = Environment::new(2, 2);
+
+    let bounds = m.environment.bounds();
+    m.environment.terrain_apply(bounds, |_| 50.0);
+    m.environment.update_water();
+
+    let pat = LanePatternBuilder::new().build();
+    let (_, r) = m
+        .make_connection(
+            MapProject::ground(vec3(96.0, 496.0, 50.0)),
+            MapProject::ground(vec3(896.0, 496.0, 50.0)),
+            None,
+            &pat,
+            false,
+        )
+        .unwrap();
+
+    // its center is on dry land but its side is close to the future lake
+    let building = m
+        .build_special_building(
+            &OBB::new(vec2(496.0, 656.0), vec2(1.0, 0.0), 40.0, 40.0),
+            BuildingKind::ExternalTrading,
+            BuildingGen::NoWalkway {
+                door_pos: vec2(0.0, -20.0),
+            },
+            None,
+            None,
+        )
+        .unwrap();
+
+    // dig a bowl under the road, it fills up into a lake
+    let center = vec2(496.0, 496.0);
+    m.environment.terrain_apply(bounds, |pos| {
+        50.0 - (30.0 - pos.xy().distance(center) * 0.2).max(0.0)
+    });
+    m.environment.water.mark_dirty();
+    m.update_water(Tick(0));
+
+    let water = &m.environment.water;
+    let level = water.level(center).unwrap();
+    assert!(water.kind(vec2(496.0, 656.0)).is_none());
+    assert!(!m.buildings().contains_key(building));
+
+    let road = &m.roads()[r];
+    let points = road.interfaced_points();
+    let mid = points.point_along(center.x - points.first().x).z - ROAD_Z_OFFSET;
+    assert!(
+        mid >= level + BRIDGE_CLEARANCE - 0.1,
+        "road at {mid} over a lake at {level}"
+    );
+}
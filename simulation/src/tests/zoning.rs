@@ -0,0 +1,44 @@
+use crate::economy::{zone_growth_system, ZoneDemand, ZONE_GROWTH_PERIOD};
+use crate::map::{BuildingKind, LotKind};
+use crate::tests::TestCtx;
+use crate::world_command::WorldCommand;
+use geom::vec3;
+use prototypes::{GameTime, Tick};
+
+#[test]
+fn zoned_lots_grow() {
+    let mut test = TestCtx::new();
+    test.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+
+    let lots = test.g.map().lots().keys().collect::<Vec<_>>();
+    assert!(lots.len() >= 2);
+
+    let mut commands = vec![WorldCommand::MapSetLotKind(lots[0], LotKind::Industrial)];
+    for &lot in &lots[1..] {
+        commands.push(WorldCommand::MapSetLotKind(lot, LotKind::Residential));
+    }
+    assert!(commands.iter().all(|c| c.validate(&test.g).is_ok()));
+    test.apply(&commands);
+
+    assert_eq!(test.g.map().lots()[lots[0]].kind, LotKind::Industrial);
+
+    test.g.write::<GameTime>().tick = Tick(ZONE_GROWTH_PERIOD);
+    zone_growth_system(&mut test.g);
+
+    // nobody lives in the empty city yet, so houses are wanted
+    assert_eq!(test.g.read::<ZoneDemand>().residential, 1.0);
+    let n_houses = test
+        .g
+        .map()
+        .buildings()
+        .values()
+        .filter(|b| b.kind == BuildingKind::House)
+        .count();
+    assert!(n_houses > 0);
+
+    // growth only happens once per period
+    let n_buildings = test.g.map().buildings().len();
+    test.g.write::<GameTime>().tick = Tick(ZONE_GROWTH_PERIOD + 1);
+    zone_growth_system(&mut test.g);
+    assert_eq!(test.g.map().buildings().len(), n_buildings);
+}
@@ -3,8 +3,8 @@ use crate::transportation::{
     Speed, TransportGrid, TransportState, TransportationGroup, Transporter,
 };
 use crate::utils::rand_provider::RandProvider;
-use crate::utils::resources::Resources;
-use crate::World;
+use crate::utils::scheduler::{SystemResources, SystemWorld};
+use crate::HumanEnt;
 use egui_inspect::Inspect;
 use geom::{angle_lerpxy, Color, Transform, Vec3};
 use prototypes::DELTA;
@@ -65,9 +65,9 @@ pub fn random_pedestrian_shirt_color(r: &mut RandProvider) -> Color {
     unreachable!();
 }
 
-pub fn pedestrian_decision_system(world: &mut World, _resources: &mut Resources) {
+pub fn pedestrian_decision_system(world: &mut SystemWorld, _resources: &SystemResources) {
     profiling::scope!("transportation::pedestrian_decision_system");
-    world.humans
+    world.store_mut::<HumanEnt>()
         .values_mut()
         //.par_bridge()
         .for_each(|human| pedestrian_decision(&mut human.it, &mut human.trans, &mut human.speed, &mut human.pedestrian))
@@ -9,13 +9,18 @@ use egui_inspect::Inspect;
 use geom::{PolyLine3, Polyline3Queue, Transform, Vec3};
 use prototypes::{RollingStockID, DELTA};
 
+use crate::events::{SimEvent, SimEvents};
 use crate::map::{IntersectionID, LaneID, Map, TraverseKind};
 use crate::map_dynamic::ItineraryFollower;
 use crate::transportation::Speed;
 use crate::utils::resources::Resources;
+use crate::utils::scheduler::{SystemResources, SystemWorld};
 use crate::world::{TrainEnt, TrainID, WagonEnt};
 use crate::{Itinerary, ItineraryLeader, Simulation, World};
 
+/// Seconds a train can stand still before being considered stuck
+pub const TRAIN_STUCK_SECONDS: f32 = 60.0;
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct TrainReservations {
     pub reservations: BTreeMap<IntersectionID, TrainID>,
@@ -335,25 +340,21 @@ pub fn train_reservations_update(world: &mut World, resources: &mut Resources) {
     });
 }
 
-pub fn locomotive_system(world: &mut World, resources: &mut Resources) {
+pub fn locomotive_system(world: &mut SystemWorld, resources: &SystemResources) {
     profiling::scope!("transportation::locomotive_system");
-    let map: &Map = &resources.read();
-    let reservs: &TrainReservations = &resources.read();
+    let map = resources.read::<Map>();
+    let reservs = resources.read::<TrainReservations>();
 
+    let trains = world.store::<TrainEnt>();
     // asume iter order stays the same
-    let mut desired_speeds = Vec::with_capacity(world.trains.len());
-
-    for (ent, train) in world.trains.iter() {
-        desired_speeds.push(locomotive_desired_speed(
-            ent,
-            map,
-            reservs,
-            &world.trains,
-            train,
-        ));
+    let mut desired_speeds = Vec::with_capacity(trains.len());
+
+    for (ent, train) in trains.iter() {
+        desired_speeds.push(locomotive_desired_speed(ent, &map, &reservs, trains, train));
     }
 
-    for (t, desired_speed) in world.trains.values_mut().zip(desired_speeds) {
+    let mut events = resources.write::<SimEvents>();
+    for ((id, t), desired_speed) in world.store_mut::<TrainEnt>().iter_mut().zip(desired_speeds) {
         let desired_dir =
             t.it.get_point()
                 .and_then(|x| {
@@ -370,14 +371,19 @@ pub fn locomotive_system(world: &mut World, resources: &mut Resources) {
             -DELTA * t.locomotive.dec_force,
             DELTA * t.locomotive.acc_force,
         );
+        let was_stuck = t.res.waited_for > TRAIN_STUCK_SECONDS;
         if t.speed.0 <= 0.001 {
             t.res.waited_for += DELTA;
         } else {
             t.res.waited_for = 0.0;
         }
+        let is_stuck = t.res.waited_for > TRAIN_STUCK_SECONDS;
+        if is_stuck && !was_stuck && !t.it.is_none_or_wait() {
+            events.push(SimEvent::TrainStuck(id));
+        }
         for v in t.res.past_travers.values_mut() {
             *v += t.speed.0 * DELTA;
-            if t.res.waited_for > 60.0 {
+            if is_stuck {
                 *v += 0.1 * DELTA;
             }
         }
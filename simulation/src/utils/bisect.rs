@@ -0,0 +1,301 @@
+use crate::utils::replay::{Replay, SimulationReplayLoader};
+use crate::utils::scheduler::Schedule;
+use crate::Simulation;
+use prototypes::Tick;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of unchanged lines shown around each change of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// The hashes of a replay run, recorded every `period` ticks.
+/// It can be saved to compare a run on another machine or version against it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HashTrace {
+    pub period: u64,
+    pub hashes: Vec<(Tick, BTreeMap<String, u64>)>,
+}
+
+impl HashTrace {
+    /// Plays the whole replay and records its hashes
+    pub fn record(replay: Replay, period: u64) -> Self {
+        let period = period.max(1);
+        let mut run = ReplayRun::new(replay);
+        let mut hashes = vec![(run.tick(), run.sim.hashes())];
+
+        while !run.is_finished() {
+            run.seek(Tick(run.tick().0 + period));
+            hashes.push((run.tick(), run.sim.hashes()));
+        }
+
+        Self { period, hashes }
+    }
+}
+
+/// Where two runs of the same replay stop being equal
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Number of ticks played when the hashes first differ
+    pub tick: Tick,
+    /// Names of the world and resources whose hash differ, as in [`Simulation::hashes`]
+    pub diverging: Vec<String>,
+    /// Diff of the first diverging resource serialized as JSON, when both states are available
+    pub diff: Option<String>,
+}
+
+/// Plays the replay twice side by side and finds the first tick where their hashes differ.
+/// The runs are compared every `period` ticks, then the diverging window is bisected by
+/// seeking both runs, which assumes the divergence happens again when replaying from a keyframe.
+pub fn bisect_replay(replay: &Replay, period: u64) -> Option<Divergence> {
+    bisect(
+        ReplayRun::new(replay.clone()),
+        ReplayRun::new(replay.clone()),
+        period,
+    )
+}
+
+fn bisect(mut a: ReplayRun, mut b: ReplayRun, period: u64) -> Option<Divergence> {
+    let period = period.max(1);
+    if diverging(&a.sim, &b.sim).is_empty() {
+        let mut good = 0;
+        let mut bad = loop {
+            if a.is_finished() {
+                return None;
+            }
+            let target = Tick(a.tick().0 + period);
+            a.seek(target);
+            b.seek(target);
+            if !diverging(&a.sim, &b.sim).is_empty() {
+                break a.tick().0;
+            }
+            good = a.tick().0;
+        };
+
+        log::info!("[bisect] runs diverge between tick {} and {}", good, bad);
+
+        while bad - good > 1 {
+            let mid = good + (bad - good) / 2;
+            a.seek(Tick(mid));
+            b.seek(Tick(mid));
+            if diverging(&a.sim, &b.sim).is_empty() {
+                good = mid;
+            } else {
+                bad = mid;
+            }
+        }
+
+        a.seek(Tick(bad));
+        b.seek(Tick(bad));
+    }
+
+    let diverging = diverging(&a.sim, &b.sim);
+    let diff = diverging.first().and_then(|name| {
+        Some(diff_text(
+            &a.sim.debug_dump(name)?,
+            &b.sim.debug_dump(name)?,
+        ))
+    });
+
+    Some(Divergence {
+        tick: a.tick(),
+        diverging,
+        diff,
+    })
+}
+
+/// Plays the replay and finds the first recorded tick where its hashes differ from the trace.
+/// The recorded states are not available, so there is no diff.
+pub fn check_trace(replay: &Replay, trace: &HashTrace) -> Option<Divergence> {
+    let mut run = ReplayRun::new(replay.clone());
+
+    for (tick, hashes) in &trace.hashes {
+        run.seek(*tick);
+        if run.tick() != *tick {
+            log::warn!(
+                "[bisect] replay ends at tick {} before the trace at tick {}",
+                run.tick().0,
+                tick.0
+            );
+            return None;
+        }
+
+        let cur = run.sim.hashes();
+        let diverging = diverging_hashes(&cur, hashes);
+        if !diverging.is_empty() {
+            return Some(Divergence {
+                tick: *tick,
+                diverging,
+                diff: None,
+            });
+        }
+    }
+
+    None
+}
+
+/// A simulation being brought to a tick of a replay
+struct ReplayRun {
+    sim: Simulation,
+    loader: SimulationReplayLoader,
+    schedule: Schedule,
+}
+
+impl ReplayRun {
+    fn new(replay: Replay) -> Self {
+        let (sim, loader) = Simulation::from_replay(replay);
+        Self {
+            sim,
+            loader,
+            schedule: Simulation::schedule(),
+        }
+    }
+
+    fn tick(&self) -> Tick {
+        self.loader.pastt
+    }
+
+    fn is_finished(&self) -> bool {
+        self.loader.is_finished()
+    }
+
+    fn seek(&mut self, tick: Tick) {
+        self.loader.seek(&mut self.sim, &mut self.schedule, tick);
+    }
+}
+
+fn diverging(a: &Simulation, b: &Simulation) -> Vec<String> {
+    diverging_hashes(&a.hashes(), &b.hashes())
+}
+
+fn diverging_hashes(a: &BTreeMap<String, u64>, b: &BTreeMap<String, u64>) -> Vec<String> {
+    let mut names = a
+        .iter()
+        .filter(|(name, h)| b.get(*name) != Some(*h))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    names.extend(b.keys().filter(|name| !a.contains_key(*name)).cloned());
+    names
+}
+
+/// Line diff showing only the changed lines and their surroundings, with line numbers of `a`
+pub fn diff_text(a: &str, b: &str) -> String {
+    let lines = diff::lines(a, b);
+    let changed = lines
+        .iter()
+        .map(|l| !matches!(l, diff::Result::Both(..)))
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    let mut line_a = 0;
+    let mut last_shown = None;
+    for (i, l) in lines.iter().enumerate() {
+        if !matches!(l, diff::Result::Right(_)) {
+            line_a += 1;
+        }
+
+        let lo = i.saturating_sub(DIFF_CONTEXT);
+        let hi = (i + DIFF_CONTEXT + 1).min(lines.len());
+        if !changed[lo..hi].iter().any(|&c| c) {
+            continue;
+        }
+
+        if last_shown.map_or(i > 0, |last| last + 1 < i) {
+            out.push_str("...\n");
+        }
+        last_shown = Some(i);
+
+        match l {
+            diff::Result::Left(l) => out.push_str(&format!("{line_a:>6} - {l}\n")),
+            diff::Result::Both(l, _) => out.push_str(&format!("{line_a:>6}   {l}\n")),
+            diff::Result::Right(r) => out.push_str(&format!("{line_a:>6} + {r}\n")),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multiplayer::chat::{Message, MessageKind};
+    use crate::world_command::WorldCommand;
+    use crate::SimulationOptions;
+    use geom::Color;
+    use prototypes::GameInstant;
+
+    /// Tick at which the second replay sends a chat message the first doesn't have
+    const DIVERGING_TICK: u64 = 123;
+
+    fn replay() -> Replay {
+        crate::init::init();
+        let mut replay = Replay::default();
+        replay.push(
+            Tick(0),
+            WorldCommand::Init(Box::new(SimulationOptions {
+                terrain_size: 1,
+                save_replay: false,
+            })),
+        );
+        replay.last_tick_recorded = Tick(300);
+        replay
+    }
+
+    fn diverging_replay() -> Replay {
+        let mut replay = replay();
+        replay.push(
+            Tick(DIVERGING_TICK),
+            WorldCommand::SendMessage {
+                message: Message {
+                    name: "bisect".to_string(),
+                    text: "diverge".to_string(),
+                    sent_at: GameInstant(Tick(0)),
+                    color: Color::WHITE,
+                    kind: MessageKind::PlayerChat,
+                },
+            },
+        );
+        replay
+    }
+
+    #[test]
+    fn bisect_finds_injected_divergence() {
+        assert!(bisect_replay(&replay(), 50).is_none());
+
+        let divergence = bisect(
+            ReplayRun::new(replay()),
+            ReplayRun::new(diverging_replay()),
+            50,
+        )
+        .unwrap();
+
+        // the commands of a tick are seen once it is played
+        assert_eq!(divergence.tick, Tick(DIVERGING_TICK + 1));
+        assert_eq!(divergence.diverging, vec!["multiplayer_state".to_string()]);
+        assert!(divergence.diff.unwrap().contains("diverge"));
+    }
+
+    #[test]
+    fn trace_finds_injected_divergence() {
+        let trace = HashTrace::record(replay(), 50);
+        assert_eq!(trace.hashes.len(), 7);
+        assert!(check_trace(&replay(), &trace).is_none());
+
+        // the trace only knows the hashes every 50 ticks
+        let divergence = check_trace(&diverging_replay(), &trace).unwrap();
+        assert_eq!(divergence.tick, Tick(150));
+        assert_eq!(divergence.diverging, vec!["multiplayer_state".to_string()]);
+        assert!(divergence.diff.is_none());
+    }
+
+    #[test]
+    fn diff_only_shows_context() {
+        let a = (0..20).map(|i| format!("{i}\n")).collect::<String>();
+        let b = a.replace("10\n", "ten\n");
+
+        let d = diff_text(&a, &b);
+        assert!(d.starts_with("...\n     8   7\n"));
+        assert!(d.contains(" - 10\n"));
+        assert!(d.contains(" + ten\n"));
+        assert!(d.ends_with("    14   13\n"));
+        assert_eq!(diff_text(&a, &a), "");
+    }
+}
@@ -1,3 +1,4 @@
+pub mod bisect;
 pub mod par_command_buffer;
 pub mod rand_provider;
 pub mod replay;
@@ -1,6 +1,8 @@
-use crate::utils::scheduler::SeqSchedule;
+use crate::events::SimEvent;
+use crate::utils::scheduler::Schedule;
 use crate::world_command::WorldCommand;
 use crate::Simulation;
+use common::saveload::{Bincode, Encoder};
 use prototypes::Tick;
 use serde::{Deserialize, Serialize};
 
@@ -9,12 +11,47 @@ pub struct Replay {
     pub enabled: bool,
     commands: Vec<(Tick, WorldCommand)>,
     pub last_tick_recorded: Tick,
+    /// Notable events that happened, to follow the replay without playing it
+    #[serde(default)]
+    events: Vec<(Tick, SimEvent)>,
 }
 
 impl Replay {
     pub fn push(&mut self, tick: Tick, command: WorldCommand) {
         self.commands.push((tick, command));
     }
+
+    pub fn push_event(&mut self, tick: Tick, event: SimEvent) {
+        self.events.push((tick, event));
+    }
+
+    pub fn events(&self) -> &[(Tick, SimEvent)] {
+        &self.events
+    }
+}
+
+/// Number of ticks between two keyframes taken while playing a replay
+pub const KEYFRAME_PERIOD: u64 = 1000;
+
+/// Number of keyframes kept at most. Past it every other keyframe is dropped and they are taken
+/// half as often, so that seeking through long replays doesn't use unbounded memory.
+pub const MAX_KEYFRAMES: usize = 16;
+
+/// A compressed snapshot of the simulation taken before playing the commands of `tick`
+#[derive(Serialize, Deserialize)]
+pub struct Keyframe {
+    pub tick: Tick,
+    data: Vec<u8>,
+}
+
+/// Keyframes saved next to their replay, so that seeking doesn't need to play it again
+#[derive(Serialize, Deserialize)]
+pub struct SavedKeyframes {
+    /// Hash of the commands played before the last keyframe, to only restore keyframes of the
+    /// same replay. Commands recorded afterwards don't matter, the save could have been continued.
+    commands_hash: u64,
+    keyframe_period: u64,
+    keyframes: Vec<Keyframe>,
 }
 
 pub struct SimulationReplayLoader {
@@ -23,60 +60,227 @@ pub struct SimulationReplayLoader {
     pub idx: usize,
     pub speed: usize,
     pub advance_n_ticks: usize,
+    /// Keyframes taken so far, sorted by tick
+    pub keyframes: Vec<Keyframe>,
+    /// Ticks between two keyframes, a power of two times [`KEYFRAME_PERIOD`]
+    keyframe_period: u64,
+    /// Tick to seek to on the next call to [`SimulationReplayLoader::advance_tick`]
+    pub seek_to: Option<Tick>,
+    /// Save the replay was loaded from, where its keyframes are kept
+    save_name: Option<String>,
 }
 
 impl SimulationReplayLoader {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            pastt: Tick::default(),
+            idx: 0,
+            speed: 1,
+            advance_n_ticks: 0,
+            keyframes: Vec::new(),
+            keyframe_period: KEYFRAME_PERIOD,
+            seek_to: None,
+            save_name: None,
+        }
+    }
+
+    /// Restores the keyframes saved next to the replay by a previous viewing, if any.
+    /// They are saved again by [`SimulationReplayLoader::save_keyframes`].
+    pub fn load_keyframes(&mut self, save_name: &str) {
+        self.save_name = Some(save_name.to_string());
+        let Ok(saved) = Bincode::load::<SavedKeyframes>(&format!("{save_name}_replay_keyframes"))
+        else {
+            return;
+        };
+        if !self.restore_keyframes(saved) {
+            log::warn!("[replay] the saved keyframes are from another replay, ignoring them");
+        }
+    }
+
+    /// Saves the keyframes taken so far next to the replay they were loaded with
+    pub fn save_keyframes(self) {
+        let Some(save_name) = self.save_name.clone() else {
+            return;
+        };
+        if self.keyframes.is_empty() {
+            return;
+        }
+        Bincode::save(
+            &self.into_saved_keyframes(),
+            &format!("{save_name}_replay_keyframes"),
+        );
+    }
+
+    pub fn into_saved_keyframes(self) -> SavedKeyframes {
+        let last_tick = self.keyframes.last().map_or(Tick(0), |kf| kf.tick);
+        SavedKeyframes {
+            commands_hash: self.commands_hash(last_tick),
+            keyframe_period: self.keyframe_period,
+            keyframes: self.keyframes,
+        }
+    }
+
+    /// Takes the saved keyframes if they come from this replay, returns false otherwise
+    pub fn restore_keyframes(&mut self, saved: SavedKeyframes) -> bool {
+        let last_tick = saved.keyframes.last().map_or(Tick(0), |kf| kf.tick);
+        if last_tick > self.replay.last_tick_recorded
+            || saved.commands_hash != self.commands_hash(last_tick)
+        {
+            return false;
+        }
+        self.keyframes = saved.keyframes;
+        self.keyframe_period = saved.keyframe_period;
+        true
+    }
+
+    fn commands_hash(&self, until: Tick) -> u64 {
+        let n = self.replay.commands.partition_point(|(t, _)| *t < until);
+        match Bincode::encode(&self.replay.commands[..n]) {
+            Ok(v) => common::hash_u64(&*v),
+            Err(e) => {
+                log::error!("[replay] could not hash the commands: {}", e);
+                0
+            }
+        }
+    }
+
     /// Returns true if the replay is finished
-    pub fn advance_tick(&mut self, sim: &mut Simulation, schedule: &mut SeqSchedule) -> bool {
-        // iterate through tick grouped commands
-        let mut ticks_left = if self.speed == 0 {
-            let v = self.advance_n_ticks;
-            self.advance_n_ticks = 0;
-            v
+    pub fn advance_tick(&mut self, sim: &mut Simulation, schedule: &mut Schedule) -> bool {
+        if let Some(target) = self.seek_to.take() {
+            self.seek(sim, schedule, target);
+            return self.is_finished();
+        }
+
+        let ticks_left = if self.speed == 0 {
+            std::mem::take(&mut self.advance_n_ticks)
         } else {
             self.speed
         };
-        while self.idx < self.replay.commands.len() && ticks_left > 0 {
-            let curt = self.replay.commands[self.idx].0;
-            while self.pastt < curt {
-                sim.tick(schedule, &[]);
-                self.pastt.0 += 1;
-                ticks_left -= 1;
-                if ticks_left == 0 {
-                    return false;
-                }
+        for _ in 0..ticks_left {
+            if self.is_finished() {
+                break;
             }
+            self.step(sim, schedule);
+        }
+        self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.idx >= self.replay.commands.len() && self.pastt >= self.replay.last_tick_recorded
+    }
 
-            let idx_start = self.idx;
-            while self.idx < self.replay.commands.len() && self.replay.commands[self.idx].0 == curt
-            {
-                self.idx += 1;
+    /// Brings the simulation to the given tick, restoring the nearest keyframe before it if
+    /// it has to go backwards or if it is closer than the current tick.
+    pub fn seek(&mut self, sim: &mut Simulation, schedule: &mut Schedule, target: Tick) {
+        let target = target.min(self.replay.last_tick_recorded);
+
+        let kf_idx = self.keyframes.partition_point(|kf| kf.tick <= target);
+        if let Some(kf) = kf_idx.checked_sub(1).map(|i| &self.keyframes[i]) {
+            if target < self.pastt || kf.tick > self.pastt {
+                match common::saveload::CompressedBincode::decode::<Simulation>(&kf.data) {
+                    Ok(restored) => {
+                        *sim = restored;
+                        self.pastt = kf.tick;
+                        self.idx = self.replay.commands.partition_point(|(t, _)| *t < kf.tick);
+                    }
+                    Err(e) => {
+                        log::error!("[replay] could not restore keyframe {:?}: {}", kf.tick, e);
+                        return;
+                    }
+                }
             }
-            let command_slice = &self.replay.commands[idx_start..self.idx];
+        }
+
+        if target < self.pastt {
+            log::warn!("[replay] no keyframe before {:?}", target);
+            return;
+        }
+
+        while self.pastt < target && !self.is_finished() {
+            self.step(sim, schedule);
+        }
+    }
+
+    /// Goes back one tick
+    pub fn step_back(&mut self, sim: &mut Simulation, schedule: &mut Schedule) {
+        if self.pastt.0 > 0 {
+            self.seek(sim, schedule, Tick(self.pastt.0 - 1));
+        }
+    }
+
+    /// Plays one tick with the commands recorded for it
+    fn step(&mut self, sim: &mut Simulation, schedule: &mut Schedule) {
+        if self.pastt.0 % self.keyframe_period == 0
+            && self
+                .keyframes
+                .last()
+                .map_or(true, |kf| kf.tick < self.pastt)
+        {
+            self.take_keyframe(sim);
+        }
+
+        let idx_start = self.idx;
+        while self.idx < self.replay.commands.len()
+            && self.replay.commands[self.idx].0 <= self.pastt
+        {
+            self.idx += 1;
+        }
+        let command_slice = &self.replay.commands[idx_start..self.idx];
 
+        if !command_slice.is_empty() {
             log::info!(
                 "[replay] acttick {:?} ({})",
                 self.pastt,
                 command_slice.len()
             );
-            sim.tick(schedule, command_slice.iter().map(|(_, c)| c));
-            self.pastt.0 += 1;
-            ticks_left -= 1;
-            if ticks_left == 0 {
-                return false;
-            }
         }
-        if self.idx < self.replay.commands.len() {
-            return false;
+        sim.tick_replayed(schedule, command_slice.iter().map(|(_, c)| c));
+        self.pastt.0 += 1;
+    }
+
+    fn take_keyframe(&mut self, sim: &Simulation) {
+        profiling::scope!("replay::take_keyframe");
+        match common::saveload::CompressedBincode::encode(sim) {
+            Ok(data) => self.push_keyframe(Keyframe {
+                tick: self.pastt,
+                data,
+            }),
+            Err(e) => log::error!("[replay] could not take keyframe: {}", e),
         }
-        while ticks_left > 0 && self.pastt < self.replay.last_tick_recorded {
-            sim.tick(schedule, &[]);
-            self.pastt.0 += 1;
-            ticks_left -= 1;
-            if ticks_left == 0 {
-                return false;
+    }
+
+    fn push_keyframe(&mut self, keyframe: Keyframe) {
+        self.keyframes.push(keyframe);
+        if self.keyframes.len() > MAX_KEYFRAMES {
+            self.keyframe_period *= 2;
+            let period = self.keyframe_period;
+            self.keyframes.retain(|kf| kf.tick.0 % period == 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyframes_are_thinned() {
+        let mut loader = SimulationReplayLoader::new(Replay::default());
+        for i in 0..1000 {
+            let tick = Tick(i * KEYFRAME_PERIOD);
+            if tick.0 % loader.keyframe_period == 0 {
+                loader.push_keyframe(Keyframe { tick, data: vec![] });
             }
         }
-        true
+
+        let kfs = &loader.keyframes;
+        assert!(kfs.len() <= MAX_KEYFRAMES);
+        assert_eq!(kfs[0].tick, Tick(0));
+        // still evenly spread over the whole replay
+        for w in kfs.windows(2) {
+            assert_eq!(w[1].tick.0 - w[0].tick.0, loader.keyframe_period);
+        }
+        assert!(kfs[kfs.len() - 1].tick.0 + loader.keyframe_period >= 999 * KEYFRAME_PERIOD);
     }
 }
@@ -1,7 +1,12 @@
-use crate::world::{CompanyEnt, HumanEnt, TrainEnt, VehicleEnt, WagonEnt};
-use crate::{FreightStationEnt, ParCommandBuffer, Simulation};
+use crate::utils::par_command_buffer::SimDrop;
+use crate::utils::resources::{Ref, RefMut, Resources};
+use crate::world::{CompanyEnt, Entity, HumanEnt, TrainEnt, VehicleEnt, WagonEnt};
+use crate::{FreightStationEnt, ParCommandBuffer, Simulation, World};
 use common::history::History;
 use ordered_float::OrderedFloat;
+use rayon::prelude::*;
+use slotmapd::HopSlotMap;
+use std::any::{type_name, Any, TypeId};
 use std::time::Instant;
 
 pub trait RunnableSystem {
@@ -24,38 +29,342 @@ impl<F: Fn(&mut Simulation)> RunnableSystem for RunnableFn<F> {
     }
 }
 
+/// Something a system can access
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Access {
+    Resource(TypeId),
+    Store(TypeId),
+}
+
+/// The resources and entity stores a system reads and writes.
+/// Systems whose accesses don't conflict can run at the same time.
+#[derive(Default, Clone)]
+pub struct SystemAccess {
+    reads: Vec<Access>,
+    writes: Vec<Access>,
+    /// Moves the written entity stores between two worlds
+    store_swaps: Vec<fn(&mut World, &mut World)>,
+}
+
+impl SystemAccess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.reads.push(Access::Resource(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.writes.push(Access::Resource(TypeId::of::<T>()));
+        self
+    }
+
+    pub fn read_store<E: Entity>(mut self) -> Self {
+        self.reads.push(Access::Store(TypeId::of::<E>()));
+        self
+    }
+
+    pub fn write_store<E: Entity>(mut self) -> Self {
+        self.writes.push(Access::Store(TypeId::of::<E>()));
+        self.store_swaps.push(swap_store::<E>);
+        self
+    }
+
+    /// Two systems conflict if one of them writes something the other uses
+    fn conflicts(&self, other: &SystemAccess) -> bool {
+        self.writes
+            .iter()
+            .any(|a| other.reads.contains(a) || other.writes.contains(a))
+            || other.writes.iter().any(|a| self.reads.contains(a))
+    }
+
+    fn can_read(&self, a: Access) -> bool {
+        self.reads.contains(&a) || self.writes.contains(&a)
+    }
+
+    fn can_write(&self, a: Access) -> bool {
+        self.writes.contains(&a)
+    }
+}
+
+fn swap_store<E: Entity>(a: &mut World, b: &mut World) {
+    std::mem::swap(E::storage_mut(a), E::storage_mut(b));
+}
+
+/// The entity stores given to a [`ParSystem`].
+/// Using a store that was not declared in its [`SystemAccess`] panics in debug builds.
+pub struct SystemWorld<'a> {
+    name: &'static str,
+    access: &'a SystemAccess,
+    world: &'a World,
+    /// The stores written by the system, moved out of `world` while it runs
+    written: &'a mut World,
+}
+
+impl SystemWorld<'_> {
+    pub fn store<E: Entity>(&self) -> &HopSlotMap<E::ID, E> {
+        let a = Access::Store(TypeId::of::<E>());
+        debug_assert!(
+            self.access.can_read(a),
+            "{} reads the undeclared store {}",
+            self.name,
+            type_name::<E>()
+        );
+        if self.access.can_write(a) {
+            return E::storage(self.written);
+        }
+        E::storage(self.world)
+    }
+
+    pub fn store_mut<E: Entity>(&mut self) -> &mut HopSlotMap<E::ID, E> {
+        debug_assert!(
+            self.access.can_write(Access::Store(TypeId::of::<E>())),
+            "{} writes the undeclared store {}",
+            self.name,
+            type_name::<E>()
+        );
+        E::storage_mut(self.written)
+    }
+}
+
+/// The resources given to a [`ParSystem`].
+/// Using a resource that was not declared in its [`SystemAccess`] panics in debug builds.
+/// The [`ParCommandBuffer`]s are the system's own, they are applied after its stage in the order
+/// the systems were added.
+pub struct SystemResources<'a> {
+    name: &'static str,
+    access: &'a SystemAccess,
+    resources: &'a Resources,
+    buffers: &'a Resources,
+}
+
+impl SystemResources<'_> {
+    pub fn read<T: Any + Send + Sync>(&self) -> Ref<'_, T> {
+        if self.buffers.contains::<T>() {
+            return self.buffers.read::<T>();
+        }
+        debug_assert!(
+            self.access.can_read(Access::Resource(TypeId::of::<T>())),
+            "{} reads the undeclared resource {}",
+            self.name,
+            type_name::<T>()
+        );
+        self.resources.read::<T>()
+    }
+
+    pub fn write<T: Any + Send + Sync>(&self) -> RefMut<'_, T> {
+        if self.buffers.contains::<T>() {
+            return self.buffers.write::<T>();
+        }
+        debug_assert!(
+            self.access.can_write(Access::Resource(TypeId::of::<T>())),
+            "{} writes the undeclared resource {}",
+            self.name,
+            type_name::<T>()
+        );
+        self.resources.write::<T>()
+    }
+}
+
+/// A system that declares what it accesses, so that it can run concurrently with others.
+#[derive(Clone)]
+pub struct ParSystem {
+    pub name: &'static str,
+    pub access: SystemAccess,
+    pub f: fn(&mut SystemWorld, &SystemResources),
+}
+
+impl ParSystem {
+    fn run(&self, world: &World, written: &mut World, resources: &Resources, buffers: &Resources) {
+        (self.f)(
+            &mut SystemWorld {
+                name: self.name,
+                access: &self.access,
+                world,
+                written,
+            },
+            &SystemResources {
+                name: self.name,
+                access: &self.access,
+                resources,
+                buffers,
+            },
+        )
+    }
+
+    /// Moves the stores written by the system out of the world
+    fn take_written(&self, world: &mut World) -> World {
+        let mut written = World::default();
+        for swap in &self.access.store_swaps {
+            swap(world, &mut written);
+        }
+        written
+    }
+
+    fn restore_written(&self, world: &mut World, mut written: World) {
+        for swap in &self.access.store_swaps {
+            swap(world, &mut written);
+        }
+    }
+}
+
+enum ScheduledSystem {
+    /// Has full access to the simulation and runs alone
+    Exclusive(Box<dyn RunnableSystem>),
+    Par(ParSystem),
+}
+
+impl ScheduledSystem {
+    fn name(&self) -> &'static str {
+        match self {
+            ScheduledSystem::Exclusive(s) => s.name(),
+            ScheduledSystem::Par(s) => s.name,
+        }
+    }
+}
+
+/// Runs the systems in the order they were added.
+/// Consecutive parallel systems that don't conflict are grouped in a stage and run concurrently
+/// on rayon, which gives the same result as running them one after the other.
 #[derive(Default)]
-pub struct SeqSchedule {
-    systems: Vec<(Box<dyn RunnableSystem>, History)>,
+pub struct Schedule {
+    systems: Vec<(ScheduledSystem, History)>,
+    /// Indices of the systems of each stage
+    stages: Vec<Vec<usize>>,
+    parallel: bool,
 }
 
-impl SeqSchedule {
+impl Schedule {
+    /// Whether stages run their systems concurrently
+    pub fn set_parallel(&mut self, parallel: bool) -> &mut Self {
+        self.parallel = parallel;
+        self
+    }
+
     pub fn add_system(&mut self, s: Box<dyn RunnableSystem>) -> &mut Self {
-        self.systems.push((s, History::new(100)));
+        self.stages.push(vec![self.systems.len()]);
+        self.systems
+            .push((ScheduledSystem::Exclusive(s), History::new(100)));
+        self
+    }
+
+    pub fn add_par_system(&mut self, s: ParSystem) -> &mut Self {
+        let joins_last = self.stages.last().is_some_and(|stage| {
+            stage.iter().all(|&i| match &self.systems[i].0 {
+                ScheduledSystem::Exclusive(_) => false,
+                ScheduledSystem::Par(other) => !other.access.conflicts(&s.access),
+            })
+        });
+
+        let idx = self.systems.len();
+        self.systems
+            .push((ScheduledSystem::Par(s), History::new(100)));
+        match self.stages.last_mut() {
+            Some(stage) if joins_last => stage.push(idx),
+            _ => self.stages.push(vec![idx]),
+        }
         self
     }
 
+    /// The names of the systems of each stage
+    pub fn stages(&self) -> Vec<Vec<&'static str>> {
+        self.stages
+            .iter()
+            .map(|stage| stage.iter().map(|&i| self.systems[i].0.name()).collect())
+            .collect()
+    }
+
     #[inline(never)]
     pub fn execute(&mut self, sim: &mut Simulation) {
         profiling::scope!("scheduler::execute");
-        for (sys, h) in &mut self.systems {
-            let start = Instant::now();
+        for stage_idx in 0..self.stages.len() {
+            if self.parallel && self.stages[stage_idx].len() > 1 {
+                self.execute_par_stage(stage_idx, sim);
+                continue;
+            }
 
-            sys.run(sim);
+            for &i in &self.stages[stage_idx] {
+                let (sys, h) = &mut self.systems[i];
+                let start = Instant::now();
 
-            ParCommandBuffer::<VehicleEnt>::apply(sim);
-            ParCommandBuffer::<HumanEnt>::apply(sim);
-            ParCommandBuffer::<TrainEnt>::apply(sim);
-            ParCommandBuffer::<WagonEnt>::apply(sim);
-            ParCommandBuffer::<FreightStationEnt>::apply(sim);
-            ParCommandBuffer::<CompanyEnt>::apply(sim);
+                match sys {
+                    ScheduledSystem::Exclusive(s) => {
+                        s.run(sim);
+                        apply_command_buffers(sim);
+                    }
+                    ScheduledSystem::Par(s) => {
+                        let mut written = s.take_written(&mut sim.world);
+                        let buffers = command_buffers();
+                        s.run(&sim.world, &mut written, &sim.resources, &buffers);
+                        s.restore_written(&mut sim.world, written);
+                        apply_system_buffers(sim, buffers);
+                    }
+                }
 
-            let elapsed = start.elapsed();
+                h.add_value(1000.0 * start.elapsed().as_secs_f32());
+            }
+        }
+    }
 
-            h.add_value(1000.0 * elapsed.as_secs_f32());
+    fn execute_par_stage(&mut self, stage_idx: usize, sim: &mut Simulation) {
+        profiling::scope!("scheduler::par_stage");
+        let stage = &self.stages[stage_idx];
+        let systems = stage
+            .iter()
+            .map(|&i| match &self.systems[i].0 {
+                ScheduledSystem::Par(s) => s,
+                ScheduledSystem::Exclusive(_) => unreachable!("exclusive systems run alone"),
+            })
+            .collect::<Vec<_>>();
+
+        // written stores are moved out of the world, no other system of the stage uses them
+        let mut written = systems
+            .iter()
+            .map(|s| s.take_written(&mut sim.world))
+            .collect::<Vec<_>>();
+        // each system has its own command buffers so that the order of the commands
+        // doesn't depend on which thread pushed first
+        let buffers = systems
+            .iter()
+            .map(|_| command_buffers())
+            .collect::<Vec<_>>();
+
+        let world = &sim.world;
+        let resources = &sim.resources;
+        let elapsed = systems
+            .par_iter()
+            .zip(written.par_iter_mut())
+            .zip(buffers.par_iter())
+            .map(|((s, written), buffers)| {
+                let start = Instant::now();
+                s.run(world, written, resources, buffers);
+                start.elapsed()
+            })
+            .collect::<Vec<_>>();
+
+        for (s, written) in systems.iter().zip(written) {
+            s.restore_written(&mut sim.world, written);
+        }
+
+        for buffers in buffers {
+            apply_system_buffers(sim, buffers);
+        }
+
+        for (&i, elapsed) in stage.iter().zip(elapsed) {
+            self.systems[i].1.add_value(1000.0 * elapsed.as_secs_f32());
         }
     }
 
+    /// The time taken by each system during the last tick in milliseconds, in the order they were added
+    pub fn last_times(&self) -> Vec<(&'static str, f32)> {
+        self.systems
+            .iter()
+            .map(|(s, h)| (s.name(), h.values.last().copied().unwrap_or(0.0)))
+            .collect()
+    }
+
     pub fn times(&self) -> Vec<(String, f32)> {
         let mut times = self
             .systems
@@ -66,3 +375,40 @@ impl SeqSchedule {
         times
     }
 }
+
+/// Empty command buffers for a parallel system
+fn command_buffers() -> Resources {
+    let mut buffers = Resources::default();
+    buffers.insert(ParCommandBuffer::<VehicleEnt>::default());
+    buffers.insert(ParCommandBuffer::<HumanEnt>::default());
+    buffers.insert(ParCommandBuffer::<TrainEnt>::default());
+    buffers.insert(ParCommandBuffer::<WagonEnt>::default());
+    buffers.insert(ParCommandBuffer::<FreightStationEnt>::default());
+    buffers.insert(ParCommandBuffer::<CompanyEnt>::default());
+    buffers
+}
+
+fn apply_system_buffers(sim: &mut Simulation, mut buffers: Resources) {
+    move_buffer::<VehicleEnt>(sim, &mut buffers);
+    move_buffer::<HumanEnt>(sim, &mut buffers);
+    move_buffer::<TrainEnt>(sim, &mut buffers);
+    move_buffer::<WagonEnt>(sim, &mut buffers);
+    move_buffer::<FreightStationEnt>(sim, &mut buffers);
+    move_buffer::<CompanyEnt>(sim, &mut buffers);
+    apply_command_buffers(sim);
+}
+
+fn move_buffer<E: SimDrop>(sim: &mut Simulation, buffers: &mut Resources) {
+    if let Some(buffer) = buffers.remove::<ParCommandBuffer<E>>() {
+        *sim.write::<ParCommandBuffer<E>>() = buffer;
+    }
+}
+
+fn apply_command_buffers(sim: &mut Simulation) {
+    ParCommandBuffer::<VehicleEnt>::apply(sim);
+    ParCommandBuffer::<HumanEnt>::apply(sim);
+    ParCommandBuffer::<TrainEnt>::apply(sim);
+    ParCommandBuffer::<WagonEnt>::apply(sim);
+    ParCommandBuffer::<FreightStationEnt>::apply(sim);
+    ParCommandBuffer::<CompanyEnt>::apply(sim);
+}
@@ -1,26 +1,30 @@
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use std::time::Instant;
 
-use prototypes::RollingStockID;
+use prototypes::{try_prototype, Money, RollingStockID, ScenarioPrototypeID, ScenarioStartMap};
 use serde::{Deserialize, Serialize};
 
-use geom::{vec3, Vec2, Vec3, OBB};
+use geom::{vec3, Spline3, Vec2, Vec3, OBB};
 use prototypes::BuildingGen;
 use prototypes::GameTime;
 use WorldCommand::*;
 
-use crate::economy::Government;
+use crate::economy::{Government, Metric, StatsRecorder};
+use crate::events::{SimEvent, SimEvents};
 use crate::map::procgen::{load_parismap, load_testfield};
 use crate::map::{
     BuildingID, BuildingKind, Environment, IntersectionID, LaneID, LanePattern, LanePatternBuilder,
-    LightPolicy, LotID, Map, MapProject, ProjectKind, RoadID, TerraformKind, TurnPolicy, Zone,
+    LightPolicy, LotID, LotKind, Map, MapProject, PowerLineEnd, PowerLineID, PowerLineKind,
+    ProjectKind, RoadID, TerraformKind, TurnPolicy, Zone,
 };
 use crate::map_dynamic::{BuildingInfos, ParkingManagement};
 use crate::multiplayer::chat::Message;
 use crate::multiplayer::MultiplayerState;
+use crate::scenario::start_scenario;
 use crate::transportation::testing_vehicles::RandomVehicles;
 use crate::transportation::train::{spawn_train, RailWagonKind};
-use crate::transportation::{spawn_parked_vehicle_with_spot, unpark, VehicleKind};
+use crate::transportation::{spawn_parked_vehicle_with_spot, unpark, VehicleKind, VehicleState};
 use crate::utils::rand_provider::RandProvider;
 use crate::{Replay, Simulation, SimulationOptions};
 
@@ -37,7 +41,9 @@ pub enum WorldCommand {
     MapRemoveIntersection(IntersectionID),
     MapRemoveRoad(RoadID),
     MapRemoveBuilding(BuildingID),
+    MapRemovePowerLine(PowerLineID),
     MapBuildHouse(LotID),
+    MapSetLotKind(LotID, LotKind),
     Terraform {
         kind: TerraformKind,
         center: Vec2,
@@ -67,11 +73,19 @@ pub enum WorldCommand {
         to: MapProject,
         inter: Option<Vec2>,
         pat: LanePattern,
+        /// Dig the road under the terrain between its two ends, even where it is flat
+        #[serde(default)]
+        tunnel: bool,
     }, // todo: allow lane pattern builder
     MapMakeMultipleConnections(
         Vec<MapProject>,
         Vec<(usize, usize, Option<Vec2>, LanePattern)>,
     ),
+    /// Changes the lanes of an existing road without demolishing it
+    MapUpdateRoadPattern {
+        road: RoadID,
+        pattern: LanePattern,
+    },
     MapUpdateIntersectionPolicy {
         inter: IntersectionID,
         turn: TurnPolicy,
@@ -86,6 +100,11 @@ pub enum WorldCommand {
         #[serde(default)]
         connected_road: Option<RoadID>,
     },
+    MapBuildPowerLine {
+        src: PowerLineEnd,
+        dst: PowerLineEnd,
+        kind: PowerLineKind,
+    },
     MapLoadParis,
     MapLoadTestField {
         pos: Vec2,
@@ -97,6 +116,51 @@ pub enum WorldCommand {
         zone: Zone,
     },
     SetGameTime(GameTime),
+    /// Starts a scenario on the current map. Scenarios starting from a saved world are rejected,
+    /// they can only be started with [`Simulation::new_scenario`]
+    StartScenario(ScenarioPrototypeID),
+    /// Chooses the statistics sampled every hour by the [`StatsRecorder`]
+    SetRecordedMetrics(Vec<Metric>),
+}
+
+/// Who is allowed to send a command in multiplayer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommandPermission {
+    /// Everyone, including spectators
+    Chat,
+    /// Players that can edit the map
+    Build,
+    /// Commands that change the whole game, like loading a map or changing the time
+    Admin,
+}
+
+/// Why a command was not applied, see [`WorldCommand::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandRejection {
+    /// The command refers to an entity or a prototype that does not exist (anymore)
+    UnknownEntity,
+    /// The command arguments are malformed
+    InvalidArguments,
+    NotEnoughMoney {
+        cost: Money,
+        money: Money,
+    },
+}
+
+impl Display for CommandRejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandRejection::UnknownEntity => write!(f, "unknown entity"),
+            CommandRejection::InvalidArguments => write!(f, "invalid arguments"),
+            CommandRejection::NotEnoughMoney { cost, money } => {
+                write!(
+                    f,
+                    "not enough money: costs {} but only {} left",
+                    cost, money
+                )
+            }
+        }
+    }
 }
 
 impl AsRef<[WorldCommand]> for WorldCommands {
@@ -122,6 +186,16 @@ impl WorldCommands {
         self.commands.iter()
     }
 
+    /// Removes the commands the sender is not allowed to send
+    /// Called by the server before sending the commands to everyone
+    pub fn retain_permitted(&mut self, can_build: bool, is_admin: bool) {
+        self.commands.retain(|c| match c.permission() {
+            CommandPermission::Chat => true,
+            CommandPermission::Build => can_build,
+            CommandPermission::Admin => is_admin,
+        });
+    }
+
     pub fn is_empty(&self) -> bool {
         self.commands.is_empty()
     }
@@ -175,25 +249,48 @@ impl WorldCommands {
         self.commands.push(MapRemoveBuilding(id))
     }
 
+    pub fn map_remove_power_line(&mut self, id: PowerLineID) {
+        self.commands.push(MapRemovePowerLine(id))
+    }
+
+    pub fn map_build_power_line(
+        &mut self,
+        src: PowerLineEnd,
+        dst: PowerLineEnd,
+        kind: PowerLineKind,
+    ) {
+        self.commands.push(MapBuildPowerLine { src, dst, kind })
+    }
+
     pub fn map_build_house(&mut self, id: LotID) {
         self.commands.push(MapBuildHouse(id))
     }
 
+    pub fn map_set_lot_kind(&mut self, id: LotID, kind: LotKind) {
+        self.commands.push(MapSetLotKind(id, kind))
+    }
+
     pub fn map_make_connection(
         &mut self,
         from: MapProject,
         to: MapProject,
         interpoint: Option<Vec2>,
         pat: LanePattern,
+        tunnel: bool,
     ) {
         self.commands.push(MapMakeConnection {
             from,
             to,
             inter: interpoint,
             pat,
+            tunnel,
         })
     }
 
+    pub fn map_update_road_pattern(&mut self, road: RoadID, pattern: LanePattern) {
+        self.commands.push(MapUpdateRoadPattern { road, pattern })
+    }
+
     pub fn map_update_intersection_policy(
         &mut self,
         id: IntersectionID,
@@ -214,16 +311,160 @@ impl WorldCommand {
         matches!(
             self,
             MapBuildHouse(_)
+                | MapSetLotKind(..)
                 | MapUpdateIntersectionPolicy { .. }
                 | UpdateZone { .. }
                 | SetGameTime(_)
+                | SetRecordedMetrics(_)
         )
     }
 
-    pub fn apply(&self, sim: &mut Simulation) {
+    pub fn permission(&self) -> CommandPermission {
+        match self {
+            SendMessage { .. } => CommandPermission::Chat,
+            Init(_)
+            | MapLoadParis
+            | MapLoadTestField { .. }
+            | SetGameTime(_)
+            | StartScenario(_) => CommandPermission::Admin,
+            _ => CommandPermission::Build,
+        }
+    }
+
+    /// Returns true if the command can be emitted by mod scripts
+    pub fn is_moddable(&self) -> bool {
+        self.permission() != CommandPermission::Admin
+    }
+
+    /// Checks that the command refers to existing entities and is affordable.
+    /// Every peer runs it on the same state before applying the command,
+    /// so invalid commands are rejected everywhere and the simulation stays in sync.
+    pub fn validate(&self, sim: &Simulation) -> Result<(), CommandRejection> {
+        self.validate_replayed(sim)?;
+
         let cost = Government::action_cost(self, sim);
-        sim.write::<Government>().money -= cost;
+        let money = sim.read::<Government>().money;
+        if cost > Money::ZERO && cost > money {
+            return Err(CommandRejection::NotEnoughMoney { cost, money });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the command refers to existing entities, but not that it is affordable.
+    /// Replays recorded before the money was checked contain commands that were applied anyway,
+    /// so they must still be applied when played back.
+    pub fn validate_replayed(&self, sim: &Simulation) -> Result<(), CommandRejection> {
+        let map = sim.map();
+        let exists = |b: bool| {
+            if b {
+                Ok(())
+            } else {
+                Err(CommandRejection::UnknownEntity)
+            }
+        };
+        let valid = |b: bool| {
+            if b {
+                Ok(())
+            } else {
+                Err(CommandRejection::InvalidArguments)
+            }
+        };
+        let project_exists = |p: &MapProject| match p.kind {
+            ProjectKind::Intersection(id) => map.intersections.contains_key(id),
+            ProjectKind::Road(id) => map.roads.contains_key(id),
+            ProjectKind::Building(id) => map.buildings.contains_key(id),
+            ProjectKind::Lot(id) => map.lots.contains_key(id),
+            ProjectKind::Ground => true,
+        };
+
+        match *self {
+            MapRemoveIntersection(id) => exists(map.intersections.contains_key(id))?,
+            MapRemoveRoad(id) => exists(map.roads.contains_key(id))?,
+            MapRemovePowerLine(id) => exists(map.power_lines.contains_key(id))?,
+            MapBuildPowerLine { src, dst, .. } => {
+                let src = map
+                    .power_line_end_pos(src)
+                    .ok_or(CommandRejection::UnknownEntity)?;
+                let dst = map
+                    .power_line_end_pos(dst)
+                    .ok_or(CommandRejection::UnknownEntity)?;
+                valid(src.is_finite() && dst.is_finite())?;
+            }
+            MapRemoveBuilding(id) | UpdateZone { building: id, .. } => {
+                exists(map.buildings.contains_key(id))?
+            }
+            MapBuildHouse(id) | MapSetLotKind(id, _) => exists(map.lots.contains_key(id))?,
+            MapUpdateIntersectionPolicy { inter, .. } => {
+                exists(map.intersections.contains_key(inter))?
+            }
+            MapUpdateRoadPattern { road, ref pattern } => {
+                let r = map.roads.get(road).ok_or(CommandRejection::UnknownEntity)?;
+                let is_rail = |p: &LanePattern| p.lanes().any(|(k, _, _)| k.is_rail());
+                valid(pattern.lanes().next().is_some())?;
+                valid(
+                    pattern
+                        .lanes()
+                        .all(|(_, _, limit)| limit.is_finite() && limit > 0.0),
+                )?;
+                // rails and roads don't have the same geometry, so they cannot be converted in place
+                valid(is_rail(pattern) == is_rail(&r.pattern(&map.lanes)))?;
+                valid(!map.road_pattern_overlaps_building(road, pattern))?;
+            }
+            AddTrain { lane, .. } => exists(map.lanes.contains_key(lane))?,
+            SpawnTrain {
+                ref wagons,
+                lane,
+                dist,
+            } => {
+                exists(map.lanes.contains_key(lane))?;
+                exists(wagons.iter().all(|w| try_prototype(*w).is_some()))?;
+                valid(!wagons.is_empty() && dist.is_finite())?;
+            }
+            MapMakeConnection {
+                ref from, ref to, ..
+            } => {
+                exists(project_exists(from) && project_exists(to))?;
+                valid(from.pos.is_finite() && to.pos.is_finite())?;
+            }
+            MapMakeMultipleConnections(ref projects, ref links) => {
+                exists(projects.iter().all(project_exists))?;
+                valid(projects.iter().all(|p| p.pos.is_finite()))?;
+                valid(
+                    links
+                        .iter()
+                        .all(|(from, to, _, _)| *from < projects.len() && *to < projects.len()),
+                )?;
+            }
+            MapBuildSpecialBuilding {
+                kind,
+                connected_road,
+                ..
+            } => {
+                exists(connected_road.map_or(true, |r| map.roads.contains_key(r)))?;
+                exists(match kind {
+                    BuildingKind::GoodsCompany(id) => try_prototype(id).is_some(),
+                    BuildingKind::RailFreightStation(id) => try_prototype(id).is_some(),
+                    BuildingKind::Leisure(id) => try_prototype(id).is_some(),
+                    _ => true,
+                })?;
+            }
+            Terraform { radius, amount, .. } => {
+                valid(radius.is_finite() && radius > 0.0 && amount.is_finite())?
+            }
+            StartScenario(id) => {
+                let proto = try_prototype(id).ok_or(CommandRejection::UnknownEntity)?;
+                // the saved world replaces the whole simulation and is only on this machine,
+                // such scenarios are started with Simulation::new_scenario
+                valid(!matches!(proto.start_map, ScenarioStartMap::Save(_)))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
 
+    pub fn apply(&self, sim: &mut Simulation) {
         let mut rep = sim.resources.write::<Replay>();
         if rep.enabled {
             let tick = sim.read::<GameTime>().tick;
@@ -231,23 +472,41 @@ impl WorldCommand {
         }
         drop(rep);
 
+        self.apply_unrecorded(sim);
+    }
+
+    /// Applies the command without recording it in the replay.
+    /// Used for commands generated deterministically by the simulation itself, like mod commands.
+    pub(crate) fn apply_unrecorded(&self, sim: &mut Simulation) {
+        let cost = Government::action_cost(self, sim);
+        sim.write::<Government>().money -= cost;
+
         match *self {
             MapRemoveIntersection(id) => sim.map_mut().remove_intersection(id),
             MapRemoveRoad(id) => drop(sim.map_mut().remove_road(id)),
             MapRemoveBuilding(id) => drop(sim.map_mut().remove_building(id)),
+            MapRemovePowerLine(id) => drop(sim.map_mut().remove_power_line(id)),
+            MapBuildPowerLine { src, dst, kind } => {
+                sim.map_mut().build_power_line(src, dst, kind);
+            }
             MapBuildHouse(id) => {
                 if let Some(build) = sim.map_mut().build_house(id) {
                     let mut infos = sim.write::<BuildingInfos>();
                     infos.insert(build);
+                    sim.write::<SimEvents>()
+                        .push(SimEvent::BuildingBuilt(build));
                 }
             }
+            MapSetLotKind(id, kind) => sim.map_mut().set_lot_kind(id, kind),
             MapMakeConnection {
                 from,
                 to,
                 inter,
                 ref pat,
+                tunnel,
             } => {
-                sim.write::<Map>().make_connection(from, to, inter, pat);
+                sim.write::<Map>()
+                    .make_connection(from, to, inter, pat, tunnel);
             }
             MapMakeMultipleConnections(ref projects, ref links) => {
                 let mut map = sim.map_mut();
@@ -263,7 +522,9 @@ impl WorldCommand {
                         toproj.kind = ProjectKind::Intersection(*i);
                     }
 
-                    if let Some((_, r)) = map.make_connection(fromproj, toproj, *interpoint, pat) {
+                    if let Some((_, r)) =
+                        map.make_connection(fromproj, toproj, *interpoint, pat, false)
+                    {
                         if fromproj.kind.is_ground() {
                             inters.insert(*from, map.roads[r].src);
                         }
@@ -273,6 +534,15 @@ impl WorldCommand {
                     }
                 }
             }
+            MapUpdateRoadPattern { road, ref pattern } => {
+                let mut map = sim.map_mut();
+                let r = unwrap_ret!(map.roads.get(road));
+                let old_lanes = r.lanes_iter().map(|(id, _)| id).collect::<Vec<_>>();
+                unwrap_ret!(map.update_road_pattern(road, pattern));
+                drop(map);
+
+                migrate_road_users(sim, &old_lanes);
+            }
             MapUpdateIntersectionPolicy {
                 inter: id,
                 turn: tp,
@@ -296,9 +566,12 @@ impl WorldCommand {
                     connected_road,
                 ) {
                     sim.write::<BuildingInfos>().insert(id);
+                    sim.write::<SimEvents>().push(SimEvent::BuildingBuilt(id));
                 }
             }
             SetGameTime(gt) => *sim.write::<GameTime>() = gt,
+            SetRecordedMetrics(ref metrics) => sim.write::<StatsRecorder>().set_metrics(metrics),
+            StartScenario(id) => start_scenario(sim, id),
             AddTrain {
                 dist: _,
                 n_wagons: _,
@@ -379,6 +652,70 @@ impl WorldCommand {
     }
 }
 
+/// Reroutes everything that was going through the lanes of an updated road, and moves the
+/// vehicles parked on removed spots to the nearest free spot, where their owners will find them
+fn migrate_road_users(sim: &mut Simulation, lanes: &[LaneID]) {
+    let (world, res) = sim.world_res();
+
+    for v in world.vehicles.values_mut() {
+        if v.it.uses_lanes(lanes) {
+            v.it.reroute();
+        }
+    }
+    for h in world.humans.values_mut() {
+        if h.it.uses_lanes(lanes) {
+            h.it.reroute();
+        }
+    }
+    for t in world.trains.values_mut() {
+        if t.it.uses_lanes(lanes) {
+            t.it.reroute();
+        }
+    }
+
+    let map = res.read::<Map>();
+    let mut pm = res.write::<ParkingManagement>();
+    let mut moved = vec![];
+    for (id, v) in world.vehicles.iter_mut() {
+        let (VehicleState::Parked(ref mut spot) | VehicleState::RoadToPark(_, _, ref mut spot)) =
+            v.vehicle.state
+        else {
+            continue;
+        };
+        if spot.exists(&map.parking) {
+            continue;
+        }
+        if !pm.relocate(spot, v.trans.pos, &map) {
+            log::warn!("could not find a new parking spot for {:?}", spot);
+            continue;
+        }
+        let spot = unwrap_cont!(spot.get(&map.parking)).trans;
+
+        match v.vehicle.state {
+            VehicleState::Parked(_) => {
+                v.trans = spot;
+                moved.push((id, spot.pos));
+            }
+            VehicleState::RoadToPark(ref mut spline, ref mut t, _) => {
+                *spline = Spline3 {
+                    from: v.trans.pos,
+                    to: spot.pos,
+                    from_derivative: v.trans.dir * 2.0,
+                    to_derivative: spot.dir * 2.0,
+                };
+                *t = 0.0;
+            }
+            _ => {}
+        }
+    }
+
+    for h in world.humans.values_mut() {
+        for &(car, pos) in &moved {
+            h.router.vehicle_moved(car, pos, &mut h.it);
+        }
+    }
+}
+
 fn generate_terrain(sim: &mut Simulation, size: u16) {
     info!("generating terrain..");
     let t = Instant::now();
@@ -398,6 +735,7 @@ fn generate_terrain(sim: &mut Simulation, size: u16) {
         MapProject::ground(c + offy * 120.0),
         None,
         &pat,
+        false,
     );
 
     if sim